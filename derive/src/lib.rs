@@ -0,0 +1,387 @@
+//! # diffkit-derive
+//!
+//! `#[derive(Diffable)]` for the `Diffable` trait of diffkit,
+//! re-exported by diffkit with its `derive` feature.
+//!
+//! - structs with named fields become maps, keyed by field name
+//! - tuple structs become tuples, diffed by position, and newtypes the node of their field
+//! - enums become a variant node, named after the variant
+//!   and holding the variant fields like a struct
+//!
+//! Fields can mix leaf types: their leaves are converted to the leaf type
+//! of the derived type, `diffkit::recursive::Scalar` unless set otherwise.
+//!
+//! ## Attributes
+//!
+//! - `#[diffable(leaf = Type)]` on the type sets its leaf type
+//! - `#[diffable(rename = "name")]` on a field or variant sets its key
+//! - `#[diffable(skip)]` on a field leaves it out, filling it with `Default` when converting back
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{
+    parse_macro_input, parse_quote, Attribute, Data, DeriveInput, Fields, GenericParam, Ident,
+    LitStr, Type,
+};
+
+#[proc_macro_derive(Diffable, attributes(diffable))]
+pub fn derive_diffable(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// The options set by a `#[diffable(...)]` attribute.
+#[derive(Default)]
+struct Options {
+    leaf: Option<Type>,
+    rename: Option<String>,
+    skip: bool,
+}
+
+fn options(attrs: &[Attribute]) -> syn::Result<Options> {
+    let mut options = Options::default();
+    for attr in attrs.iter().filter(|a| a.path().is_ident("diffable")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("leaf") {
+                options.leaf = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("rename") {
+                options.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("skip") {
+                options.skip = true;
+            } else {
+                return Err(meta.error("unknown diffable attribute"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(options)
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let leaf = options(&input.attrs)?
+        .leaf
+        .unwrap_or_else(|| parse_quote!(::diffkit::recursive::Scalar));
+    let mut field_types = vec![];
+    let mut skipped_types = vec![];
+
+    let (to_node, from_node) = match &input.data {
+        Data::Struct(data) => {
+            let shape = Shape::new(&data.fields, &mut field_types, &mut skipped_types)?;
+            let pattern = shape.pattern();
+            let to_node = shape.build_node(&leaf);
+            let from_node = shape.construct(&quote!(Self), &leaf);
+            (
+                quote! {
+                    let Self #pattern = self;
+                    #to_node
+                },
+                quote! { Ok(#from_node) },
+            )
+        }
+        Data::Enum(data) => {
+            let mut to_arms = vec![];
+            let mut from_arms = vec![];
+            for variant in &data.variants {
+                let ident = &variant.ident;
+                let key = options(&variant.attrs)?
+                    .rename
+                    .unwrap_or_else(|| ident.to_string());
+                let shape = Shape::new(&variant.fields, &mut field_types, &mut skipped_types)?;
+                let pattern = shape.pattern();
+                let to_node = shape.build_node(&leaf);
+                let from_node = shape.construct(&quote!(Self::#ident), &leaf);
+                to_arms.push(quote! {
+                    Self::#ident #pattern => ::diffkit::recursive::Node::Variant(
+                        #key.to_string(),
+                        ::std::boxed::Box::new({ #to_node }),
+                    ),
+                });
+                from_arms.push(quote! {
+                    #key => (|| -> ::std::result::Result<Self, ::diffkit::recursive::ApplyError> {
+                        Ok(#from_node)
+                    })()
+                    .map_err(|e| e.within(::diffkit::recursive::PathSegment::Key(tag))),
+                });
+            }
+            // an enum without variants has no value to match
+            let matched = if to_arms.is_empty() {
+                quote!(*self)
+            } else {
+                quote!(self)
+            };
+            (
+                quote! {
+                    match #matched {
+                        #(#to_arms)*
+                    }
+                },
+                quote! {
+                    let (tag, node) = match node {
+                        ::diffkit::recursive::Node::Variant(tag, content) => (tag, *content),
+                        ::diffkit::recursive::Node::Map(map) if map.len() == 1 => {
+                            map.into_iter().next().unwrap()
+                        }
+                        _ => return Err(::diffkit::recursive::ApplyError::TypeMismatch(vec![])),
+                    };
+                    match tag.as_str() {
+                        #(#from_arms)*
+                        _ => Err(::diffkit::recursive::ApplyError::TypeMismatch(vec![])),
+                    }
+                },
+            )
+        }
+        Data::Union(data) => {
+            return Err(syn::Error::new(
+                data.union_token.span,
+                "Diffable can't be derived for unions",
+            ))
+        }
+    };
+
+    // only bound the fields depending on generic parameters:
+    // bounds on the other ones are checked anyway, and could recurse
+    let generic_idents: Vec<&Ident> = input
+        .generics
+        .params
+        .iter()
+        .filter_map(|p| match p {
+            GenericParam::Type(t) => Some(&t.ident),
+            _ => None,
+        })
+        .collect();
+    let is_generic = |ty: &&Type| {
+        let tokens = quote!(#ty).to_string();
+        generic_idents.iter().any(|ident| {
+            tokens
+                .split(|c: char| !c.is_alphanumeric() && c != '_')
+                .any(|t| **ident == t)
+        })
+    };
+    let field_types: Vec<&Type> = field_types.iter().filter(is_generic).collect();
+    let skipped_types: Vec<&Type> = skipped_types.iter().filter(is_generic).collect();
+
+    let mut generics = input.generics.clone();
+    let where_clause = generics.make_where_clause();
+    for ty in field_types {
+        where_clause.predicates.push(parse_quote! {
+            #ty: ::diffkit::recursive::Diffable
+        });
+        where_clause.predicates.push(parse_quote! {
+            #leaf: ::std::convert::From<<#ty as ::diffkit::recursive::Diffable>::P>
+        });
+        where_clause.predicates.push(parse_quote! {
+            <#ty as ::diffkit::recursive::Diffable>::P: ::std::convert::TryFrom<#leaf>
+        });
+    }
+    for ty in skipped_types {
+        where_clause.predicates.push(parse_quote! {
+            #ty: ::std::default::Default
+        });
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::diffkit::recursive::Diffable for #name #ty_generics #where_clause {
+            type P = #leaf;
+
+            fn to_node(&self) -> ::diffkit::recursive::Node<Self::P> {
+                #to_node
+            }
+
+            fn from_node(
+                node: ::diffkit::recursive::Node<Self::P>,
+            ) -> ::std::result::Result<Self, ::diffkit::recursive::ApplyError> {
+                #from_node
+            }
+        }
+    })
+}
+
+/// The fields of a struct or of an enum variant.
+enum Shape {
+    Named(Vec<Field>),
+    Unnamed(Vec<Field>),
+    Unit,
+}
+
+struct Field {
+    /// The field name, for named fields
+    ident: Option<Ident>,
+    /// The map key of a named field
+    key: String,
+    /// The variable bound to the field, `None` if skipped
+    binding: Option<Ident>,
+    ty: Type,
+}
+
+impl Shape {
+    fn new(
+        fields: &Fields,
+        field_types: &mut Vec<Type>,
+        skipped_types: &mut Vec<Type>,
+    ) -> syn::Result<Shape> {
+        let mut converted = vec![];
+        for (i, field) in fields.iter().enumerate() {
+            let options = options(&field.attrs)?;
+            if options.leaf.is_some() {
+                return Err(syn::Error::new_spanned(
+                    field,
+                    "leaf can only be set on the type",
+                ));
+            }
+            let binding = if options.skip {
+                skipped_types.push(field.ty.clone());
+                None
+            } else {
+                field_types.push(field.ty.clone());
+                Some(format_ident!("__field{}", i))
+            };
+            let key = options
+                .rename
+                .or_else(|| field.ident.as_ref().map(|i| i.to_string()))
+                .unwrap_or_default();
+            converted.push(Field {
+                ident: field.ident.clone(),
+                key,
+                binding,
+                ty: field.ty.clone(),
+            });
+        }
+        Ok(match fields {
+            Fields::Named(_) => Shape::Named(converted),
+            Fields::Unnamed(_) => Shape::Unnamed(converted),
+            Fields::Unit => Shape::Unit,
+        })
+    }
+
+    /// The pattern binding the fields, following the name of the struct or variant.
+    fn pattern(&self) -> TokenStream2 {
+        match self {
+            Shape::Named(fields) => {
+                let bindings = fields.iter().filter_map(|f| {
+                    let ident = &f.ident;
+                    f.binding.as_ref().map(|b| quote!(#ident: #b))
+                });
+                quote!({ #(#bindings,)* .. })
+            }
+            Shape::Unnamed(fields) => {
+                let bindings = fields.iter().map(|f| match &f.binding {
+                    Some(b) => quote!(#b),
+                    None => quote!(_),
+                });
+                quote!((#(#bindings),*))
+            }
+            Shape::Unit => quote!(),
+        }
+    }
+
+    /// The expression building the node, from the bound fields.
+    fn build_node(&self, leaf: &Type) -> TokenStream2 {
+        let convert = |f: &Field| {
+            let ty = &f.ty;
+            f.binding
+                .as_ref()
+                .map(|b| quote!(::diffkit::recursive::derive_support::to_node::<#ty, #leaf>(#b)))
+        };
+        match self {
+            Shape::Named(fields) => {
+                let entries = fields.iter().filter_map(|f| {
+                    let key = &f.key;
+                    convert(f).map(|value| quote!((#key.to_string(), #value)))
+                });
+                quote!(::diffkit::recursive::Node::Map(::std::collections::HashMap::from([#(#entries),*])))
+            }
+            Shape::Unnamed(fields) => {
+                let values: Vec<TokenStream2> = fields.iter().filter_map(convert).collect();
+                if values.len() == 1 && fields.len() == 1 {
+                    values[0].clone()
+                } else {
+                    quote!(::diffkit::recursive::Node::Tuple(vec![#(#values),*]))
+                }
+            }
+            Shape::Unit => {
+                quote!(::diffkit::recursive::Node::Map(
+                    ::std::collections::HashMap::new()
+                ))
+            }
+        }
+    }
+
+    /// The expression building the struct or variant from `node`,
+    /// returning early on errors.
+    fn construct(&self, constructor: &TokenStream2, leaf: &Type) -> TokenStream2 {
+        let from_field =
+            |ty: &Type| quote!(::diffkit::recursive::derive_support::from_field::<#ty, #leaf>);
+        let default = quote!(::std::default::Default::default());
+        match self {
+            Shape::Named(fields) => {
+                let values = fields.iter().map(|f| {
+                    let ident = &f.ident;
+                    let key = &f.key;
+                    let from_field = from_field(&f.ty);
+                    match f.binding {
+                        Some(_) => quote! {
+                            #ident: #from_field(
+                                map.remove(#key),
+                                ::diffkit::recursive::PathSegment::Key(#key.to_string()),
+                            )?
+                        },
+                        None => quote!(#ident: #default),
+                    }
+                });
+                quote! {{
+                    let ::diffkit::recursive::Node::Map(mut map) = node else {
+                        return Err(::diffkit::recursive::ApplyError::TypeMismatch(vec![]));
+                    };
+                    #constructor { #(#values),* }
+                }}
+            }
+            Shape::Unnamed(fields) if fields.len() == 1 && fields[0].binding.is_some() => {
+                let ty = &fields[0].ty;
+                quote! {
+                    #constructor(::diffkit::recursive::derive_support::from_node::<#ty, #leaf>(node)?)
+                }
+            }
+            Shape::Unnamed(fields) => {
+                let count = fields.iter().filter(|f| f.binding.is_some()).count();
+                let mut index = 0usize;
+                let values = fields.iter().map(|f| match f.binding {
+                    Some(_) => {
+                        let from_field = from_field(&f.ty);
+                        let value = quote! {
+                            #from_field(
+                                elements.next(),
+                                ::diffkit::recursive::PathSegment::Index(#index),
+                            )?
+                        };
+                        index += 1;
+                        value
+                    }
+                    None => default.clone(),
+                });
+                let values: Vec<TokenStream2> = values.collect();
+                quote! {{
+                    let (::diffkit::recursive::Node::Tuple(elements)
+                    | ::diffkit::recursive::Node::Sequence(elements)) = node else {
+                        return Err(::diffkit::recursive::ApplyError::TypeMismatch(vec![]));
+                    };
+                    if elements.len() != #count {
+                        return Err(::diffkit::recursive::ApplyError::TypeMismatch(vec![]));
+                    }
+                    let mut elements = elements.into_iter();
+                    #constructor(#(#values),*)
+                }}
+            }
+            Shape::Unit => quote! {{
+                let ::diffkit::recursive::Node::Map(_) = node else {
+                    return Err(::diffkit::recursive::ApplyError::TypeMismatch(vec![]));
+                };
+                #constructor
+            }},
+        }
+    }
+}
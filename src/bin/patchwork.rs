@@ -0,0 +1,218 @@
+//! `patchwork`, a command line front end to diffkit.
+//!
+//! ```text
+//! patchwork diff [--json] OLD NEW
+//! patchwork apply [--json] FILE PATCH
+//! patchwork revert [--json] FILE PATCH
+//! ```
+//!
+//! `diff` writes a unified diff of the lines of two files, or with `--json`
+//! a JSON Patch (RFC 6902) between two JSON documents, with `test` operations
+//! recording the old values. It exits with 1 if the files differ, like `diff`.
+//! `apply` writes the file patched with a patch of the same kind, and `revert`
+//! the file unpatched, from a JSON Patch written by `patchwork diff --json`.
+//! Files aren't modified. Errors are reported on stderr, with the exit code 2.
+
+use diffkit::myers::Edit;
+use diffkit::patch::{apply, diff_files, Hunk, PatchFile};
+use diffkit::recursive::{self, from_json_patch, to_json_patch};
+use diffkit::serialization::{FromPatch, ToPatch};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::Path;
+use std::process::ExitCode;
+
+const USAGE: &str = "\
+usage: patchwork diff [--json] OLD NEW
+       patchwork apply [--json] FILE PATCH
+       patchwork revert [--json] FILE PATCH";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match run(&args) {
+        Ok((output, differ)) => {
+            print!("{}", output);
+            ExitCode::from(u8::from(differ))
+        }
+        Err(message) => {
+            eprintln!("patchwork: {}", message);
+            ExitCode::from(2)
+        }
+    }
+}
+
+/// The output of a command, and whether the files it compared differ.
+fn run(args: &[String]) -> Result<(String, bool), String> {
+    let json = args.iter().any(|arg| arg == "--json");
+    let args: Vec<&str> = args
+        .iter()
+        .map(String::as_str)
+        .filter(|arg| *arg != "--json")
+        .collect();
+    let [command, first, second] = args[..] else {
+        return Err(USAGE.to_string());
+    };
+    if (command, json) == ("diff", false) {
+        return diff_lines(first, second);
+    }
+    let (first_content, second_content) = (read(first)?, read(second)?);
+    match (command, json) {
+        ("diff", true) => diff_json(
+            &parse_json(first, &first_content)?,
+            &parse_json(second, &second_content)?,
+        ),
+        ("apply", false) => apply_lines(&first_content, &second_content, false),
+        ("apply", true) => {
+            let patch = parse_json(second, &second_content)?;
+            apply_json(&parse_json(first, &first_content)?, &patch)
+        }
+        ("revert", false) => apply_lines(&first_content, &second_content, true),
+        ("revert", true) => {
+            let patch = invert_json_patch(&parse_json(second, &second_content)?)?;
+            apply_json(&parse_json(first, &first_content)?, &patch)
+        }
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+fn read(path: &str) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))
+}
+
+fn parse_json(path: &str, content: &str) -> Result<Value, String> {
+    serde_json::from_str(content).map_err(|e| format!("{}: {}", path, e))
+}
+
+fn diff_lines(old: &str, new: &str) -> Result<(String, bool), String> {
+    let file = diff_files(Path::new(old), Path::new(new))
+        .map_err(|e| format!("{}, {}: {}", old, new, e))?;
+    if file.hunks.is_empty() {
+        return Ok((String::new(), false));
+    }
+    let mut patch = file.to_patch(&file.header);
+    patch.push('\n');
+    Ok((patch, true))
+}
+
+fn diff_json(old: &Value, new: &Value) -> Result<(String, bool), String> {
+    let changes = recursive::diff(old, new);
+    let patch = to_json_patch(&changes, true).map_err(|e| e.to_string())?;
+    Ok((pretty(&patch)?, !changes.is_empty()))
+}
+
+/// Applies a unified diff to the lines of `content`, or its inverse if `revert`.
+/// The content keeps its trailing newline, or its lack of one.
+fn apply_lines(content: &str, patch: &str, revert: bool) -> Result<(String, bool), String> {
+    let file = PatchFile::<String>::from_patch(patch).map_err(|e| e.to_string())?;
+    let hunks: Vec<Hunk<String>> = if revert {
+        file.hunks.iter().map(invert_hunk).collect()
+    } else {
+        file.hunks
+    };
+    let (old, trailing_newline) = lines(content);
+    let new = apply(&old, &hunks).map_err(|e| e.to_string())?;
+    let mut output = new.join("\n");
+    if trailing_newline && !new.is_empty() {
+        output.push('\n');
+    }
+    Ok((output, false))
+}
+
+fn apply_json(document: &Value, patch: &Value) -> Result<(String, bool), String> {
+    let changes = from_json_patch(document, patch).map_err(|e| e.to_string())?;
+    let patched = recursive::apply(document, &changes).map_err(|e| e.to_string())?;
+    Ok((pretty(&patched)?, false))
+}
+
+fn pretty(value: &Value) -> Result<String, String> {
+    let mut output = serde_json::to_string_pretty(value).map_err(|e| e.to_string())?;
+    output.push('\n');
+    Ok(output)
+}
+
+/// The lines of a file, and whether its last line ends with a newline.
+fn lines(content: &str) -> (Vec<String>, bool) {
+    let trailing_newline = content.is_empty() || content.ends_with('\n');
+    let content = content.strip_suffix('\n').unwrap_or(content);
+    if content.is_empty() && trailing_newline {
+        return (vec![], true);
+    }
+    let lines = content.split('\n').map(ToString::to_string).collect();
+    (lines, trailing_newline)
+}
+
+/// The hunk turning the new lines of `hunk` back into its old ones.
+fn invert_hunk(hunk: &Hunk<String>) -> Hunk<String> {
+    let changes = hunk
+        .changes
+        .iter()
+        .map(|edit| match edit {
+            Edit::Insert(line) => Edit::Delete(line.clone()),
+            Edit::Delete(line) => Edit::Insert(line.clone()),
+            Edit::Equal(line) => Edit::Equal(line.clone()),
+        })
+        .collect();
+    Hunk {
+        old_start: hunk.new_start,
+        new_start: hunk.old_start,
+        changes,
+        section: hunk.section.clone(),
+    }
+}
+
+/// The JSON Patch undoing `patch`, which must record the values it removes
+/// or replaces in a `test` operation right before, like `patchwork diff --json`.
+/// The new values are tested in turn.
+fn invert_json_patch(patch: &Value) -> Result<Value, String> {
+    let operations = patch
+        .as_array()
+        .ok_or("JSON Patch is not an array of operations")?;
+    let mut inverted = vec![];
+    let mut tested: Option<(&Value, &Value)> = None;
+    for (i, operation) in operations.iter().enumerate() {
+        let field = |name: &str| {
+            operation
+                .get(name)
+                .ok_or_else(|| format!("malformed operation #{}", i))
+        };
+        let op = field("op")?.as_str().unwrap_or_default();
+        let path = field("path")?;
+        let old = tested
+            .take()
+            .filter(|(tested_path, _)| *tested_path == path)
+            .map(|(_, value)| value);
+        let missing_old = || format!("operation #{} doesn't record the old value", i);
+        let undo = match op {
+            "test" => {
+                tested = Some((path, field("value")?));
+                continue;
+            }
+            "add" => {
+                let new = field("value")?;
+                match old {
+                    // only the whole document is replaced by an `add` after its test
+                    Some(old) => vec![json!({ "op": "add", "path": path, "value": old })],
+                    None => vec![
+                        json!({ "op": "test", "path": path, "value": new }),
+                        json!({ "op": "remove", "path": path }),
+                    ],
+                }
+            }
+            "remove" => {
+                let old = old.ok_or_else(missing_old)?;
+                vec![json!({ "op": "add", "path": path, "value": old })]
+            }
+            "replace" => {
+                let old = old.ok_or_else(missing_old)?;
+                vec![
+                    json!({ "op": "test", "path": path, "value": field("value")? }),
+                    json!({ "op": "replace", "path": path, "value": old }),
+                ]
+            }
+            "move" => vec![json!({ "op": "move", "from": path, "path": field("from")? })],
+            _ => return Err(format!("operation #{} can't be reverted", i)),
+        };
+        inverted.push(undo);
+    }
+    Ok(Value::Array(inverted.into_iter().rev().flatten().collect()))
+}
@@ -0,0 +1,854 @@
+//! Binary deltas between arbitrary byte files, like `bsdiff` or VCDIFF, e.g. to ship
+//! updates of executables or archives as the bytes that changed.
+//!
+//! A delta is a list of instructions rebuilding the new file: copies of ranges of
+//! the old file, found by looking up the longest matches of the new bytes in a suffix
+//! array of the old ones, and insertions of the bytes found nowhere in it.
+//!
+//! Deltas are stored in a stable format: the magic `DKB`, a version byte, the lengths
+//! of both files as varints and their 64-bit FNV-1a hashes as little-endian `u64`s,
+//! then the instructions. A copy is the tag 0, the distance from the end of the
+//! previous copy to its offset as a zigzag varint, and its length as a varint;
+//! an insertion is the tag 1, the length of its bytes as a varint, then the bytes.
+//!
+//! ```
+//! use diffkit::delta::{apply_delta, encode_delta, DeltaError};
+//!
+//! let old = b"the quick brown fox jumps over the lazy dog".repeat(10);
+//! let mut new = old.clone();
+//! new[200..205].copy_from_slice(b"black");
+//!
+//! let delta = encode_delta(&old, &new);
+//! assert!(delta.len() < 60);
+//! assert_eq!(apply_delta(&old, &delta), Ok(new));
+//! assert_eq!(apply_delta(b"another file", &delta), Err(DeltaError::WrongSource));
+//! ```
+//!
+//! Like `rsync`, a delta can also be computed without the old file, from its
+//! [`signature`]: the checksums of its blocks, much smaller than the file itself.
+//! The machine holding the old file sends its signature, the one holding the new file
+//! answers with the [`delta`] from it, and the first one applies it with [`apply_delta`],
+//! so that neither file crosses the network. Such a delta only copies whole blocks
+//! of the old file, so it's larger than one computed from the old file itself.
+//!
+//! A signature starts with the magic `DKS`, a version byte, the block size and the
+//! length of the file as varints and its hash, followed by the checksums of each block:
+//! the weak rolling checksum of `rsync` as a little-endian `u32`, and the FNV-1a hash
+//! of the block as a little-endian `u64`.
+//!
+//! ```
+//! use diffkit::delta::{apply_delta, delta, signature};
+//!
+//! let base = b"the quick brown fox jumps over the lazy dog".repeat(1000);
+//! let mut new = base.clone();
+//! new[20_000..20_005].copy_from_slice(b"black");
+//!
+//! // on the machine with `base`
+//! let signature = signature(&base);
+//! // on the machine with `new`
+//! let delta = delta(&signature, &new).unwrap();
+//! assert!(signature.len() + delta.len() < 2000);
+//! // back on the machine with `base`
+//! assert_eq!(apply_delta(&base, &delta), Ok(new));
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hasher;
+
+const MAGIC: &[u8; 3] = b"DKB";
+const SIGNATURE_MAGIC: &[u8; 3] = b"DKS";
+const VERSION: u8 = 1;
+
+/// The tags of the instructions.
+const COPY: u8 = 0;
+const INSERT: u8 = 1;
+
+/// The shortest match copied from the old file; shorter ones take about as much
+/// room as the bytes they copy, and are inserted instead.
+const MIN_COPY: usize = 8;
+
+/// Computes the delta turning `old` into `new`, in the format described in the
+/// [module documentation](self).
+pub fn encode_delta(old: &[u8], new: &[u8]) -> Vec<u8> {
+    BinaryDelta::between(old, new).to_bytes()
+}
+
+/// Rebuilds the new file from `old` and a delta written by [`encode_delta`].
+///
+/// # Errors
+///
+/// Returns a [`DeltaError`] if `delta` isn't a delta, if it was computed from
+/// another old file, or if the result doesn't hash like the new file.
+pub fn apply_delta(old: &[u8], delta: &[u8]) -> Result<Vec<u8>, DeltaError> {
+    BinaryDelta::from_bytes(delta)?.apply(old)
+}
+
+/// Computes the [`Signature`] of `base`, in the format described in the
+/// [module documentation](self), with blocks of about the square root of its length,
+/// but at least 700 bytes.
+pub fn signature(base: &[u8]) -> Vec<u8> {
+    let block_size = base.len().isqrt().clamp(700, 1 << 17);
+    Signature::new(base, block_size).to_bytes()
+}
+
+/// Computes the delta turning the file of `signature`, written by [`signature`],
+/// into `new`, to be applied with [`apply_delta`].
+///
+/// # Errors
+///
+/// Returns a [`DeltaError`] if `signature` isn't a signature.
+pub fn delta(signature: &[u8], new: &[u8]) -> Result<Vec<u8>, DeltaError> {
+    let signature = Signature::from_bytes(signature)?;
+    Ok(BinaryDelta::from_signature(&signature, new).to_bytes())
+}
+
+/// One step rebuilding the new file.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum Instruction {
+    /// Appends `len` bytes of the old file, from `offset`.
+    Copy { offset: usize, len: usize },
+    /// Appends bytes found nowhere in the old file.
+    Insert(Vec<u8>),
+}
+
+/// A decoded delta: the instructions turning the old file into the new one,
+/// with the lengths and hashes checking both files.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct BinaryDelta {
+    pub old_len: usize,
+    pub old_hash: u64,
+    pub new_len: usize,
+    pub new_hash: u64,
+    pub instructions: Vec<Instruction>,
+}
+
+impl BinaryDelta {
+    /// The delta turning `old` into `new`: each run of new bytes is copied from
+    /// its longest match in `old` when it has one of at least 8 bytes.
+    pub fn between(old: &[u8], new: &[u8]) -> Self {
+        let suffixes = suffix_array(old);
+        let mut instructions = vec![];
+        let mut inserted = vec![];
+        let mut i = 0;
+        while i < new.len() {
+            let (offset, len) = longest_match(old, &suffixes, &new[i..]);
+            if len < MIN_COPY {
+                inserted.push(new[i]);
+                i += 1;
+                continue;
+            }
+            if !inserted.is_empty() {
+                instructions.push(Instruction::Insert(std::mem::take(&mut inserted)));
+            }
+            instructions.push(Instruction::Copy { offset, len });
+            i += len;
+        }
+        if !inserted.is_empty() {
+            instructions.push(Instruction::Insert(inserted));
+        }
+        BinaryDelta {
+            old_len: old.len(),
+            old_hash: fnv1a(old),
+            new_len: new.len(),
+            new_hash: fnv1a(new),
+            instructions,
+        }
+    }
+
+    /// The delta turning the file of `signature` into `new`: each run of new bytes
+    /// with the checksums of a block of the old file is copied from that block.
+    ///
+    /// Blocks are looked up by their weak checksum, rolled over `new` one byte
+    /// at a time, then confirmed by their [`strong`](BlockChecksum::strong) hash.
+    /// When both collide, a wrong block is copied, and only the `new_hash` check
+    /// of [`apply`](BinaryDelta::apply) catches it, rejecting the whole delta
+    /// as [`DeltaError::Corrupted`].
+    pub fn from_signature(signature: &Signature, new: &[u8]) -> Self {
+        let block_size = signature.block_size;
+        let mut by_weak: HashMap<u32, Vec<usize>> = HashMap::new();
+        for (i, block) in signature.blocks.iter().enumerate() {
+            by_weak.entry(block.weak).or_default().push(i);
+        }
+        let last_len = signature.len % block_size;
+        let find = |window: &[u8], weak: u32| {
+            let candidates = by_weak.get(&weak)?;
+            let strong = fnv1a(window);
+            candidates.iter().copied().find(|&i| {
+                signature.blocks[i].strong == strong && signature.block_len(i) == window.len()
+            })
+        };
+
+        let mut instructions = vec![];
+        let mut inserted = vec![];
+        // the checksum of the window at `i`, when rolled from the previous one
+        let mut rolled: Option<Rolling> = None;
+        let mut i = 0;
+        while i < new.len() {
+            let len = block_size.min(new.len() - i);
+            // past the last full window, only a shorter last block can match
+            if len < block_size && len != last_len {
+                inserted.push(new[i]);
+                i += 1;
+                continue;
+            }
+            let window = &new[i..i + len];
+            let checksum = rolled.unwrap_or_else(|| Rolling::new(window));
+            if let Some(block) = find(window, checksum.digest()) {
+                if !inserted.is_empty() {
+                    instructions.push(Instruction::Insert(std::mem::take(&mut inserted)));
+                }
+                let offset = block * block_size;
+                match instructions.last_mut() {
+                    Some(Instruction::Copy {
+                        offset: previous,
+                        len: previous_len,
+                    }) if *previous + *previous_len == offset => *previous_len += len,
+                    _ => instructions.push(Instruction::Copy { offset, len }),
+                }
+                rolled = None;
+                i += len;
+                continue;
+            }
+            inserted.push(new[i]);
+            rolled = match new.get(i + len) {
+                Some(&next) if len == block_size => Some(checksum.roll(new[i], next)),
+                _ => None,
+            };
+            i += 1;
+        }
+        if !inserted.is_empty() {
+            instructions.push(Instruction::Insert(inserted));
+        }
+        BinaryDelta {
+            old_len: signature.len,
+            old_hash: signature.hash,
+            new_len: new.len(),
+            new_hash: fnv1a(new),
+            instructions,
+        }
+    }
+
+    /// Rebuilds the new file from `old`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DeltaError::WrongSource`] if `old` isn't the file the delta was
+    /// computed from, [`DeltaError::Malformed`] if a copy reaches past its end
+    /// or the instructions don't add up to `new_len` bytes,
+    /// and [`DeltaError::Corrupted`] if the result isn't the new file.
+    pub fn apply(&self, old: &[u8]) -> Result<Vec<u8>, DeltaError> {
+        if old.len() != self.old_len || fnv1a(old) != self.old_hash {
+            return Err(DeltaError::WrongSource);
+        }
+        // the lengths come from the delta: check them all before allocating
+        let mut pieces = Vec::with_capacity(self.instructions.len());
+        let mut total: usize = 0;
+        for instruction in &self.instructions {
+            let bytes = match instruction {
+                Instruction::Copy { offset, len } => {
+                    let end = offset.checked_add(*len).ok_or(DeltaError::Malformed)?;
+                    old.get(*offset..end).ok_or(DeltaError::Malformed)?
+                }
+                Instruction::Insert(bytes) => bytes,
+            };
+            total = total
+                .checked_add(bytes.len())
+                .ok_or(DeltaError::Malformed)?;
+            pieces.push(bytes);
+        }
+        if total != self.new_len {
+            return Err(DeltaError::Malformed);
+        }
+        let new = pieces.concat();
+        if fnv1a(&new) != self.new_hash {
+            return Err(DeltaError::Corrupted);
+        }
+        Ok(new)
+    }
+
+    /// Encodes the delta in the format described in the [module documentation](self).
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+        write_varint(&mut bytes, self.old_len as u64);
+        bytes.extend(self.old_hash.to_le_bytes());
+        write_varint(&mut bytes, self.new_len as u64);
+        bytes.extend(self.new_hash.to_le_bytes());
+        let mut copied_to = 0;
+        for instruction in &self.instructions {
+            match instruction {
+                Instruction::Copy { offset, len } => {
+                    bytes.push(COPY);
+                    let distance = *offset as i64 - copied_to as i64;
+                    write_varint(&mut bytes, ((distance << 1) ^ (distance >> 63)) as u64);
+                    write_varint(&mut bytes, *len as u64);
+                    copied_to = offset + len;
+                }
+                Instruction::Insert(inserted) => {
+                    bytes.push(INSERT);
+                    write_varint(&mut bytes, inserted.len() as u64);
+                    bytes.extend(inserted);
+                }
+            }
+        }
+        bytes
+    }
+
+    /// Decodes a delta written by [`to_bytes`](BinaryDelta::to_bytes).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DeltaError::InvalidHeader`] if the bytes don't start like a delta,
+    /// and [`DeltaError::Malformed`] if they end in the middle of an instruction
+    /// or hold an unknown tag.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DeltaError> {
+        match bytes.split_at_checked(MAGIC.len()) {
+            Some((magic, [VERSION, ..])) if magic == MAGIC => {}
+            _ => return Err(DeltaError::InvalidHeader),
+        }
+        let mut reader = Reader {
+            bytes,
+            pos: MAGIC.len() + 1,
+        };
+        let old_len = reader.len()?;
+        let old_hash = reader.hash()?;
+        let new_len = reader.len()?;
+        let new_hash = reader.hash()?;
+        let mut instructions = vec![];
+        let mut copied_to: i64 = 0;
+        while reader.pos < bytes.len() {
+            match reader.take(1)?[0] {
+                COPY => {
+                    let zigzag = reader.varint()?;
+                    let distance = (zigzag >> 1) as i64 ^ -((zigzag & 1) as i64);
+                    let offset = copied_to
+                        .checked_add(distance)
+                        .and_then(|offset| usize::try_from(offset).ok())
+                        .ok_or(DeltaError::Malformed)?;
+                    let len = reader.len()?;
+                    copied_to = offset
+                        .checked_add(len)
+                        .and_then(|end| i64::try_from(end).ok())
+                        .ok_or(DeltaError::Malformed)?;
+                    instructions.push(Instruction::Copy { offset, len });
+                }
+                INSERT => {
+                    let len = reader.len()?;
+                    instructions.push(Instruction::Insert(reader.take(len)?.to_vec()));
+                }
+                _ => return Err(DeltaError::Malformed),
+            }
+        }
+        Ok(BinaryDelta {
+            old_len,
+            old_hash,
+            new_len,
+            new_hash,
+            instructions,
+        })
+    }
+}
+
+/// The checksums of the blocks of a file, from which [`BinaryDelta::from_signature`]
+/// computes a delta without the file itself.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Signature {
+    pub block_size: usize,
+    /// The length of the file.
+    pub len: usize,
+    /// The FNV-1a hash of the file.
+    pub hash: u64,
+    /// The checksums of each block of the file, the last of which
+    /// is shorter if the length isn't a multiple of the block size.
+    pub blocks: Vec<BlockChecksum>,
+}
+
+/// The checksums of a block of a [`Signature`].
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct BlockChecksum {
+    /// The rolling checksum of `rsync`, cheap to compute at every offset.
+    pub weak: u32,
+    /// The 64-bit FNV-1a hash of the block. Despite its name, it's weak:
+    /// it isn't cryptographic, so blocks can be crafted to collide with it,
+    /// and a collision is only caught by the hash of the whole new file,
+    /// once the delta is applied.
+    pub strong: u64,
+}
+
+impl Signature {
+    /// The signature of `base`, cut in blocks of `block_size` bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `block_size` is 0.
+    pub fn new(base: &[u8], block_size: usize) -> Self {
+        assert!(block_size > 0, "the block size must be positive");
+        let blocks = base
+            .chunks(block_size)
+            .map(|block| BlockChecksum {
+                weak: Rolling::new(block).digest(),
+                strong: fnv1a(block),
+            })
+            .collect();
+        Signature {
+            block_size,
+            len: base.len(),
+            hash: fnv1a(base),
+            blocks,
+        }
+    }
+
+    /// Encodes the signature in the format described in the [module documentation](self).
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = SIGNATURE_MAGIC.to_vec();
+        bytes.push(VERSION);
+        write_varint(&mut bytes, self.block_size as u64);
+        write_varint(&mut bytes, self.len as u64);
+        bytes.extend(self.hash.to_le_bytes());
+        for block in &self.blocks {
+            bytes.extend(block.weak.to_le_bytes());
+            bytes.extend(block.strong.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Decodes a signature written by [`to_bytes`](Signature::to_bytes).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DeltaError::InvalidHeader`] if the bytes don't start like a signature,
+    /// and [`DeltaError::Malformed`] if they don't hold one checksum per block.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DeltaError> {
+        match bytes.split_at_checked(SIGNATURE_MAGIC.len()) {
+            Some((magic, [VERSION, ..])) if magic == SIGNATURE_MAGIC => {}
+            _ => return Err(DeltaError::InvalidHeader),
+        }
+        let mut reader = Reader {
+            bytes,
+            pos: SIGNATURE_MAGIC.len() + 1,
+        };
+        let block_size = reader.len()?;
+        let len = reader.len()?;
+        let hash = reader.hash()?;
+        if block_size == 0 {
+            return Err(DeltaError::Malformed);
+        }
+        let count = len.div_ceil(block_size);
+        if bytes.len() - reader.pos != count.checked_mul(12).ok_or(DeltaError::Malformed)? {
+            return Err(DeltaError::Malformed);
+        }
+        let mut blocks = Vec::with_capacity(count);
+        for _ in 0..count {
+            let mut weak = [0; 4];
+            weak.copy_from_slice(reader.take(4)?);
+            blocks.push(BlockChecksum {
+                weak: u32::from_le_bytes(weak),
+                strong: reader.hash()?,
+            });
+        }
+        Ok(Signature {
+            block_size,
+            len,
+            hash,
+            blocks,
+        })
+    }
+
+    /// The length of the block at `index`.
+    fn block_len(&self, index: usize) -> usize {
+        self.block_size.min(self.len - index * self.block_size)
+    }
+}
+
+/// Error returned when a delta can't be decoded or applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeltaError {
+    /// The bytes don't start with the header of a delta, or of a signature.
+    InvalidHeader,
+    /// The bytes end in the middle of an instruction, hold an unknown tag,
+    /// or copy bytes past the end of the old file.
+    Malformed,
+    /// The old file isn't the one the delta was computed from.
+    WrongSource,
+    /// The rebuilt file doesn't have the length or hash of the new file.
+    Corrupted,
+}
+
+impl fmt::Display for DeltaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeltaError::InvalidHeader => write!(f, "not a binary delta"),
+            DeltaError::Malformed => write!(f, "malformed binary delta"),
+            DeltaError::WrongSource => write!(f, "the delta was computed from another file"),
+            DeltaError::Corrupted => write!(f, "the patched file doesn't match the delta"),
+        }
+    }
+}
+
+impl std::error::Error for DeltaError {}
+
+/// The weak checksum of `rsync`: the sum of the bytes of a window, and the sum of
+/// those sums, both modulo 2^16, which can be rolled one byte at a time.
+#[derive(Clone, Copy)]
+struct Rolling {
+    a: u32,
+    b: u32,
+    len: u32,
+}
+
+impl Rolling {
+    fn new(window: &[u8]) -> Self {
+        let (mut a, mut b) = (0u32, 0u32);
+        for &byte in window {
+            a = a.wrapping_add(u32::from(byte));
+            b = b.wrapping_add(a);
+        }
+        Rolling {
+            a,
+            b,
+            len: window.len() as u32,
+        }
+    }
+
+    /// The checksum of the window moved by one byte, dropping `out` and adding `next`.
+    fn roll(self, out: u8, next: u8) -> Self {
+        let a = self
+            .a
+            .wrapping_sub(u32::from(out))
+            .wrapping_add(u32::from(next));
+        let b = self
+            .b
+            .wrapping_sub(self.len.wrapping_mul(u32::from(out)))
+            .wrapping_add(a);
+        Rolling { a, b, ..self }
+    }
+
+    fn digest(&self) -> u32 {
+        (self.a & 0xffff) | (self.b << 16)
+    }
+}
+
+/// The starting offsets of the suffixes of `bytes`, in lexicographic order,
+/// sorted by doubling the length of the prefixes compared at each round.
+fn suffix_array(bytes: &[u8]) -> Vec<usize> {
+    let n = bytes.len();
+    let mut suffixes: Vec<usize> = (0..n).collect();
+    // ranks start at 1, so that the end of the bytes sorts before any of them
+    let mut rank: Vec<usize> = bytes.iter().map(|&b| usize::from(b) + 1).collect();
+    let mut next = vec![0; n];
+    let mut k = 1;
+    while k < n {
+        let key = |i: usize| (rank[i], rank.get(i + k).copied().unwrap_or(0));
+        suffixes.sort_unstable_by_key(|&i| key(i));
+        next[suffixes[0]] = 1;
+        for w in suffixes.windows(2) {
+            next[w[1]] = next[w[0]] + usize::from(key(w[0]) != key(w[1]));
+        }
+        std::mem::swap(&mut rank, &mut next);
+        if rank[suffixes[n - 1]] == n {
+            break;
+        }
+        k *= 2;
+    }
+    suffixes
+}
+
+/// The offset and length of the longest prefix of `target` found in `old`,
+/// next to where `target` sorts among its suffixes.
+fn longest_match(old: &[u8], suffixes: &[usize], target: &[u8]) -> (usize, usize) {
+    let i = suffixes.partition_point(|&s| &old[s..] < target);
+    [i.checked_sub(1), Some(i)]
+        .into_iter()
+        .flatten()
+        .filter_map(|j| suffixes.get(j))
+        .map(|&s| {
+            let len = old[s..]
+                .iter()
+                .zip(target)
+                .take_while(|(a, b)| a == b)
+                .count();
+            (s, len)
+        })
+        .max_by_key(|&(_, len)| len)
+        .unwrap_or((0, 0))
+}
+
+/// The 64-bit FNV-1a hash of `bytes`, which unlike `std`'s hashers is stable.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hasher = Fnv1a::default();
+    hasher.write(bytes);
+    hasher.finish()
+}
+
+/// A [`Hasher`] computing [`fnv1a`] over the bytes it's given.
+pub(crate) struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for Fnv1a {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.0 = bytes.iter().fold(self.0, |hash, &b| {
+            (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+        });
+    }
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        bytes.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    bytes.push(n as u8);
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], DeltaError> {
+        let end = self.pos.checked_add(len).ok_or(DeltaError::Malformed)?;
+        let bytes = self.bytes.get(self.pos..end).ok_or(DeltaError::Malformed)?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn varint(&mut self) -> Result<u64, DeltaError> {
+        let mut n = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.take(1)?[0];
+            n |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(n);
+            }
+        }
+        Err(DeltaError::Malformed)
+    }
+
+    fn len(&mut self) -> Result<usize, DeltaError> {
+        usize::try_from(self.varint()?).map_err(|_| DeltaError::Malformed)
+    }
+
+    fn hash(&mut self) -> Result<u64, DeltaError> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pseudo-random bytes, which have no long matches of their own.
+    fn noise(len: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+                (state >> 56) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_suffix_array() {
+        assert_eq!(suffix_array(b"banana"), [5, 3, 1, 0, 4, 2]);
+        assert_eq!(suffix_array(b"aaaa"), [3, 2, 1, 0]);
+        assert_eq!(suffix_array(b""), Vec::<usize>::new());
+        let bytes = noise(500, 1);
+        let suffixes = suffix_array(&bytes);
+        assert!(suffixes.windows(2).all(|w| bytes[w[0]..] < bytes[w[1]..]));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let old = noise(10_000, 1);
+        let mut new = old.clone();
+        new[100] ^= 0xff;
+        new.splice(5000..5000, noise(50, 2));
+        new.drain(8000..8100);
+        new.extend_from_slice(&old[..300]);
+        let delta = BinaryDelta::between(&old, &new);
+        assert_eq!(delta.apply(&old), Ok(new.clone()));
+        let bytes = delta.to_bytes();
+        assert!(bytes.len() < 120, "{} bytes", bytes.len());
+        assert_eq!(BinaryDelta::from_bytes(&bytes), Ok(delta));
+        assert_eq!(apply_delta(&old, &bytes), Ok(new));
+
+        let cases: [(&[u8], &[u8]); 4] = [
+            (b"", b""),
+            (b"", b"abc"),
+            (b"abcdefghijkl", b""),
+            (b"abcdefghijkl", b"xxabcdefghijkl"),
+        ];
+        for (old, new) in cases {
+            assert_eq!(apply_delta(old, &encode_delta(old, new)), Ok(new.to_vec()));
+        }
+        let delta = BinaryDelta::between(b"abcdefghijkl", b"xxabcdefghijkl");
+        assert_eq!(
+            delta.instructions,
+            [
+                Instruction::Insert(b"xx".to_vec()),
+                Instruction::Copy { offset: 0, len: 12 }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let old = noise(100, 1);
+        let new = [&old[50..], &old[..50]].concat();
+        let bytes = encode_delta(&old, &new);
+        assert_eq!(apply_delta(&new, &bytes), Err(DeltaError::WrongSource));
+        assert_eq!(apply_delta(&old, b"DKB"), Err(DeltaError::InvalidHeader));
+        assert_eq!(
+            apply_delta(&old, &bytes[1..]),
+            Err(DeltaError::InvalidHeader)
+        );
+        assert_eq!(
+            apply_delta(&old, &bytes[..bytes.len() - 1]),
+            Err(DeltaError::Malformed)
+        );
+        assert_eq!(
+            apply_delta(&old, &[&bytes[..], &[7]].concat()),
+            Err(DeltaError::Malformed)
+        );
+
+        let mut delta = BinaryDelta::from_bytes(&bytes).unwrap();
+        delta.new_hash ^= 1;
+        assert_eq!(delta.apply(&old), Err(DeltaError::Corrupted));
+        delta.instructions.push(Instruction::Insert(vec![0]));
+        assert_eq!(delta.apply(&old), Err(DeltaError::Malformed));
+        delta.instructions = vec![Instruction::Copy {
+            offset: 90,
+            len: 20,
+        }];
+        assert_eq!(delta.apply(&old), Err(DeltaError::Malformed));
+
+        // a crafted length isn't trusted to allocate the new file
+        let header = |new_len: usize| {
+            let mut header = bytes[..MAGIC.len() + 1].to_vec();
+            write_varint(&mut header, old.len() as u64);
+            header.extend(fnv1a(&old).to_le_bytes());
+            write_varint(&mut header, new_len as u64);
+            header.extend(fnv1a(&new).to_le_bytes());
+            header
+        };
+        let instructions = &bytes[header(new.len()).len()..];
+        assert_eq!([header(new.len()), instructions.to_vec()].concat(), bytes);
+        let crafted = [header(usize::MAX / 2), instructions.to_vec()].concat();
+        assert_eq!(apply_delta(&old, &crafted), Err(DeltaError::Malformed));
+        assert_eq!(
+            DeltaError::WrongSource.to_string(),
+            "the delta was computed from another file"
+        );
+    }
+
+    #[test]
+    fn test_rolling_checksum() {
+        let bytes = noise(300, 3);
+        let mut rolling = Rolling::new(&bytes[..64]);
+        for i in 1..=bytes.len() - 64 {
+            rolling = rolling.roll(bytes[i - 1], bytes[i + 63]);
+            assert_eq!(rolling.digest(), Rolling::new(&bytes[i..i + 64]).digest());
+        }
+    }
+
+    #[test]
+    fn test_sync() {
+        let base = noise(10_000, 1);
+        let mut new = base.clone();
+        new[100] ^= 0xff;
+        new.splice(5000..5000, noise(50, 2));
+        new.drain(8000..8100);
+        new.extend_from_slice(&base[..300]);
+        let signature = Signature::new(&base, 256);
+        assert_eq!(
+            Signature::from_bytes(&signature.to_bytes()),
+            Ok(signature.clone())
+        );
+
+        let delta = BinaryDelta::from_signature(&signature, &new);
+        assert_eq!(delta.apply(&base), Ok(new.clone()));
+        // the three changed blocks, the copied start of the file, and the short last block
+        let inserted: usize = delta
+            .instructions
+            .iter()
+            .map(|i| match i {
+                Instruction::Insert(bytes) => bytes.len(),
+                Instruction::Copy { .. } => 0,
+            })
+            .sum();
+        assert!(inserted < 4 * 256 + 300, "{} bytes inserted", inserted);
+        let bytes = super::delta(&signature.to_bytes(), &new).unwrap();
+        assert_eq!(apply_delta(&base, &bytes), Ok(new));
+
+        let cases: [(&[u8], &[u8]); 5] = [
+            (b"", b""),
+            (b"", b"abc"),
+            (b"abcdefghijkl", b""),
+            (b"abcdefghijkl", b"xxabcdefghijkl"),
+            (b"abcdefghijkl", b"ijklabcdefghijkl"),
+        ];
+        for (base, new) in cases {
+            let delta = BinaryDelta::from_signature(&Signature::new(base, 4), new);
+            assert_eq!(delta.apply(base), Ok(new.to_vec()));
+        }
+        let delta =
+            BinaryDelta::from_signature(&Signature::new(b"abcdefghijkl", 4), b"xxabcdefghijkl");
+        assert_eq!(
+            delta.instructions,
+            [
+                Instruction::Insert(b"xx".to_vec()),
+                Instruction::Copy { offset: 0, len: 12 }
+            ]
+        );
+        // a shorter last block matches at the end of the new file only
+        let delta = BinaryDelta::from_signature(&Signature::new(b"abcdefghij", 4), b"abcdij");
+        assert_eq!(
+            delta.instructions,
+            [
+                Instruction::Copy { offset: 0, len: 4 },
+                Instruction::Copy { offset: 8, len: 2 }
+            ]
+        );
+        let delta = BinaryDelta::from_signature(&Signature::new(b"abcdefghij", 4), b"ijabcd");
+        assert_eq!(
+            delta.instructions,
+            [
+                Instruction::Insert(b"ij".to_vec()),
+                Instruction::Copy { offset: 0, len: 4 }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_signature_errors() {
+        let bytes = signature(&noise(1000, 1));
+        assert_eq!(bytes.len(), 3 + 1 + 2 + 2 + 8 + 2 * 12);
+        assert_eq!(delta(&bytes[1..], b""), Err(DeltaError::InvalidHeader));
+        assert_eq!(
+            delta(&encode_delta(b"", b""), b""),
+            Err(DeltaError::InvalidHeader)
+        );
+        assert_eq!(
+            delta(&bytes[..bytes.len() - 1], b""),
+            Err(DeltaError::Malformed)
+        );
+        assert_eq!(
+            delta(&[&bytes[..], &[0]].concat(), b""),
+            Err(DeltaError::Malformed)
+        );
+    }
+}
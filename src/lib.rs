@@ -5,12 +5,54 @@
 //! ## Features
 //!
 //! - **Myers diff** — efficient sequence diffing via the Myers algorithm
+//! - **Incremental re-diff** — update a sequence diff after a range of one input is replaced, diffing again only around the edit, e.g. for live previews
+//! - **Text comparison options** — diff lines by a comparison key while keeping the original lines in the diff: ignore `\r\n` versus `\n` line endings, also when applying patches, expand tabs to a given width, compare indentation by level so that re-indented code or YAML isn't a change, or compare lines in Unicode NFC or NFKC form so that differently encoded but identical text isn't a change (`unicode` feature)
 //! - **Recursive diff** — structural diffing of nested maps and sequences
+//! - **Per-path policies** — ignore, compare whole, or pair the sequence elements of subtrees differently, e.g. `spec.tags = unordered` and `spec.containers = keyed name`, from a small configuration format
+//! - **Derive** — `#[derive(Diffable)]` for structs and enums (`derive` feature)
+//! - **Floats** — diff `f32` and `f64` leaves, compared as `OrderedFloat`s (`float` feature)
+//! - **Timestamps** — diff `chrono` `DateTime<Utc>` and `NaiveDate` leaves (`chrono` feature)
+//! - **UUIDs** — diff `uuid::Uuid` leaves (`uuid` feature)
+//! - **Reports** — render changes as readable lines like `~ spec.replicas: 3 → 5`, optionally colored or indented under their containers like `kubectl diff`
+//! - **HTML reports** — render changes as a collapsible HTML tree with path breadcrumbs, for review pages
+//! - **Graphviz** — export both trees as a DOT graph with the changed nodes highlighted
+//! - **Change sets** — attach an id and metadata like the author to a list of changes, kept with them in JSON, and queue them into a replayable history, with periodic snapshots
+//! - **Structural patches** — write changes as text, one per line like `modify spec.replicas 3 -> 5`, and parse them back
+//! - **Binary encoding** — encode changes compactly to send them over the network, optionally LZ4-compressed (`compress` feature)
+//! - **Storage** — write change sets and patches to files as length-prefixed frames, compressed with zstd or deflate (`zstd` and `deflate` features)
+//! - **Blob leaves** — diff raw bytes as single leaves, with byte-level deltas instead of both versions of a modified blob
+//! - **Large leaves** — keep only a hash and the length of string and blob leaves over a size limit in changes
+//! - **Redaction** — replace the values of changes under sensitive paths, such as passwords and tokens, with `<redacted>` before they are logged or serialized
+//! - **Shared trees** — apply changes to `Arc`-backed trees, sharing the unchanged subtrees between versions instead of cloning them
+//! - **Subscriptions** — call callbacks registered on path patterns for the changes touching them, as they are applied
+//! - **Transactions** — apply several lists of changes all or nothing, reporting which one doesn't fit
+//! - **Normalization** — drop the changes without effect from a list assembled from several sources, keep the last change to each key, and order them like a diff
+//! - **Diff cache** — memoize diffs by stable hashes of both versions, checked against them on a hit, in memory or in a store of your own, e.g. for services comparing the same artifacts repeatedly
+//! - **Tree edit distance** — minimal relabel, insert and delete node operations between two trees, matching structurally close subtrees across levels
 //! - **Hunks** — group changes with context lines
 //! - **Unified diff** — serialize and deserialize patches in unified diff format
+//! - **File diffs** — diff the lines of two files into hunks named after their paths
+//! - **Memory-mapped files** — diff the lines of huge text files as slices of their memory mappings, without copying them (`mmap` feature)
+//! - **Ropes** — diff `ropey::Rope`s line by line as slices of the ropes, and apply hunks to a rope in place, e.g. for editors (`ropey` feature)
+//! - **Binary deltas** — compact `bsdiff`-style deltas between arbitrary byte files, matched with a suffix array, in a stable format checked by hashes of both files, e.g. for updaters
+//! - **Delta sync** — `rsync`-style signatures of a file's blocks, and deltas computed from a signature instead of the file, to sync large files between machines without sending either copy
+//! - **Command line** — a `patchwork` binary to diff, apply and revert unified diffs of text files and JSON Patches of JSON documents (`cli` feature)
+//! - **Test assertions** — `assert_eq_diff!`, which fails with a colored unified diff of strings or a report of the changes between `Diffable` values instead of both values (`testing` feature)
+//! - **Multi-file patches** — parse git-style patches and apply them to a directory, `patch -pN` style
+//! - **Patch integrity** — embed hashes of each file before and after the patch in its header, and check them when applying it, to catch stale files and corrupted patches, or with SHA-256 (`sha256` feature) to detect tampering when the hashes are sent apart from the patch or signed
+//! - **Directory diffs** — diff two directory trees into a multi-file patch with created and deleted files, skipping `.gitignore`-style patterns, e.g. for deployment or backup deltas
+//! - **Patch emails** — read the author, date, message and patch of `git format-patch` output
+//! - **TOML** — diff `toml::Value`s and documents, e.g. to track `Cargo.toml` or config drift (`toml` feature)
+//! - **CSV** — diff tables cell by cell, pairing rows by position or by one or more key columns (`csv` feature)
+//! - **INI / dotenv** — parse `.ini` and `.env` files into maps of sections and keys, and write them back, to diff them key by key
+//! - **MessagePack / CBOR** — diff `rmpv` and `ciborium` values without going through JSON (`msgpack` and `cbor` features)
+//! - **JSON** — diff `serde_json::Value`s, export changes as RFC 6902 JSON Patch or RFC 7386 JSON Merge Patch documents, and import them back (`json` feature)
+//! - **WebAssembly** — builds for `wasm32-unknown-unknown`, except the `mmap` and `cli` features, with string-only entry points like `diff_json_strings` to export with `wasm-bindgen`, e.g. for a review UI in the browser (`json` feature)
 //!
 //! ## Quick Start
 //!
+//! The most used items can be imported at once with `use diffkit::prelude::*;`.
+//!
 //! A simple Vec of primitives can be diffed using Myers algorithm.
 //! The diff can be transformed into a series of [`patch::Hunk`]s.
 //! Hunks can be transformed into a textual diff or applied to an input.
@@ -19,7 +61,7 @@
 //!
 //! ```rust
 //! use diffkit::myers::diff;
-//! use diffkit::patch::{apply, hunks};
+//! use diffkit::patch::{apply, hunks, PatchHeader};
 //! use diffkit::serialization::ToPatch;
 //!
 //! let old = vec!["hello", "world"];
@@ -27,7 +69,7 @@
 //! let myers_edits = diff(&old, &new);
 //!
 //! let hunks = hunks(myers_edits);
-//! let patch = hunks.to_patch(Some("lib.rs"), Some("lib.rs"));
+//! let patch = hunks.to_patch(&PatchHeader::new("lib.rs", "lib.rs"));
 //!
 //! let equal_to_new = apply(&old, &hunks);
 //! ```
@@ -35,7 +77,7 @@
 //! For nested structures a recursive diffing algorithm is provided.
 //! The diff will return a list of [`recursive::Change`]s.
 //! Changes can be transformed into Hunks and applied.
-//! Changes cannot be serialized, since there is no consensus on a textual format.
+//! With the `json` feature, changes convert to and from JSON Patch documents.
 //!
 //! `apply(&old, hunks(diff(&old, &new))) == Ok(new)`
 //!
@@ -50,10 +92,16 @@
 //! new.insert("Hello".to_string(), 2);
 //! let changes = diff(&old, &new);
 //!
-//! let equal_to_new = apply(&old, &changes);
+//! let equal_to_new = apply(&old, &changes).unwrap();
 //! ```
 
+pub mod delta;
 pub mod myers;
 pub mod patch;
+pub mod prelude;
 pub mod recursive;
 pub mod serialization;
+#[cfg(any(feature = "zstd", feature = "deflate"))]
+pub mod store;
+#[cfg(feature = "testing")]
+pub mod testing;
@@ -0,0 +1,191 @@
+use crate::myers::{diff, Diff, Edit};
+use std::ops::Range;
+
+/// Which input of a diff is edited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Old,
+    New,
+}
+
+/// An edit to one input of a diff: the elements in `range` are replaced by `replacement`,
+/// like with [`Vec::splice`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Splice<T> {
+    pub side: Side,
+    pub range: Range<usize>,
+    pub replacement: Vec<T>,
+}
+
+/// Updates `diff` after one of its inputs was edited by `splice`, instead of diffing
+/// the whole inputs again, e.g. to refresh a live preview on every keystroke.
+///
+/// Only the part of the diff between the equal elements surrounding the edit is
+/// computed again; the rest is kept as it is. Both inputs are read from the diff itself.
+/// The result is a valid diff of the edited inputs, minimal around the edit,
+/// but it may pair elements differently than [`diff`] would.
+/// ```
+/// use diffkit::myers::{diff, rediff, Edit, Side, Splice};
+///
+/// let old = vec!["a", "b", "c", "d"];
+/// let mut new = vec!["a", "b", "x", "d"];
+/// let mut edits = diff(&old, &new);
+///
+/// new.splice(2..3, ["c"]);
+/// rediff(&mut edits, Splice { side: Side::New, range: 2..3, replacement: vec!["c"] });
+/// assert_eq!(edits, diff(&old, &new));
+/// ```
+///
+/// # Panics
+///
+/// Panics if the range is out of the bounds of the edited input.
+pub fn rediff<T: Eq + Clone>(diff: &mut Diff<T>, splice: Splice<T>) {
+    let Splice {
+        side,
+        range,
+        replacement,
+    } = splice;
+    let on_side = |edit: &Edit<T>| {
+        matches!(
+            (side, edit),
+            (_, Edit::Equal(_)) | (Side::Old, Edit::Delete(_)) | (Side::New, Edit::Insert(_))
+        )
+    };
+    let len = diff.iter().filter(|edit| on_side(edit)).count();
+    assert!(
+        range.start <= range.end && range.end <= len,
+        "range {:?} out of bounds for an input of length {}",
+        range,
+        len
+    );
+
+    // the window to diff again, between the equal elements surrounding the range
+    let (mut start, mut end) = (0, diff.len());
+    let (mut position, mut start_position) = (0, 0);
+    for (i, edit) in diff.iter().enumerate() {
+        let equal = matches!(edit, Edit::Equal(_));
+        if equal && position >= range.end {
+            end = i;
+            break;
+        }
+        if on_side(edit) {
+            position += 1;
+        }
+        if equal && position <= range.start {
+            (start, start_position) = (i + 1, position);
+        }
+    }
+
+    let mut old = vec![];
+    let mut new = vec![];
+    for edit in &diff[start..end] {
+        match edit {
+            Edit::Equal(e) => {
+                old.push(e.clone());
+                new.push(e.clone());
+            }
+            Edit::Delete(e) => old.push(e.clone()),
+            Edit::Insert(e) => new.push(e.clone()),
+        }
+    }
+    let edited = match side {
+        Side::Old => &mut old,
+        Side::New => &mut new,
+    };
+    edited.splice(
+        range.start - start_position..range.end - start_position,
+        replacement,
+    );
+    diff.splice(start..end, self::diff(&old, &new));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    /// The inputs of a diff.
+    fn inputs(diff: &Diff<u8>) -> (Vec<u8>, Vec<u8>) {
+        let mut old = vec![];
+        let mut new = vec![];
+        for edit in diff {
+            match edit {
+                Edit::Equal(e) => {
+                    old.push(*e);
+                    new.push(*e);
+                }
+                Edit::Delete(e) => old.push(*e),
+                Edit::Insert(e) => new.push(*e),
+            }
+        }
+        (old, new)
+    }
+
+    proptest! {
+        #[test]
+        fn test_rediff(
+            old: Vec<u8>,
+            new: Vec<u8>,
+            a: usize,
+            b: usize,
+            replacement: Vec<u8>,
+            edit_old: bool,
+        ) {
+            let mut edits = diff(&old, &new);
+            let (mut old, mut new) = (old, new);
+            let (side, edited) = if edit_old { (Side::Old, &mut old) } else { (Side::New, &mut new) };
+            let (a, b) = (a % (edited.len() + 1), b % (edited.len() + 1));
+            let range = a.min(b)..a.max(b);
+            edited.splice(range.clone(), replacement.iter().copied());
+            rediff(&mut edits, Splice { side, range, replacement });
+            prop_assert_eq!(inputs(&edits), (old, new));
+        }
+    }
+
+    #[test]
+    fn test_rediff_keeps_the_rest() {
+        let old: Vec<u8> = (0..100).collect();
+        let mut new = old.clone();
+        new[10] = 200;
+        new[90] = 201;
+        let mut edits = diff(&old, &new);
+        let before = edits.clone();
+
+        rediff(
+            &mut edits,
+            Splice {
+                side: Side::New,
+                range: 50..50,
+                replacement: vec![202, 203],
+            },
+        );
+        new.splice(50..50, [202, 203]);
+        assert_eq!(edits, diff(&old, &new));
+        assert_eq!(edits[..51], before[..51]);
+        assert_eq!(edits[53..], before[51..]);
+
+        rediff(
+            &mut edits,
+            Splice {
+                side: Side::Old,
+                range: 0..100,
+                replacement: vec![],
+            },
+        );
+        assert!(edits.iter().all(|edit| matches!(edit, Edit::Insert(_))));
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds")]
+    fn test_rediff_out_of_bounds() {
+        let mut edits = diff(&[1, 2], &[1]);
+        rediff(
+            &mut edits,
+            Splice {
+                side: Side::New,
+                range: 0..2,
+                replacement: vec![],
+            },
+        );
+    }
+}
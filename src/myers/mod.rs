@@ -1,4 +1,8 @@
+mod incremental;
+mod text;
 pub mod types;
+pub use incremental::*;
+pub use text::*;
 pub use types::*;
 
 use std::cmp::max;
@@ -144,6 +148,8 @@ fn traceback<T: Eq + Clone>(
 }
 
 #[cfg(test)]
+// the property tests clone their `u8` elements like any other element type
+#[allow(clippy::clone_on_copy)]
 mod tests {
     use super::*;
     use proptest::prelude::*;
@@ -0,0 +1,451 @@
+use crate::myers::{diff, Diff, Edit};
+use std::borrow::Cow;
+
+/// Computes the diff between two sequences comparing their elements by `key`,
+/// e.g. to ignore differences that don't matter, while keeping the elements
+/// themselves in the diff.
+///
+/// Equal elements are taken from `old`, so that the hunks of the diff apply to it.
+/// ```
+/// use diffkit::myers::{diff_by_key, Edit};
+///
+/// let old = vec!["Hello", "world"];
+/// let new = vec!["hello", "World", "!"];
+/// let result = diff_by_key(&old, &new, |s| s.to_lowercase());
+/// assert_eq!(result, vec![
+///     Edit::Equal("Hello"),
+///     Edit::Equal("world"),
+///     Edit::Insert("!"),
+/// ]);
+/// ```
+pub fn diff_by_key<T: Clone, K: Eq + Clone>(
+    old: &[T],
+    new: &[T],
+    key: impl Fn(&T) -> K,
+) -> Diff<T> {
+    let old_keys: Vec<K> = old.iter().map(&key).collect();
+    let new_keys: Vec<K> = new.iter().map(&key).collect();
+    diff_keys(old, new, &old_keys, &new_keys)
+}
+
+/// The diff of `old` and `new`, given the keys of their elements.
+fn diff_keys<T: Clone, K: Eq + Clone>(
+    old: &[T],
+    new: &[T],
+    old_keys: &[K],
+    new_keys: &[K],
+) -> Diff<T> {
+    let (mut old_index, mut new_index) = (0, 0);
+    diff(old_keys, new_keys)
+        .into_iter()
+        .map(|edit| match edit {
+            Edit::Equal(_) => {
+                old_index += 1;
+                new_index += 1;
+                Edit::Equal(old[old_index - 1].clone())
+            }
+            Edit::Delete(_) => {
+                old_index += 1;
+                Edit::Delete(old[old_index - 1].clone())
+            }
+            Edit::Insert(_) => {
+                new_index += 1;
+                Edit::Insert(new[new_index - 1].clone())
+            }
+        })
+        .collect()
+}
+
+/// Options of [`diff_lines_with`], changing how lines are compared.
+/// The diff still holds the lines as they are.
+///
+/// `ignore_line_endings` compares lines without their trailing `\r`, so that
+/// `\r\n` and `\n` line endings are the same, see [`TextOptions::ignore_line_endings`].
+///
+/// `tab_width` compares lines with their tabs expanded to spaces,
+/// see [`TextOptions::expand_tabs`].
+///
+/// `normalize_indent` compares the indentation of lines by level instead of width,
+/// see [`TextOptions::normalize_indent`].
+///
+/// `normalization` compares lines in a Unicode normalization form,
+/// with the `unicode` feature, see `TextOptions::normalize`.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+#[non_exhaustive]
+pub struct TextOptions {
+    pub ignore_line_endings: bool,
+    pub tab_width: Option<usize>,
+    pub normalize_indent: bool,
+    #[cfg(feature = "unicode")]
+    pub normalization: Option<Normalization>,
+}
+
+/// A Unicode normalization form, see [`TextOptions::normalize`].
+#[cfg(feature = "unicode")]
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Normalization {
+    /// Canonical composition: the same characters, e.g. `é` as one code point
+    /// or as `e` and a combining accent, compare equal.
+    Nfc,
+    /// Compatibility composition: additionally, characters with the same meaning,
+    /// e.g. the ligature `ﬁ` and `fi`, or a full-width and a regular `A`, compare equal.
+    Nfkc,
+}
+
+impl TextOptions {
+    /// The default options: lines are compared as they are.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compares lines without their line endings, e.g. so that a file converted
+    /// between `\r\n` and `\n` line endings by a checkout on another platform
+    /// isn't changed on every line. The diff keeps the line endings of each line,
+    /// and [`apply_with`](crate::patch::apply_with) those of the patched file.
+    /// ```
+    /// use diffkit::myers::{diff_lines_with, Edit, TextOptions};
+    ///
+    /// let options = TextOptions::new().ignore_line_endings();
+    /// let result = diff_lines_with("a\r\nb\r\n", "a\nc\n", &options);
+    /// assert_eq!(result, vec![
+    ///     Edit::Equal("a\r".to_string()),
+    ///     Edit::Insert("c".to_string()),
+    ///     Edit::Delete("b\r".to_string()),
+    ///     Edit::Equal(String::new()),
+    /// ]);
+    /// ```
+    pub fn ignore_line_endings(mut self) -> Self {
+        self.ignore_line_endings = true;
+        self
+    }
+
+    /// Compares lines with their tabs expanded to spaces, up to the next multiple
+    /// of `width` columns, so that indenting with tabs or with spaces is the same.
+    /// ```
+    /// use diffkit::myers::{diff_lines_with, Edit, TextOptions};
+    ///
+    /// let options = TextOptions::new().expand_tabs(4);
+    /// let result = diff_lines_with("\tx = 1", "    x = 1", &options);
+    /// assert_eq!(result, vec![Edit::Equal("\tx = 1".to_string())]);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `width` is 0.
+    pub fn expand_tabs(mut self, width: usize) -> Self {
+        assert!(width > 0, "the tab width must be positive");
+        self.tab_width = Some(width);
+        self
+    }
+
+    /// Compares the leading whitespace of lines by indentation level instead of width,
+    /// e.g. so that re-indenting a YAML file from 2 to 4 spaces, or code from tabs
+    /// to spaces, doesn't change every line, while moving a line to another level does.
+    ///
+    /// The indentation unit of each text is the greatest common divisor of the widths
+    /// of its indented lines, with tabs counted up to the next multiple of the tab width,
+    /// 8 unless set by [`expand_tabs`](TextOptions::expand_tabs).
+    /// Only [`diff_lines_with`] uses it, as a hunk alone doesn't tell the unit of its text.
+    /// The context lines of the diff come from the old text, so its hunks still apply
+    /// to it with [`apply_with`](crate::patch::apply_with).
+    /// ```
+    /// use diffkit::myers::{diff_lines_with, Edit, TextOptions};
+    ///
+    /// let old = "spec:\n  replicas: 3\n  ports:\n    - 80";
+    /// let new = "spec:\n    replicas: 3\n    ports:\n    - 80";
+    /// let options = TextOptions::new().normalize_indent();
+    /// let result = diff_lines_with(old, new, &options);
+    /// assert_eq!(result[3], Edit::Insert("    - 80".to_string()));
+    /// assert_eq!(result.len(), 5);
+    /// ```
+    pub fn normalize_indent(mut self) -> Self {
+        self.normalize_indent = true;
+        self
+    }
+
+    /// Compares lines in the normalization form `form`, so that lines that only
+    /// differ by how their characters are encoded aren't changes, with the
+    /// `unicode` feature.
+    /// ```
+    /// use diffkit::myers::{diff_lines_with, Edit, Normalization, TextOptions};
+    ///
+    /// let options = TextOptions::new().normalize(Normalization::Nfc);
+    /// let result = diff_lines_with("caf\u{e9}", "cafe\u{301}", &options);
+    /// assert_eq!(result, vec![Edit::Equal("caf\u{e9}".to_string())]);
+    /// ```
+    #[cfg(feature = "unicode")]
+    pub fn normalize(mut self, form: Normalization) -> Self {
+        self.normalization = Some(form);
+        self
+    }
+
+    /// What is compared of `line`, borrowed when it's the line itself.
+    /// The indentation is normalized separately, as it depends on the other lines.
+    pub fn key<'a>(&self, line: &'a str) -> Cow<'a, str> {
+        let line = match line.strip_suffix('\r') {
+            Some(stripped) if self.ignore_line_endings => stripped,
+            _ => line,
+        };
+        #[cfg(feature = "unicode")]
+        let line = match self.normalization {
+            Some(form) => normalize(line, form),
+            None => Cow::Borrowed(line),
+        };
+        #[cfg(not(feature = "unicode"))]
+        let line = Cow::Borrowed(line);
+        match self.tab_width {
+            Some(width) if line.contains('\t') => Cow::Owned(expand_tabs(&line, width)),
+            _ => line,
+        }
+    }
+
+    /// The keys of all the lines of a text.
+    fn keys<'a>(&self, lines: &[&'a str]) -> Vec<Cow<'a, str>> {
+        let keys: Vec<Cow<'a, str>> = lines.iter().map(|line| self.key(line)).collect();
+        if !self.normalize_indent {
+            return keys;
+        }
+        let tab_width = self.tab_width.unwrap_or(8);
+        let indented = |key: &str| !key.trim_start().is_empty() && key.starts_with([' ', '\t']);
+        let unit = keys
+            .iter()
+            .filter(|key| indented(key))
+            .map(|key| indent_width(key, tab_width))
+            .fold(0, gcd);
+        keys.into_iter()
+            .map(|key| {
+                if !indented(&key) {
+                    return key;
+                }
+                let level = indent_width(&key, tab_width) / unit;
+                let text = key.trim_start_matches([' ', '\t']);
+                Cow::Owned(format!("{}{}", "\t".repeat(level), text))
+            })
+            .collect()
+    }
+}
+
+/// `line` with its tabs replaced by spaces up to the next multiple of `width` columns.
+fn expand_tabs(line: &str, width: usize) -> String {
+    let mut expanded = String::with_capacity(line.len());
+    let mut column = 0;
+    for c in line.chars() {
+        if c == '\t' {
+            let spaces = width - column % width;
+            expanded.extend(std::iter::repeat_n(' ', spaces));
+            column += spaces;
+        } else {
+            expanded.push(c);
+            column += 1;
+        }
+    }
+    expanded
+}
+
+/// The width in columns of the leading spaces and tabs of `line`.
+fn indent_width(line: &str, tab_width: usize) -> usize {
+    line.chars()
+        .take_while(|c| matches!(c, ' ' | '\t'))
+        .fold(0, |column, c| match c {
+            '\t' => column + tab_width - column % tab_width,
+            _ => column + 1,
+        })
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+#[cfg(feature = "unicode")]
+fn normalize(text: &str, form: Normalization) -> Cow<'_, str> {
+    use unicode_normalization::{is_nfc_quick, is_nfkc_quick, IsNormalized, UnicodeNormalization};
+
+    let normalized = match form {
+        Normalization::Nfc => is_nfc_quick(text.chars()) == IsNormalized::Yes,
+        Normalization::Nfkc => is_nfkc_quick(text.chars()) == IsNormalized::Yes,
+    };
+    if normalized {
+        return Cow::Borrowed(text);
+    }
+    match form {
+        Normalization::Nfc => Cow::Owned(text.nfc().collect()),
+        Normalization::Nfkc => Cow::Owned(text.nfkc().collect()),
+    }
+}
+
+/// Computes the diff between the lines of two strings like [`diff_lines`](crate::myers::diff_lines),
+/// comparing them as set by `options`. The diff holds the original lines,
+/// those of `old` for equal lines.
+pub fn diff_lines_with(old: &str, new: &str, options: &TextOptions) -> Diff<String> {
+    let old_lines: Vec<&str> = old.split('\n').collect();
+    let new_lines: Vec<&str> = new.split('\n').collect();
+    diff_keys(
+        &old_lines,
+        &new_lines,
+        &options.keys(&old_lines),
+        &options.keys(&new_lines),
+    )
+    .into_iter()
+    .map(|edit| match edit {
+        Edit::Equal(line) => Edit::Equal(line.to_string()),
+        Edit::Delete(line) => Edit::Delete(line.to_string()),
+        Edit::Insert(line) => Edit::Insert(line.to_string()),
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::myers::diff_lines;
+
+    #[test]
+    fn test_diff_by_key() {
+        let old = [(1, 'a'), (2, 'b'), (3, 'c')];
+        let new = [(2, 'x'), (3, 'y'), (4, 'z')];
+        assert_eq!(
+            diff_by_key(&old, &new, |&(n, _)| n),
+            vec![
+                Edit::Delete((1, 'a')),
+                Edit::Equal((2, 'b')),
+                Edit::Equal((3, 'c')),
+                Edit::Insert((4, 'z')),
+            ]
+        );
+        assert_eq!(diff_by_key(&old, &new, |&x| x), diff(&old, &new));
+    }
+
+    #[test]
+    fn test_diff_lines_with_default_options() {
+        let (old, new) = ("a\nb\nc", "a\nc\nd");
+        assert_eq!(
+            diff_lines_with(old, new, &TextOptions::new()),
+            diff_lines(old, new)
+        );
+    }
+
+    #[test]
+    fn test_ignore_line_endings() {
+        use crate::patch::{apply_with, hunks};
+
+        let old = "a\r\nb\r\nc\r\n";
+        let new = "a\nb\nx\n";
+        let options = TextOptions::new().ignore_line_endings();
+        let diff = diff_lines_with(old, new, &options);
+        assert_eq!(
+            diff.iter().filter(|e| !matches!(e, Edit::Equal(_))).count(),
+            2
+        );
+        assert_eq!(diff_lines(old, new).len(), 7);
+
+        // applied with the line endings of the patched file
+        let hunks = hunks(diff);
+        let lines = |text: &str| {
+            text.split('\n')
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            apply_with(&lines(old), &hunks, &options)
+                .unwrap()
+                .join("\n"),
+            "a\r\nb\r\nx\r\n"
+        );
+        assert_eq!(
+            apply_with(&lines("a\nb\nc\n"), &hunks, &options)
+                .unwrap()
+                .join("\n"),
+            new
+        );
+        // or exactly, without the option
+        assert!(apply_with(&lines("a\nb\nc\n"), &hunks, &TextOptions::new()).is_err());
+        assert_eq!(
+            apply_with(&lines(old), &hunks, &TextOptions::new())
+                .unwrap()
+                .join("\n"),
+            "a\r\nb\r\nx\n"
+        );
+    }
+
+    #[test]
+    fn test_expand_tabs() {
+        assert_eq!(expand_tabs("\tx", 4), "    x");
+        assert_eq!(expand_tabs("ab\tc\t", 4), "ab  c   ");
+        assert_eq!(expand_tabs("abcd\tx", 4), "abcd    x");
+        assert_eq!(indent_width(" \t  x", 4), 6);
+
+        let options = TextOptions::new().expand_tabs(8);
+        assert_eq!(diff_lines_with("\tx", "    x", &options).len(), 2);
+        assert_eq!(diff_lines_with("\tx", "        x", &options).len(), 1);
+        assert!(matches!(options.key("no tabs"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_normalize_indent() {
+        let options = TextOptions::new().normalize_indent();
+        // from tabs to 4 spaces, with a blank line holding stray spaces
+        let old = "fn main() {\n\tif x {\n\t\ty();\n  \n\t}\n}";
+        let new = "fn main() {\n    if x {\n        y();\n  \n    }\n}";
+        assert!(diff_lines_with(old, new, &options)
+            .iter()
+            .all(|edit| matches!(edit, Edit::Equal(_))));
+        assert_eq!(diff_lines_with(old, new, &TextOptions::new()).len(), 9);
+
+        // a line moved to another level is still a change
+        let new = "fn main() {\n    if x {\n    y();\n  \n    }\n}";
+        assert_eq!(
+            diff_lines_with(old, new, &options)
+                .iter()
+                .filter(|edit| !matches!(edit, Edit::Equal(_)))
+                .count(),
+            2
+        );
+
+        // tabs count as set by `expand_tabs`
+        let old = "a:\n\tb";
+        let new = "a:\n  b\n    c";
+        let options = options.expand_tabs(2);
+        assert_eq!(
+            diff_lines_with(old, new, &options)[1],
+            Edit::Equal("\tb".to_string())
+        );
+    }
+
+    #[cfg(feature = "unicode")]
+    #[test]
+    fn test_normalization() {
+        use crate::patch::{apply, hunks};
+
+        // "Å" as one code point, as `A` and a ring, and as the angstrom sign
+        let old = "\u{c5}ngstr\u{f6}m\nrest";
+        let new = "A\u{30a}ngstro\u{308}m\n\u{212b}\nrest";
+        let nfc = TextOptions::new().normalize(Normalization::Nfc);
+        assert_eq!(
+            diff_lines_with(old, new, &nfc),
+            vec![
+                Edit::Equal("\u{c5}ngstr\u{f6}m".to_string()),
+                Edit::Insert("\u{212b}".to_string()),
+                Edit::Equal("rest".to_string()),
+            ]
+        );
+        assert_eq!(diff_lines_with(old, new, &TextOptions::new()).len(), 4);
+
+        let (old, new) = ("\u{fb01}le\nx", "file\ny");
+        assert!(diff_lines_with(old, new, &nfc).contains(&Edit::Delete("\u{fb01}le".to_string())));
+        let nfkc = TextOptions::new().normalize(Normalization::Nfkc);
+        let diff = diff_lines_with(old, new, &nfkc);
+        assert_eq!(diff[0], Edit::Equal("\u{fb01}le".to_string()));
+        // the hunks still apply to the original lines
+        let old_lines: Vec<String> = old.split('\n').map(ToString::to_string).collect();
+        assert_eq!(
+            apply(&old_lines, &hunks(diff)).unwrap(),
+            ["\u{fb01}le", "y"]
+        );
+        assert_eq!(nfkc.key("\u{fb01}"), "fi");
+        assert!(matches!(nfkc.key("plain"), Cow::Borrowed(_)));
+    }
+}
@@ -0,0 +1,203 @@
+use crate::myers::{Diff, Edit};
+use crate::patch::{Column, CombinedHunk, CombinedLine};
+
+const CONTEXT: usize = 3;
+
+/// Generates combined hunks for a merge result from the diffs of each
+/// parent against it, i.e. `diffs[i] == diff(&parents[i], &result)`.
+///
+/// Lines removed from several parents at the same position are shown once
+/// when they are equal. Hunks keep up to 3 context lines, like [`super::hunks`].
+/// ```
+/// use diffkit::myers::diff;
+/// use diffkit::patch::{combined_hunks, Column};
+///
+/// let ours = vec!["a", "b", "c"];
+/// let theirs = vec!["a", "x", "c"];
+/// let merged = vec!["a", "b", "x", "c"];
+/// let hunks = combined_hunks(&[diff(&ours, &merged), diff(&theirs, &merged)]);
+/// assert_eq!(hunks.len(), 1);
+/// assert_eq!(hunks[0].lines[1].columns, vec![Column::Context, Column::Added]);
+/// assert_eq!(hunks[0].lines[2].columns, vec![Column::Added, Column::Context]);
+/// ```
+///
+/// # Panics
+///
+/// Panics if the diffs don't all lead to a result of the same length.
+pub fn combined_hunks<T: Eq + Clone>(diffs: &[Diff<T>]) -> Vec<CombinedHunk<T>> {
+    let lines = combined_lines(diffs);
+
+    let interesting: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, l)| l.columns.iter().any(|c| *c != Column::Context))
+        .map(|(i, _)| i)
+        .collect();
+
+    // merge the context windows around interesting lines into ranges
+    let mut ranges: Vec<(usize, usize)> = vec![];
+    for i in interesting {
+        let start = i.saturating_sub(CONTEXT);
+        let end = (i + CONTEXT + 1).min(lines.len());
+        match ranges.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+
+    let mut hunks = vec![];
+    let mut parent_lines = vec![0; diffs.len()];
+    let mut new_line = 0;
+    let mut position = 0;
+    for (start, end) in ranges {
+        for line in &lines[position..start] {
+            advance(line, &mut parent_lines, &mut new_line);
+        }
+        let hunk = CombinedHunk {
+            parent_starts: parent_lines.clone(),
+            new_start: new_line,
+            lines: lines[start..end].to_vec(),
+        };
+        for line in &hunk.lines {
+            advance(line, &mut parent_lines, &mut new_line);
+        }
+        hunks.push(hunk);
+        position = end;
+    }
+    hunks
+}
+
+fn advance<T>(line: &CombinedLine<T>, parent_lines: &mut [usize], new_line: &mut usize) {
+    for (parent, count) in parent_lines.iter_mut().enumerate() {
+        if line.in_parent(parent) {
+            *count += 1;
+        }
+    }
+    if line.in_result() {
+        *new_line += 1;
+    }
+}
+
+/// Builds every line of the combined diff, including unchanged ones.
+fn combined_lines<T: Eq + Clone>(diffs: &[Diff<T>]) -> Vec<CombinedLine<T>> {
+    let parents = diffs.len();
+    let mut result: Vec<T> = vec![];
+    // present[i][r]: whether result line r is in parent i
+    let mut present: Vec<Vec<bool>> = vec![];
+    // lost[i][r]: lines of parent i removed right before result line r
+    let mut lost: Vec<Vec<Vec<T>>> = vec![];
+
+    for (parent, diff) in diffs.iter().enumerate() {
+        let mut parent_present = vec![];
+        let mut parent_lost = vec![vec![]];
+        for edit in diff {
+            match edit {
+                Edit::Delete(el) => parent_lost.last_mut().unwrap().push(el.clone()),
+                Edit::Equal(el) | Edit::Insert(el) => {
+                    if parent == 0 {
+                        result.push(el.clone());
+                    }
+                    parent_present.push(matches!(edit, Edit::Equal(_)));
+                    parent_lost.push(vec![]);
+                }
+            }
+        }
+        assert_eq!(
+            parent_present.len(),
+            present.first().map_or(parent_present.len(), Vec::len),
+            "diffs must lead to the same result"
+        );
+        present.push(parent_present);
+        lost.push(parent_lost);
+    }
+
+    let mut lines = vec![];
+    for r in 0..=result.len() {
+        let mut removed: Vec<CombinedLine<T>> = vec![];
+        for (parent, parent_lost) in lost.iter().enumerate() {
+            // share equal lines already removed from a previous parent,
+            // keeping the order of both
+            let mut cursor = 0;
+            for el in &parent_lost[r] {
+                match removed[cursor..]
+                    .iter()
+                    .position(|l| l.value == *el && l.columns[parent] == Column::Context)
+                {
+                    Some(found) => {
+                        removed[cursor + found].columns[parent] = Column::Removed;
+                        cursor += found + 1;
+                    }
+                    None => {
+                        let mut columns = vec![Column::Context; parents];
+                        columns[parent] = Column::Removed;
+                        removed.insert(
+                            cursor,
+                            CombinedLine {
+                                columns,
+                                value: el.clone(),
+                            },
+                        );
+                        cursor += 1;
+                    }
+                }
+            }
+        }
+        lines.append(&mut removed);
+
+        if let Some(el) = result.get(r) {
+            lines.push(CombinedLine {
+                columns: present
+                    .iter()
+                    .map(|p| if p[r] { Column::Context } else { Column::Added })
+                    .collect(),
+                value: el.clone(),
+            });
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::myers::diff;
+
+    #[test]
+    fn test_shared_removal_is_shown_once() {
+        let ours = vec!["a", "gone", "b"];
+        let theirs = vec!["a", "gone", "b"];
+        let merged = vec!["a", "b"];
+        let hunks = combined_hunks(&[diff(&ours, &merged), diff(&theirs, &merged)]);
+        assert_eq!(
+            hunks[0].lines,
+            vec![
+                CombinedLine {
+                    columns: vec![Column::Context, Column::Context],
+                    value: "a"
+                },
+                CombinedLine {
+                    columns: vec![Column::Removed, Column::Removed],
+                    value: "gone"
+                },
+                CombinedLine {
+                    columns: vec![Column::Context, Column::Context],
+                    value: "b"
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_hunk_starts() {
+        let ours: Vec<u8> = (0..10).collect();
+        let mut theirs = ours.clone();
+        theirs.insert(0, 100);
+        let mut merged = ours.clone();
+        merged[8] = 42;
+        let hunks = combined_hunks(&[diff(&ours, &merged), diff(&theirs, &merged)]);
+        assert_eq!(hunks.len(), 2);
+        assert_eq!(hunks[0].parent_starts, vec![0, 0]);
+        assert_eq!(hunks[1].parent_starts, vec![5, 6]);
+        assert_eq!(hunks[1].new_start, 5);
+    }
+}
@@ -0,0 +1,900 @@
+use crate::myers::{diff, Edit};
+use crate::patch::{apply_verified, hunks, Patch, PatchFile, PatchHeader, Relation, DEV_NULL};
+use crate::serialization::PatchError;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// Strips `level` leading components from `path`, like `patch -p<level>`.
+/// Consecutive slashes count as one separator.
+/// Returns `None` if `path` has fewer than `level` components to strip.
+/// ```
+/// use diffkit::patch::strip_path;
+///
+/// assert_eq!(strip_path("a/src/lib.rs", 0), Some("a/src/lib.rs"));
+/// assert_eq!(strip_path("a/src/lib.rs", 1), Some("src/lib.rs"));
+/// assert_eq!(strip_path("a/src/lib.rs", 3), None);
+/// ```
+pub fn strip_path(path: &str, level: usize) -> Option<&str> {
+    let mut rest = path;
+    for _ in 0..level {
+        let (_, tail) = rest.split_once('/')?;
+        rest = tail.trim_start_matches('/');
+    }
+    Some(rest)
+}
+
+/// Diffs the lines of two files, naming them by their paths in the header.
+///
+/// Like in `git diff`, each `\n`-terminated line of a file is one element,
+/// and a last line without a newline differs from the same line with one:
+/// the header then records the missing newline, see [`PatchHeader`].
+/// The hunks can be written with [`ToPatch`](crate::serialization::ToPatch),
+/// which takes the header of the file:
+/// ```no_run
+/// use std::path::Path;
+/// use diffkit::patch::{diff_files, PatchHeader};
+/// use diffkit::serialization::ToPatch;
+///
+/// let file = diff_files(Path::new("old/config.ini"), Path::new("new/config.ini")).unwrap();
+/// print!("{}", file.to_patch(&PatchHeader::default()));
+/// ```
+///
+/// # Errors
+///
+/// Returns the error of [`fs::read_to_string`] if a file can't be read,
+/// or isn't UTF-8.
+pub fn diff_files(old: &Path, new: &Path) -> io::Result<PatchFile<String>> {
+    Ok(file_diff(
+        old.display().to_string(),
+        new.display().to_string(),
+        &split_lines(&fs::read_to_string(old)?),
+        &split_lines(&fs::read_to_string(new)?),
+        None,
+    ))
+}
+
+/// Builds a multi-file patch between two sets of files, keyed by name.
+///
+/// Names get git's `a/` and `b/` prefixes, see [`apply_to_dir`].
+/// A file only found in `new` is reported as a rename of a file only found in `old`,
+/// or otherwise as a copy of any file in `old`, when they share at least
+/// `min_similarity` percent of their lines. Otherwise it's a creation,
+/// and files only found in `old` are deletions.
+/// ```
+/// use std::collections::BTreeMap;
+/// use diffkit::patch::{diff_file_sets, Relation};
+///
+/// let old = BTreeMap::from([("lib.rs".to_string(), "a\nb\nc\nd\n".to_string())]);
+/// let new = BTreeMap::from([("main.rs".to_string(), "a\nb\nc\nx\n".to_string())]);
+/// let patch = diff_file_sets(&old, &new, 50);
+/// assert_eq!(patch.files[0].header.relation, Some(Relation::Rename { similarity: 75 }));
+/// ```
+pub fn diff_file_sets(
+    old: &BTreeMap<String, String>,
+    new: &BTreeMap<String, String>,
+    min_similarity: u8,
+) -> Patch<String> {
+    diff_named(old, new, Some(min_similarity))
+}
+
+/// Builds the patch of [`diff_file_sets`], without looking for renames
+/// and copies if `min_similarity` is `None`.
+fn diff_named(
+    old: &BTreeMap<String, String>,
+    new: &BTreeMap<String, String>,
+    min_similarity: Option<u8>,
+) -> Patch<String> {
+    let lines = |content: &String| split_lines(content);
+    let mut files = vec![];
+    let mut renamed = BTreeSet::new();
+
+    for (name, content) in new {
+        let new_lines = lines(content);
+        if let Some(old_content) = old.get(name) {
+            if old_content != content {
+                files.push(file_diff(
+                    format!("a/{}", name),
+                    format!("b/{}", name),
+                    &lines(old_content),
+                    &new_lines,
+                    None,
+                ));
+            }
+            continue;
+        }
+
+        // prefer renaming a vanished file, then copying any file
+        let vanished = old
+            .iter()
+            .filter(|(n, _)| !new.contains_key(*n) && !renamed.contains(*n));
+        let rename = min_similarity.and_then(|min| best_match(vanished, &new_lines.0, min));
+        let (old_name, relation) = match rename {
+            Some((similarity, old_name)) => {
+                renamed.insert(old_name.clone());
+                (old_name, Some(Relation::Rename { similarity }))
+            }
+            None => {
+                match min_similarity.and_then(|min| best_match(old.iter(), &new_lines.0, min)) {
+                    Some((similarity, old_name)) => (old_name, Some(Relation::Copy { similarity })),
+                    None => {
+                        files.push(file_diff(
+                            DEV_NULL.to_string(),
+                            format!("b/{}", name),
+                            &(vec![], true),
+                            &new_lines,
+                            None,
+                        ));
+                        continue;
+                    }
+                }
+            }
+        };
+        files.push(file_diff(
+            format!("a/{}", old_name),
+            format!("b/{}", name),
+            &lines(&old[old_name]),
+            &new_lines,
+            relation,
+        ));
+    }
+
+    for (name, content) in old {
+        if !new.contains_key(name) && !renamed.contains(name) {
+            files.push(file_diff(
+                format!("a/{}", name),
+                DEV_NULL.to_string(),
+                &lines(content),
+                &(vec![], true),
+                None,
+            ));
+        }
+    }
+    Patch { files }
+}
+
+/// Diffs two files given as their lines and whether they end with a newline,
+/// like [`split_lines`] returns them.
+fn file_diff(
+    old_name: String,
+    new_name: String,
+    old: &(Vec<String>, bool),
+    new: &(Vec<String>, bool),
+    relation: Option<Relation>,
+) -> PatchFile<String> {
+    let header = PatchHeader {
+        relation,
+        ..PatchHeader::new(old_name, new_name)
+    };
+    diff_lines(header, (&old.0, old.1), (&new.0, new.1))
+}
+
+/// Diffs the lines of two files, each given with whether it ends with a newline,
+/// setting the missing newlines that the last hunk reaches in `header`.
+pub(crate) fn diff_lines<T: Eq + Clone>(
+    mut header: PatchHeader,
+    old: (&[T], bool),
+    new: (&[T], bool),
+) -> PatchFile<T> {
+    // like in git, a last line without its newline isn't equal to the same line with it
+    let edits = diff(&terminated(old), &terminated(new))
+        .into_iter()
+        .map(|edit| match edit {
+            Edit::Equal((line, _)) => Edit::Equal(line.clone()),
+            Edit::Delete((line, _)) => Edit::Delete(line.clone()),
+            Edit::Insert((line, _)) => Edit::Insert(line.clone()),
+        })
+        .collect();
+    let hunks = hunks(edits);
+    // where the last hunk ends in each file
+    let (old_end, new_end) = hunks.last().map_or((0, 0), |h| {
+        let count = |kind: fn(&Edit<T>) -> bool| h.changes.iter().filter(|e| kind(e)).count();
+        let inserted = count(|e| matches!(e, Edit::Insert(_)));
+        let deleted = count(|e| matches!(e, Edit::Delete(_)));
+        (
+            h.old_start + h.changes.len() - inserted,
+            h.new_start + h.changes.len() - deleted,
+        )
+    });
+    header.old_missing_newline = !old.1 && old_end == old.0.len();
+    header.new_missing_newline = !new.1 && new_end == new.0.len();
+    PatchFile { header, hunks }
+}
+
+/// The lines of a file, each with whether it ends with a newline.
+fn terminated<T>((lines, trailing_newline): (&[T], bool)) -> Vec<(&T, bool)> {
+    let last = lines.len().saturating_sub(1);
+    let lines = lines.iter().enumerate();
+    lines
+        .map(|(i, l)| (l, trailing_newline || i < last))
+        .collect()
+}
+
+/// The most similar candidate, if it reaches `min_similarity`.
+fn best_match<'a>(
+    candidates: impl Iterator<Item = (&'a String, &'a String)>,
+    new: &[String],
+    min_similarity: u8,
+) -> Option<(u8, &'a String)> {
+    candidates
+        .map(|(name, content)| (similarity(&split_lines(content).0, new), name))
+        .filter(|(similarity, _)| *similarity >= min_similarity)
+        .max_by_key(|(similarity, _)| *similarity)
+}
+
+/// Percentage of lines shared by both files.
+fn similarity(old: &[String], new: &[String]) -> u8 {
+    if old.is_empty() && new.is_empty() {
+        return 100;
+    }
+    let equal = diff(old, new)
+        .iter()
+        .filter(|e| matches!(e, Edit::Equal(_)))
+        .count();
+    (200 * equal / (old.len() + new.len())) as u8
+}
+
+/// Options of [`diff_dirs`], built from the defaults with its builder methods:
+/// `DirDiffOptions::new().ignore("target").ignore("*.log")`.
+///
+/// `ignore` lists glob patterns over the paths of files and directories relative
+/// to the compared directories, with `/` separators. `*` matches any part
+/// of a name, `?` a single character and `**` any number of directories.
+/// Like in `.gitignore`, a pattern without `/`, but at its end, matches a name
+/// at any depth, while the others match from the compared directories.
+/// An ignored directory is skipped along with everything under it.
+///
+/// `min_similarity` reports files only found in the new directory as renames
+/// or copies of files sharing that percentage of their lines, see [`diff_file_sets`].
+/// Without it, they are creations.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DirDiffOptions {
+    pub ignore: Vec<String>,
+    pub min_similarity: Option<u8>,
+}
+
+impl DirDiffOptions {
+    /// The default options: nothing ignored, no renames.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Skips the files and directories matching `pattern`.
+    pub fn ignore(mut self, pattern: impl Into<String>) -> Self {
+        self.ignore.push(pattern.into());
+        self
+    }
+
+    /// Detects renames and copies of files sharing `min_similarity` percent of their lines.
+    pub fn detect_renames(mut self, min_similarity: u8) -> Self {
+        self.min_similarity = Some(min_similarity);
+        self
+    }
+
+    /// Whether the file or directory at `path`, relative to the compared directories,
+    /// is skipped.
+    pub fn ignores(&self, path: &str) -> bool {
+        self.ignore.iter().any(|pattern| {
+            let pattern = pattern.strip_suffix('/').unwrap_or(pattern);
+            let path: Vec<&str> = path.split('/').collect();
+            if pattern.contains('/') {
+                let pattern: Vec<&str> = pattern.trim_start_matches('/').split('/').collect();
+                glob_path(&pattern, &path)
+            } else {
+                path.last().is_some_and(|name| glob(pattern, name))
+            }
+        })
+    }
+}
+
+/// Builds a multi-file patch between the files under two directories,
+/// e.g. for the delta between two deployments or backups.
+///
+/// Files are named by their paths relative to the directories, with git's
+/// `a/` and `b/` prefixes like [`diff_file_sets`]: files only found under `new`
+/// are created, and files only found under `old` deleted. Symbolic links under
+/// the directories are skipped, so that a link to a parent can't loop.
+/// ```no_run
+/// use std::path::Path;
+/// use diffkit::patch::{diff_dirs, DirDiffOptions, PatchHeader};
+/// use diffkit::serialization::ToPatch;
+///
+/// let options = DirDiffOptions::new().ignore("target").ignore("*.log");
+/// let patch = diff_dirs(Path::new("release-1"), Path::new("release-2"), &options).unwrap();
+/// print!("{}", patch.to_patch(&PatchHeader::default()));
+/// ```
+///
+/// # Errors
+///
+/// Returns the errors of reading the directories, and [`io::ErrorKind::InvalidData`]
+/// for a file that isn't UTF-8. The error messages name the path involved.
+pub fn diff_dirs(old: &Path, new: &Path, options: &DirDiffOptions) -> io::Result<Patch<String>> {
+    let old = read_dir(old, options)?;
+    let new = read_dir(new, options)?;
+    Ok(diff_named(&old, &new, options.min_similarity))
+}
+
+/// The contents of the files under `dir` not ignored by `options`,
+/// keyed by their relative paths.
+fn read_dir(dir: &Path, options: &DirDiffOptions) -> io::Result<BTreeMap<String, String>> {
+    let with_path =
+        |path: &Path, e: io::Error| io::Error::new(e.kind(), format!("{}: {}", path.display(), e));
+    let mut files = BTreeMap::new();
+    let mut pending = vec![(dir.to_path_buf(), String::new())];
+    while let Some((path, relative)) = pending.pop() {
+        if !relative.is_empty() && options.ignores(&relative) {
+            continue;
+        }
+        // the compared directories may be links themselves
+        let metadata = match relative.as_str() {
+            "" => fs::metadata(&path),
+            _ => fs::symlink_metadata(&path),
+        }
+        .map_err(|e| with_path(&path, e))?;
+        if metadata.is_symlink() {
+            continue;
+        }
+        if metadata.is_dir() {
+            for entry in fs::read_dir(&path).map_err(|e| with_path(&path, e))? {
+                let entry = entry.map_err(|e| with_path(&path, e))?;
+                let name = entry.file_name().to_string_lossy().into_owned();
+                let relative = match relative.as_str() {
+                    "" => name,
+                    parent => format!("{}/{}", parent, name),
+                };
+                pending.push((entry.path(), relative));
+            }
+        } else {
+            let content = fs::read_to_string(&path).map_err(|e| with_path(&path, e))?;
+            files.insert(relative, content);
+        }
+    }
+    Ok(files)
+}
+
+/// Whether the segments of `path` match those of `pattern`, where `**` matches
+/// any number of segments.
+fn glob_path(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| glob_path(rest, &path[skip..])),
+        Some((first, rest)) => path
+            .split_first()
+            .is_some_and(|(segment, path)| glob(first, segment) && glob_path(rest, path)),
+    }
+}
+
+/// Whether `name` matches `pattern`, where `*` matches any characters and `?` one.
+fn glob(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    // the positions of the last `*` and of the character it's been matched up to
+    let (mut p, mut n) = (0, 0);
+    let mut star = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Applies a multi-file patch to the files under `dir`.
+///
+/// File names are resolved against `dir` after stripping `strip` leading
+/// components, see [`strip_path`]. Use `1` for patches produced by `git diff`,
+/// whose names carry `a/` and `b/` prefixes.
+/// Files whose old name is [`DEV_NULL`] are created, along with their
+/// parent directories; files whose new name is `DEV_NULL` are removed.
+/// Renamed and copied files are read from their old name and written to the new one.
+///
+/// Like in `git diff`, each `\n`-terminated line of a file is one element.
+/// A patched file ends with a newline unless the header says it's missing,
+/// see [`PatchHeader`]; when the header says nothing about it, e.g. because
+/// the last hunk doesn't reach the end of the file, the file keeps its own end.
+///
+/// All files are patched in memory before anything is written,
+/// so a hunk that fails to apply leaves `dir` untouched.
+///
+/// # Errors
+///
+/// Returns [`PatchError::Io`] if a file can't be read or written,
+/// or its name can't be stripped.
+/// Returns [`PatchError::UnsafePath`] if a stripped name is absolute
+/// or has a `..` component, which would reach outside `dir`.
+/// Returns [`PatchError::FileConflict`] if a created file already exists,
+/// or a deleted file has lines left that its hunks don't remove.
+/// Returns the error of [`apply_verified`] if a hunk doesn't match its file,
+/// or the file doesn't have the hashes of its header.
+pub fn apply_to_dir(dir: &Path, patch: &Patch<String>, strip: usize) -> Result<(), PatchError> {
+    let mut actions = vec![];
+    for file in &patch.files {
+        let header = &file.header;
+        if header.is_creation() {
+            let target = resolve(dir, &header.new_name, strip)?;
+            if target.exists() {
+                return Err(PatchError::FileConflict(format!(
+                    "{}: already exists",
+                    target.display()
+                )));
+            }
+            let new = apply_verified(&[], file)?;
+            let trailing_newline = !header.new_missing_newline;
+            actions.push(Action::Write(target, to_content(&new, trailing_newline)));
+        } else if header.is_deletion() {
+            let target = resolve(dir, &header.old_name, strip)?;
+            let (old, _) = read_lines(&target)?;
+            if !apply_verified(&old, file)?.is_empty() {
+                return Err(PatchError::FileConflict(format!(
+                    "{}: not empty after deletion",
+                    target.display()
+                )));
+            }
+            actions.push(Action::Remove(target));
+        } else if let Some(relation) = header.relation {
+            let source = resolve(dir, &header.old_name, strip)?;
+            let target = resolve(dir, &header.new_name, strip)?;
+            let (old, trailing_newline) = read_lines(&source)?;
+            let new = apply_verified(&old, file)?;
+            let trailing_newline = ends_with_newline(header, trailing_newline);
+            actions.push(Action::Write(target, to_content(&new, trailing_newline)));
+            if matches!(relation, Relation::Rename { .. }) {
+                actions.push(Action::Remove(source));
+            }
+        } else {
+            let target = resolve(dir, &header.new_name, strip)?;
+            let (old, trailing_newline) = read_lines(&target)?;
+            let new = apply_verified(&old, file)?;
+            let trailing_newline = ends_with_newline(header, trailing_newline);
+            actions.push(Action::Write(target, to_content(&new, trailing_newline)));
+        }
+    }
+
+    for action in actions {
+        match action {
+            Action::Write(target, content) => {
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent).map_err(|e| io_error(parent, e))?;
+                }
+                fs::write(&target, content).map_err(|e| io_error(&target, e))?;
+            }
+            Action::Remove(target) => {
+                fs::remove_file(&target).map_err(|e| io_error(&target, e))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether a patched file ends with a newline: as its header says when it
+/// records a missing newline on either side, or like the old file otherwise.
+fn ends_with_newline(header: &PatchHeader, old_trailing_newline: bool) -> bool {
+    if header.old_missing_newline || header.new_missing_newline {
+        !header.new_missing_newline
+    } else {
+        old_trailing_newline
+    }
+}
+
+enum Action {
+    Write(PathBuf, String),
+    Remove(PathBuf),
+}
+
+fn resolve(dir: &Path, name: &str, strip: usize) -> Result<PathBuf, PatchError> {
+    let stripped = strip_path(name, strip).ok_or_else(|| {
+        PatchError::Io(format!("cannot strip {} components from '{}'", strip, name))
+    })?;
+    // like `patch` and `git apply`, never follow a patch out of `dir`
+    let escapes = Path::new(stripped).components().any(|c| {
+        matches!(
+            c,
+            Component::Prefix(_) | Component::RootDir | Component::ParentDir
+        )
+    });
+    if escapes {
+        return Err(PatchError::UnsafePath(name.to_string()));
+    }
+    Ok(dir.join(stripped))
+}
+
+/// Reads the lines of a file, and whether its last line ends with a newline.
+fn read_lines(path: &Path) -> Result<(Vec<String>, bool), PatchError> {
+    let content = fs::read_to_string(path).map_err(|e| io_error(path, e))?;
+    Ok(split_lines(&content))
+}
+
+fn split_lines(content: &str) -> (Vec<String>, bool) {
+    let trailing_newline = content.is_empty() || content.ends_with('\n');
+    let lines = content.strip_suffix('\n').unwrap_or(content);
+    if lines.is_empty() && trailing_newline {
+        return (vec![], true);
+    }
+    (
+        lines.split('\n').map(ToString::to_string).collect(),
+        trailing_newline,
+    )
+}
+
+fn to_content(lines: &[String], trailing_newline: bool) -> String {
+    let mut content = lines.join("\n");
+    if trailing_newline && !lines.is_empty() {
+        content.push('\n');
+    }
+    content
+}
+
+fn io_error(path: &Path, error: std::io::Error) -> PatchError {
+    PatchError::Io(format!("{}: {}", path.display(), error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patch::Hunk;
+    use crate::serialization::{FromPatch, ToPatch};
+
+    #[test]
+    fn test_strip_path_collapses_slashes() {
+        assert_eq!(strip_path("a//src/lib.rs", 1), Some("src/lib.rs"));
+        assert_eq!(strip_path("/usr/src/lib.rs", 1), Some("usr/src/lib.rs"));
+    }
+
+    #[test]
+    fn test_diff_files() {
+        let dir = std::env::temp_dir().join(format!("diffkit-files-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("old"), "a\nb\nc\n").unwrap();
+        fs::write(dir.join("new"), "a\nc\nd").unwrap();
+
+        let file = diff_files(&dir.join("old"), &dir.join("new")).unwrap();
+        assert_eq!(file.header.old_name, dir.join("old").display().to_string());
+        assert_eq!(file.header.new_name, dir.join("new").display().to_string());
+        assert_eq!(
+            file.hunks,
+            vec![Hunk {
+                old_start: 0,
+                new_start: 0,
+                changes: vec![
+                    Edit::Equal("a".to_string()),
+                    Edit::Delete("b".to_string()),
+                    Edit::Equal("c".to_string()),
+                    Edit::Insert("d".to_string()),
+                ],
+                section: None,
+            }]
+        );
+        assert!(!file.header.old_missing_newline);
+        assert!(file.header.new_missing_newline);
+        let same = diff_files(&dir.join("old"), &dir.join("old")).unwrap();
+        assert!(same.hunks.is_empty());
+
+        // losing only the final newline changes the last line
+        fs::write(dir.join("new"), "a\nb\nc").unwrap();
+        let file = diff_files(&dir.join("old"), &dir.join("new")).unwrap();
+        assert_eq!(file.hunks.len(), 1);
+        let changes = &file.hunks[0].changes;
+        assert!(changes.contains(&Edit::Delete("c".to_string())));
+        assert!(changes.contains(&Edit::Insert("c".to_string())));
+        assert!(file.header.new_missing_newline);
+        let patch = file.to_patch(&PatchHeader::default());
+        assert!(patch.contains("+c\n\\ No newline at end of file"));
+        let missing = diff_files(&dir.join("old"), &dir.join("missing"));
+        assert_eq!(missing.unwrap_err().kind(), io::ErrorKind::NotFound);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_diff_dirs() {
+        let dir = std::env::temp_dir().join(format!("diffkit-dirs-{}", std::process::id()));
+        let write = |path: &str, content: &str| {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        };
+        write("old/src/lib.rs", "a\nb\n");
+        write("old/src/gone.rs", "x\n");
+        write("old/target/build.log", "1\n");
+        write("old/notes.txt", "same\n");
+        write("new/src/lib.rs", "a\nc\n");
+        write("new/src/nested/fresh.rs", "y\n");
+        write("new/target/build.log", "2\n");
+        write("new/debug.log", "z\n");
+        write("new/notes.txt", "same\n");
+
+        let options = DirDiffOptions::new().ignore("target").ignore("*.log");
+        let patch = diff_dirs(&dir.join("old"), &dir.join("new"), &options).unwrap();
+        let names: Vec<_> = patch
+            .files
+            .iter()
+            .map(|f| (f.header.old_name.as_str(), f.header.new_name.as_str()))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                ("a/src/lib.rs", "b/src/lib.rs"),
+                (DEV_NULL, "b/src/nested/fresh.rs"),
+                ("a/src/gone.rs", DEV_NULL),
+            ]
+        );
+
+        // the patch turns one directory into the other
+        apply_to_dir(&dir.join("old"), &patch, 1).unwrap();
+        let patch = diff_dirs(&dir.join("old"), &dir.join("new"), &options).unwrap();
+        assert!(patch.files.is_empty());
+
+        let error = diff_dirs(&dir.join("old"), &dir.join("missing"), &options).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+        assert!(error.to_string().contains("missing"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_diff_dirs_skips_symlinks() {
+        let dir = std::env::temp_dir().join(format!("diffkit-links-{}", std::process::id()));
+        fs::create_dir_all(dir.join("old")).unwrap();
+        fs::create_dir_all(dir.join("new/src")).unwrap();
+        fs::write(dir.join("new/src/lib.rs"), "a\n").unwrap();
+        // a link back to a parent would make the walk loop
+        std::os::unix::fs::symlink(dir.join("new"), dir.join("new/src/parent")).unwrap();
+        std::os::unix::fs::symlink(dir.join("new/src"), dir.join("linked")).unwrap();
+
+        let options = DirDiffOptions::new();
+        let patch = diff_dirs(&dir.join("old"), &dir.join("new"), &options).unwrap();
+        let names: Vec<_> = patch
+            .files
+            .iter()
+            .map(|f| f.header.new_name.as_str())
+            .collect();
+        assert_eq!(names, vec!["b/src/lib.rs"]);
+        // the compared directories themselves are followed
+        let patch = diff_dirs(&dir.join("old"), &dir.join("linked"), &options).unwrap();
+        let names: Vec<_> = patch
+            .files
+            .iter()
+            .map(|f| f.header.new_name.as_str())
+            .collect();
+        assert_eq!(names, vec!["b/lib.rs"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_ignore_patterns() {
+        let options = DirDiffOptions::new()
+            .ignore("*.lo?")
+            .ignore("/build/")
+            .ignore("docs/**/draft-*.md");
+        assert!(options.ignores("a.log"));
+        assert!(options.ignores("src/deep/a.lot"));
+        assert!(!options.ignores("a.logs"));
+        assert!(options.ignores("build"));
+        assert!(!options.ignores("src/build"));
+        assert!(options.ignores("docs/draft-1.md"));
+        assert!(options.ignores("docs/a/b/draft-.md"));
+        assert!(!options.ignores("docs/a/final.md"));
+        assert!(glob("*a*b", "xaab"));
+        assert!(!glob("*a*b", "xaba"));
+        assert!(glob("", ""));
+    }
+
+    #[test]
+    fn test_apply_to_dir() {
+        let dir = std::env::temp_dir().join(format!("diffkit-apply-{}", std::process::id()));
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("src/lib.rs"), "a\nb\nc\n").unwrap();
+        fs::write(dir.join("README"), "hello\n").unwrap();
+
+        let patch = "diff --git a/src/lib.rs b/src/lib.rs\n\
+                     --- a/src/lib.rs\n\
+                     +++ b/src/lib.rs\n\
+                     @@ -1,3 +1,3 @@\n a\n-b\n+x\n c\n\
+                     diff --git a/README b/README\n\
+                     --- a/README\n\
+                     +++ b/README\n\
+                     @@ -1 +1 @@\n-hello\n+world\n";
+        let patch = Patch::<String>::from_patch(patch).unwrap();
+        assert_eq!(patch.files.len(), 2);
+        apply_to_dir(&dir, &patch, 1).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(dir.join("src/lib.rs")).unwrap(),
+            "a\nx\nc\n"
+        );
+        assert_eq!(fs::read_to_string(dir.join("README")).unwrap(), "world\n");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_apply_to_dir_creates_and_deletes() {
+        let dir = std::env::temp_dir().join(format!("diffkit-create-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("old.txt"), "a\nb\n").unwrap();
+
+        let patch = "--- /dev/null\n\
+                     +++ b/nested/new.txt\n\
+                     @@ -0,0 +1,2 @@\n+x\n+y\n\
+                     --- a/old.txt\n\
+                     +++ /dev/null\n\
+                     @@ -1,2 +0,0 @@\n-a\n-b\n";
+        let patch = Patch::<String>::from_patch(patch).unwrap();
+        apply_to_dir(&dir, &patch, 1).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(dir.join("nested/new.txt")).unwrap(),
+            "x\ny\n"
+        );
+        assert!(!dir.join("old.txt").exists());
+
+        // creating it again conflicts with the existing file
+        assert!(matches!(
+            apply_to_dir(&dir, &patch, 1),
+            Err(PatchError::FileConflict(_))
+        ));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_missing_trailing_newline_is_kept() {
+        let dir = std::env::temp_dir().join(format!("diffkit-newline-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("f"), "a\nb").unwrap();
+        let patch = Patch::<String>::from_patch("--- f\n+++ f\n@@ -2 +2 @@\n-b\n+c").unwrap();
+        apply_to_dir(&dir, &patch, 0).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("f")).unwrap(), "a\nc");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_missing_newline_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("diffkit-ends-{}", std::process::id()));
+        let write = |path: &str, content: &str| {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        };
+        write("old/gains", "a\nb");
+        write("old/loses", "a\nb\n");
+        write("old/both", "a\nb\nc\nd\ne\nf");
+        write("old/edited", "a\nb");
+        write("new/gains", "a\nb\n");
+        write("new/loses", "a\nb");
+        write("new/both", "x\nb\nc\nd\ne\nf");
+        write("new/edited", "a\nc");
+        write("new/created", "x\ny");
+
+        let options = DirDiffOptions::new();
+        let patch = diff_dirs(&dir.join("old"), &dir.join("new"), &options).unwrap();
+        // a change of the final newline alone still makes a hunk
+        assert!(patch.files.iter().all(|f| !f.hunks.is_empty()));
+        let patch = Patch::<String>::from_patch(&patch.to_patch(&PatchHeader::default())).unwrap();
+        apply_to_dir(&dir.join("old"), &patch, 1).unwrap();
+        for name in ["gains", "loses", "both", "edited", "created"] {
+            assert_eq!(
+                fs::read_to_string(dir.join("old").join(name)).unwrap(),
+                fs::read_to_string(dir.join("new").join(name)).unwrap(),
+                "{}",
+                name
+            );
+        }
+        let patch = diff_dirs(&dir.join("old"), &dir.join("new"), &options).unwrap();
+        assert!(patch.files.is_empty());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_apply_to_dir_rejects_unsafe_paths() {
+        let dir = std::env::temp_dir().join(format!("diffkit-unsafe-{}", std::process::id()));
+        let inside = dir.join("inside");
+        fs::create_dir_all(&inside).unwrap();
+        let cases = [("/abs", 0), ("a/../../x", 0), ("a/../x", 1), ("b/..", 1)];
+        for (name, strip) in cases {
+            assert_eq!(
+                resolve(&inside, name, strip),
+                Err(PatchError::UnsafePath(name.to_string()))
+            );
+        }
+        assert_eq!(resolve(&inside, "b/./x", 1), Ok(inside.join("./x")));
+
+        let patch = "--- /dev/null\n+++ b/../escaped\n@@ -0,0 +1 @@\n+x\n";
+        let patch = Patch::<String>::from_patch(patch).unwrap();
+        assert!(matches!(
+            apply_to_dir(&inside, &patch, 1),
+            Err(PatchError::UnsafePath(_))
+        ));
+        assert!(!dir.join("escaped").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_apply_to_dir_checks_hashes() {
+        let dir = std::env::temp_dir().join(format!("diffkit-hashes-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("f"), "a\nb\nc\nd\n").unwrap();
+        let mut patch =
+            Patch::<String>::from_patch("--- f\n+++ f\n@@ -1,2 +1,2 @@\n a\n-b\n+x").unwrap();
+        // made against a version of the file without its last line
+        let stale: Vec<String> = ["a", "b", "c"].map(String::from).to_vec();
+        patch.files[0].embed_hashes(&stale).unwrap();
+        assert!(matches!(
+            apply_to_dir(&dir, &patch, 0),
+            Err(PatchError::HashMismatch { .. })
+        ));
+        assert_eq!(fs::read_to_string(dir.join("f")).unwrap(), "a\nb\nc\nd\n");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_diff_file_sets_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("diffkit-sets-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let old = BTreeMap::from([
+            ("keep".to_string(), "1\n2\n".to_string()),
+            ("move".to_string(), "a\nb\nc\nd\n".to_string()),
+            ("gone".to_string(), "x\n".to_string()),
+        ]);
+        let new = BTreeMap::from([
+            ("keep".to_string(), "1\n3\n".to_string()),
+            ("moved".to_string(), "a\nb\nc\nd\ne\n".to_string()),
+            ("copy".to_string(), "1\n2\n".to_string()),
+            ("fresh".to_string(), "y\n".to_string()),
+        ]);
+        for (name, content) in &old {
+            fs::write(dir.join(name), content).unwrap();
+        }
+
+        let patch = diff_file_sets(&old, &new, 50);
+        let relations: Vec<_> = patch
+            .files
+            .iter()
+            .map(|f| {
+                (
+                    f.header.old_name.as_str(),
+                    f.header.new_name.as_str(),
+                    f.header.relation,
+                )
+            })
+            .collect();
+        assert_eq!(
+            relations,
+            vec![
+                ("a/keep", "b/copy", Some(Relation::Copy { similarity: 100 })),
+                ("/dev/null", "b/fresh", None),
+                ("a/keep", "b/keep", None),
+                (
+                    "a/move",
+                    "b/moved",
+                    Some(Relation::Rename { similarity: 88 })
+                ),
+                ("a/gone", "/dev/null", None),
+            ]
+        );
+
+        // apply the serialized patch, honoring renames and copies
+        let patch = Patch::<String>::from_patch(&patch.to_patch(&PatchHeader::default())).unwrap();
+        apply_to_dir(&dir, &patch, 1).unwrap();
+        for (name, content) in &new {
+            assert_eq!(&fs::read_to_string(dir.join(name)).unwrap(), content);
+        }
+        assert!(!dir.join("move").exists());
+        assert!(!dir.join("gone").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,279 @@
+use crate::delta::fnv1a;
+use crate::patch::{apply, PatchFile};
+use crate::serialization::{PatchError, PatchSide};
+use std::fmt::{self, Display};
+
+/// The hash of a file kept in a [`PatchHeader`](crate::patch::PatchHeader),
+/// computed over its lines, each followed by `\n`, so that it's stable
+/// across builds and platforms.
+///
+/// Whoever can change a patch can change its hashes too: to detect tampering,
+/// and not only stale or corrupted files, use SHA-256 and send the hashes
+/// separately from the patch, or sign them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentHash {
+    /// The 64-bit FNV-1a hash, which detects a stale or corrupted file,
+    /// but is easily forged.
+    Fnv1a(u64),
+    /// The SHA-256 hash, which can only be computed, and so checked,
+    /// with the `sha256` feature.
+    Sha256([u8; 32]),
+}
+
+impl ContentHash {
+    /// The FNV-1a hash of `lines`.
+    pub fn fnv1a<T: Display>(lines: &[T]) -> Self {
+        ContentHash::Fnv1a(fnv1a(text(lines).as_bytes()))
+    }
+
+    /// The SHA-256 hash of `lines`.
+    #[cfg(feature = "sha256")]
+    pub fn sha256<T: Display>(lines: &[T]) -> Self {
+        use sha2::{Digest, Sha256};
+        ContentHash::Sha256(Sha256::digest(text(lines).as_bytes()).into())
+    }
+
+    /// The hash of `lines` with the same algorithm, unless it needs a disabled feature.
+    fn recompute<T: Display>(&self, lines: &[T]) -> Option<Self> {
+        match self {
+            ContentHash::Fnv1a(_) => Some(ContentHash::fnv1a(lines)),
+            #[cfg(feature = "sha256")]
+            ContentHash::Sha256(_) => Some(ContentHash::sha256(lines)),
+            #[cfg(not(feature = "sha256"))]
+            ContentHash::Sha256(_) => None,
+        }
+    }
+
+    /// Parses a hash written by `Display`, telling the algorithms apart by length.
+    pub(crate) fn from_hex(hex: &str) -> Option<Self> {
+        let byte = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        match hex.len() {
+            16 => u64::from_str_radix(hex, 16).ok().map(ContentHash::Fnv1a),
+            64 => {
+                let mut digest = [0; 32];
+                for (i, b) in digest.iter_mut().enumerate() {
+                    *b = byte(2 * i)?;
+                }
+                Some(ContentHash::Sha256(digest))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Writes the hash in lowercase hexadecimal: 16 digits for FNV-1a, 64 for SHA-256.
+impl fmt::Display for ContentHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContentHash::Fnv1a(hash) => write!(f, "{:016x}", hash),
+            ContentHash::Sha256(digest) => digest.iter().try_for_each(|b| write!(f, "{:02x}", b)),
+        }
+    }
+}
+
+fn text<T: Display>(lines: &[T]) -> String {
+    let mut text = String::new();
+    for line in lines {
+        text.push_str(&line.to_string());
+        text.push('\n');
+    }
+    text
+}
+
+impl<T: PartialEq + Display + Clone> PatchFile<T> {
+    /// Sets the hashes of the header to the [`ContentHash::fnv1a`]s of `old`
+    /// and of `old` once patched, to be written with the patch as
+    /// `pre-image hash` and `post-image hash` lines, see [`ToPatch`](crate::serialization::ToPatch).
+    /// ```
+    /// use diffkit::myers::diff;
+    /// use diffkit::patch::{apply_verified, hunks, PatchFile, PatchHeader};
+    /// use diffkit::serialization::ToPatch;
+    ///
+    /// let (old, new) = (vec!["a", "b"], vec!["a", "c"]);
+    /// let mut file = PatchFile {
+    ///     header: PatchHeader::new("a/notes.txt", "b/notes.txt"),
+    ///     hunks: hunks(diff(&old, &new)),
+    /// };
+    /// file.embed_hashes(&old).unwrap();
+    /// let patch = file.to_patch(&PatchHeader::default());
+    /// assert!(patch.starts_with("diff --git a/notes.txt b/notes.txt\npre-image hash "));
+    /// assert_eq!(apply_verified(&old, &file).unwrap(), new);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of [`apply`] if the hunks don't apply to `old`.
+    pub fn embed_hashes(&mut self, old: &[T]) -> Result<(), PatchError> {
+        self.embed_hashes_with(old, ContentHash::fnv1a)
+    }
+
+    /// Sets the hashes of the header like [`embed_hashes`](PatchFile::embed_hashes),
+    /// computed with `hash`, e.g. `ContentHash::sha256` with the `sha256` feature.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of [`apply`] if the hunks don't apply to `old`.
+    pub fn embed_hashes_with(
+        &mut self,
+        old: &[T],
+        hash: fn(&[T]) -> ContentHash,
+    ) -> Result<(), PatchError> {
+        let new = apply(old, &self.hunks)?;
+        self.header.old_hash = Some(hash(old));
+        self.header.new_hash = Some(hash(&new));
+        Ok(())
+    }
+}
+
+/// Applies the hunks of a file like [`apply`], checking the hashes of its header
+/// that are set: `old_hash` against `old` before applying them,
+/// and `new_hash` against the result.
+///
+/// # Errors
+///
+/// Returns [`PatchError::HashMismatch`] if `old`, or the result, doesn't have
+/// the hash of the header, e.g. because the file changed since the patch was made,
+/// and [`PatchError::UncheckedHash`] if a hash is SHA-256 without the `sha256` feature.
+/// Returns the error of [`apply`] if a hunk doesn't match `old`.
+pub fn apply_verified<T: PartialEq + Display + Clone>(
+    old: &[T],
+    file: &PatchFile<T>,
+) -> Result<Vec<T>, PatchError> {
+    verify(PatchSide::Old, file.header.old_hash, old)?;
+    let new = apply(old, &file.hunks)?;
+    verify(PatchSide::New, file.header.new_hash, &new)?;
+    Ok(new)
+}
+
+fn verify<T: Display>(
+    side: PatchSide,
+    expected: Option<ContentHash>,
+    lines: &[T],
+) -> Result<(), PatchError> {
+    let Some(expected) = expected else {
+        return Ok(());
+    };
+    let found = expected
+        .recompute(lines)
+        .ok_or(PatchError::UncheckedHash(side))?;
+    if found != expected {
+        return Err(PatchError::HashMismatch {
+            side,
+            expected,
+            found,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::myers::{diff, Edit};
+    use crate::patch::{hunks, Patch, PatchHeader};
+    use crate::serialization::{FromPatch, ToPatch};
+
+    #[test]
+    fn test_content_hash() {
+        assert_eq!(
+            ContentHash::fnv1a::<&str>(&[]),
+            ContentHash::Fnv1a(fnv1a(b""))
+        );
+        assert_eq!(
+            ContentHash::fnv1a(&["a", "b"]),
+            ContentHash::Fnv1a(fnv1a(b"a\nb\n"))
+        );
+        assert_ne!(ContentHash::fnv1a(&["ab"]), ContentHash::fnv1a(&["a", "b"]));
+
+        let hash = ContentHash::Fnv1a(0xab);
+        assert_eq!(hash.to_string(), "00000000000000ab");
+        assert_eq!(ContentHash::from_hex(&hash.to_string()), Some(hash));
+        let hash = ContentHash::Sha256([0xab; 32]);
+        assert_eq!(hash.to_string(), "ab".repeat(32));
+        assert_eq!(ContentHash::from_hex(&hash.to_string()), Some(hash));
+        assert_eq!(ContentHash::from_hex("ab"), None);
+        assert_eq!(ContentHash::from_hex(&"é".repeat(32)), None);
+    }
+
+    #[cfg(feature = "sha256")]
+    #[test]
+    fn test_sha256() {
+        assert_eq!(
+            ContentHash::sha256(&["abc"]).to_string(),
+            "edeaaff3f1774ad2888673770c6d64097e391bc362d7d6fb34982ddf0efd18cb"
+        );
+    }
+
+    #[test]
+    fn test_apply_verified() {
+        let old: Vec<String> = ["a", "b", "c"].map(String::from).to_vec();
+        let new: Vec<String> = ["a", "x", "c"].map(String::from).to_vec();
+        let mut file = PatchFile {
+            header: PatchHeader::new("a/f", "b/f"),
+            hunks: hunks(diff(&old, &new)),
+        };
+        // without hashes, like `apply`
+        assert_eq!(apply_verified(&old, &file).unwrap(), new);
+
+        file.embed_hashes(&old).unwrap();
+        let serialized = Patch {
+            files: vec![file.clone()],
+        }
+        .to_patch(&PatchHeader::default());
+        let parsed = Patch::<String>::from_patch(&serialized).unwrap();
+        assert_eq!(parsed.files, [file.clone()]);
+        assert_eq!(apply_verified(&old, &parsed.files[0]).unwrap(), new);
+
+        // a line outside the hunk changed since the patch was made
+        let stale: Vec<String> = ["a", "b", "c", "d"].map(String::from).to_vec();
+        assert_eq!(
+            apply_verified(&stale, &file),
+            Err(PatchError::HashMismatch {
+                side: PatchSide::Old,
+                expected: ContentHash::fnv1a(&old),
+                found: ContentHash::fnv1a(&stale),
+            })
+        );
+
+        // the hunk was changed after the hashes were embedded
+        let mut tampered = file.clone();
+        for change in &mut tampered.hunks[0].changes {
+            if let Edit::Insert(line) = change {
+                *line = "y".to_string();
+            }
+        }
+        assert_eq!(
+            apply_verified(&old, &tampered),
+            Err(PatchError::HashMismatch {
+                side: PatchSide::New,
+                expected: ContentHash::fnv1a(&new),
+                found: ContentHash::fnv1a(&["a", "y", "c"]),
+            })
+        );
+
+        file.header.old_hash = Some(ContentHash::Sha256([0; 32]));
+        let result = apply_verified(&old, &file);
+        #[cfg(not(feature = "sha256"))]
+        assert_eq!(result, Err(PatchError::UncheckedHash(PatchSide::Old)));
+        #[cfg(feature = "sha256")]
+        assert!(matches!(result, Err(PatchError::HashMismatch { .. })));
+    }
+
+    #[cfg(feature = "sha256")]
+    #[test]
+    fn test_apply_verified_sha256() {
+        let old: Vec<String> = ["a", "b"].map(String::from).to_vec();
+        let new: Vec<String> = ["a", "c"].map(String::from).to_vec();
+        let mut file = PatchFile {
+            header: PatchHeader::new("a/f", "b/f"),
+            hunks: hunks(diff(&old, &new)),
+        };
+        file.embed_hashes_with(&old, ContentHash::sha256).unwrap();
+        assert_eq!(file.header.new_hash, Some(ContentHash::sha256(&new)));
+        let serialized = file.to_patch(&PatchHeader::default());
+        assert!(serialized.contains(&format!("post-image hash {}", ContentHash::sha256(&new))));
+        let parsed = Patch::<String>::from_patch(&serialized).unwrap();
+        assert_eq!(parsed.files, [file]);
+        assert_eq!(apply_verified(&old, &parsed.files[0]).unwrap(), new);
+    }
+}
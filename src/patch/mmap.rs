@@ -0,0 +1,140 @@
+use crate::patch::files::diff_lines;
+use crate::patch::{PatchFile, PatchHeader};
+use memmap2::Mmap;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+/// A text file mapped in memory, whose lines are diffed as slices of the mapping
+/// instead of being copied, e.g. for exports of several gigabytes.
+///
+/// The file is checked to be UTF-8 once opened; its pages are then loaded
+/// by the operating system as they are read.
+///
+/// Like any memory mapping, the content changes if the file is modified
+/// while it's mapped, which may make the diff inconsistent:
+/// only map files that no other process writes to.
+#[derive(Debug)]
+pub struct MappedFile {
+    name: String,
+    /// `None` for an empty file, which can't be mapped on every platform
+    map: Option<Mmap>,
+}
+
+impl MappedFile {
+    /// Maps the file at `path`, named by its path.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of opening and mapping the file,
+    /// and [`io::ErrorKind::InvalidData`] if it isn't UTF-8.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let map = if file.metadata()?.len() == 0 {
+            None
+        } else {
+            // SAFETY: the mapping is only read, and the caller is told not to
+            // map files modified by other processes
+            Some(unsafe { Mmap::map(&file)? })
+        };
+        let mapped = MappedFile {
+            name: path.display().to_string(),
+            map,
+        };
+        std::str::from_utf8(mapped.bytes())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(mapped)
+    }
+
+    /// The content of the file, or nothing if it stopped being UTF-8 since it was opened.
+    pub fn text(&self) -> &str {
+        // checked again rather than assumed, since the file may have changed since `open`
+        std::str::from_utf8(self.bytes()).unwrap_or_default()
+    }
+
+    /// The lines of the file: like in `git diff`, each `\n`-terminated line
+    /// is one element.
+    pub fn lines(&self) -> Vec<&str> {
+        let text = self.text();
+        let text = text.strip_suffix('\n').unwrap_or(text);
+        if text.is_empty() {
+            return vec![];
+        }
+        text.split('\n').collect()
+    }
+
+    fn bytes(&self) -> &[u8] {
+        self.map.as_deref().unwrap_or_default()
+    }
+}
+
+/// Diffs the lines of two mapped files like [`diff_files`](crate::patch::diff_files),
+/// with hunks borrowing their lines from the mappings.
+/// ```no_run
+/// use std::path::Path;
+/// use diffkit::patch::{diff_mapped_files, MappedFile, PatchHeader};
+/// use diffkit::serialization::ToPatch;
+///
+/// let old = MappedFile::open(Path::new("export-monday.csv")).unwrap();
+/// let new = MappedFile::open(Path::new("export-tuesday.csv")).unwrap();
+/// let file = diff_mapped_files(&old, &new);
+/// print!("{}", file.to_patch(&PatchHeader::default()));
+/// ```
+pub fn diff_mapped_files<'a>(old: &'a MappedFile, new: &'a MappedFile) -> PatchFile<&'a str> {
+    let ends_with_newline =
+        |file: &MappedFile| file.text().is_empty() || file.text().ends_with('\n');
+    diff_lines(
+        PatchHeader::new(old.name.clone(), new.name.clone()),
+        (&old.lines(), ends_with_newline(old)),
+        (&new.lines(), ends_with_newline(new)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::myers::Edit;
+    use crate::patch::{diff_files, Hunk};
+    use std::fs;
+
+    #[test]
+    fn test_diff_mapped_files() {
+        let dir = std::env::temp_dir().join(format!("diffkit-mmap-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("old"), "a\nb\nc\n").unwrap();
+        fs::write(dir.join("new"), "a\nc\nd").unwrap();
+        fs::write(dir.join("empty"), "").unwrap();
+        fs::write(dir.join("binary"), [0xff, 0xfe]).unwrap();
+
+        let old = MappedFile::open(&dir.join("old")).unwrap();
+        let new = MappedFile::open(&dir.join("new")).unwrap();
+        let empty = MappedFile::open(&dir.join("empty")).unwrap();
+        assert_eq!(old.lines(), ["a", "b", "c"]);
+        assert_eq!(new.lines(), ["a", "c", "d"]);
+        assert_eq!(empty.lines(), Vec::<&str>::new());
+        assert_eq!(empty.text(), "");
+
+        let file = diff_mapped_files(&old, &new);
+        assert_eq!(
+            file.hunks,
+            vec![Hunk {
+                old_start: 0,
+                new_start: 0,
+                changes: vec![
+                    Edit::Equal("a"),
+                    Edit::Delete("b"),
+                    Edit::Equal("c"),
+                    Edit::Insert("d"),
+                ],
+                section: None,
+            }]
+        );
+        let copied = diff_files(&dir.join("old"), &dir.join("new")).unwrap();
+        assert_eq!(file.header, copied.header);
+        assert_eq!(diff_mapped_files(&empty, &old).hunks[0].changes.len(), 3);
+
+        let error = MappedFile::open(&dir.join("binary")).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
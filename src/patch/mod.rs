@@ -1,7 +1,21 @@
+mod combined;
+mod files;
+mod integrity;
+#[cfg(feature = "mmap")]
+mod mmap;
+#[cfg(feature = "ropey")]
+mod rope;
 mod types;
+pub use combined::*;
+pub use files::*;
+pub use integrity::*;
+#[cfg(feature = "mmap")]
+pub use mmap::*;
+#[cfg(feature = "ropey")]
+pub use rope::*;
 pub use types::*;
 
-use crate::myers::Edit;
+use crate::myers::{Edit, TextOptions};
 use crate::serialization::PatchError;
 use std::collections::VecDeque;
 use std::fmt::Display;
@@ -62,6 +76,7 @@ impl<T: Eq + Clone> HunkBuilder<T> {
                         old_start,
                         new_start,
                         changes,
+                        section: None,
                     })
                 };
 
@@ -97,6 +112,7 @@ impl<T: Eq + Clone> HunkBuilder<T> {
 ///          Edit::Insert(99),
 ///          Edit::Delete(3)
 ///      ],
+///      section: None,
 ///  }];
 ///  let edits = diff(&old, &new);
 ///  let result = hunks(edits);
@@ -115,11 +131,14 @@ pub fn hunks<T: Eq + Clone>(edits: Vec<Edit<T>>) -> Vec<Hunk<T>> {
 ///
 /// # Errors
 ///
-/// Returns [`PatchError::InvalidFormat`] if a hunk's context lines don't match
-/// the corresponding lines in `old`, or if hunks cannot be applied in order.
+/// Returns [`PatchError::ContextMismatch`] if a hunk's context or deleted lines
+/// don't match the corresponding lines in `old`.
+/// Returns [`PatchError::HunkOutOfBounds`] if a hunk overlaps the previous one
+/// or reaches past the end of `old`.
 /// ```
 ///  use diffkit::myers::{diff, Edit};
 ///  use diffkit::patch::{apply, Hunk};
+///  use diffkit::serialization::PatchError;
 ///
 ///  let old = vec!["a".to_string(), "b".to_string(), "c".to_string()];
 ///  let bad_hunk = Hunk {
@@ -130,72 +149,112 @@ pub fn hunks<T: Eq + Clone>(edits: Vec<Edit<T>>) -> Vec<Hunk<T>> {
 ///          Edit::Delete("y".to_string()),
 ///          Edit::Insert("z".to_string()),
 ///      ],
+///      section: None,
 ///  };
 ///  let result = apply(&old, &[bad_hunk]);
-///  assert!(result.is_err());
+///  assert_eq!(
+///      result,
+///      Err(PatchError::ContextMismatch {
+///          line: 1,
+///          expected: "x".to_string(),
+///          found: "a".to_string(),
+///      })
+///  );
 /// ```
 pub fn apply<T: PartialEq + Display + Clone>(
     old: &[T],
     hunks: &[Hunk<T>],
 ) -> Result<Vec<T>, PatchError> {
-    if old.is_empty() {
-        return Ok(hunks
-            .iter()
-            .flat_map(|h| h.changes.iter())
-            .filter_map(|e| match e {
-                Edit::Insert(t) => Some(t.clone()),
-                _ => None,
-            })
-            .collect());
-    }
+    apply_by(
+        old,
+        hunks,
+        |found, expected| found == expected,
+        Clone::clone,
+    )
+}
 
-    if hunks.is_empty() {
-        return Ok(old.to_vec());
-    }
+/// Applies hunks to the lines of a text like [`apply`], comparing the lines
+/// as set by `options`, like [`diff_lines_with`](crate::myers::diff_lines_with).
+///
+/// The context lines are kept as they are in `old`, not as they are in the hunks.
+/// When `options` ignore line endings, added lines also take the line ending
+/// of the first line of `old`, so that a patch made on a checkout with `\n` line
+/// endings applies to one with `\r\n` line endings, and the other way around.
+/// ```
+/// use diffkit::myers::{diff_lines, TextOptions};
+/// use diffkit::patch::{apply_with, hunks};
+///
+/// let hunks = hunks(diff_lines("a\nb\n", "a\nx\n"));
+/// let old = vec!["a\r".to_string(), "b\r".to_string(), String::new()];
+/// let options = TextOptions::new().ignore_line_endings();
+/// assert_eq!(apply_with(&old, &hunks, &options).unwrap(), ["a\r", "x\r", ""]);
+/// ```
+///
+/// # Errors
+///
+/// Returns the errors of [`apply`].
+pub fn apply_with(
+    old: &[String],
+    hunks: &[Hunk<String>],
+    options: &TextOptions,
+) -> Result<Vec<String>, PatchError> {
+    let crlf = old.first().is_some_and(|line| line.ends_with('\r'));
+    apply_by(
+        old,
+        hunks,
+        |found, expected| options.key(found) == options.key(expected),
+        |added| match added.strip_suffix('\r') {
+            _ if !options.ignore_line_endings => added.clone(),
+            Some(line) if !crlf => line.to_string(),
+            None if crlf => format!("{}\r", added),
+            _ => added.clone(),
+        },
+    )
+}
 
+/// Applies hunks, comparing the elements of `old` to those of the hunks with `eq`,
+/// and adding the result of `added` for each added element.
+fn apply_by<T: Display + Clone>(
+    old: &[T],
+    hunks: &[Hunk<T>],
+    eq: impl Fn(&T, &T) -> bool,
+    added: impl Fn(&T) -> T,
+) -> Result<Vec<T>, PatchError> {
     let mut result = vec![];
-    let mut hunk_iter = hunks.iter().peekable();
     let mut old_line = 0;
 
-    while old_line < old.len() {
-        if let Some(hunk) = hunk_iter.peek() {
-            match old_line.cmp(&hunk.old_start) {
-                std::cmp::Ordering::Equal => {
-                    for change in &hunk.changes {
-                        match change {
-                            Edit::Equal(t) => {
-                                if old[old_line] != *t {
-                                    return Err(PatchError::InvalidFormat(format!(
-                                        "Context mismatch at line {}: expected '{}', found '{}'",
-                                        old_line, t, old[old_line]
-                                    )));
-                                }
-                                result.push(old[old_line].clone());
-                                old_line += 1;
-                            }
-                            Edit::Insert(t) => {
-                                result.push(t.clone());
-                            }
-                            Edit::Delete(_) => {
-                                old_line += 1;
-                            }
-                        }
+    for (index, hunk) in hunks.iter().enumerate() {
+        let out_of_bounds = || PatchError::HunkOutOfBounds {
+            hunk: index,
+            old_start: hunk.old_start,
+        };
+        if hunk.old_start < old_line || hunk.old_start > old.len() {
+            return Err(out_of_bounds());
+        }
+        result.extend_from_slice(&old[old_line..hunk.old_start]);
+        old_line = hunk.old_start;
+
+        for change in &hunk.changes {
+            match change {
+                Edit::Insert(t) => result.push(added(t)),
+                Edit::Equal(t) | Edit::Delete(t) => {
+                    let found = old.get(old_line).ok_or_else(out_of_bounds)?;
+                    if !eq(found, t) {
+                        return Err(PatchError::ContextMismatch {
+                            line: old_line + 1,
+                            expected: t.to_string(),
+                            found: found.to_string(),
+                        });
+                    }
+                    if matches!(change, Edit::Equal(_)) {
+                        result.push(found.clone());
                     }
-                    hunk_iter.next();
-                }
-                std::cmp::Ordering::Less => {
-                    result.push(old[old_line].clone());
                     old_line += 1;
                 }
-                std::cmp::Ordering::Greater => {
-                    return Err(PatchError::InvalidFormat("Cannot apply hunks".to_string()));
-                }
             }
-        } else {
-            result.push(old[old_line].clone());
-            old_line += 1;
         }
     }
+    result.extend_from_slice(&old[old_line..]);
 
     Ok(result)
 }
@@ -254,6 +313,7 @@ mod tests {
                     Edit::Equal(3),
                     Edit::Equal(4),
                 ],
+                section: None,
             },
             Hunk {
                 old_start: 6,
@@ -265,6 +325,7 @@ mod tests {
                     Edit::Insert(99),
                     Edit::Delete(10),
                 ],
+                section: None,
             },
         ];
         let edits = diff(&old, &new);
@@ -286,6 +347,7 @@ mod tests {
                 Edit::Equal(3),
                 Edit::Equal(4),
             ],
+            section: None,
         }];
         let edits = diff(&old, &new);
         let result = hunks(edits);
@@ -306,6 +368,7 @@ mod tests {
                 Edit::Insert(99),
                 Edit::Delete(5),
             ],
+            section: None,
         }];
         let edits = diff(&old, &new);
         let result = hunks(edits);
@@ -369,9 +432,67 @@ mod tests {
                 Edit::Delete("y".to_string()),
                 Edit::Insert("z".to_string()),
             ],
+            section: None,
         };
 
         let result = apply(&old, &[bad_hunk]);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_apply_deleted_line_mismatch() {
+        let old = vec!["a", "b"];
+        let hunk = Hunk {
+            old_start: 1,
+            new_start: 1,
+            changes: vec![Edit::Delete("x")],
+            section: None,
+        };
+        assert_eq!(
+            apply(&old, &[hunk]),
+            Err(PatchError::ContextMismatch {
+                line: 2,
+                expected: "x".to_string(),
+                found: "b".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_apply_hunk_out_of_bounds() {
+        let old = vec!["a", "b"];
+        let past_end = Hunk {
+            old_start: 1,
+            new_start: 1,
+            changes: vec![Edit::Equal("b"), Edit::Delete("c")],
+            section: None,
+        };
+        assert_eq!(
+            apply(&old, &[past_end]),
+            Err(PatchError::HunkOutOfBounds {
+                hunk: 0,
+                old_start: 1
+            })
+        );
+
+        let first = Hunk {
+            old_start: 0,
+            new_start: 0,
+            changes: vec![Edit::Delete("a"), Edit::Equal("b")],
+            section: None,
+        };
+        let overlapping = Hunk {
+            old_start: 1,
+            new_start: 0,
+            changes: vec![Edit::Delete("b")],
+            section: None,
+        };
+        assert_eq!(
+            apply(&old, &[first, overlapping]),
+            Err(PatchError::HunkOutOfBounds {
+                hunk: 1,
+                old_start: 1
+            })
+        );
+    }
 }
@@ -0,0 +1,216 @@
+use crate::myers::{diff, Edit};
+use crate::patch::{hunks, Hunk};
+use crate::serialization::PatchError;
+use ropey::{Rope, RopeSlice};
+use std::fmt::Display;
+
+/// The lines of a rope as slices of it, without copying them: like in `git diff`,
+/// each `\n`-terminated line is one element, without its `\n`.
+///
+/// Only `\n` ends a line, whatever the features ropey is built with:
+/// with its `unicode_lines` or `cr_lines` features, its own lines also end at
+/// other breaks, like `\r` or a form feed.
+pub fn rope_lines(rope: &Rope) -> Vec<RopeSlice<'_>> {
+    let mut start = 0;
+    let mut lines: Vec<RopeSlice<'_>> = line_breaks(rope)
+        .into_iter()
+        .map(|end| {
+            let line = rope.byte_slice(start..end);
+            start = end + 1;
+            line
+        })
+        .collect();
+    // the line after the last `\n`, if not empty
+    if start < rope.len_bytes() {
+        lines.push(rope.byte_slice(start..));
+    }
+    lines
+}
+
+/// The byte offsets of the `\n`s of a rope.
+fn line_breaks(rope: &Rope) -> Vec<usize> {
+    let mut breaks = vec![];
+    let mut offset = 0;
+    for chunk in rope.chunks() {
+        breaks.extend(chunk.match_indices('\n').map(|(i, _)| offset + i));
+        offset += chunk.len();
+    }
+    breaks
+}
+
+/// Diffs the lines of two ropes into hunks borrowing their lines from the ropes,
+/// e.g. for an editor diffing its buffer against the file on disk.
+/// ```
+/// use ropey::Rope;
+/// use diffkit::patch::{apply_to_rope, diff_ropes, PatchHeader};
+/// use diffkit::serialization::ToPatch;
+///
+/// let old = Rope::from_str("a\nb\nc\n");
+/// let new = Rope::from_str("a\nc\nd\n");
+/// let hunks = diff_ropes(&old, &new);
+/// assert_eq!(
+///     hunks.to_patch(&PatchHeader::new("old", "new")),
+///     "--- old\n+++ new\n@@ -1,3 +1,3 @@\n a\n-b\n c\n+d"
+/// );
+///
+/// let mut buffer = old.clone();
+/// apply_to_rope(&mut buffer, &hunks).unwrap();
+/// assert_eq!(buffer, new);
+/// ```
+pub fn diff_ropes<'a>(old: &'a Rope, new: &'a Rope) -> Vec<Hunk<RopeSlice<'a>>> {
+    hunks(diff(&rope_lines(old), &rope_lines(new)))
+}
+
+/// Applies hunks to the lines of a rope in place, like [`apply`](crate::patch::apply),
+/// editing only the lines of the hunks. The lines of the hunks are compared with
+/// the lines of the rope, as split by [`rope_lines`], through their `Display`.
+///
+/// The rope is left as it is if a hunk doesn't apply. It keeps its trailing newline,
+/// or its lack of one.
+///
+/// # Errors
+///
+/// Returns [`PatchError::ContextMismatch`] if a hunk's context or deleted lines
+/// don't match the corresponding lines of the rope.
+/// Returns [`PatchError::HunkOutOfBounds`] if a hunk overlaps the previous one
+/// or reaches past the end of the rope.
+pub fn apply_to_rope<T: Display>(rope: &mut Rope, hunks: &[Hunk<T>]) -> Result<(), PatchError> {
+    check_hunks(&rope_lines(rope), hunks)?;
+    let trailing_newline = rope.len_chars() == 0 || rope.char(rope.len_chars() - 1) == '\n';
+    // the char index where a line starts, or the end of the rope past the last line
+    let breaks = line_breaks(rope);
+    let line_to_char = |rope: &Rope, line: usize| match line.checked_sub(1) {
+        None => 0,
+        Some(previous) => {
+            rope.byte_to_char(breaks.get(previous).map_or(rope.len_bytes(), |b| b + 1))
+        }
+    };
+    // from the last hunk, so that the lines of the earlier ones don't move
+    for hunk in hunks.iter().rev() {
+        let old_lines = hunk
+            .changes
+            .iter()
+            .filter(|change| !matches!(change, Edit::Insert(_)))
+            .count();
+        let start = line_to_char(rope, hunk.old_start);
+        let end = line_to_char(rope, hunk.old_start + old_lines);
+        let mut text = String::new();
+        for change in &hunk.changes {
+            if let Edit::Equal(line) | Edit::Insert(line) = change {
+                text.push_str(&line.to_string());
+                text.push('\n');
+            }
+        }
+        if end == rope.len_chars() && !trailing_newline {
+            if start == end && start > 0 {
+                // lines added after the last line, which gets the newline
+                text.insert(0, '\n');
+            }
+            text.pop();
+        }
+        rope.remove(start..end);
+        rope.insert(start, &text);
+    }
+    Ok(())
+}
+
+/// Checks that the hunks apply to `lines`, in order.
+fn check_hunks<T: Display>(lines: &[RopeSlice<'_>], hunks: &[Hunk<T>]) -> Result<(), PatchError> {
+    let mut old_line = 0;
+    for (index, hunk) in hunks.iter().enumerate() {
+        let out_of_bounds = || PatchError::HunkOutOfBounds {
+            hunk: index,
+            old_start: hunk.old_start,
+        };
+        if hunk.old_start < old_line || hunk.old_start > lines.len() {
+            return Err(out_of_bounds());
+        }
+        old_line = hunk.old_start;
+        for change in &hunk.changes {
+            if let Edit::Equal(expected) | Edit::Delete(expected) = change {
+                let found = lines.get(old_line).ok_or_else(out_of_bounds)?;
+                let expected = expected.to_string();
+                if *found != expected.as_str() {
+                    return Err(PatchError::ContextMismatch {
+                        line: old_line + 1,
+                        expected,
+                        found: found.to_string(),
+                    });
+                }
+                old_line += 1;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patch::{apply, PatchFile};
+    use crate::serialization::FromPatch;
+
+    #[test]
+    fn test_rope_lines() {
+        assert_eq!(rope_lines(&Rope::from_str("")), Vec::<RopeSlice>::new());
+        assert_eq!(rope_lines(&Rope::from_str("a\nb")), ["a", "b"]);
+        assert_eq!(rope_lines(&Rope::from_str("a\r\nb\n")), ["a\r", "b"]);
+        assert_eq!(rope_lines(&Rope::from_str("a\n\n")), ["a", ""]);
+        // not split at the other breaks of ropey's `unicode_lines`, enabled for tests
+        let rope = Rope::from_str("x\u{c}y\nz\rw\n");
+        assert_eq!(rope.len_lines(), 5);
+        assert_eq!(rope_lines(&rope), ["x\u{c}y", "z\rw"]);
+        let long = "é\u{85}\n".repeat(2000);
+        assert_eq!(rope_lines(&Rope::from_str(&long)).len(), 2000);
+    }
+
+    #[test]
+    fn test_apply_to_rope() {
+        let cases = [
+            ("a\nb\nc\n", "a\nc\nd\n"),
+            ("a\nb\nc", "a\nb\nc\nd"),
+            ("a\nb\nc", "x\nb\nc"),
+            ("", "a\nb\n"),
+            ("a\nb\n", ""),
+            (
+                "1\n2\n3\n4\n5\n6\n7\n8\n9\n10",
+                "0\n1\n2\n3\n4\n5\n6\n7\n8\n9",
+            ),
+            ("a\rb\nc\u{2028}d\n", "a\rb\nx\n"),
+        ];
+        for (old, new) in cases {
+            let (old, new) = (Rope::from_str(old), Rope::from_str(new));
+            let mut rope = old.clone();
+            apply_to_rope(&mut rope, &diff_ropes(&old, &new)).unwrap();
+            assert_eq!(rope, new);
+        }
+
+        // hunks parsed from a patch apply like to a vector of lines
+        let patch = "--- a\n+++ b\n@@ -1,2 +1,2 @@\n a\n-b\n+x\n";
+        let file = PatchFile::<String>::from_patch(patch).unwrap();
+        let mut rope = Rope::from_str("a\nb\nc\n");
+        apply_to_rope(&mut rope, &file.hunks).unwrap();
+        assert_eq!(rope, "a\nx\nc\n");
+        let lines: Vec<String> = ["a", "b", "c"].map(String::from).to_vec();
+        assert_eq!(apply(&lines, &file.hunks).unwrap(), ["a", "x", "c"]);
+
+        let mut rope = Rope::from_str("a\ny\nc\n");
+        assert_eq!(
+            apply_to_rope(&mut rope, &file.hunks),
+            Err(PatchError::ContextMismatch {
+                line: 2,
+                expected: "b".to_string(),
+                found: "y".to_string(),
+            })
+        );
+        assert_eq!(rope, "a\ny\nc\n");
+        let mut rope = Rope::from_str("a\n");
+        assert_eq!(
+            apply_to_rope(&mut rope, &file.hunks),
+            Err(PatchError::HunkOutOfBounds {
+                hunk: 0,
+                old_start: 0
+            })
+        );
+    }
+}
@@ -1,11 +1,171 @@
 use crate::myers::Edit;
+use crate::patch::ContentHash;
 
 /// Represents a Hunk resulting from a Myers diff.
 /// Please note that `changes` will include maximum 3 context elements, i.e. `Edit::Equal`
 /// and this is reflected in the `old_start` value
+///
+/// `section` is the optional heading printed after the closing `@@`
+/// of the hunk header, e.g. the enclosing function name.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Hunk<T> {
     pub old_start: usize,
     pub new_start: usize,
     pub changes: Vec<Edit<T>>,
+    pub section: Option<String>,
+}
+
+/// File name standing for a missing side of a [`PatchFile`].
+pub const DEV_NULL: &str = "/dev/null";
+
+/// Metadata of a file inside a [`Patch`], from the `---`/`+++` lines
+/// and the extended header lines of `git diff`.
+///
+/// `old_name` and `new_name` are the names found in the `---`/`+++` header,
+/// including any `a/`/`b/` prefix, and `old_time`/`new_time` the timestamps
+/// following them after a tab.
+/// A file is created when `old_name` is [`DEV_NULL`], and deleted when `new_name` is.
+/// `mode` is the octal file mode of the new file, or of the deleted one;
+/// `old_mode` is only set when the mode changes.
+/// `relation` is set when the new file is a rename or copy of the old one.
+/// `old_hash` and `new_hash` are the [`ContentHash`](crate::patch::ContentHash)es
+/// of the file before and after the patch, checked by
+/// [`apply_verified`](crate::patch::apply_verified) when set.
+/// `old_missing_newline` and `new_missing_newline` are set when the last hunk
+/// reaches the end of the file before or after the patch, and that file
+/// doesn't end with a newline: its last line is then followed by
+/// `\ No newline at end of file`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatchHeader {
+    pub old_name: String,
+    pub new_name: String,
+    pub old_time: Option<String>,
+    pub new_time: Option<String>,
+    pub old_mode: Option<u32>,
+    pub mode: Option<u32>,
+    pub relation: Option<Relation>,
+    pub old_hash: Option<ContentHash>,
+    pub new_hash: Option<ContentHash>,
+    pub old_missing_newline: bool,
+    pub new_missing_newline: bool,
+}
+
+impl PatchHeader {
+    /// A header with the given names and no other metadata.
+    pub fn new(old_name: impl Into<String>, new_name: impl Into<String>) -> Self {
+        PatchHeader {
+            old_name: old_name.into(),
+            new_name: new_name.into(),
+            old_time: None,
+            new_time: None,
+            old_mode: None,
+            mode: None,
+            relation: None,
+            old_hash: None,
+            new_hash: None,
+            old_missing_newline: false,
+            new_missing_newline: false,
+        }
+    }
+
+    /// Whether the patch creates the file.
+    pub fn is_creation(&self) -> bool {
+        self.old_name == DEV_NULL
+    }
+
+    /// Whether the patch deletes the file.
+    pub fn is_deletion(&self) -> bool {
+        self.new_name == DEV_NULL
+    }
+}
+
+/// Names the files `"old"` and `"new"`.
+impl Default for PatchHeader {
+    fn default() -> Self {
+        PatchHeader::new("old", "new")
+    }
+}
+
+/// The hunks of a single file inside a multi-file [`Patch`], with its header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatchFile<T> {
+    pub header: PatchHeader,
+    pub hunks: Vec<Hunk<T>>,
+}
+
+/// Represents a file derived from another one,
+/// with the percentage of lines they share.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Relation {
+    /// The old file is moved to the new name.
+    Rename { similarity: u8 },
+    /// The old file is kept, and the new file starts as a copy of it.
+    Copy { similarity: u8 },
+}
+
+/// A patch touching several files, e.g. the output of `git diff`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Patch<T> {
+    pub files: Vec<PatchFile<T>>,
+}
+
+/// A commit mailed by `git format-patch`, with its headers,
+/// commit message and the patch it carries.
+///
+/// `commit` is the hash on the leading `From ` line, and `subject` is
+/// stripped of any `[PATCH ...]` prefix. Header values are kept as they are,
+/// without decoding RFC 2047 encoded words.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmailPatch<T> {
+    pub commit: String,
+    pub author: String,
+    pub date: String,
+    pub subject: String,
+    pub message: String,
+    pub patch: Patch<T>,
+}
+
+/// How a line of a [`CombinedHunk`] relates to one of the merge parents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Column {
+    /// The line is shared by the parent and the result, printed as ` `.
+    /// On a line removed from other parents, the parent doesn't have it either.
+    Context,
+    /// The line is in the result but not in the parent, printed as `+`.
+    Added,
+    /// The line is in the parent but not in the result, printed as `-`.
+    Removed,
+}
+
+/// A line of a [`CombinedHunk`], with one [`Column`] per merge parent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CombinedLine<T> {
+    pub columns: Vec<Column>,
+    pub value: T,
+}
+
+impl<T> CombinedLine<T> {
+    /// Whether the line is part of the merge result.
+    pub fn in_result(&self) -> bool {
+        !self.columns.contains(&Column::Removed)
+    }
+
+    /// Whether the line is part of the given parent.
+    pub fn in_parent(&self, parent: usize) -> bool {
+        match self.columns[parent] {
+            Column::Removed => true,
+            Column::Added => false,
+            Column::Context => self.in_result(),
+        }
+    }
+}
+
+/// Represents a hunk of a combined diff, comparing a merge result
+/// against several parents at once, like `git diff --cc`.
+/// `parent_starts` holds one start per parent; all starts are 0-based.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CombinedHunk<T> {
+    pub parent_starts: Vec<usize>,
+    pub new_start: usize,
+    pub lines: Vec<CombinedLine<T>>,
 }
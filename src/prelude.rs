@@ -0,0 +1,25 @@
+//! The items most programs need, to import at once.
+//!
+//! It holds the recursive differ with its types, the traits to convert
+//! values and patches, and the types of the sequence differ. The Myers
+//! `diff` and the hunk `apply` share their names with the recursive ones,
+//! so they stay in [`myers`](crate::myers) and [`patch`](crate::patch).
+//!
+//! ```
+//! use std::collections::HashMap;
+//! use diffkit::prelude::*;
+//!
+//! let old = HashMap::from([("replicas".to_string(), 3)]);
+//! let new = HashMap::from([("replicas".to_string(), 5)]);
+//! let changes: Vec<Change<i32>> = diff(&old, &new);
+//! assert_eq!(changes[0].kind, ChangeKind::Modified(3, 5));
+//! assert_eq!(apply(&old, &changes), Ok(new));
+//! ```
+
+pub use crate::myers::{Diff, Edit};
+pub use crate::patch::{hunks, Hunk, PatchHeader};
+pub use crate::recursive::{
+    apply, apply_strict, apply_with, diff, diff_with, ApplyError, ApplyOptions, Change, ChangeKind,
+    DiffOptions, Diffable, Node, PathSegment, Primitive,
+};
+pub use crate::serialization::{FromPatch, PatchError, ToPatch};
@@ -0,0 +1,258 @@
+use crate::recursive::diffable::Diffable;
+use crate::recursive::types::{ApplyError, Node, Primitive};
+use std::any::Any;
+use std::fmt;
+use std::str::FromStr;
+
+/// A leaf holding raw bytes, e.g. an image embedded in a document.
+///
+/// A `Vec<u8>` is diffed as a sequence of bytes; a `Blob` is a single leaf.
+/// With [`DiffOptions::blob_deltas`](crate::recursive::DiffOptions::blob_deltas),
+/// its modifications are reported as a [`BlobDelta`] instead of both versions.
+/// It's written in lowercase hexadecimal.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct Blob(pub Vec<u8>);
+
+impl Primitive for Blob {}
+
+impl Diffable for Blob {
+    type P = Self;
+    fn to_node(&self) -> Node<Self::P> {
+        Node::Leaf(self.clone())
+    }
+
+    fn from_node(node: Node<Self::P>) -> Result<Self, ApplyError> {
+        match node {
+            Node::Leaf(v) => Ok(v),
+            _ => Err(ApplyError::TypeMismatch(vec![])),
+        }
+    }
+}
+
+impl From<Vec<u8>> for Blob {
+    fn from(bytes: Vec<u8>) -> Self {
+        Blob(bytes)
+    }
+}
+
+impl fmt::Display for Blob {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_hex(f, &self.0)
+    }
+}
+
+impl FromStr for Blob {
+    type Err = ParseBlobError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_hex(s).map(Blob)
+    }
+}
+
+/// Error returned when a [`Blob`] isn't valid hexadecimal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseBlobError;
+
+impl fmt::Display for ParseBlobError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid hexadecimal bytes")
+    }
+}
+
+impl std::error::Error for ParseBlobError {}
+
+/// The bytes of a [`Blob`] replaced at `offset`: `removed` is the part of the old blob
+/// that differs from the new one, and `inserted` the part of the new one.
+///
+/// Both sides are kept so that the delta can be inverted and checked
+/// by `apply_strict`, but the bytes the versions share are not.
+/// ```
+/// use diffkit::recursive::BlobDelta;
+///
+/// let delta = BlobDelta::between(b"a red cat", b"a blue cat");
+/// assert_eq!(delta.offset, 2);
+/// assert_eq!((&delta.removed[..], &delta.inserted[..]), (&b"red"[..], &b"blue"[..]));
+/// ```
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct BlobDelta {
+    pub offset: usize,
+    pub removed: Vec<u8>,
+    pub inserted: Vec<u8>,
+}
+
+impl BlobDelta {
+    /// The delta turning `old` into `new`: a single replacement from the first
+    /// to the last byte that differ.
+    pub fn between(old: &[u8], new: &[u8]) -> Self {
+        let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+        let (old_rest, new_rest) = (&old[prefix..], &new[prefix..]);
+        let suffix = old_rest
+            .iter()
+            .rev()
+            .zip(new_rest.iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+        BlobDelta {
+            offset: prefix,
+            removed: old_rest[..old_rest.len() - suffix].to_vec(),
+            inserted: new_rest[..new_rest.len() - suffix].to_vec(),
+        }
+    }
+
+    /// The delta undoing this one.
+    pub fn invert(&self) -> Self {
+        BlobDelta {
+            offset: self.offset,
+            removed: self.inserted.clone(),
+            inserted: self.removed.clone(),
+        }
+    }
+
+    /// Whether `bytes` are long enough for the delta.
+    pub fn fits(&self, bytes: &[u8]) -> bool {
+        self.offset
+            .checked_add(self.removed.len())
+            .is_some_and(|end| end <= bytes.len())
+    }
+
+    /// Whether `bytes` hold the removed bytes at the offset.
+    pub fn matches(&self, bytes: &[u8]) -> bool {
+        self.fits(bytes) && bytes[self.offset..self.offset + self.removed.len()] == self.removed
+    }
+
+    /// Replaces the removed bytes by the inserted ones.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the delta doesn't [fit](BlobDelta::fits) `bytes`.
+    pub fn apply(&self, bytes: &mut Vec<u8>) {
+        let end = self.offset + self.removed.len();
+        bytes.splice(self.offset..end, self.inserted.iter().copied());
+    }
+}
+
+/// The delta between two leaves, if they are blobs with bytes in common.
+pub(crate) fn leaf_delta<P: Primitive>(old: &P, new: &P) -> Option<BlobDelta> {
+    let old = (old as &dyn Any).downcast_ref::<Blob>()?;
+    let new = (new as &dyn Any).downcast_ref::<Blob>()?;
+    let delta = BlobDelta::between(&old.0, &new.0);
+    let size = delta.removed.len() + delta.inserted.len();
+    (size < old.0.len() + new.0.len()).then_some(delta)
+}
+
+/// The bytes of a leaf, if it's a blob.
+pub(crate) fn leaf_bytes<P: Primitive>(leaf: &P) -> Option<&[u8]> {
+    (leaf as &dyn Any)
+        .downcast_ref::<Blob>()
+        .map(|blob| &blob.0[..])
+}
+
+/// Applies `delta` to a leaf, if it's a blob the delta fits.
+pub(crate) fn patch_leaf<P: Primitive>(leaf: &mut P, delta: &BlobDelta) {
+    if let Some(blob) = (leaf as &mut dyn Any).downcast_mut::<Blob>() {
+        if delta.fits(&blob.0) {
+            delta.apply(&mut blob.0);
+        }
+    }
+}
+
+fn write_hex(f: &mut fmt::Formatter<'_>, bytes: &[u8]) -> fmt::Result {
+    bytes.iter().try_for_each(|b| write!(f, "{:02x}", b))
+}
+
+fn parse_hex(s: &str) -> Result<Vec<u8>, ParseBlobError> {
+    if !s.len().is_multiple_of(2) || !s.is_ascii() {
+        return Err(ParseBlobError);
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).map_err(|_| ParseBlobError))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patch::PatchHeader;
+    use crate::recursive::{
+        apply, apply_strict, diff_with, from_bytes, invert_changes, to_bytes, Change, ChangeKind,
+        DiffOptions, PathSegment,
+    };
+    use crate::serialization::{FromPatch, ToPatch};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_delta() {
+        let cases: [(&[u8], &[u8]); 5] = [
+            (b"", b""),
+            (b"abc", b"abc"),
+            (b"", b"abc"),
+            (b"aXc", b"ac"),
+            (b"aaa", b"aaaa"),
+        ];
+        for (old, new) in cases {
+            let delta = BlobDelta::between(old, new);
+            assert!(delta.matches(old));
+            let mut bytes = old.to_vec();
+            delta.apply(&mut bytes);
+            assert_eq!(bytes, new);
+            delta.invert().apply(&mut bytes);
+            assert_eq!(bytes, old);
+        }
+        let delta = BlobDelta::between(b"aXc", b"ac");
+        assert_eq!((delta.offset, &delta.removed[..]), (1, &b"X"[..]));
+        assert!(!delta.fits(b"a"));
+        assert!(!delta.matches(b"abc"));
+    }
+
+    #[test]
+    fn test_diff_and_apply() {
+        let old = HashMap::from([("logo".to_string(), Blob(b"<svg>red</svg>".to_vec()))]);
+        let new = HashMap::from([("logo".to_string(), Blob(b"<svg>blue</svg>".to_vec()))]);
+        let options = DiffOptions::new().blob_deltas();
+        let changes = diff_with(&old, &new, &options);
+        let delta = BlobDelta {
+            offset: 5,
+            removed: b"red".to_vec(),
+            inserted: b"blue".to_vec(),
+        };
+        assert_eq!(
+            changes,
+            vec![Change {
+                path: vec![PathSegment::Key("logo".to_string())],
+                kind: ChangeKind::BlobPatched(delta),
+            }]
+        );
+        assert_eq!(apply_strict(&old, &changes), Ok(new.clone()));
+        assert_eq!(apply(&new, &invert_changes(&changes)), Ok(old.clone()));
+        assert_eq!(
+            apply_strict(&new, &changes),
+            Err(ApplyError::Conflict(changes[0].path.clone()))
+        );
+        let empty = HashMap::from([("logo".to_string(), Blob::default())]);
+        assert_eq!(
+            apply(&empty, &changes),
+            Err(ApplyError::InvalidChangeKind(changes[0].path.clone()))
+        );
+
+        let text = changes.to_patch(&PatchHeader::default());
+        assert_eq!(text, "patch-blob logo 5 726564 -> 626c7565");
+        assert_eq!(Vec::from_patch(&text), Ok(changes.clone()));
+        assert_eq!(from_bytes(&to_bytes(&changes)), Ok(changes));
+
+        // without anything in common, both versions are kept
+        let other = HashMap::from([("logo".to_string(), Blob(b"png".to_vec()))]);
+        let changes = diff_with(&old, &other, &options);
+        assert!(matches!(changes[0].kind, ChangeKind::Modified(..)));
+    }
+
+    #[test]
+    fn test_hex() {
+        let blob = Blob(vec![0, 15, 255]);
+        assert_eq!(blob.to_string(), "000fff");
+        assert_eq!("000fff".parse(), Ok(blob));
+        assert_eq!("0".parse::<Blob>(), Err(ParseBlobError));
+        assert_eq!("zz".parse::<Blob>(), Err(ParseBlobError));
+        assert_eq!("é1".parse::<Blob>(), Err(ParseBlobError));
+    }
+}
@@ -0,0 +1,174 @@
+use crate::recursive::types::{Node, Primitive};
+use std::collections::HashMap;
+
+impl<P: Primitive> From<P> for Node<P> {
+    fn from(v: P) -> Self {
+        Node::Leaf(v)
+    }
+}
+
+/// Builders for trees in tests and tools, e.g.
+/// `Node::map().insert("ports", Node::sequence().push(80).push(443))`.
+/// See also [`node!`](crate::node).
+impl<P: Primitive> Node<P> {
+    /// An empty map.
+    pub fn map() -> Self {
+        Node::Map(HashMap::new())
+    }
+
+    /// An empty sequence.
+    pub fn sequence() -> Self {
+        Node::Sequence(vec![])
+    }
+
+    /// Sets `key` to `value` in a map.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the node isn't a map.
+    pub fn insert(self, key: impl Into<String>, value: impl Into<Node<P>>) -> Self {
+        let Node::Map(mut map) = self else {
+            panic!("insert called on a node that isn't a map");
+        };
+        map.insert(key.into(), value.into());
+        Node::Map(map)
+    }
+
+    /// Appends `value` to a sequence or a tuple, or adds it to a set.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the node isn't a sequence, a tuple or a set.
+    pub fn push(self, value: impl Into<Node<P>>) -> Self {
+        match self {
+            Node::Sequence(mut v) => {
+                v.push(value.into());
+                Node::Sequence(v)
+            }
+            Node::Tuple(mut v) => {
+                v.push(value.into());
+                Node::Tuple(v)
+            }
+            Node::Set(mut v) => {
+                let value = value.into();
+                if !v.contains(&value) {
+                    v.push(value);
+                }
+                Node::Set(v)
+            }
+            _ => panic!("push called on a node that isn't a sequence, a tuple or a set"),
+        }
+    }
+}
+
+/// Builds a [`Node`] with a JSON-like syntax: `{ "key": value }` for maps,
+/// `[value, ...]` for sequences and `null` for [`Node::Null`].
+/// Any other expression becomes a leaf, converted with `Into`,
+/// so the leaf type usually needs to be spelled out.
+/// ```
+/// use diffkit::node;
+/// use diffkit::recursive::{Node, Scalar};
+///
+/// let built: Node<Scalar> = node!({ "a": [1, -2], "b": "x", "c": null });
+/// let expected = Node::map()
+///     .insert("a", Node::sequence().push(Scalar::from(1)).push(Scalar::from(-2)))
+///     .insert("b", Scalar::from("x"))
+///     .insert("c", Node::Null);
+/// assert_eq!(built, expected);
+/// ```
+#[macro_export]
+macro_rules! node {
+    (null) => {
+        $crate::recursive::Node::Null
+    };
+    ([ $($tt:tt)* ]) => {
+        $crate::recursive::Node::Sequence($crate::node!(@sequence [] $($tt)*))
+    };
+    ({ $($tt:tt)* }) => {
+        $crate::recursive::Node::Map($crate::node!(@map [] $($tt)*))
+    };
+
+    // sequence elements, collected up to each comma
+    (@sequence [$($done:expr,)*]) => {
+        vec![$($done),*]
+    };
+    (@sequence [$($done:expr,)*] $($rest:tt)+) => {
+        $crate::node!(@element [$($done,)*] [] $($rest)+)
+    };
+    (@element [$($done:expr,)*] [$($current:tt)+] , $($rest:tt)*) => {
+        $crate::node!(@sequence [$($done,)* $crate::node!($($current)+),] $($rest)*)
+    };
+    (@element [$($done:expr,)*] [$($current:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::node!(@element [$($done,)*] [$($current)* $next] $($rest)*)
+    };
+    (@element [$($done:expr,)*] [$($current:tt)+]) => {
+        $crate::node!(@sequence [$($done,)* $crate::node!($($current)+),])
+    };
+
+    // map entries, with a single token as key
+    (@map [$(($key:expr, $value:expr),)*]) => {
+        ::std::collections::HashMap::from([$((::std::string::ToString::to_string(&$key), $value)),*])
+    };
+    (@map [$($done:tt)*] $key:tt : $($rest:tt)+) => {
+        $crate::node!(@value [$($done)*] $key [] $($rest)+)
+    };
+    (@value [$($done:tt)*] $key:tt [$($current:tt)+] , $($rest:tt)*) => {
+        $crate::node!(@map [$($done)* ($key, $crate::node!($($current)+)),] $($rest)*)
+    };
+    (@value [$($done:tt)*] $key:tt [$($current:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::node!(@value [$($done)*] $key [$($current)* $next] $($rest)*)
+    };
+    (@value [$($done:tt)*] $key:tt [$($current:tt)+]) => {
+        $crate::node!(@map [$($done)* ($key, $crate::node!($($current)+)),])
+    };
+
+    ($leaf:expr) => {
+        $crate::recursive::Node::Leaf(::std::convert::Into::into($leaf))
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_node_macro() {
+        let key = "port";
+        let port = 8080;
+        let built: Node<i32> = node!({
+            "servers": [{ key: port, "tags": [] }, null,],
+            "count": (1 + 1),
+        });
+        let expected = Node::map()
+            .insert(
+                "servers",
+                Node::sequence()
+                    .push(
+                        Node::map()
+                            .insert("port", 8080)
+                            .insert("tags", Node::sequence()),
+                    )
+                    .push(Node::Null),
+            )
+            .insert("count", 2);
+        assert_eq!(built, expected);
+        assert_eq!(node!({}), Node::<i32>::map());
+        assert_eq!(node!(-1), Node::Leaf(-1));
+    }
+
+    #[test]
+    fn test_push() {
+        let set = Node::Set(vec![]).push(1).push(2).push(1);
+        assert_eq!(set, Node::Set(vec![Node::Leaf(1), Node::Leaf(2)]));
+        assert_eq!(
+            Node::Tuple(vec![]).push(1),
+            Node::Tuple(vec![Node::Leaf(1)])
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_insert_into_sequence() {
+        let _ = Node::sequence().insert("a", 1);
+    }
+}
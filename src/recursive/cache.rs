@@ -0,0 +1,270 @@
+use crate::recursive::diff_trees;
+use crate::recursive::diffable::Diffable;
+use crate::recursive::types::{Change, DiffOptions, Node, Primitive};
+use std::collections::{HashMap, VecDeque};
+
+/// The key of a cached diff: a hash of both versions, see [`CacheStore`].
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub struct CacheKey {
+    pub old: u64,
+    pub new: u64,
+}
+
+/// Where a [`DiffCache`] keeps its diffs, e.g. in memory with [`MemoryStore`],
+/// or in files or a shared service to reuse them across processes.
+///
+/// Keys are FNV-1a hashes of the versions, like their
+/// [`structural_hash`](crate::recursive::Node::structural_hash) but the same
+/// across builds on platforms of the same byte order and pointer width.
+/// Each diff is kept with the versions it was computed from, which a hit
+/// is checked against, so that versions with colliding hashes can't be
+/// given each other's diff.
+pub trait CacheStore<P: Primitive> {
+    /// The diff cached for `key`, if any.
+    fn get(&mut self, key: &CacheKey) -> Option<CachedDiff<P>>;
+    /// Caches the diff for `key`.
+    fn insert(&mut self, key: CacheKey, diff: CachedDiff<P>);
+}
+
+/// A diff kept by a [`CacheStore`], with the versions it was computed from.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct CachedDiff<P: Primitive> {
+    pub old: Node<P>,
+    pub new: Node<P>,
+    pub changes: Vec<Change<P>>,
+}
+
+/// A [`CacheStore`] keeping up to a number of diffs in memory, along with
+/// their versions, forgetting the oldest one when it's full.
+#[derive(Clone, Debug)]
+pub struct MemoryStore<P: Primitive> {
+    capacity: usize,
+    diffs: HashMap<CacheKey, CachedDiff<P>>,
+    order: VecDeque<CacheKey>,
+}
+
+impl<P: Primitive> MemoryStore<P> {
+    /// An empty store for up to `capacity` diffs.
+    pub fn new(capacity: usize) -> Self {
+        MemoryStore {
+            capacity,
+            diffs: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// The number of diffs in the store.
+    pub fn len(&self) -> usize {
+        self.diffs.len()
+    }
+
+    /// Whether the store holds no diff.
+    pub fn is_empty(&self) -> bool {
+        self.diffs.is_empty()
+    }
+}
+
+impl<P: Primitive> CacheStore<P> for MemoryStore<P> {
+    fn get(&mut self, key: &CacheKey) -> Option<CachedDiff<P>> {
+        self.diffs.get(key).cloned()
+    }
+
+    fn insert(&mut self, key: CacheKey, diff: CachedDiff<P>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.diffs.insert(key, diff).is_none() {
+            self.order.push_back(key);
+        }
+        while self.diffs.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.diffs.remove(&oldest);
+            }
+        }
+    }
+}
+
+/// Memoizes diffs by the hashes of their versions, e.g. for a service
+/// comparing the same artifacts again and again.
+///
+/// Hashing both versions takes a single pass over them, and checking a hit
+/// against the versions kept with it another, much less than diffing them.
+/// The options aren't part of the key: a store must only be shared by caches
+/// with the same options.
+/// ```
+/// use std::collections::HashMap;
+/// use diffkit::recursive::DiffCache;
+///
+/// let old = HashMap::from([("replicas".to_string(), 3)]);
+/// let new = HashMap::from([("replicas".to_string(), 5)]);
+/// let mut cache = DiffCache::new(100);
+/// let changes = cache.diff(&old, &new);
+/// assert_eq!(cache.diff(&old, &new), changes);
+/// assert_eq!((cache.hits(), cache.misses()), (1, 1));
+/// ```
+#[derive(Clone, Debug)]
+pub struct DiffCache<P: Primitive, S: CacheStore<P> = MemoryStore<P>> {
+    store: S,
+    options: DiffOptions<P>,
+    hits: usize,
+    misses: usize,
+}
+
+impl<P: Primitive> DiffCache<P> {
+    /// A cache keeping up to `capacity` diffs in memory.
+    pub fn new(capacity: usize) -> Self {
+        Self::with_store(MemoryStore::new(capacity))
+    }
+}
+
+impl<P: Primitive, S: CacheStore<P>> DiffCache<P, S> {
+    /// A cache keeping its diffs in `store`.
+    pub fn with_store(store: S) -> Self {
+        DiffCache {
+            store,
+            options: DiffOptions::default(),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Diffs with `options` instead of the default ones.
+    pub fn options(mut self, options: DiffOptions<P>) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// The changes between `old` and `new`, from the store if they were diffed before,
+    /// otherwise diffed with [`diff_with`](crate::recursive::diff_with) and stored.
+    pub fn diff<T: Diffable<P = P> + ?Sized>(&mut self, old: &T, new: &T) -> Vec<Change<P>> {
+        let (old, new) = (old.to_node(), new.to_node());
+        let key = CacheKey {
+            old: old.stable_hash(),
+            new: new.stable_hash(),
+        };
+        // a diff of other versions with the same hashes is a miss
+        let cached = self.store.get(&key);
+        if let Some(cached) = cached.filter(|c| c.old == old && c.new == new) {
+            self.hits += 1;
+            return cached.changes;
+        }
+        self.misses += 1;
+        let changes = diff_trees(old.clone(), new.clone(), &self.options);
+        let diff = CachedDiff {
+            old,
+            new,
+            changes: changes.clone(),
+        };
+        self.store.insert(key, diff);
+        changes
+    }
+
+    /// The number of diffs found in the store.
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    /// The number of diffs computed.
+    pub fn misses(&self) -> usize {
+        self.misses
+    }
+
+    /// The store of the cache.
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recursive::{diff, PathPattern};
+    use std::collections::BTreeMap;
+
+    fn map(entries: &[(&str, i32)]) -> BTreeMap<String, i32> {
+        entries.iter().map(|(k, v)| (k.to_string(), *v)).collect()
+    }
+
+    #[test]
+    fn test_diff_cache() {
+        let versions = [
+            map(&[("a", 1)]),
+            map(&[("a", 2)]),
+            map(&[("a", 2), ("b", 3)]),
+        ];
+        let mut cache = DiffCache::new(2);
+        for _ in 0..2 {
+            for pair in versions.windows(2) {
+                assert_eq!(cache.diff(&pair[0], &pair[1]), diff(&pair[0], &pair[1]));
+            }
+        }
+        assert_eq!((cache.hits(), cache.misses()), (2, 2));
+        assert_eq!(
+            cache.diff(&versions[1], &versions[0]),
+            diff(&versions[1], &versions[0])
+        );
+        assert_eq!(cache.misses(), 3);
+        assert_eq!(cache.store().len(), 2);
+        // the first diff was forgotten
+        cache.diff(&versions[0], &versions[1]);
+        assert_eq!(cache.misses(), 4);
+
+        let mut cache = DiffCache::new(0);
+        cache.diff(&versions[0], &versions[1]);
+        cache.diff(&versions[0], &versions[1]);
+        assert_eq!((cache.hits(), cache.misses()), (0, 2));
+        assert!(cache.store().is_empty());
+    }
+
+    #[test]
+    fn test_custom_store() {
+        /// A store counting its lookups.
+        #[derive(Default)]
+        struct Counting {
+            diffs: HashMap<CacheKey, CachedDiff<i32>>,
+            lookups: usize,
+        }
+
+        impl CacheStore<i32> for Counting {
+            fn get(&mut self, key: &CacheKey) -> Option<CachedDiff<i32>> {
+                self.lookups += 1;
+                self.diffs.get(key).cloned()
+            }
+
+            fn insert(&mut self, key: CacheKey, diff: CachedDiff<i32>) {
+                self.diffs.insert(key, diff);
+            }
+        }
+
+        let options = DiffOptions::new().ignore("b".parse::<PathPattern>().unwrap());
+        let mut cache = DiffCache::with_store(Counting::default()).options(options);
+        let (old, new) = (map(&[("a", 1), ("b", 1)]), map(&[("a", 2), ("b", 2)]));
+        assert_eq!(cache.diff(&old, &new).len(), 1);
+        assert_eq!(cache.diff(&old, &new).len(), 1);
+        assert_eq!((cache.store().lookups, cache.hits()), (2, 1));
+    }
+
+    #[test]
+    fn test_colliding_keys() {
+        /// A store giving its last diff for any key, as if all keys collided.
+        #[derive(Default)]
+        struct Colliding(Option<CachedDiff<i32>>);
+
+        impl CacheStore<i32> for Colliding {
+            fn get(&mut self, _: &CacheKey) -> Option<CachedDiff<i32>> {
+                self.0.clone()
+            }
+
+            fn insert(&mut self, _: CacheKey, diff: CachedDiff<i32>) {
+                self.0 = Some(diff);
+            }
+        }
+
+        let mut cache = DiffCache::with_store(Colliding::default());
+        let (a, b, c) = (map(&[("a", 1)]), map(&[("a", 2)]), map(&[("b", 3)]));
+        assert_eq!(cache.diff(&a, &b), diff(&a, &b));
+        assert_eq!(cache.diff(&a, &c), diff(&a, &c));
+        assert_eq!(cache.diff(&a, &c), diff(&a, &c));
+        assert_eq!((cache.hits(), cache.misses()), (1, 2));
+    }
+}
@@ -0,0 +1,197 @@
+use crate::recursive::diffable::Diffable;
+use crate::recursive::types::{ApplyError, Node, PathSegment, Primitive};
+use ciborium::Value;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+/// A leaf of a CBOR value: a `ciborium::Value` other than an array
+/// or a map with text keys.
+///
+/// Maps with other keys, or with the same key twice, are kept whole as leaves,
+/// and so are tagged values. Floats are compared by their bits, so `NaN` equals itself.
+#[derive(Clone, Debug)]
+pub struct CborLeaf(pub Value);
+
+impl PartialEq for CborLeaf {
+    fn eq(&self, other: &Self) -> bool {
+        same(&self.0, &other.0)
+    }
+}
+
+impl Eq for CborLeaf {}
+
+impl Hash for CborLeaf {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        hash_value(&self.0, state);
+    }
+}
+
+impl Primitive for CborLeaf {}
+
+fn same(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Float(a), Value::Float(b)) => a.to_bits() == b.to_bits(),
+        (Value::Tag(ta, a), Value::Tag(tb, b)) => ta == tb && same(a, b),
+        (Value::Array(a), Value::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same(a, b))
+        }
+        (Value::Map(a), Value::Map(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .zip(b)
+                    .all(|((ka, va), (kb, vb))| same(ka, kb) && same(va, vb))
+        }
+        (a, b) => a == b,
+    }
+}
+
+/// Hashes a value consistently with [`same`].
+fn hash_value<H: Hasher>(value: &Value, state: &mut H) {
+    std::mem::discriminant(value).hash(state);
+    match value {
+        Value::Integer(v) => v.hash(state),
+        Value::Bytes(v) => v.hash(state),
+        Value::Float(v) => v.to_bits().hash(state),
+        Value::Text(v) => v.hash(state),
+        Value::Bool(v) => v.hash(state),
+        Value::Tag(tag, v) => {
+            tag.hash(state);
+            hash_value(v, state);
+        }
+        Value::Array(values) => {
+            values.len().hash(state);
+            values.iter().for_each(|v| hash_value(v, state));
+        }
+        Value::Map(entries) => {
+            entries.len().hash(state);
+            for (k, v) in entries {
+                hash_value(k, state);
+                hash_value(v, state);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The entries of a map keyed by distinct text strings.
+fn text_keyed(entries: &[(Value, Value)]) -> Option<HashMap<String, Node<CborLeaf>>> {
+    let mut map = HashMap::new();
+    for (key, value) in entries {
+        if map
+            .insert(key.as_text()?.to_string(), value.to_node())
+            .is_some()
+        {
+            return None;
+        }
+    }
+    Some(map)
+}
+
+/// CBOR values: maps with text keys become maps, arrays sequences,
+/// `null` becomes [`Node::Null`], and the other values [`CborLeaf`]s.
+/// Map entries converted back from a [`Node`] are sorted by key.
+/// ```
+/// use diffkit::recursive::{apply, diff};
+/// use ciborium::Value;
+///
+/// let old = Value::Map(vec![("id".into(), 1.into()), ("tags".into(), Value::Array(vec![]))]);
+/// let new = Value::Map(vec![("id".into(), 1.into()), ("tags".into(), Value::Array(vec!["a".into()]))]);
+/// let changes = diff(&old, &new);
+/// assert_eq!(changes.len(), 1);
+/// assert_eq!(apply(&old, &changes), Ok(new));
+/// ```
+impl Diffable for Value {
+    type P = CborLeaf;
+    fn to_node(&self) -> Node<Self::P> {
+        match self {
+            Value::Array(array) => Node::Sequence(array.iter().map(Diffable::to_node).collect()),
+            Value::Map(entries) => match text_keyed(entries) {
+                Some(map) => Node::Map(map),
+                None => Node::Leaf(CborLeaf(self.clone())),
+            },
+            Value::Null => Node::Null,
+            leaf => Node::Leaf(CborLeaf(leaf.clone())),
+        }
+    }
+
+    fn from_node(node: Node<Self::P>) -> Result<Self, ApplyError> {
+        match node {
+            Node::Map(map) => {
+                let mut entries = map
+                    .into_iter()
+                    .map(|(k, v)| match Self::from_node(v) {
+                        Ok(v) => Ok((k, v)),
+                        Err(e) => Err(e.within(PathSegment::Key(k))),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+                Ok(Value::Map(
+                    entries.into_iter().map(|(k, v)| (k.into(), v)).collect(),
+                ))
+            }
+            Node::Sequence(sequence) | Node::Tuple(sequence) | Node::Set(sequence) => sequence
+                .into_iter()
+                .enumerate()
+                .map(|(i, v)| Self::from_node(v).map_err(|e| e.within(PathSegment::Index(i))))
+                .collect::<Result<_, _>>()
+                .map(Value::Array),
+            Node::Leaf(leaf) => Ok(leaf.0),
+            Node::Null => Ok(Value::Null),
+            Node::Variant(tag, content) => {
+                Self::from_node(Node::Map(HashMap::from([(tag, *content)])))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recursive::{apply, diff, ChangeKind};
+
+    fn map(entries: Vec<(&str, Value)>) -> Value {
+        Value::Map(entries.into_iter().map(|(k, v)| (k.into(), v)).collect())
+    }
+
+    #[test]
+    fn test_encoded_values() {
+        let old = map(vec![
+            ("at", Value::Tag(1, Box::new(1_700_000_000.into()))),
+            ("ratio", Value::Float(f64::NAN)),
+            ("items", Value::Array(vec![1.into(), 2.into()])),
+        ]);
+        let new = map(vec![
+            ("at", Value::Tag(1, Box::new(1_700_000_001.into()))),
+            ("ratio", Value::Float(f64::NAN)),
+            ("items", Value::Array(vec![1.into(), 2.into()])),
+        ]);
+        let mut bytes = vec![];
+        ciborium::into_writer(&old, &mut bytes).unwrap();
+        let decoded: Value = ciborium::from_reader(bytes.as_slice()).unwrap();
+
+        let changes = diff(&decoded, &new);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].path, vec![PathSegment::Key("at".to_string())]);
+        let patched = apply(&decoded, &changes).unwrap();
+        assert!(same(
+            &patched,
+            &map(vec![
+                ("at", Value::Tag(1, Box::new(1_700_000_001.into()))),
+                ("items", Value::Array(vec![1.into(), 2.into()])),
+                ("ratio", Value::Float(f64::NAN)),
+            ])
+        ));
+    }
+
+    #[test]
+    fn test_maps_without_text_keys_are_leaves() {
+        let old = Value::Map(vec![(1.into(), "a".into())]);
+        let new = Value::Map(vec![(1.into(), "b".into())]);
+        assert_eq!(
+            diff(&old, &new)[0].kind,
+            ChangeKind::Modified(CborLeaf(old.clone()), CborLeaf(new.clone()))
+        );
+        let duplicated = map(vec![("a", 1.into()), ("a", 2.into())]);
+        assert!(matches!(duplicated.to_node(), Node::Leaf(_)));
+    }
+}
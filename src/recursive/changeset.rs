@@ -0,0 +1,53 @@
+use crate::recursive::invert_changes;
+use crate::recursive::types::{Change, Primitive};
+use std::collections::BTreeMap;
+
+/// A list of changes with an id and metadata, e.g. for an audit log
+/// recording who produced a diff, when and why.
+///
+/// Metadata are free-form strings; with the `json` feature,
+/// `ChangeSet::to_json` serializes them alongside the changes.
+/// ```
+/// use std::collections::HashMap;
+/// use diffkit::recursive::{apply, diff, ChangeSet};
+///
+/// let old = HashMap::from([("replicas".to_string(), 3)]);
+/// let new = HashMap::from([("replicas".to_string(), 5)]);
+/// let set = ChangeSet::new("42", diff(&old, &new))
+///     .meta("author", "ann")
+///     .meta("reason", "load test");
+/// assert_eq!(set.meta["author"], "ann");
+/// assert_eq!(apply(&old, &set.changes), Ok(new));
+/// ```
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ChangeSet<P: Primitive> {
+    pub id: String,
+    pub meta: BTreeMap<String, String>,
+    pub changes: Vec<Change<P>>,
+}
+
+impl<P: Primitive> ChangeSet<P> {
+    /// A change set without metadata.
+    pub fn new(id: impl Into<String>, changes: Vec<Change<P>>) -> Self {
+        ChangeSet {
+            id: id.into(),
+            meta: BTreeMap::new(),
+            changes,
+        }
+    }
+
+    /// Sets the metadata `key` to `value`.
+    pub fn meta(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.meta.insert(key.into(), value.into());
+        self
+    }
+
+    /// The change set undoing this one, with the same id and metadata.
+    pub fn invert(&self) -> Self {
+        ChangeSet {
+            id: self.id.clone(),
+            meta: self.meta.clone(),
+            changes: invert_changes(&self.changes),
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use crate::recursive::diffable::{Diffable, Scalar};
+use crate::recursive::types::{ApplyError, Node, Primitive};
+use ::chrono::{DateTime, NaiveDate, Utc};
+
+macro_rules! impl_diffable_chrono {
+    ($($t:ty),*) => {
+        $(
+            impl Primitive for $t {}
+
+            impl Diffable for $t {
+                type P = Self;
+                fn to_node(&self) -> Node<Self::P> {
+                    Node::Leaf(*self)
+                }
+
+                fn from_node(node: Node<Self::P>) -> Result<Self, ApplyError> {
+                    match node {
+                        Node::Leaf(v) => Ok(v),
+                        _ => Err(ApplyError::TypeMismatch(vec![])),
+                    }
+                }
+            }
+
+            /// Kept as a string, in the format of `Display`.
+            impl From<$t> for Scalar {
+                fn from(v: $t) -> Self {
+                    Scalar::String(v.to_string())
+                }
+            }
+
+            impl TryFrom<Scalar> for $t {
+                type Error = Scalar;
+                fn try_from(scalar: Scalar) -> Result<Self, Scalar> {
+                    match &scalar {
+                        Scalar::String(v) => v.parse().map_err(|_| scalar),
+                        _ => Err(scalar),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_diffable_chrono!(DateTime<Utc>, NaiveDate);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recursive::{apply, diff, ChangeKind};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_timestamps() {
+        let at = |s: &str| s.parse::<DateTime<Utc>>().unwrap();
+        let old = HashMap::from([("created".to_string(), at("2024-01-01T00:00:00Z"))]);
+        let new = HashMap::from([("created".to_string(), at("2024-01-01T00:00:01Z"))]);
+        let changes = diff(&old, &new);
+        assert_eq!(
+            changes[0].kind,
+            ChangeKind::Modified(at("2024-01-01T00:00:00Z"), at("2024-01-01T00:00:01Z"))
+        );
+        assert_eq!(apply(&old, &changes), Ok(new));
+    }
+
+    #[test]
+    fn test_scalar_conversions() {
+        let date = NaiveDate::from_ymd_opt(2024, 2, 29).unwrap();
+        assert_eq!(NaiveDate::try_from(Scalar::from(date)), Ok(date));
+        let at = "2024-02-29T12:30:00.25Z".parse::<DateTime<Utc>>().unwrap();
+        assert_eq!(DateTime::<Utc>::try_from(Scalar::from(at)), Ok(at));
+        let scalar = Scalar::String("yesterday".to_string());
+        assert_eq!(NaiveDate::try_from(scalar.clone()), Err(scalar));
+    }
+}
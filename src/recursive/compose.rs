@@ -0,0 +1,196 @@
+use crate::recursive::types::{Change, ChangeKind, DiffOptions, PathSegment, Primitive};
+use crate::recursive::{apply_at, diff_nodes};
+
+/// Builds a single list of changes equivalent to applying `first`, then `second`.
+///
+/// Changes to the same value are squashed: an addition followed by a modification
+/// becomes a single addition, a modification followed by a removal a single removal,
+/// and changes cancelling each other out disappear. Changes under a node added
+/// by an earlier change are folded into the added node.
+/// ```
+/// use std::collections::HashMap;
+/// use diffkit::recursive::{apply, compose, diff, Change, ChangeKind, PathSegment};
+///
+/// let a = HashMap::from([("x".to_string(), 1)]);
+/// let b = HashMap::from([("x".to_string(), 2)]);
+/// let c = HashMap::from([("x".to_string(), 3)]);
+/// let changes = compose(&diff(&a, &b), &diff(&b, &c));
+/// assert_eq!(
+///     changes,
+///     vec![Change {
+///         path: vec![PathSegment::Key("x".to_string())],
+///         kind: ChangeKind::Modified(1, 3)
+///     }]
+/// );
+/// assert_eq!(apply(&a, &changes), Ok(c));
+/// ```
+pub fn compose<P: Primitive>(first: &[Change<P>], second: &[Change<P>]) -> Vec<Change<P>> {
+    let mut result = vec![];
+    for change in first.iter().chain(second) {
+        push(&mut result, change.clone());
+    }
+    result
+}
+
+/// Appends `change` to `result`, squashing it into the latest change it interacts with.
+/// The changes in between don't interact with it, so it can be moved before them.
+fn push<P: Primitive>(result: &mut Vec<Change<P>>, change: Change<P>) {
+    let mut change = change;
+    loop {
+        let Some(pos) = result.iter().rposition(|r| interacts(r, &change)) else {
+            result.push(change);
+            return;
+        };
+        let earlier = &result[pos];
+        let depth = earlier.path.len().min(change.path.len());
+
+        if earlier.path == change.path {
+            let squashed = squash(earlier, &change);
+            result.splice(pos..=pos, squashed);
+            return;
+        } else if change.path.starts_with(&earlier.path) {
+            // `change` is inside the node added by `earlier`
+            if let ChangeKind::NodeAdded(node) = &mut result[pos].kind {
+                if apply_at(node, &change, depth, false).is_ok() {
+                    return;
+                }
+            }
+        } else if !earlier.path.starts_with(&change.path) {
+            // `earlier` only shifts the indices of `change`, or the other way around
+        } else if let ChangeKind::NodeRemoved(node) = &mut change.kind {
+            // `change` removes the node modified by `earlier`:
+            // record the node as it was before
+            if apply_at(node, &earlier.invert(), depth, false).is_ok() {
+                result.remove(pos);
+                continue;
+            }
+        }
+        result.push(change);
+        return;
+    }
+}
+
+/// The changes with the effect of `earlier` followed by `later`, at the same path.
+fn squash<P: Primitive>(earlier: &Change<P>, later: &Change<P>) -> Vec<Change<P>> {
+    let at = |kind| Change {
+        path: later.path.clone(),
+        kind,
+    };
+    match (&earlier.kind, &later.kind) {
+        (ChangeKind::Added(_), ChangeKind::Modified(_, new)) => {
+            vec![at(ChangeKind::Added(new.clone()))]
+        }
+        (ChangeKind::Added(_), ChangeKind::Removed(_))
+        | (ChangeKind::NodeAdded(_), ChangeKind::NodeRemoved(_)) => vec![],
+        (ChangeKind::Modified(old, _), ChangeKind::Modified(_, new))
+        | (ChangeKind::Removed(old), ChangeKind::Added(new)) => {
+            if old == new {
+                vec![]
+            } else {
+                vec![at(ChangeKind::Modified(old.clone(), new.clone()))]
+            }
+        }
+        (ChangeKind::Modified(old, _), ChangeKind::Removed(_)) => {
+            vec![at(ChangeKind::Removed(old.clone()))]
+        }
+        // replacing a node: keep its inner changes, unless there are more of them
+        (ChangeKind::NodeRemoved(old), ChangeKind::NodeAdded(new)) => {
+            let changes = diff_nodes(old, new, later.path.clone(), &DiffOptions::default());
+            if changes.len() <= 2 {
+                changes
+            } else {
+                vec![earlier.clone(), later.clone()]
+            }
+        }
+        _ => vec![earlier.clone(), later.clone()],
+    }
+}
+
+/// Whether two changes touch the same value, or one of them
+/// shifts the indices of the sequence holding the other.
+fn interacts<P: Primitive>(a: &Change<P>, b: &Change<P>) -> bool {
+    a.path.starts_with(&b.path) || b.path.starts_with(&a.path) || shifts(a, b) || shifts(b, a)
+}
+
+/// Whether `a` adds or removes an element of a sequence containing `b`.
+fn shifts<P: Primitive>(a: &Change<P>, b: &Change<P>) -> bool {
+    let Some((PathSegment::Index(_), parent)) = a.path.split_last() else {
+        return false;
+    };
+    let resizes = !matches!(
+        a.kind,
+        ChangeKind::Modified(..)
+            | ChangeKind::Renamed { .. }
+            | ChangeKind::Moved { .. }
+            | ChangeKind::TruncatedSubtreeChanged { .. }
+            | ChangeKind::BlobPatched(_)
+            | ChangeKind::TruncatedLeafChanged {
+                old: Some(_),
+                new: Some(_)
+            }
+    );
+    resizes
+        && b.path.starts_with(parent)
+        && matches!(b.path.get(parent.len()), Some(PathSegment::Index(_)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recursive::Node;
+    use std::collections::HashMap;
+
+    fn change(path: Vec<PathSegment>, kind: ChangeKind<i32>) -> Change<i32> {
+        Change { path, kind }
+    }
+
+    fn key(k: &str) -> PathSegment {
+        PathSegment::Key(k.to_string())
+    }
+
+    #[test]
+    fn test_squash_same_path() {
+        let first = vec![change(vec![key("a")], ChangeKind::Added(1))];
+        let second = vec![change(vec![key("a")], ChangeKind::Removed(1))];
+        assert_eq!(compose(&first, &second), vec![]);
+
+        let first = vec![change(vec![key("a")], ChangeKind::Modified(1, 2))];
+        let second = vec![change(vec![key("a")], ChangeKind::Removed(2))];
+        assert_eq!(
+            compose(&first, &second),
+            vec![change(vec![key("a")], ChangeKind::Removed(1))]
+        );
+    }
+
+    #[test]
+    fn test_fold_into_added_node() {
+        let node = Node::Map(HashMap::from([("b".to_string(), Node::Leaf(1))]));
+        let first = vec![change(vec![key("a")], ChangeKind::NodeAdded(node))];
+        let second = vec![change(vec![key("a"), key("b")], ChangeKind::Modified(1, 2))];
+        let expected = Node::Map(HashMap::from([("b".to_string(), Node::Leaf(2))]));
+        assert_eq!(
+            compose(&first, &second),
+            vec![change(vec![key("a")], ChangeKind::NodeAdded(expected))]
+        );
+    }
+
+    #[test]
+    fn test_removal_undoes_inner_changes() {
+        let first = vec![change(vec![key("a"), key("b")], ChangeKind::Modified(1, 2))];
+        let after = Node::Map(HashMap::from([("b".to_string(), Node::Leaf(2))]));
+        let second = vec![change(vec![key("a")], ChangeKind::NodeRemoved(after))];
+        let before = Node::Map(HashMap::from([("b".to_string(), Node::Leaf(1))]));
+        assert_eq!(
+            compose(&first, &second),
+            vec![change(vec![key("a")], ChangeKind::NodeRemoved(before))]
+        );
+    }
+
+    #[test]
+    fn test_shifted_indices_are_not_squashed() {
+        // the second removal targets what was index 1 before the first one
+        let first = vec![change(vec![PathSegment::Index(0)], ChangeKind::Removed(1))];
+        let second = vec![change(vec![PathSegment::Index(0)], ChangeKind::Removed(2))];
+        assert_eq!(compose(&first, &second), [first, second].concat());
+    }
+}
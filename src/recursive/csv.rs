@@ -0,0 +1,217 @@
+use crate::recursive::diffable::Diffable;
+use crate::recursive::types::{ApplyError, Node, PathSegment};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// A CSV file: a header row naming the columns, then rows of cells.
+///
+/// It's diffed as a map with the `columns` and the `rows`, each row a map
+/// from column names to cells, so that a modified cell is reported on its own
+/// at `rows/<index>/<column>`, and an added column at `columns/<index>` then
+/// in each row. Rows are paired by position unless
+/// [`SequenceStrategy::KeyedBy`](crate::recursive::SequenceStrategy::KeyedBy)
+/// names key columns.
+/// ```
+/// use diffkit::recursive::{
+///     apply, diff_with, ChangeKind, CsvTable, DiffOptions, PathSegment, SequenceStrategy,
+/// };
+///
+/// let old: CsvTable = "id,name,city\n1,ann,rome\n2,bob,oslo\n".parse().unwrap();
+/// let new: CsvTable = "id,name,city\n1,ann,rome\n3,cy,nice\n2,bob,bergen\n".parse().unwrap();
+/// let keys = SequenceStrategy::KeyedBy(vec!["id".to_string()]);
+/// let changes = diff_with(&old, &new, &DiffOptions::new().sequence_strategy(keys));
+/// assert_eq!(changes.len(), 2);
+/// let cell = changes.last().unwrap();
+/// assert_eq!(cell.path[2], PathSegment::Key("city".to_string()));
+/// assert_eq!(cell.kind, ChangeKind::Modified("oslo".to_string(), "bergen".to_string()));
+/// assert_eq!(apply(&old, &changes), Ok(new));
+/// ```
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct CsvTable {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+/// Parses a CSV file whose first row holds the column names.
+/// Rows must have as many cells as there are columns.
+impl FromStr for CsvTable {
+    type Err = ::csv::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut reader = ::csv::Reader::from_reader(s.as_bytes());
+        let columns = reader.headers()?.iter().map(str::to_string).collect();
+        let rows = reader
+            .records()
+            .map(|row| row.map(|row| row.iter().map(str::to_string).collect()))
+            .collect::<Result<_, _>>()?;
+        Ok(CsvTable { columns, rows })
+    }
+}
+
+impl fmt::Display for CsvTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut writer = ::csv::Writer::from_writer(vec![]);
+        for row in std::iter::once(&self.columns).chain(&self.rows) {
+            writer.write_record(row).map_err(|_| fmt::Error)?;
+        }
+        let bytes = writer.into_inner().map_err(|_| fmt::Error)?;
+        f.write_str(&String::from_utf8_lossy(&bytes))
+    }
+}
+
+/// Rows can't hold a column twice, so tables with duplicate
+/// column names can't be rebuilt from their nodes.
+impl Diffable for CsvTable {
+    type P = String;
+    fn to_node(&self) -> Node<Self::P> {
+        let rows = self.rows.iter().map(|row| {
+            let cells = self.columns.iter().zip(row);
+            Node::Map(
+                cells
+                    .map(|(column, cell)| (column.clone(), Node::Leaf(cell.clone())))
+                    .collect(),
+            )
+        });
+        Node::Map(HashMap::from([
+            ("columns".to_string(), self.columns.to_node()),
+            ("rows".to_string(), Node::Sequence(rows.collect())),
+        ]))
+    }
+
+    fn from_node(node: Node<Self::P>) -> Result<Self, ApplyError> {
+        let Node::Map(mut map) = node else {
+            return Err(ApplyError::TypeMismatch(vec![]));
+        };
+        let mut field = |key: &str| {
+            map.remove(key)
+                .ok_or_else(|| ApplyError::PathNotFound(vec![PathSegment::Key(key.to_string())]))
+        };
+        let columns = field("columns")?;
+        let columns = Vec::<String>::from_node(columns)
+            .map_err(|e| e.within(PathSegment::Key("columns".to_string())))?;
+        let Node::Sequence(rows) = field("rows")? else {
+            return Err(ApplyError::TypeMismatch(vec![PathSegment::Key(
+                "rows".to_string(),
+            )]));
+        };
+        let rows = rows
+            .into_iter()
+            .enumerate()
+            .map(|(i, row)| {
+                row_from_node(&columns, row).map_err(|e| {
+                    e.within(PathSegment::Index(i))
+                        .within(PathSegment::Key("rows".to_string()))
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(CsvTable { columns, rows })
+    }
+}
+
+/// The cells of a row in the order of the columns, which it must hold exactly.
+fn row_from_node(columns: &[String], row: Node<String>) -> Result<Vec<String>, ApplyError> {
+    let Node::Map(mut cells) = row else {
+        return Err(ApplyError::TypeMismatch(vec![]));
+    };
+    let row = columns
+        .iter()
+        .map(|column| match cells.remove(column) {
+            Some(Node::Leaf(cell)) => Ok(cell),
+            Some(_) => Err(ApplyError::TypeMismatch(vec![PathSegment::Key(
+                column.clone(),
+            )])),
+            None => Err(ApplyError::PathNotFound(vec![PathSegment::Key(
+                column.clone(),
+            )])),
+        })
+        .collect::<Result<_, _>>()?;
+    match cells.into_keys().next() {
+        Some(column) => Err(ApplyError::TypeMismatch(vec![PathSegment::Key(column)])),
+        None => Ok(row),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recursive::SequenceStrategy;
+    use crate::recursive::{apply, diff, diff_with, Change, ChangeKind, DiffOptions};
+
+    fn parse(s: &str) -> CsvTable {
+        s.parse().unwrap()
+    }
+
+    fn at(path: &[&str]) -> Vec<PathSegment> {
+        path.iter()
+            .map(|s| match s.parse() {
+                Ok(i) => PathSegment::Index(i),
+                Err(_) => PathSegment::Key(s.to_string()),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_cells_rows_and_columns() {
+        let old = parse("sku,qty\na,1\nb,2\n");
+        let new = parse("sku,qty,note\na,1,x\nb,3,\n");
+        let changes = diff(&old, &new);
+        let added = |path, cell: &str| Change {
+            path: at(path),
+            kind: ChangeKind::Added(cell.to_string()),
+        };
+        assert_eq!(changes.len(), 4);
+        assert!(changes.contains(&added(&["columns", "2"], "note")));
+        assert!(changes.contains(&added(&["rows", "0", "note"], "x")));
+        assert!(changes.contains(&Change {
+            path: at(&["rows", "1", "qty"]),
+            kind: ChangeKind::Modified("2".to_string(), "3".to_string()),
+        }));
+        assert_eq!(apply(&old, &changes), Ok(new.clone()));
+        assert_eq!(parse(&new.to_string()), new);
+
+        // a keyed row inserted in the middle leaves the others alone
+        let old = parse("id,v\n1,a\n2,b\n");
+        let new = parse("id,v\n1,a\n9,z\n2,b\n");
+        let keys = SequenceStrategy::KeyedBy(vec!["id".to_string()]);
+        let changes = diff_with(&old, &new, &DiffOptions::new().sequence_strategy(keys));
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].path, at(&["rows", "1"]));
+        assert!(matches!(changes[0].kind, ChangeKind::NodeAdded(_)));
+    }
+
+    #[test]
+    fn test_composite_keys() {
+        let old = parse("day,shop,sales\nmon,a,1\nmon,b,2\ntue,a,3\n");
+        let new = parse("day,shop,sales\nmon,a,1\ntue,a,4\n");
+        let keys = SequenceStrategy::KeyedBy(vec!["day".to_string(), "shop".to_string()]);
+        let changes = diff_with(&old, &new, &DiffOptions::new().sequence_strategy(keys));
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].path, at(&["rows", "1"]));
+        assert!(matches!(changes[0].kind, ChangeKind::NodeRemoved(_)));
+        assert_eq!(
+            changes[1],
+            Change {
+                path: at(&["rows", "1", "sales"]),
+                kind: ChangeKind::Modified("3".to_string(), "4".to_string()),
+            }
+        );
+        assert_eq!(apply(&old, &changes), Ok(new.clone()));
+        // by position, the removed row is paired with the next one
+        assert_eq!(diff(&old, &new).len(), 4);
+    }
+
+    #[test]
+    fn test_invalid_tables() {
+        assert!("a,b\n1\n".parse::<CsvTable>().is_err());
+        let table = parse("a,b\n1,2\n");
+        let change = Change {
+            path: at(&["rows", "0", "c"]),
+            kind: ChangeKind::Added("3".to_string()),
+        };
+        assert_eq!(
+            apply(&table, &[change]),
+            Err(ApplyError::TypeMismatch(at(&["rows", "0", "c"])))
+        );
+    }
+}
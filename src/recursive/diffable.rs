@@ -1,48 +1,265 @@
-use crate::recursive::types::{Node, Primitive};
-use std::collections::HashMap;
+use crate::recursive::types::{ApplyError, Node, PathSegment, Primitive};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::ffi::OsString;
+use std::hash::Hash;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
 
 /// Trait to transform a given structure into a `[Node]` tree or viceversa.
 ///
 /// Exposes two functions:
 /// `to_node` transforms a structure into a `[Node]` tree
-/// `from_node` transforms a `[Node]` tree into the initial structure
+/// `from_node` transforms a `[Node]` tree into the initial structure,
+/// failing with [`ApplyError::TypeMismatch`] if the tree doesn't have its shape
 ///
-/// It's implemented for `Vec<T>`, `HashMap<String, T>` where T : Diffable
-/// as well as Rust primitives except floats which lack `[Eq]`.
+/// It's implemented for `Vec<T>`, `VecDeque<T>`, `Box<[T]>`, `[T]`, `[T; N]`, `HashMap<String, T>`,
+/// `BTreeMap<String, T>`, `HashSet<T>`, `BTreeSet<T>`, `Option<T>`, `Box<T>`, `Rc<T>`, `Arc<T>`
+/// and `Cow<'_, T>` where T : Diffable,
+/// tuples of up to 12 Diffable elements sharing their leaf type,
+/// as well as Rust primitives, floats only with the `float` feature since they lack `[Eq]`,
+/// `Duration`, IP and socket addresses, `PathBuf`, `OsString`,
+/// `chrono`'s `DateTime<Utc>` and `NaiveDate` with the `chrono` feature, `Uuid` with the `uuid` feature,
+/// the [`Blob`](crate::recursive::Blob), [`IniFile`](crate::recursive::IniFile)
+/// and [`EnvFile`](crate::recursive::EnvFile) types of this crate, `CsvTable` with the `csv` feature,
+/// and for the values of `serde_json`, `toml`, `rmpv` and `ciborium`
+/// with the `json`, `toml`, `msgpack` and `cbor` features.
+/// With the `derive` feature, `#[derive(Diffable)]` implements it for structs and enums.
 pub trait Diffable {
     type P: Primitive;
     fn to_node(&self) -> Node<Self::P>;
-    fn from_node(node: Node<Self::P>) -> Self;
+    fn from_node(node: Node<Self::P>) -> Result<Self, ApplyError>
+    where
+        Self: Sized;
 }
 
-impl<T: Diffable> Diffable for Vec<T> {
+macro_rules! impl_diffable_sequence {
+    ($($sequence:ty),*) => {
+        $(
+            impl<T: Diffable> Diffable for $sequence {
+                type P = T::P;
+                fn to_node(&self) -> Node<T::P> {
+                    Node::Sequence(self.iter().map(Diffable::to_node).collect())
+                }
+
+                fn from_node(node: Node<Self::P>) -> Result<Self, ApplyError> {
+                    match node {
+                        Node::Sequence(v) => v
+                            .into_iter()
+                            .enumerate()
+                            .map(|(i, v)| {
+                                T::from_node(v).map_err(|e| e.within(PathSegment::Index(i)))
+                            })
+                            .collect(),
+                        _ => Err(ApplyError::TypeMismatch(vec![])),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_diffable_sequence!(Vec<T>, VecDeque<T>, Box<[T]>);
+
+/// Slices can be diffed but not rebuilt: apply changes to a `Vec<T>` instead.
+impl<T: Diffable> Diffable for [T] {
     type P = T::P;
     fn to_node(&self) -> Node<T::P> {
         Node::Sequence(self.iter().map(Diffable::to_node).collect())
     }
+}
+
+macro_rules! impl_diffable_map {
+    ($($map:ident),*) => {
+        $(
+            impl<T: Diffable> Diffable for $map<String, T> {
+                type P = T::P;
+                fn to_node(&self) -> Node<T::P> {
+                    Node::Map(self.iter().map(|(k, v)| (k.clone(), v.to_node())).collect())
+                }
 
-    fn from_node(node: Node<Self::P>) -> Self {
+                fn from_node(node: Node<Self::P>) -> Result<Self, ApplyError> {
+                    match node {
+                        Node::Map(v) => v
+                            .into_iter()
+                            .map(|(k, v)| match T::from_node(v) {
+                                Ok(v) => Ok((k, v)),
+                                Err(e) => Err(e.within(PathSegment::Key(k))),
+                            })
+                            .collect(),
+                        _ => Err(ApplyError::TypeMismatch(vec![])),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_diffable_map!(HashMap, BTreeMap);
+
+macro_rules! impl_diffable_set {
+    ($($set:ident: $($bound:path),*);*) => {
+        $(
+            /// Sets become [`Node::Set`]s: their members are added or removed
+            /// at [`PathSegment::Member`], whatever their order.
+            impl<T: Diffable $(+ $bound)*> Diffable for $set<T> {
+                type P = T::P;
+                fn to_node(&self) -> Node<T::P> {
+                    Node::Set(self.iter().map(Diffable::to_node).collect())
+                }
+
+                fn from_node(node: Node<Self::P>) -> Result<Self, ApplyError> {
+                    match node {
+                        Node::Set(v) | Node::Sequence(v) => v
+                            .into_iter()
+                            .map(|v| T::from_node(v).map_err(|e| e.within(PathSegment::Member)))
+                            .collect(),
+                        _ => Err(ApplyError::TypeMismatch(vec![])),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_diffable_set!(HashSet: Eq, Hash; BTreeSet: Ord);
+
+/// `None` becomes [`Node::Null`], so `Some(Some(v))` and `Some(None)`
+/// can't be told apart from `Some(v)` and `None`.
+impl<T: Diffable> Diffable for Option<T> {
+    type P = T::P;
+    fn to_node(&self) -> Node<T::P> {
+        match self {
+            Some(v) => v.to_node(),
+            None => Node::Null,
+        }
+    }
+
+    fn from_node(node: Node<Self::P>) -> Result<Self, ApplyError> {
         match node {
-            Node::Sequence(v) => v.into_iter().map(T::from_node).collect(),
-            _ => unreachable!(),
+            Node::Null => Ok(None),
+            node => T::from_node(node).map(Some),
         }
     }
 }
 
-impl<T: Diffable> Diffable for HashMap<String, T> {
+macro_rules! impl_diffable_pointer {
+    ($($pointer:ident),*) => {
+        $(
+            /// Pointers are diffed as the value they point to.
+            impl<T: Diffable> Diffable for $pointer<T> {
+                type P = T::P;
+                fn to_node(&self) -> Node<T::P> {
+                    (**self).to_node()
+                }
+
+                fn from_node(node: Node<Self::P>) -> Result<Self, ApplyError> {
+                    T::from_node(node).map($pointer::new)
+                }
+            }
+        )*
+    };
+}
+
+impl_diffable_pointer!(Box, Rc, Arc);
+
+/// Diffed as the value it borrows or owns, and always rebuilt owned,
+/// e.g. a `Cow<'_, [T]>` from a `Vec<T>`.
+impl<T> Diffable for Cow<'_, T>
+where
+    T: Diffable + ToOwned + ?Sized,
+    T::Owned: Diffable<P = T::P>,
+{
     type P = T::P;
     fn to_node(&self) -> Node<T::P> {
-        Node::Map(self.iter().map(|(k, v)| (k.clone(), v.to_node())).collect())
+        (**self).to_node()
     }
 
-    fn from_node(node: Node<Self::P>) -> Self {
-        match node {
-            Node::Map(v) => v.into_iter().map(|(k, v)| (k, T::from_node(v))).collect(),
-            _ => unreachable!(),
-        }
+    fn from_node(node: Node<Self::P>) -> Result<Self, ApplyError> {
+        T::Owned::from_node(node).map(Cow::Owned)
+    }
+}
+
+/// String slices can be diffed but not rebuilt, like slices.
+impl Diffable for str {
+    type P = String;
+    fn to_node(&self) -> Node<String> {
+        Node::Leaf(self.to_string())
+    }
+}
+
+/// The elements of a fixed-length sequence of `len` elements.
+/// Sequences are accepted too, e.g. arrays imported from JSON.
+fn elements<P: Primitive>(node: Node<P>, len: usize) -> Result<Vec<Node<P>>, ApplyError> {
+    match node {
+        Node::Tuple(elements) | Node::Sequence(elements) if elements.len() == len => Ok(elements),
+        _ => Err(ApplyError::TypeMismatch(vec![])),
+    }
+}
+
+/// Arrays become [`Node::Tuple`]s, so their elements are diffed by position.
+/// ```
+/// use diffkit::recursive::{diff, ChangeKind, PathSegment};
+///
+/// let changes = diff(&[1, 2], &[2, 1]);
+/// assert_eq!(changes[0].path, vec![PathSegment::Index(0)]);
+/// assert_eq!(changes[0].kind, ChangeKind::Modified(1, 2));
+/// assert_eq!(changes[1].path, vec![PathSegment::Index(1)]);
+/// ```
+impl<T: Diffable, const N: usize> Diffable for [T; N] {
+    type P = T::P;
+    fn to_node(&self) -> Node<T::P> {
+        Node::Tuple(self.iter().map(Diffable::to_node).collect())
+    }
+
+    fn from_node(node: Node<Self::P>) -> Result<Self, ApplyError> {
+        let values = elements(node, N)?
+            .into_iter()
+            .enumerate()
+            .map(|(i, v)| T::from_node(v).map_err(|e| e.within(PathSegment::Index(i))))
+            .collect::<Result<Vec<_>, _>>()?;
+        values
+            .try_into()
+            .map_err(|_| ApplyError::TypeMismatch(vec![]))
     }
 }
 
+macro_rules! impl_diffable_tuple {
+    ($first:ident $(, $t:ident)*; $($i:tt),+) => {
+        /// Tuples become [`Node::Tuple`]s, so their elements are diffed by position.
+        impl<$first: Diffable $(, $t: Diffable<P = $first::P>)*> Diffable for ($first, $($t,)*) {
+            type P = $first::P;
+            fn to_node(&self) -> Node<Self::P> {
+                Node::Tuple(vec![$(self.$i.to_node()),+])
+            }
+
+            fn from_node(node: Node<Self::P>) -> Result<Self, ApplyError> {
+                let mut elements = elements(node, [$($i),+].len())?.into_iter();
+                Ok(($(
+                    Diffable::from_node(elements.next().ok_or(ApplyError::TypeMismatch(vec![]))?)
+                        .map_err(|e: ApplyError| e.within(PathSegment::Index($i)))?,
+                )+))
+            }
+        }
+    };
+}
+
+impl_diffable_tuple!(A; 0);
+impl_diffable_tuple!(A, B; 0, 1);
+impl_diffable_tuple!(A, B, C; 0, 1, 2);
+impl_diffable_tuple!(A, B, C, D; 0, 1, 2, 3);
+impl_diffable_tuple!(A, B, C, D, E; 0, 1, 2, 3, 4);
+impl_diffable_tuple!(A, B, C, D, E, F; 0, 1, 2, 3, 4, 5);
+impl_diffable_tuple!(A, B, C, D, E, F, G; 0, 1, 2, 3, 4, 5, 6);
+impl_diffable_tuple!(A, B, C, D, E, F, G, H; 0, 1, 2, 3, 4, 5, 6, 7);
+impl_diffable_tuple!(A, B, C, D, E, F, G, H, I; 0, 1, 2, 3, 4, 5, 6, 7, 8);
+impl_diffable_tuple!(A, B, C, D, E, F, G, H, I, J; 0, 1, 2, 3, 4, 5, 6, 7, 8, 9);
+impl_diffable_tuple!(A, B, C, D, E, F, G, H, I, J, K; 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10);
+impl_diffable_tuple!(A, B, C, D, E, F, G, H, I, J, K, L; 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11);
+
 macro_rules! impl_diffable_leaf {
     ($($t:ty),*) => {
         $(
@@ -53,10 +270,10 @@ macro_rules! impl_diffable_leaf {
                     Node::Leaf(self.clone())
                 }
 
-                fn from_node(node : Node<Self::P>) -> Self {
+                fn from_node(node : Node<Self::P>) -> Result<Self, ApplyError> {
                     match node {
-                        Node::Leaf(v) => v,
-                        _ => unreachable!()
+                        Node::Leaf(v) => Ok(v),
+                        _ => Err(ApplyError::TypeMismatch(vec![]))
                     }
                 }
             }
@@ -65,5 +282,252 @@ macro_rules! impl_diffable_leaf {
 }
 
 impl_diffable_leaf!(
-    i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize, bool, String, char
+    i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize, bool, String, char, Duration,
+    IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, PathBuf, OsString, Scalar
 );
+
+/// A leaf holding any of the Rust primitives, so that a single tree can mix them.
+///
+/// It's the default leaf of `#[derive(Diffable)]`: the leaves of each field are
+/// converted to and from `Scalar` with `From` and `TryFrom`.
+/// Integers convert back to any integer type they fit in.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Scalar {
+    Bool(bool),
+    Int(i128),
+    UInt(u128),
+    Char(char),
+    String(String),
+    /// `f32`s and `f64`s, with the `float` feature
+    #[cfg(feature = "float")]
+    Float(ordered_float::OrderedFloat<f64>),
+}
+
+macro_rules! impl_scalar_conversions {
+    ($variant:ident, $($t:ty),*) => {
+        $(
+            impl From<$t> for Scalar {
+                fn from(v: $t) -> Self {
+                    Scalar::$variant(v.into())
+                }
+            }
+
+            impl TryFrom<Scalar> for $t {
+                type Error = Scalar;
+                fn try_from(scalar: Scalar) -> Result<Self, Scalar> {
+                    match scalar {
+                        Scalar::Int(v) => v.try_into().map_err(|_| scalar),
+                        Scalar::UInt(v) => v.try_into().map_err(|_| scalar),
+                        _ => Err(scalar),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_scalar_conversions!(Int, i8, i16, i32, i64, i128);
+impl_scalar_conversions!(UInt, u8, u16, u32, u64, u128);
+
+impl From<isize> for Scalar {
+    fn from(v: isize) -> Self {
+        Scalar::Int(v as i128)
+    }
+}
+
+impl TryFrom<Scalar> for isize {
+    type Error = Scalar;
+    fn try_from(scalar: Scalar) -> Result<Self, Scalar> {
+        i64::try_from(scalar).and_then(|v| v.try_into().map_err(|_| Scalar::Int(v.into())))
+    }
+}
+
+impl From<usize> for Scalar {
+    fn from(v: usize) -> Self {
+        Scalar::UInt(v as u128)
+    }
+}
+
+impl TryFrom<Scalar> for usize {
+    type Error = Scalar;
+    fn try_from(scalar: Scalar) -> Result<Self, Scalar> {
+        u64::try_from(scalar).and_then(|v| v.try_into().map_err(|_| Scalar::UInt(v.into())))
+    }
+}
+
+macro_rules! impl_scalar_variant {
+    ($variant:ident, $t:ty) => {
+        impl From<$t> for Scalar {
+            fn from(v: $t) -> Self {
+                Scalar::$variant(v)
+            }
+        }
+
+        impl TryFrom<Scalar> for $t {
+            type Error = Scalar;
+            fn try_from(scalar: Scalar) -> Result<Self, Scalar> {
+                match scalar {
+                    Scalar::$variant(v) => Ok(v),
+                    _ => Err(scalar),
+                }
+            }
+        }
+    };
+}
+
+impl_scalar_variant!(Bool, bool);
+impl_scalar_variant!(Char, char);
+impl_scalar_variant!(String, String);
+
+impl From<&str> for Scalar {
+    fn from(v: &str) -> Self {
+        Scalar::String(v.to_string())
+    }
+}
+
+macro_rules! impl_scalar_string {
+    ($($t:ty),*) => {
+        $(
+            /// Kept as a string, in the format of `Display`.
+            impl From<$t> for Scalar {
+                fn from(v: $t) -> Self {
+                    Scalar::String(v.to_string())
+                }
+            }
+
+            impl TryFrom<Scalar> for $t {
+                type Error = Scalar;
+                fn try_from(scalar: Scalar) -> Result<Self, Scalar> {
+                    match &scalar {
+                        Scalar::String(v) => v.parse().map_err(|_| scalar),
+                        _ => Err(scalar),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_scalar_string!(IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr);
+
+macro_rules! impl_scalar_os_string {
+    ($($t:ty),*) => {
+        $(
+            /// Kept as a string, replacing invalid UTF-8 with `U+FFFD`.
+            impl From<$t> for Scalar {
+                fn from(v: $t) -> Self {
+                    Scalar::String(v.as_os_str().to_string_lossy().into_owned())
+                }
+            }
+
+            impl TryFrom<Scalar> for $t {
+                type Error = Scalar;
+                fn try_from(scalar: Scalar) -> Result<Self, Scalar> {
+                    match scalar {
+                        Scalar::String(v) => Ok(v.into()),
+                        _ => Err(scalar),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_scalar_os_string!(PathBuf, OsString);
+
+/// Kept as a number of nanoseconds.
+impl From<Duration> for Scalar {
+    fn from(v: Duration) -> Self {
+        Scalar::UInt(v.as_nanos())
+    }
+}
+
+impl TryFrom<Scalar> for Duration {
+    type Error = Scalar;
+    fn try_from(scalar: Scalar) -> Result<Self, Scalar> {
+        match scalar {
+            Scalar::UInt(v) => match u64::try_from(v / 1_000_000_000) {
+                Ok(secs) => Ok(Duration::new(secs, (v % 1_000_000_000) as u32)),
+                Err(_) => Err(scalar),
+            },
+            _ => Err(scalar),
+        }
+    }
+}
+
+/// Functions called by the code of `#[derive(Diffable)]`,
+/// converting fields to and from nodes with the leaves of the derived type.
+#[doc(hidden)]
+pub mod derive_support {
+    use super::Diffable;
+    use crate::recursive::types::{ApplyError, Node, PathSegment, Primitive};
+
+    pub fn to_node<T: Diffable, L: Primitive + From<T::P>>(value: &T) -> Node<L> {
+        value.to_node().map_leaves(&mut L::from)
+    }
+
+    pub fn from_node<T: Diffable, L: Primitive>(node: Node<L>) -> Result<T, ApplyError>
+    where
+        T::P: TryFrom<L>,
+    {
+        T::from_node(node.try_map_leaves(&mut |leaf| T::P::try_from(leaf).ok())?)
+    }
+
+    /// Converts the field at `segment`, missing if `node` is `None`.
+    pub fn from_field<T: Diffable, L: Primitive>(
+        node: Option<Node<L>>,
+        segment: PathSegment,
+    ) -> Result<T, ApplyError>
+    where
+        T::P: TryFrom<L>,
+    {
+        let node = node.ok_or_else(|| ApplyError::PathNotFound(vec![segment.clone()]))?;
+        from_node(node).map_err(|e| e.within(segment))
+    }
+}
+
+/// JSON values: objects become maps, arrays sequences, `null` becomes [`Node::Null`],
+/// and booleans, numbers and strings are leaves.
+#[cfg(feature = "json")]
+impl Primitive for serde_json::Value {}
+
+#[cfg(feature = "json")]
+impl Diffable for serde_json::Value {
+    type P = serde_json::Value;
+    fn to_node(&self) -> Node<Self::P> {
+        match self {
+            serde_json::Value::Object(map) => {
+                Node::Map(map.iter().map(|(k, v)| (k.clone(), v.to_node())).collect())
+            }
+            serde_json::Value::Array(array) => {
+                Node::Sequence(array.iter().map(Diffable::to_node).collect())
+            }
+            serde_json::Value::Null => Node::Null,
+            leaf => Node::Leaf(leaf.clone()),
+        }
+    }
+
+    fn from_node(node: Node<Self::P>) -> Result<Self, ApplyError> {
+        match node {
+            Node::Map(map) => map
+                .into_iter()
+                .map(|(k, v)| match Self::from_node(v) {
+                    Ok(v) => Ok((k, v)),
+                    Err(e) => Err(e.within(PathSegment::Key(k))),
+                })
+                .collect::<Result<_, _>>()
+                .map(serde_json::Value::Object),
+            Node::Sequence(sequence) | Node::Tuple(sequence) | Node::Set(sequence) => sequence
+                .into_iter()
+                .enumerate()
+                .map(|(i, v)| Self::from_node(v).map_err(|e| e.within(PathSegment::Index(i))))
+                .collect::<Result<_, _>>()
+                .map(serde_json::Value::Array),
+            Node::Leaf(v) => Ok(v),
+            Node::Null => Ok(serde_json::Value::Null),
+            Node::Variant(tag, content) => {
+                Self::from_node(Node::Map(HashMap::from([(tag, *content)])))
+            }
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use crate::recursive::blob::Blob;
+use crate::recursive::diffable::Scalar;
+use crate::recursive::types::{Change, ChangeKind, Primitive};
+use std::any::Any;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+/// A leaf left out of a change for its size, see
+/// [`DiffOptions::truncate_leaves`](crate::recursive::DiffOptions::truncate_leaves):
+/// a hash of the leaf and its length in bytes.
+///
+/// Like [`Node::structural_hash`](crate::recursive::Node::structural_hash),
+/// the hash is stable within a build, not across Rust releases.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub struct LeafDigest {
+    pub hash: u64,
+    pub len: usize,
+}
+
+impl LeafDigest {
+    /// The digest of `leaf`, if it's a `String`, a `Scalar::String` or a [`Blob`].
+    pub fn of<P: Primitive>(leaf: &P) -> Option<Self> {
+        let len = leaf_len(leaf)?;
+        let mut state = DefaultHasher::new();
+        leaf.hash(&mut state);
+        Some(LeafDigest {
+            hash: state.finish(),
+            len,
+        })
+    }
+}
+
+fn leaf_len<P: Primitive>(leaf: &P) -> Option<usize> {
+    let leaf = leaf as &dyn Any;
+    if let Some(s) = leaf.downcast_ref::<String>() {
+        return Some(s.len());
+    }
+    if let Some(Scalar::String(s)) = leaf.downcast_ref::<Scalar>() {
+        return Some(s.len());
+    }
+    leaf.downcast_ref::<Blob>().map(|blob| blob.0.len())
+}
+
+/// Replaces the leaves longer than `max_len` bytes in `changes` with their digest,
+/// as [`ChangeKind::TruncatedLeafChanged`].
+pub(crate) fn truncate_leaves<P: Primitive>(changes: &mut [Change<P>], max_len: usize) {
+    let long = |leaf: &P| leaf_len(leaf).is_some_and(|len| len > max_len);
+    for change in changes {
+        let (old, new) = match &change.kind {
+            ChangeKind::Added(new) if long(new) => (None, LeafDigest::of(new)),
+            ChangeKind::Removed(old) if long(old) => (LeafDigest::of(old), None),
+            // both sides are left out, so that the change stays symmetric
+            ChangeKind::Modified(old, new) if long(old) || long(new) => {
+                (LeafDigest::of(old), LeafDigest::of(new))
+            }
+            _ => continue,
+        };
+        change.kind = ChangeKind::TruncatedLeafChanged { old, new };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patch::PatchHeader;
+    use crate::recursive::{
+        apply, diff_with, from_bytes, invert_changes, to_bytes, ApplyError, ChangeStats,
+        DiffOptions, PathSegment,
+    };
+    use crate::serialization::{FromPatch, ToPatch};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_truncated_leaves() {
+        let large = "x".repeat(64);
+        let old = HashMap::from([
+            ("body".to_string(), large.clone()),
+            ("title".to_string(), "a".to_string()),
+            ("gone".to_string(), large.clone()),
+        ]);
+        let new = HashMap::from([
+            ("body".to_string(), "short".to_string()),
+            ("title".to_string(), "b".to_string()),
+            ("note".to_string(), large.clone()),
+        ]);
+        let mut changes = diff_with(&old, &new, &DiffOptions::new().truncate_leaves(16));
+        changes.sort_by_key(|c| format!("{:?}", c.path));
+        let digest = |s: &str| LeafDigest::of(&s.to_string());
+        let kinds: Vec<_> = changes.iter().map(|c| c.kind.clone()).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ChangeKind::TruncatedLeafChanged {
+                    old: digest(&large),
+                    new: digest("short"),
+                },
+                ChangeKind::TruncatedLeafChanged {
+                    old: digest(&large),
+                    new: None,
+                },
+                ChangeKind::TruncatedLeafChanged {
+                    old: None,
+                    new: digest(&large),
+                },
+                ChangeKind::Modified("a".to_string(), "b".to_string()),
+            ]
+        );
+        assert_eq!(digest(&large).unwrap().len, 64);
+        assert_eq!(ChangeStats::from(&changes[..]).truncated, 3);
+
+        let text = changes.to_patch(&PatchHeader::default());
+        assert!(text.contains("truncated-leaf gone "));
+        assert!(text.contains(":64 -> -"));
+        assert_eq!(Vec::from_patch(&text), Ok(changes.clone()));
+        assert_eq!(from_bytes(&to_bytes(&changes)), Ok(changes.clone()));
+        let inverted = invert_changes(&invert_changes(&changes));
+        assert_eq!(inverted, changes);
+        assert_eq!(
+            apply(&old, &changes[..1]),
+            Err(ApplyError::InvalidChangeKind(vec![PathSegment::Key(
+                "body".to_string()
+            )]))
+        );
+
+        // other leaves are kept whole
+        let old = vec![i64::MAX];
+        let changes = diff_with(&old, &vec![0], &DiffOptions::new().truncate_leaves(0));
+        assert_eq!(changes[0].kind, ChangeKind::Modified(i64::MAX, 0));
+    }
+}
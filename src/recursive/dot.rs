@@ -0,0 +1,390 @@
+use crate::recursive::report::{DisplaySegment, Mark};
+use crate::recursive::types::{Change, ChangeKind, Node, PathSegment, Primitive};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Write};
+
+/// A [Graphviz](https://graphviz.org) DOT graph of two trees side by side,
+/// with the nodes touched by `changes` filled: green for additions, red for
+/// removals, yellow for modifications, blue for renames and moves and grey
+/// for truncated diffs. Added and removed subtrees are filled as a whole.
+///
+/// `changes` are those turning `old` into `new`, e.g. from
+/// [`diff_with`](crate::recursive::diff_with) on the values the trees come from;
+/// they are followed in order, so that each node is found even once
+/// the elements before it were added or removed. It's meant for debugging diffs
+/// and illustrating them: render it with e.g. `dot -Tsvg`.
+/// ```
+/// use std::collections::HashMap;
+/// use diffkit::recursive::{diff, to_dot, Diffable};
+///
+/// let old = HashMap::from([("replicas".to_string(), 3)]);
+/// let new = HashMap::from([("replicas".to_string(), 5)]);
+/// let dot = to_dot(&old.to_node(), &new.to_node(), &diff(&old, &new));
+/// assert!(dot.starts_with("digraph diff {\n"));
+/// assert!(dot.contains(r##"old1 [label="replicas: 3", style=filled, fillcolor="#fff8c5"];"##));
+/// assert!(dot.contains("new0 -> new1;"));
+/// ```
+pub fn to_dot<P: Primitive + fmt::Debug>(
+    old: &Node<P>,
+    new: &Node<P>,
+    changes: &[Change<P>],
+) -> String {
+    let mut old_marks = HashMap::new();
+    let mut root = Tracked::new(Some(old), None);
+    for change in changes {
+        follow(&mut root, change, &mut old_marks);
+    }
+    let new_marks = root.marks(new);
+    let mut dot = String::from("digraph diff {\n  node [shape=box, fontname=\"monospace\"];\n");
+    write_cluster(&mut dot, "old", old, &old_marks);
+    write_cluster(&mut dot, "new", new, &new_marks);
+    dot.push_str("}\n");
+    dot
+}
+
+/// The marks of nodes, by address.
+type Marks<P> = HashMap<*const Node<P>, Mark>;
+
+/// A node of the old tree as the changes reshape it, or a node they add.
+///
+/// Its children are only listed once a change is found below it; until then,
+/// they are those of the node of the old tree.
+struct Tracked<'a, P: Primitive> {
+    old: Option<&'a Node<P>>,
+    mark: Option<Mark>,
+    children: Option<Children<'a, P>>,
+    /// The members added to a set, found in the new one by value
+    members: Vec<(Node<P>, Mark)>,
+}
+
+enum Children<'a, P: Primitive> {
+    Map(BTreeMap<String, Tracked<'a, P>>),
+    Sequence(Vec<Tracked<'a, P>>),
+}
+
+impl<'a, P: Primitive> Tracked<'a, P> {
+    fn new(old: Option<&'a Node<P>>, mark: Option<Mark>) -> Self {
+        Tracked {
+            old,
+            mark,
+            children: None,
+            members: vec![],
+        }
+    }
+
+    fn children(&mut self) -> Option<&mut Children<'a, P>> {
+        if self.children.is_none() {
+            let tracked = |node| Tracked::new(Some(node), None);
+            self.children = match self.old? {
+                Node::Map(map) => Some(Children::Map(
+                    map.iter().map(|(k, v)| (k.clone(), tracked(v))).collect(),
+                )),
+                Node::Sequence(v) | Node::Tuple(v) => {
+                    Some(Children::Sequence(v.iter().map(tracked).collect()))
+                }
+                Node::Variant(tag, content) => Some(Children::Map(BTreeMap::from([(
+                    tag.clone(),
+                    tracked(content),
+                )]))),
+                Node::Set(_) | Node::Leaf(_) | Node::Null => None,
+            };
+        }
+        self.children.as_mut()
+    }
+
+    fn get_mut(&mut self, path: &[PathSegment]) -> Option<&mut Self> {
+        path.iter().try_fold(self, |tracked, segment| {
+            match (tracked.children()?, segment) {
+                (Children::Map(map), PathSegment::Key(key)) => map.get_mut(key),
+                (Children::Sequence(v), PathSegment::Index(i)) => v.get_mut(*i),
+                _ => None,
+            }
+        })
+    }
+
+    fn insert(&mut self, segment: &PathSegment, child: Self) -> Option<()> {
+        match (self.children()?, segment) {
+            (Children::Map(map), PathSegment::Key(key)) => {
+                map.insert(key.clone(), child);
+            }
+            (Children::Sequence(v), PathSegment::Index(i)) if *i <= v.len() => {
+                v.insert(*i, child);
+            }
+            _ => return None,
+        }
+        Some(())
+    }
+
+    fn remove(&mut self, segment: &PathSegment) -> Option<Self> {
+        match (self.children()?, segment) {
+            (Children::Map(map), PathSegment::Key(key)) => map.remove(key),
+            (Children::Sequence(v), PathSegment::Index(i)) if *i < v.len() => Some(v.remove(*i)),
+            _ => None,
+        }
+    }
+
+    /// The marks of the nodes of `new`, the tree the changes lead to.
+    fn marks(&self, new: &Node<P>) -> Marks<P> {
+        let mut marks = HashMap::new();
+        let mut stack = vec![(self, new)];
+        while let Some((tracked, node)) = stack.pop() {
+            if let Some(mark) = tracked.mark {
+                marks.insert(node as *const Node<P>, mark);
+            }
+            if let Node::Set(members) = node {
+                for (member, mark) in &tracked.members {
+                    if let Some(member) = members.iter().find(|m| *m == member) {
+                        marks.insert(member as *const Node<P>, *mark);
+                    }
+                }
+            }
+            match (&tracked.children, node) {
+                (Some(Children::Map(map)), Node::Map(entries)) => {
+                    stack.extend(map.iter().filter_map(|(k, t)| Some((t, entries.get(k)?))));
+                }
+                (Some(Children::Map(map)), Node::Variant(tag, content)) => {
+                    stack.extend(map.get(tag).map(|t| (t, &**content)));
+                }
+                (Some(Children::Sequence(v)), Node::Sequence(elements) | Node::Tuple(elements)) => {
+                    stack.extend(v.iter().zip(elements));
+                }
+                _ => {}
+            }
+        }
+        marks
+    }
+}
+
+/// Marks the nodes `change` touches: those of the old tree in `old_marks`,
+/// and those of the new one in the reshaped tree. Changes that don't fit are skipped.
+fn follow<'a, P: Primitive>(
+    root: &mut Tracked<'a, P>,
+    change: &Change<P>,
+    old_marks: &mut Marks<P>,
+) -> Option<()> {
+    let mark = Mark::of(&change.kind);
+    let mut mark_old = |tracked: &Tracked<'a, P>, mark| {
+        if let Some(old) = tracked.old {
+            old_marks.insert(old as *const Node<P>, mark);
+        }
+    };
+    match &change.kind {
+        ChangeKind::Renamed { from, to } => {
+            let map = root.get_mut(&change.path)?;
+            let mut child = map.remove(&PathSegment::Key(from.clone()))?;
+            mark_old(&child, mark);
+            child.mark = Some(mark);
+            map.insert(&PathSegment::Key(to.clone()), child)
+        }
+        ChangeKind::Moved {
+            from_index,
+            to_index,
+        } => {
+            let sequence = root.get_mut(&change.path)?;
+            let mut child = sequence.remove(&PathSegment::Index(*from_index))?;
+            mark_old(&child, mark);
+            child.mark = Some(mark);
+            sequence.insert(&PathSegment::Index(*to_index), child)
+        }
+        _ => {
+            let Some((last, parent)) = change.path.split_last() else {
+                match mark {
+                    Mark::Added => *root = Tracked::new(None, Some(mark)),
+                    Mark::Removed => {
+                        mark_old(root, mark);
+                        *root = Tracked::new(None, None);
+                    }
+                    _ => {
+                        mark_old(root, mark);
+                        root.mark = Some(mark);
+                    }
+                }
+                return Some(());
+            };
+            let parent = root.get_mut(parent)?;
+            if *last == PathSegment::Member {
+                let member = match &change.kind {
+                    ChangeKind::Added(v) | ChangeKind::Removed(v) => Node::Leaf(v.clone()),
+                    ChangeKind::NodeAdded(node) | ChangeKind::NodeRemoved(node) => node.clone(),
+                    _ => return None,
+                };
+                if mark != Mark::Removed {
+                    parent.members.push((member, mark));
+                    return Some(());
+                }
+                let Some(Node::Set(members)) = parent.old else {
+                    return None;
+                };
+                let member = members.iter().find(|m| **m == member)?;
+                old_marks.insert(member as *const Node<P>, mark);
+                return Some(());
+            }
+            match mark {
+                Mark::Added => parent.insert(last, Tracked::new(None, Some(mark))),
+                Mark::Removed => {
+                    let child = parent.remove(last)?;
+                    mark_old(&child, mark);
+                    Some(())
+                }
+                _ => {
+                    let child = parent.get_mut(std::slice::from_ref(last))?;
+                    mark_old(child, mark);
+                    child.mark = Some(mark);
+                    Some(())
+                }
+            }
+        }
+    }
+}
+
+/// Writes `tree` as a cluster of nodes named after it, filled after their marks
+/// and those of the added or removed subtrees they are in.
+fn write_cluster<P: Primitive + fmt::Debug>(
+    dot: &mut String,
+    name: &str,
+    tree: &Node<P>,
+    marks: &Marks<P>,
+) {
+    let _ = writeln!(dot, "  subgraph cluster_{} {{", name);
+    let _ = writeln!(dot, "    label=\"{}\";", name);
+    // the ids and subtree marks of the ancestors of the visited node
+    let mut ancestors: Vec<(usize, Option<Mark>)> = vec![];
+    let mut id = 0;
+    tree.walk(&mut |path: &[PathSegment], node: &Node<P>| {
+        ancestors.truncate(path.len());
+        let inherited = ancestors.last().and_then(|(_, mark)| *mark);
+        let mark = marks.get(&(node as *const Node<P>)).copied().or(inherited);
+        let label = Escaped(NodeLabel(path.last(), node)).to_string();
+        let _ = write!(dot, "    {}{} [label=\"{}\"", name, id, label);
+        if let Some(mark) = mark {
+            let _ = write!(dot, ", style=filled, fillcolor=\"{}\"", fill(mark));
+        }
+        dot.push_str("];\n");
+        if let Some((parent, _)) = ancestors.last() {
+            let _ = writeln!(dot, "    {}{} -> {}{};", name, parent, name, id);
+        }
+        let subtree = mark.filter(|mark| matches!(mark, Mark::Added | Mark::Removed));
+        ancestors.push((id, subtree));
+        id += 1;
+    });
+    dot.push_str("  }\n");
+}
+
+fn fill(mark: Mark) -> &'static str {
+    match mark {
+        Mark::Added => "#dafbe1",
+        Mark::Removed => "#ffebe9",
+        Mark::Modified => "#fff8c5",
+        Mark::Moved => "#ddf4ff",
+        Mark::Truncated => "#eaeef2",
+    }
+}
+
+/// The segment a node is found at, and its value for leaves, like `replicas: 3`.
+struct NodeLabel<'a, P: Primitive>(Option<&'a PathSegment>, &'a Node<P>);
+
+impl<P: Primitive + fmt::Debug> fmt::Display for NodeLabel<'_, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(segment) => write!(f, "{}", DisplaySegment(segment))?,
+            None => write!(f, ".")?,
+        }
+        match self.1 {
+            Node::Leaf(v) => write!(f, ": {:?}", v),
+            Node::Null => write!(f, ": null"),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Writes its content escaped for a quoted DOT string.
+struct Escaped<T>(T);
+
+impl<T: fmt::Display> fmt::Display for Escaped<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.0.to_string().chars() {
+            match c {
+                '"' | '\\' => write!(f, "\\{}", c)?,
+                '\n' => write!(f, "\\n")?,
+                c => write!(f, "{}", c)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node;
+    use crate::recursive::{diff_nodes, DiffOptions};
+
+    /// The labels of the filled nodes of a cluster, with their fill.
+    fn filled(dot: &str, cluster: &str) -> Vec<String> {
+        dot.lines()
+            .filter(|line| line.trim_start().starts_with(cluster) && line.contains("fillcolor"))
+            .map(|line| {
+                let parts: Vec<&str> = line.split('"').collect();
+                format!("{} {}", parts[1], parts[3])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let old: Node<i32> = node!({ "ports": [80, 443, 8080], "name": 1, "tags": [7] });
+        let new: Node<i32> =
+            node!({ "ports": [443, 8080, 9090], "title": 1, "tags": [8], "extra": { "a": 1 } });
+        let options = DiffOptions::new().detect_renames();
+        let changes = diff_nodes(&old, &new, vec![], &options);
+        let dot = to_dot(&old, &new, &changes);
+        assert!(dot.starts_with(
+            "digraph diff {\n  \
+               node [shape=box, fontname=\"monospace\"];\n  \
+               subgraph cluster_old {\n    \
+                 label=\"old\";\n    \
+                 old0 [label=\".\"];\n    \
+                 old1 [label=\"name: 1\", style=filled, fillcolor=\"#ddf4ff\"];\n    \
+                 old0 -> old1;\n    \
+                 old2 [label=\"ports\"];\n"
+        ));
+        // the elements after a removed one are found at their new index
+        assert_eq!(
+            filled(&dot, "old"),
+            vec!["name: 1 #ddf4ff", "[0]: 80 #ffebe9", "[0]: 7 #fff8c5"]
+        );
+        assert_eq!(
+            filled(&dot, "new"),
+            vec![
+                "extra #dafbe1",
+                "a: 1 #dafbe1",
+                "[2]: 9090 #dafbe1",
+                "[0]: 8 #fff8c5",
+                "title: 1 #ddf4ff"
+            ]
+        );
+        assert!(dot.ends_with("  }\n}\n"));
+
+        // the root is replaced
+        let new: Node<i32> = node!([1]);
+        let changes = diff_nodes(&old, &new, vec![], &DiffOptions::new());
+        let dot = to_dot(&old, &new, &changes);
+        assert_eq!(filled(&dot, "old").len(), 8);
+        assert_eq!(filled(&dot, "new"), vec![". #dafbe1", "[0]: 1 #dafbe1"]);
+    }
+
+    #[test]
+    fn test_sets_and_escaping() {
+        let old: Node<String> = Node::Set(vec![node!("a"), node!("b\"")]);
+        let new: Node<String> = Node::Set(vec![node!("b\""), node!("c")]);
+        let changes = diff_nodes(&old, &new, vec![], &DiffOptions::new());
+        let dot = to_dot(&old, &new, &changes);
+        assert!(
+            dot.contains("old1 [label=\"[*]: \\\"a\\\"\", style=filled, fillcolor=\"#ffebe9\"];")
+        );
+        assert!(dot.contains("old2 [label=\"[*]: \\\"b\\\\\\\"\\\"\"];"));
+        assert!(
+            dot.contains("new2 [label=\"[*]: \\\"c\\\"\", style=filled, fillcolor=\"#dafbe1\"];")
+        );
+    }
+}
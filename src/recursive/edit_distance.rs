@@ -0,0 +1,345 @@
+use crate::recursive::types::{Node, PathSegment, Primitive};
+
+/// An operation of a tree edit script, see [`tree_edits`].
+///
+/// Paths of the old tree are those before any edit, and paths of the new tree
+/// those after all of them; set members are all found at [`PathSegment::Member`].
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum TreeEdit {
+    /// The node at `old` in the old tree becomes the node at `new` in the new tree,
+    /// with another label: another type, variant name, leaf or map key.
+    Relabel {
+        old: Vec<PathSegment>,
+        new: Vec<PathSegment>,
+    },
+    /// Deletes the node at the path of the old tree, its children taking its place.
+    Delete(Vec<PathSegment>),
+    /// Inserts the node at the path of the new tree, adopting
+    /// a run of consecutive siblings as its children.
+    Insert(Vec<PathSegment>),
+}
+
+/// A minimal list of node relabelings, deletions and insertions turning `old`
+/// into `new`, seen as ordered trees, found with the Zhang–Shasha algorithm.
+///
+/// Unlike [`diff`](crate::recursive::diff), which compares maps key by key and
+/// aligns sequences level by level, it finds structurally close matches across levels,
+/// e.g. a subtree wrapped into a new node, as for ASTs or XML-like trees.
+/// A node is labeled with its type, variant name or leaf, and the key it's found at
+/// in a map, whose entries are ordered by key.
+/// Its cost grows with the square of the sizes of the trees, at least:
+/// it's meant for trees of a few thousand nodes.
+///
+/// The edits describe how the trees relate rather than changes to apply.
+/// ```
+/// use diffkit::node;
+/// use diffkit::recursive::{tree_edits, Node, PathSegment, TreeEdit};
+///
+/// let old: Node<i32> = node!([1, 2, 3]);
+/// let new: Node<i32> = node!([1, [2, 3]]);
+/// assert_eq!(
+///     tree_edits(&old, &new),
+///     vec![TreeEdit::Insert(vec![PathSegment::Index(1)])]
+/// );
+/// ```
+pub fn tree_edits<P: Primitive>(old: &Node<P>, new: &Node<P>) -> Vec<TreeEdit> {
+    let (old, new) = (Tree::new(old), Tree::new(new));
+    let mut distances = Distances::new(&old, &new);
+    distances.compute();
+    distances.edits()
+}
+
+/// The number of edits of [`tree_edits`], without building them.
+pub fn tree_edit_distance<P: Primitive>(old: &Node<P>, new: &Node<P>) -> usize {
+    let (old, new) = (Tree::new(old), Tree::new(new));
+    let mut distances = Distances::new(&old, &new);
+    distances.compute();
+    distances.tree[old.len() - 1][new.len() - 1]
+}
+
+/// What a node is compared on.
+#[derive(PartialEq)]
+struct Label<'a, P: Primitive> {
+    key: Option<&'a str>,
+    kind: Kind<'a, P>,
+}
+
+#[derive(PartialEq)]
+enum Kind<'a, P: Primitive> {
+    Map,
+    Sequence,
+    Tuple,
+    Variant(&'a str),
+    Set,
+    Leaf(&'a P),
+    Null,
+}
+
+/// The nodes of a tree in post-order, children before their parent.
+struct Tree<'a, P: Primitive> {
+    labels: Vec<Label<'a, P>>,
+    paths: Vec<Vec<PathSegment>>,
+    /// The index of the leftmost leaf below each node
+    leftmost: Vec<usize>,
+    /// The nodes with no left sibling in their leftmost leaf's ancestors,
+    /// highest first from the left, ascending
+    keyroots: Vec<usize>,
+}
+
+impl<'a, P: Primitive> Tree<'a, P> {
+    /// Numbers the nodes from an explicit stack, so deep trees don't exhaust it.
+    fn new(root: &'a Node<P>) -> Self {
+        let mut tree = Tree {
+            labels: vec![],
+            paths: vec![],
+            leftmost: vec![],
+            keyroots: vec![],
+        };
+        // each node with its key in its map, path, and whether its children are done;
+        // the leftmost leaf of the first child is recorded on the way back up
+        let mut stack = vec![(root, None, vec![], false)];
+        let mut firsts: Vec<Option<usize>> = vec![];
+        while let Some((node, key, path, visited)) = stack.pop() {
+            let children = children(node, &path);
+            if !visited && !children.is_empty() {
+                stack.push((node, key, path, true));
+                firsts.push(None);
+                stack.extend(
+                    children
+                        .into_iter()
+                        .rev()
+                        .map(|(child, key, path)| (child, key, path, false)),
+                );
+                continue;
+            }
+            let index = tree.labels.len();
+            let leftmost = if visited {
+                firsts.pop().flatten().unwrap()
+            } else {
+                index
+            };
+            if let Some(first) = firsts.last_mut() {
+                first.get_or_insert(leftmost);
+            }
+            tree.labels.push(Label {
+                key,
+                kind: kind(node),
+            });
+            tree.paths.push(path);
+            tree.leftmost.push(leftmost);
+        }
+        let mut seen = vec![false; tree.len()];
+        for i in (0..tree.len()).rev() {
+            if !seen[tree.leftmost[i]] {
+                seen[tree.leftmost[i]] = true;
+                tree.keyroots.push(i);
+            }
+        }
+        tree.keyroots.reverse();
+        tree
+    }
+
+    fn len(&self) -> usize {
+        self.labels.len()
+    }
+}
+
+fn kind<P: Primitive>(node: &Node<P>) -> Kind<'_, P> {
+    match node {
+        Node::Map(_) => Kind::Map,
+        Node::Sequence(_) => Kind::Sequence,
+        Node::Tuple(_) => Kind::Tuple,
+        Node::Variant(tag, _) => Kind::Variant(tag),
+        Node::Set(_) => Kind::Set,
+        Node::Leaf(v) => Kind::Leaf(v),
+        Node::Null => Kind::Null,
+    }
+}
+
+/// The children of a node with their key in a map, if any, and path;
+/// map entries ordered by key.
+fn children<'a, P: Primitive>(
+    node: &'a Node<P>,
+    path: &[PathSegment],
+) -> Vec<(&'a Node<P>, Option<&'a str>, Vec<PathSegment>)> {
+    let at = |segment| {
+        let mut path = path.to_vec();
+        path.push(segment);
+        path
+    };
+    match node {
+        Node::Map(map) => {
+            let mut entries: Vec<(&String, &Node<P>)> = map.iter().collect();
+            entries.sort_by_key(|(key, _)| *key);
+            entries
+                .into_iter()
+                .map(|(key, child)| (child, Some(key.as_str()), at(PathSegment::Key(key.clone()))))
+                .collect()
+        }
+        Node::Sequence(v) | Node::Tuple(v) => v
+            .iter()
+            .enumerate()
+            .map(|(i, child)| (child, None, at(PathSegment::Index(i))))
+            .collect(),
+        Node::Set(v) => v
+            .iter()
+            .map(|child| (child, None, at(PathSegment::Member)))
+            .collect(),
+        Node::Variant(tag, content) => vec![(&**content, None, at(PathSegment::Key(tag.clone())))],
+        Node::Leaf(_) | Node::Null => vec![],
+    }
+}
+
+/// The edit distances between the subtrees of two trees.
+struct Distances<'t, 'a, P: Primitive> {
+    old: &'t Tree<'a, P>,
+    new: &'t Tree<'a, P>,
+    /// The distance between the subtrees rooted at each pair of nodes
+    tree: Vec<Vec<usize>>,
+}
+
+impl<'t, 'a, P: Primitive> Distances<'t, 'a, P> {
+    fn new(old: &'t Tree<'a, P>, new: &'t Tree<'a, P>) -> Self {
+        Distances {
+            old,
+            new,
+            tree: vec![vec![0; new.len()]; old.len()],
+        }
+    }
+
+    fn compute(&mut self) {
+        for &i in &self.old.keyroots {
+            for &j in &self.new.keyroots {
+                self.forest(i, j);
+            }
+        }
+    }
+
+    fn relabel_cost(&self, i: usize, j: usize) -> usize {
+        usize::from(self.old.labels[i] != self.new.labels[j])
+    }
+
+    /// The distances between the forests of the nodes from the leftmost leaves
+    /// of `i` and `j` up to each node below them, recording those between subtrees.
+    /// `forest[x][y]` holds the distance up to the nodes before `x` and `y`, offset by
+    /// their leftmost leaves.
+    fn forest(&mut self, i: usize, j: usize) -> Vec<Vec<usize>> {
+        let (li, lj) = (self.old.leftmost[i], self.new.leftmost[j]);
+        let (rows, columns) = (i - li + 2, j - lj + 2);
+        let mut forest = vec![vec![0; columns]; rows];
+        forest[0] = (0..columns).collect();
+        for (x, row) in forest.iter_mut().enumerate() {
+            row[0] = x;
+        }
+        for x in 1..rows {
+            for y in 1..columns {
+                let (a, b) = (li + x - 1, lj + y - 1);
+                let edit = (forest[x - 1][y] + 1).min(forest[x][y - 1] + 1);
+                if self.old.leftmost[a] == li && self.new.leftmost[b] == lj {
+                    forest[x][y] = edit.min(forest[x - 1][y - 1] + self.relabel_cost(a, b));
+                    self.tree[a][b] = forest[x][y];
+                } else {
+                    let (la, lb) = (self.old.leftmost[a] - li, self.new.leftmost[b] - lj);
+                    forest[x][y] = edit.min(forest[la][lb] + self.tree[a][b]);
+                }
+            }
+        }
+        forest
+    }
+
+    /// Walks back through the distances of the whole trees, then of the pairs
+    /// of subtrees they map to each other.
+    fn edits(&mut self) -> Vec<TreeEdit> {
+        let mut edits = vec![];
+        let mut pairs = vec![(self.old.len() - 1, self.new.len() - 1)];
+        while let Some((i, j)) = pairs.pop() {
+            let forest = self.forest(i, j);
+            let (li, lj) = (self.old.leftmost[i], self.new.leftmost[j]);
+            let (mut x, mut y) = (i - li + 1, j - lj + 1);
+            while x > 0 || y > 0 {
+                let (a, b) = ((li + x).wrapping_sub(1), (lj + y).wrapping_sub(1));
+                if x > 0 && forest[x - 1][y] + 1 == forest[x][y] {
+                    edits.push(TreeEdit::Delete(self.old.paths[a].clone()));
+                    x -= 1;
+                } else if y > 0 && forest[x][y - 1] + 1 == forest[x][y] {
+                    edits.push(TreeEdit::Insert(self.new.paths[b].clone()));
+                    y -= 1;
+                } else if self.old.leftmost[a] == li && self.new.leftmost[b] == lj {
+                    if self.relabel_cost(a, b) > 0 {
+                        edits.push(TreeEdit::Relabel {
+                            old: self.old.paths[a].clone(),
+                            new: self.new.paths[b].clone(),
+                        });
+                    }
+                    x -= 1;
+                    y -= 1;
+                } else {
+                    // the subtrees of a and b are mapped to each other, walked later
+                    pairs.push((a, b));
+                    x = self.old.leftmost[a] - li;
+                    y = self.new.leftmost[b] - lj;
+                }
+            }
+        }
+        edits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node;
+    use crate::recursive::{diff_nodes, DiffOptions};
+
+    fn key(k: &str) -> PathSegment {
+        PathSegment::Key(k.to_string())
+    }
+
+    #[test]
+    fn test_tree_edits() {
+        let old: Node<i32> = node!({ "a": [1, 2], "b": 3 });
+        assert_eq!(tree_edits(&old, &old), vec![]);
+        assert_eq!(tree_edit_distance(&old, &old), 0);
+
+        // a leaf changes, then a map key
+        let new: Node<i32> = node!({ "a": [1, 5], "b": 3 });
+        let at = vec![key("a"), PathSegment::Index(1)];
+        assert_eq!(
+            tree_edits(&old, &new),
+            vec![TreeEdit::Relabel {
+                old: at.clone(),
+                new: at
+            }]
+        );
+        let new: Node<i32> = node!({ "a": [1, 2], "c": 3 });
+        assert_eq!(tree_edit_distance(&old, &new), 1);
+
+        // flattening a sequence is a deletion, where diff replaces elements
+        let old: Node<i32> = node!({ "x": [[1, 2], 3] });
+        let new: Node<i32> = node!({ "x": [1, 2, 3] });
+        assert_eq!(
+            tree_edits(&old, &new),
+            vec![TreeEdit::Delete(vec![key("x"), PathSegment::Index(0)])]
+        );
+        assert!(diff_nodes(&old, &new, vec![], &DiffOptions::new()).len() > 1);
+        assert_eq!(tree_edits(&new, &old).len(), 1);
+    }
+
+    #[test]
+    fn test_edit_counts() {
+        let trees: [Node<i32>; 5] = [
+            node!([1, [2, 3], { "a": 4 }]),
+            node!([[1, 2], 3]),
+            node!({ "a": [4], "b": null }),
+            node!(1),
+            node!([]),
+        ];
+        for old in &trees {
+            for new in &trees {
+                let distance = tree_edit_distance(old, new);
+                assert_eq!(tree_edits(old, new).len(), distance);
+                assert_eq!(tree_edit_distance(new, old), distance);
+            }
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use crate::recursive::diffable::Diffable;
+use crate::recursive::types::{ApplyError, Node};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// A `.env` file of environment variables, diffed as a map from names to values.
+///
+/// Comments and the order of the variables aren't kept.
+/// ```
+/// use diffkit::recursive::{diff, ChangeKind, EnvFile, PathSegment};
+///
+/// let old: EnvFile = "# api\nAPI_URL=https://a.test\nexport TOKEN='x y'\n".parse().unwrap();
+/// let new: EnvFile = "API_URL=https://b.test\nTOKEN=\"x y\"\n".parse().unwrap();
+/// let changes = diff(&old, &new);
+/// assert_eq!(changes.len(), 1);
+/// assert_eq!(changes[0].path, vec![PathSegment::Key("API_URL".to_string())]);
+/// assert_eq!(new.to_string(), "API_URL=https://b.test\nTOKEN=\"x y\"\n");
+/// ```
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct EnvFile(pub BTreeMap<String, String>);
+
+/// Parses `NAME=value` lines, optionally preceded by `export`, skipping blank
+/// lines and comments. Values are unquoted: single quotes keep their content
+/// as it is, double quotes handle `\n`, `\t`, `\r`, `\"` and `\\` escapes,
+/// and comments after unquoted values start with ` #`.
+/// Repeated variables keep their last value.
+impl FromStr for EnvFile {
+    type Err = ParseEnvError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut variables = BTreeMap::new();
+        for (i, line) in s.lines().enumerate() {
+            let error = ParseEnvError { line: i + 1 };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let line = line.strip_prefix("export ").unwrap_or(line);
+            let (name, value) = line.split_once('=').ok_or(error.clone())?;
+            let name = name.trim();
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                return Err(error);
+            }
+            let value = parse_value(value.trim_start()).ok_or(error)?;
+            variables.insert(name.to_string(), value);
+        }
+        Ok(EnvFile(variables))
+    }
+}
+
+/// Unquotes a value, checking that only a comment follows the closing quote.
+fn parse_value(value: &str) -> Option<String> {
+    let (value, rest) = if let Some(quoted) = value.strip_prefix('\'') {
+        let end = quoted.find('\'')?;
+        (quoted[..end].to_string(), &quoted[end + 1..])
+    } else if let Some(quoted) = value.strip_prefix('"') {
+        let mut unquoted = String::new();
+        let mut chars = quoted.char_indices();
+        let end = loop {
+            match chars.next()? {
+                (i, '"') => break i,
+                (_, '\\') => unquoted.push(match chars.next()?.1 {
+                    'n' => '\n',
+                    't' => '\t',
+                    'r' => '\r',
+                    c @ ('"' | '\\') => c,
+                    _ => return None,
+                }),
+                (_, c) => unquoted.push(c),
+            }
+        };
+        (unquoted, &quoted[end + 1..])
+    } else {
+        let end = value.find(" #").unwrap_or(value.len());
+        return Some(value[..end].trim_end().to_string());
+    };
+    let rest = rest.trim_start();
+    (rest.is_empty() || rest.starts_with('#')).then_some(value)
+}
+
+/// Writes values made of letters, digits and `-_./:@,+=%` as they are,
+/// and the others between double quotes.
+impl fmt::Display for EnvFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, value) in &self.0 {
+            let plain = value
+                .chars()
+                .all(|c| c.is_alphanumeric() || "-_./:@,+=%".contains(c));
+            if plain {
+                writeln!(f, "{}={}", name, value)?;
+                continue;
+            }
+            write!(f, "{}=\"", name)?;
+            for c in value.chars() {
+                match c {
+                    '\n' => write!(f, "\\n")?,
+                    '\t' => write!(f, "\\t")?,
+                    '\r' => write!(f, "\\r")?,
+                    '"' | '\\' => write!(f, "\\{}", c)?,
+                    c => write!(f, "{}", c)?,
+                }
+            }
+            writeln!(f, "\"")?;
+        }
+        Ok(())
+    }
+}
+
+impl Diffable for EnvFile {
+    type P = String;
+    fn to_node(&self) -> Node<Self::P> {
+        self.0.to_node()
+    }
+
+    fn from_node(node: Node<Self::P>) -> Result<Self, ApplyError> {
+        BTreeMap::from_node(node).map(EnvFile)
+    }
+}
+
+/// Error returned when a line of an [`EnvFile`] is neither a variable,
+/// a comment nor blank.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseEnvError {
+    /// The number of the line, starting at 1
+    pub line: usize,
+}
+
+impl fmt::Display for ParseEnvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid env line {}", self.line)
+    }
+}
+
+impl std::error::Error for ParseEnvError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recursive::{apply, diff, Change, ChangeKind, PathSegment};
+
+    fn parse(s: &str) -> EnvFile {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_and_write() {
+        let file = parse(
+            "A=1 # one\nexport B = two words\nC='a \"b\" #c'\nD=\"x\\ny\\\\\" # note\nE=\nA=3\n",
+        );
+        let expected = [
+            ("A", "3"),
+            ("B", "two words"),
+            ("C", "a \"b\" #c"),
+            ("D", "x\ny\\"),
+            ("E", ""),
+        ];
+        let expected = expected.map(|(k, v)| (k.to_string(), v.to_string()));
+        assert_eq!(file, EnvFile(BTreeMap::from(expected)));
+        assert_eq!(parse(&file.to_string()), file);
+
+        for (text, line) in [
+            ("A=1\nB\n", 2),
+            ("A B=1\n", 1),
+            ("A='x\n", 1),
+            ("A=\"x\" y\n", 1),
+        ] {
+            assert_eq!(text.parse::<EnvFile>(), Err(ParseEnvError { line }));
+        }
+    }
+
+    #[test]
+    fn test_diff_and_apply() {
+        let old = parse("HOST=a\nPORT=1\n");
+        let new = parse("HOST=a\nPORT=2\nDEBUG=1\n");
+        let changes = diff(&old, &new);
+        assert_eq!(changes.len(), 2);
+        assert!(changes.contains(&Change {
+            path: vec![PathSegment::Key("PORT".to_string())],
+            kind: ChangeKind::Modified("1".to_string(), "2".to_string()),
+        }));
+        assert_eq!(apply(&old, &changes), Ok(new));
+    }
+}
@@ -0,0 +1,110 @@
+use crate::recursive::diffable::{Diffable, Scalar};
+use crate::recursive::types::{ApplyError, Node, Primitive};
+use ordered_float::OrderedFloat;
+
+macro_rules! impl_diffable_float {
+    ($($t:ty),*) => {
+        $(
+            impl Primitive for OrderedFloat<$t> {}
+
+            /// Floats are [`OrderedFloat`] leaves: `NaN` equals itself, and `0.0` equals `-0.0`.
+            impl Diffable for $t {
+                type P = OrderedFloat<$t>;
+                fn to_node(&self) -> Node<Self::P> {
+                    Node::Leaf(OrderedFloat(*self))
+                }
+
+                fn from_node(node: Node<Self::P>) -> Result<Self, ApplyError> {
+                    match node {
+                        Node::Leaf(v) => Ok(v.0),
+                        _ => Err(ApplyError::TypeMismatch(vec![])),
+                    }
+                }
+            }
+
+            impl Diffable for OrderedFloat<$t> {
+                type P = Self;
+                fn to_node(&self) -> Node<Self::P> {
+                    Node::Leaf(*self)
+                }
+
+                fn from_node(node: Node<Self::P>) -> Result<Self, ApplyError> {
+                    match node {
+                        Node::Leaf(v) => Ok(v),
+                        _ => Err(ApplyError::TypeMismatch(vec![])),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_diffable_float!(f32, f64);
+
+impl From<OrderedFloat<f64>> for Scalar {
+    fn from(v: OrderedFloat<f64>) -> Self {
+        Scalar::Float(v)
+    }
+}
+
+impl From<OrderedFloat<f32>> for Scalar {
+    fn from(v: OrderedFloat<f32>) -> Self {
+        Scalar::Float(OrderedFloat(v.0.into()))
+    }
+}
+
+impl TryFrom<Scalar> for OrderedFloat<f64> {
+    type Error = Scalar;
+    fn try_from(scalar: Scalar) -> Result<Self, Scalar> {
+        match scalar {
+            Scalar::Float(v) => Ok(v),
+            _ => Err(scalar),
+        }
+    }
+}
+
+/// Only the floats that were `f32`s convert back, NaNs included.
+impl TryFrom<Scalar> for OrderedFloat<f32> {
+    type Error = Scalar;
+    fn try_from(scalar: Scalar) -> Result<Self, Scalar> {
+        match scalar {
+            Scalar::Float(v) if v.is_nan() || f64::from(v.0 as f32) == v.0 => {
+                Ok(OrderedFloat(v.0 as f32))
+            }
+            _ => Err(scalar),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recursive::{apply, diff, ChangeKind, PathSegment};
+
+    #[test]
+    fn test_float_leaves() {
+        let old = vec![(1.5, f64::NAN), (0.0, 2.0)];
+        let new = vec![(1.5, f64::NAN), (-0.0, 2.5)];
+        let changes = diff(&old, &new);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(
+            changes[0].path,
+            vec![PathSegment::Index(1), PathSegment::Index(1)]
+        );
+        assert_eq!(
+            changes[0].kind,
+            ChangeKind::Modified(OrderedFloat(2.0), OrderedFloat(2.5))
+        );
+        let patched = apply(&old, &changes).unwrap();
+        assert_eq!(patched[1], (0.0, 2.5));
+    }
+
+    #[test]
+    fn test_scalar_conversions() {
+        let scalar = Scalar::from(OrderedFloat(0.5f32));
+        assert_eq!(OrderedFloat::<f32>::try_from(scalar), Ok(OrderedFloat(0.5)));
+        let scalar = Scalar::from(OrderedFloat(0.1f64));
+        assert_eq!(OrderedFloat::<f32>::try_from(scalar.clone()), Err(scalar));
+        assert!(OrderedFloat::<f32>::try_from(Scalar::from(OrderedFloat(f64::NAN))).is_ok());
+    }
+}
@@ -0,0 +1,147 @@
+use crate::delta::Fnv1a;
+use crate::recursive::types::{Node, Primitive};
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+impl<P: Primitive> Node<P> {
+    /// A hash of the whole tree: equal trees have equal hashes,
+    /// whatever the order of their map entries and set members.
+    ///
+    /// The hash is stable within a build, not across Rust releases,
+    /// so it shouldn't be persisted.
+    /// ```
+    /// use diffkit::node;
+    /// use diffkit::recursive::Node;
+    ///
+    /// let a: Node<i32> = node!({ "x": 1, "y": [2, 3] });
+    /// let b: Node<i32> = node!({ "y": [2, 3], "x": 1 });
+    /// assert_eq!(a.structural_hash(), b.structural_hash());
+    /// let c: Node<i32> = node!({ "x": 1, "y": [3, 2] });
+    /// assert_ne!(a.structural_hash(), c.structural_hash());
+    /// ```
+    pub fn structural_hash(&self) -> u64 {
+        hash_nodes::<P, DefaultHasher>(self, |_, _| {})
+    }
+
+    /// The [`structural_hash`](Node::structural_hash) computed with FNV-1a,
+    /// which doesn't change across builds, as long as the hashes of the leaves
+    /// don't: `std` hashes integers and lengths in the byte order and width
+    /// of the platform.
+    pub(crate) fn stable_hash(&self) -> u64 {
+        hash_nodes::<P, Fnv1a>(self, |_, _| {})
+    }
+
+    /// The [`structural_hash`](Node::structural_hash) of every node of the tree,
+    /// by address, computed in a single pass.
+    pub(crate) fn subtree_hashes(&self) -> HashMap<*const Node<P>, u64> {
+        let mut hashes = HashMap::new();
+        hash_nodes::<P, DefaultHasher>(self, |node, hash| {
+            hashes.insert(node as *const Node<P>, hash);
+        });
+        hashes
+    }
+}
+
+/// Hashes `tree` with `H`, passing each of its nodes to `f` along with its hash.
+fn hash_nodes<'a, P: Primitive, H: Hasher + Default>(
+    tree: &'a Node<P>,
+    mut f: impl FnMut(&'a Node<P>, u64),
+) -> u64 {
+    // nodes are hashed after their children, from an explicit stack
+    // so deep trees don't exhaust it
+    let mut stack = vec![(tree, false)];
+    let mut hashes: Vec<u64> = vec![];
+    while let Some((node, visited)) = stack.pop() {
+        let children = children(node);
+        if !visited && !children.is_empty() {
+            stack.push((node, true));
+            stack.extend(children.into_iter().rev().map(|child| (child, false)));
+            continue;
+        }
+        let mut child_hashes = hashes.split_off(hashes.len() - children.len());
+        let mut state = H::default();
+        // the kind of node, numbered here rather than by its discriminant
+        // so that the stable hash stays so
+        match node {
+            Node::Leaf(v) => {
+                state.write_u8(0);
+                v.hash(&mut state);
+            }
+            Node::Map(map) => {
+                state.write_u8(1);
+                let mut keys: Vec<&String> = map.keys().collect();
+                keys.sort();
+                keys.hash(&mut state);
+            }
+            Node::Variant(tag, _) => {
+                state.write_u8(2);
+                tag.hash(&mut state);
+            }
+            Node::Set(_) => {
+                state.write_u8(3);
+                child_hashes.sort_unstable();
+            }
+            Node::Sequence(_) => state.write_u8(4),
+            Node::Tuple(_) => state.write_u8(5),
+            Node::Null => state.write_u8(6),
+        }
+        child_hashes.hash(&mut state);
+        let hash = state.finish();
+        f(node, hash);
+        hashes.push(hash);
+    }
+    hashes[0]
+}
+
+/// The children of a node, map values ordered by key.
+fn children<P: Primitive>(node: &Node<P>) -> Vec<&Node<P>> {
+    match node {
+        Node::Map(map) => {
+            let mut entries: Vec<(&String, &Node<P>)> = map.iter().collect();
+            entries.sort_by_key(|(key, _)| *key);
+            entries.into_iter().map(|(_, value)| value).collect()
+        }
+        Node::Sequence(v) | Node::Tuple(v) | Node::Set(v) => v.iter().collect(),
+        Node::Variant(_, content) => vec![content],
+        Node::Leaf(_) | Node::Null => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_structural_hash() {
+        let set = |values: &[i32]| Node::Set(values.iter().map(|v| Node::Leaf(*v)).collect());
+        assert_eq!(
+            set(&[1, 2]).structural_hash(),
+            set(&[2, 1]).structural_hash()
+        );
+        assert_ne!(
+            set(&[1, 2]).structural_hash(),
+            Node::Sequence(vec![Node::Leaf(1), Node::Leaf(2)]).structural_hash()
+        );
+        assert_ne!(
+            Node::<i32>::Variant("a".to_string(), Box::new(Node::Null)).structural_hash(),
+            Node::<i32>::Variant("b".to_string(), Box::new(Node::Null)).structural_hash()
+        );
+        assert_ne!(
+            Node::<i32>::Sequence(vec![]).structural_hash(),
+            Node::<i32>::Tuple(vec![]).structural_hash()
+        );
+    }
+
+    #[test]
+    fn test_stable_hash() {
+        let a: Node<i32> = crate::node!({ "x": 1, "y": [2, 3] });
+        let b: Node<i32> = crate::node!({ "y": [2, 3], "x": 1 });
+        let c: Node<i32> = crate::node!({ "x": 1, "y": [3, 2] });
+        assert_eq!(a.stable_hash(), b.stable_hash());
+        assert_ne!(a.stable_hash(), c.stable_hash());
+        // a null hashes its kind, then its empty list of children
+        let mut bytes = vec![6];
+        bytes.extend(0usize.to_ne_bytes());
+        assert_eq!(Node::<i32>::Null.stable_hash(), crate::delta::fnv1a(&bytes));
+    }
+}
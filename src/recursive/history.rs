@@ -0,0 +1,208 @@
+use crate::recursive::apply_node_in_place;
+use crate::recursive::changeset::ChangeSet;
+use crate::recursive::diffable::Diffable;
+use crate::recursive::queue::{ChangeQueue, QueueError};
+use crate::recursive::types::Node;
+
+/// The value left by the change sets of a queue up to the one with the given id,
+/// included, saved so that they don't have to be replayed again.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Snapshot<T> {
+    pub id: String,
+    pub value: T,
+}
+
+/// The history of a value: a base, the queue of change sets applied on top of it,
+/// and snapshots taken every `interval` change sets, e.g. for the versions of a document.
+///
+/// A version is rebuilt from the last snapshot before it, so that at most
+/// `interval - 1` change sets are replayed; with an interval of 0, no snapshot
+/// is taken and versions are replayed from the base.
+/// Snapshots can be saved along with the queue, and replayed from with
+/// [`ChangeQueue::replay_from`].
+/// ```
+/// use std::collections::HashMap;
+/// use diffkit::recursive::{diff, ChangeSet, History};
+///
+/// let versions: Vec<_> = (0..5).map(|i| HashMap::from([("rev".to_string(), i)])).collect();
+/// let mut history = History::new(versions[0].clone(), 2);
+/// for (i, pair) in versions.windows(2).enumerate() {
+///     let set = ChangeSet::new(format!("v{}", i + 1), diff(&pair[0], &pair[1]));
+///     history.append(set).unwrap();
+/// }
+/// let snapshots: Vec<_> = history.snapshots().map(|s| s.id.as_str()).collect();
+/// assert_eq!(snapshots, ["v2", "v4"]);
+/// assert_eq!(history.replay_until("v3"), Ok(versions[3].clone()));
+/// assert_eq!(history.current(), Ok(versions[4].clone()));
+/// ```
+#[derive(Clone, Debug)]
+pub struct History<T: Diffable> {
+    base: T,
+    queue: ChangeQueue<T::P>,
+    /// The snapshots, with the number of change sets they include
+    snapshots: Vec<(usize, Snapshot<T>)>,
+    interval: usize,
+}
+
+impl<T: Diffable> History<T> {
+    /// An empty history of `base`, taking a snapshot every `interval` change sets.
+    pub fn new(base: T, interval: usize) -> Self {
+        History {
+            base,
+            queue: ChangeQueue::new(),
+            snapshots: vec![],
+            interval,
+        }
+    }
+
+    /// Appends `set` on top of the last change set, checking that it applies
+    /// to the current version; takes a snapshot if one is due.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`ChangeQueue::append`], and [`QueueError::Apply`]
+    /// if `set` doesn't fit the current version. `set` isn't appended then.
+    pub fn append(&mut self, set: ChangeSet<T::P>) -> Result<(), QueueError> {
+        if self.queue.get(&set.id).is_some() {
+            return Err(QueueError::DuplicateId(set.id));
+        }
+        let mut node = self.replay(self.queue.len())?;
+        let error = |e| QueueError::Apply(set.id.clone(), e);
+        apply_node_in_place(&mut node, &set.changes).map_err(error)?;
+        let value = T::from_node(node).map_err(error)?;
+        let id = set.id.clone();
+        self.queue.append(set)?;
+        let count = self.queue.len();
+        if self.interval > 0 && count.is_multiple_of(self.interval) {
+            self.snapshots.push((count, Snapshot { id, value }));
+        }
+        Ok(())
+    }
+
+    /// The value the history starts from.
+    pub fn base(&self) -> &T {
+        &self.base
+    }
+
+    /// The change sets appended so far.
+    pub fn queue(&self) -> &ChangeQueue<T::P> {
+        &self.queue
+    }
+
+    /// The snapshots taken so far, from the first one.
+    pub fn snapshots(&self) -> impl Iterator<Item = &Snapshot<T>> {
+        self.snapshots.iter().map(|(_, snapshot)| snapshot)
+    }
+
+    /// The current version, left by all the change sets.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`QueueError::Apply`] if the version doesn't convert back to `T`.
+    pub fn current(&self) -> Result<T, QueueError> {
+        self.version(self.queue.len())
+    }
+
+    /// The version left by the change sets up to the one with the given id, included.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`QueueError::UnknownId`] if no change set has the id,
+    /// or the error of [`current`](History::current).
+    pub fn replay_until(&self, id: &str) -> Result<T, QueueError> {
+        let position = self.queue.iter().position(|set| set.id == id);
+        let position = position.ok_or_else(|| QueueError::UnknownId(id.to_string()))?;
+        self.version(position + 1)
+    }
+
+    fn version(&self, count: usize) -> Result<T, QueueError> {
+        let node = self.replay(count)?;
+        T::from_node(node).map_err(|e| {
+            let id = count.checked_sub(1).and_then(|i| self.queue.iter().nth(i));
+            QueueError::Apply(id.map_or(String::new(), |set| set.id.clone()), e)
+        })
+    }
+
+    /// The tree left by the first `count` change sets, from the last snapshot before them.
+    fn replay(&self, count: usize) -> Result<Node<T::P>, QueueError> {
+        let snapshot = self.snapshots.iter().rev().find(|(n, _)| *n <= count);
+        let (start, mut node) = match snapshot {
+            Some((n, snapshot)) => (*n, snapshot.value.to_node()),
+            None => (0, self.base.to_node()),
+        };
+        for set in self.queue.iter().take(count).skip(start) {
+            apply_node_in_place(&mut node, &set.changes)
+                .map_err(|e| QueueError::Apply(set.id.clone(), e))?;
+        }
+        Ok(node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recursive::{diff, ApplyError, PathSegment};
+
+    #[test]
+    fn test_history() {
+        let versions: Vec<Vec<i32>> = (0..7).map(|i| (0..i).collect()).collect();
+        let mut history = History::new(vec![], 3);
+        for (i, pair) in versions.windows(2).enumerate() {
+            let set = ChangeSet::new(i.to_string(), diff(&pair[0], &pair[1]));
+            history.append(set).unwrap();
+        }
+        let snapshots: Vec<_> = history.snapshots().cloned().collect();
+        assert_eq!(
+            snapshots,
+            vec![
+                Snapshot {
+                    id: "2".to_string(),
+                    value: versions[3].clone()
+                },
+                Snapshot {
+                    id: "5".to_string(),
+                    value: versions[6].clone()
+                },
+            ]
+        );
+        for (i, version) in versions.iter().enumerate().skip(1) {
+            assert_eq!(
+                history.replay_until(&(i - 1).to_string()).as_ref(),
+                Ok(version)
+            );
+        }
+        assert_eq!(
+            history.replay_until("9"),
+            Err(QueueError::UnknownId("9".to_string()))
+        );
+        assert_eq!(history.current(), Ok(versions[6].clone()));
+        assert_eq!(
+            history.queue().replay_from(&snapshots[0]),
+            Ok(versions[6].clone())
+        );
+
+        // a change set that doesn't fit is rejected
+        let stale = ChangeSet::new("stale", diff(&versions[6], &versions[5]));
+        history.append(stale.clone()).unwrap();
+        let stale = ChangeSet {
+            id: "again".to_string(),
+            ..stale
+        };
+        assert_eq!(
+            history.append(stale),
+            Err(QueueError::Apply(
+                "again".to_string(),
+                ApplyError::PathNotFound(vec![PathSegment::Index(5)])
+            ))
+        );
+        assert_eq!(
+            history.append(ChangeSet::new("0", vec![])),
+            Err(QueueError::DuplicateId("0".to_string()))
+        );
+        assert_eq!(history.queue().len(), 7);
+
+        let empty = History::new(vec![1], 0);
+        assert_eq!(empty.current(), Ok(vec![1]));
+        assert_eq!(empty.base(), &vec![1]);
+    }
+}
@@ -0,0 +1,226 @@
+use crate::recursive::report::{DisplaySegment, Group, Label, Mark, Visit};
+use crate::recursive::types::{Change, PathSegment, Primitive};
+use std::fmt;
+
+const STYLE: &str = "\
+.diffkit { font-family: monospace; }
+.diffkit ul { list-style: none; margin: 0; padding-left: 1.5em; }
+.diffkit summary { cursor: pointer; }
+.diffkit .added { color: #1a7f37; }
+.diffkit .removed { color: #cf222e; }
+.diffkit .modified { color: #9a6700; }
+.diffkit .moved { color: #0969da; }
+.diffkit .truncated { color: #6e7781; }
+.diffkit .path { color: #6e7781; margin-left: 1em; font-size: smaller; }
+";
+
+/// Renders a list of changes as a collapsible HTML tree, e.g. for a review page.
+///
+/// Changes are grouped under the containers they are found in, each a `<details>`
+/// element that can be folded. Each change is a list item with the class `added`,
+/// `removed`, `modified`, `moved` or `truncated`, holding its marker and values
+/// like a [`Report`](crate::recursive::Report) line, then the breadcrumbs of its path.
+/// Text is escaped. By default it's a `<div class="diffkit">` to embed in a page;
+/// [`standalone`](HtmlReport::standalone) makes it a whole page, with a style sheet.
+/// ```
+/// use std::collections::HashMap;
+/// use diffkit::recursive::{diff, HtmlReport};
+///
+/// let spec = |image: &str| {
+///     let spec = HashMap::from([("image".to_string(), image.to_string())]);
+///     HashMap::from([("spec".to_string(), spec)])
+/// };
+/// let changes = diff(&spec("a<1>"), &spec("a<2>"));
+/// let html = HtmlReport::new(&changes).to_string();
+/// assert!(html.contains("<details open><summary>spec</summary>"));
+/// assert!(html.contains(r#"<li class="modified">~ image: &quot;a&lt;1&gt;&quot; → &quot;a&lt;2&gt;&quot;"#));
+/// assert!(html.contains(r#"<span class="path">spec › image</span>"#));
+/// ```
+#[derive(Clone, Debug)]
+pub struct HtmlReport<'a, P: Primitive> {
+    changes: &'a [Change<P>],
+    standalone: bool,
+}
+
+impl<'a, P: Primitive> HtmlReport<'a, P> {
+    /// An HTML fragment for `changes`.
+    pub fn new(changes: &'a [Change<P>]) -> Self {
+        HtmlReport {
+            changes,
+            standalone: false,
+        }
+    }
+
+    /// Whether to render a whole page, with a style sheet coloring the changes.
+    pub fn standalone(mut self, standalone: bool) -> Self {
+        self.standalone = standalone;
+        self
+    }
+}
+
+impl<P: Primitive + fmt::Debug> fmt::Display for HtmlReport<'_, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.standalone {
+            writeln!(f, "<!DOCTYPE html>")?;
+            writeln!(f, "<html>")?;
+            writeln!(f, "<head>")?;
+            writeln!(f, "<meta charset=\"utf-8\">")?;
+            writeln!(f, "<title>Changes</title>")?;
+            write!(f, "<style>\n{}</style>\n", STYLE)?;
+            writeln!(f, "</head>")?;
+            writeln!(f, "<body>")?;
+        }
+        writeln!(f, "<div class=\"diffkit\">")?;
+        writeln!(f, "<ul>")?;
+        for visit in Group::new(self.changes).walk() {
+            match visit {
+                Visit::Enter(segment, depth) => {
+                    let indent = Indent(depth);
+                    let segment = Escaped(DisplaySegment(segment));
+                    writeln!(
+                        f,
+                        "{}<li><details open><summary>{}</summary>",
+                        indent, segment
+                    )?;
+                    writeln!(f, "{}<ul>", indent)?;
+                }
+                Visit::Change(change, depth) => {
+                    let mark = Mark::of(&change.kind);
+                    writeln!(
+                        f,
+                        "{}<li class=\"{}\">{} {}<span class=\"path\">{}</span></li>",
+                        Indent(depth),
+                        class(mark),
+                        Escaped(mark.symbol()),
+                        Escaped(Label(change)),
+                        Breadcrumbs(&change.path),
+                    )?;
+                }
+                Visit::Leave(depth) => {
+                    writeln!(f, "{}</ul>", Indent(depth))?;
+                    writeln!(f, "{}</details></li>", Indent(depth))?;
+                }
+            }
+        }
+        writeln!(f, "</ul>")?;
+        writeln!(f, "</div>")?;
+        if self.standalone {
+            writeln!(f, "</body>")?;
+            writeln!(f, "</html>")?;
+        }
+        Ok(())
+    }
+}
+
+fn class(mark: Mark) -> &'static str {
+    match mark {
+        Mark::Added => "added",
+        Mark::Removed => "removed",
+        Mark::Modified => "modified",
+        Mark::Moved => "moved",
+        Mark::Truncated => "truncated",
+    }
+}
+
+struct Indent(usize);
+
+impl fmt::Display for Indent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:1$}", "", 2 * self.0)
+    }
+}
+
+/// The segments of a path like `spec › containers › [2]`, escaped.
+struct Breadcrumbs<'a>(&'a [PathSegment]);
+
+impl fmt::Display for Breadcrumbs<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return write!(f, ".");
+        }
+        for (i, segment) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, " › ")?;
+            }
+            write!(f, "{}", Escaped(DisplaySegment(segment)))?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes its content with the characters special to HTML escaped.
+struct Escaped<T>(T);
+
+impl<T: fmt::Display> fmt::Display for Escaped<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.0.to_string().chars() {
+            match c {
+                '&' => write!(f, "&amp;")?,
+                '<' => write!(f, "&lt;")?,
+                '>' => write!(f, "&gt;")?,
+                '"' => write!(f, "&quot;")?,
+                '\'' => write!(f, "&#39;")?,
+                c => write!(f, "{}", c)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recursive::{ChangeKind, Node};
+
+    fn key(k: &str) -> PathSegment {
+        PathSegment::Key(k.to_string())
+    }
+
+    #[test]
+    fn test_html_report() {
+        let changes = vec![
+            Change {
+                path: vec![key("spec"), key("replicas")],
+                kind: ChangeKind::Modified(3, 5),
+            },
+            Change {
+                path: vec![key("spec"), key("containers"), PathSegment::Index(2)],
+                kind: ChangeKind::NodeRemoved(Node::Null),
+            },
+            Change {
+                path: vec![key("<b>")],
+                kind: ChangeKind::Added(1),
+            },
+            Change {
+                path: vec![key("spec")],
+                kind: ChangeKind::Renamed {
+                    from: "name".to_string(),
+                    to: "title".to_string(),
+                },
+            },
+        ];
+        assert_eq!(
+            HtmlReport::new(&changes).to_string(),
+            "<div class=\"diffkit\">\n\
+             <ul>\n\
+             <li class=\"added\">+ &lt;b&gt;: 1<span class=\"path\">&lt;b&gt;</span></li>\n\
+             <li><details open><summary>spec</summary>\n\
+             <ul>\n  \
+               <li class=\"modified\">~ replicas: 3 → 5<span class=\"path\">spec › replicas</span></li>\n  \
+               <li class=\"moved\">&gt; name → title<span class=\"path\">spec</span></li>\n  \
+               <li><details open><summary>containers</summary>\n  \
+               <ul>\n    \
+                 <li class=\"removed\">- [2]<span class=\"path\">spec › containers › [2]</span></li>\n  \
+               </ul>\n  \
+               </details></li>\n\
+             </ul>\n\
+             </details></li>\n\
+             </ul>\n\
+             </div>\n"
+        );
+        let page = HtmlReport::new(&changes[..1]).standalone(true).to_string();
+        assert!(page.starts_with("<!DOCTYPE html>\n"));
+        assert!(page.contains(".diffkit .added"));
+        assert!(page.ends_with("</div>\n</body>\n</html>\n"));
+    }
+}
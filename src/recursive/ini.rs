@@ -0,0 +1,175 @@
+use crate::recursive::diffable::Diffable;
+use crate::recursive::types::{ApplyError, Node, PathSegment};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// An `.ini` file: the keys before the first section, then sections of keys.
+///
+/// It's diffed as a map holding the keys before the first section as leaves
+/// and each section as a map, so changes are reported key by key.
+/// Values are trimmed and kept as they are, without unquoting;
+/// comments and the order of keys aren't kept.
+/// ```
+/// use diffkit::recursive::{diff, ChangeKind, IniFile, PathSegment};
+///
+/// let old: IniFile = "debug = false\n[db]\nhost = a\nport = 5432\n".parse().unwrap();
+/// let new: IniFile = "debug = false\n[db]\nhost = b\nport = 5432\n".parse().unwrap();
+/// let changes = diff(&old, &new);
+/// assert_eq!(changes.len(), 1);
+/// assert_eq!(
+///     changes[0].path,
+///     vec![PathSegment::Key("db".to_string()), PathSegment::Key("host".to_string())]
+/// );
+/// assert_eq!(changes[0].kind, ChangeKind::Modified("a".to_string(), "b".to_string()));
+/// ```
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct IniFile {
+    pub globals: BTreeMap<String, String>,
+    pub sections: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+/// Parses `key = value` lines under `[section]` headers, skipping blank lines
+/// and comments starting with `;` or `#`. Repeated keys keep their last value,
+/// and repeated sections are merged.
+impl FromStr for IniFile {
+    type Err = ParseIniError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut file = IniFile::default();
+        let mut section = None;
+        for (i, line) in s.lines().enumerate() {
+            let error = ParseIniError { line: i + 1 };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with([';', '#']) {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[') {
+                let name = name.strip_suffix(']').ok_or(error.clone())?.trim();
+                // sections share the map of the file with the keys before them
+                if file.globals.contains_key(name) {
+                    return Err(error);
+                }
+                section = Some(file.sections.entry(name.to_string()).or_default());
+                continue;
+            }
+            let (key, value) = line.split_once('=').ok_or(error.clone())?;
+            let key = key.trim_end();
+            if key.is_empty() {
+                return Err(error);
+            }
+            let keys = match &mut section {
+                Some(section) => section,
+                None => &mut file.globals,
+            };
+            keys.insert(key.to_string(), value.trim_start().to_string());
+        }
+        Ok(file)
+    }
+}
+
+impl fmt::Display for IniFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let write_keys = |f: &mut fmt::Formatter<'_>, keys: &BTreeMap<String, String>| {
+            keys.iter()
+                .try_for_each(|(key, value)| writeln!(f, "{} = {}", key, value))
+        };
+        write_keys(f, &self.globals)?;
+        for (i, (name, keys)) in self.sections.iter().enumerate() {
+            if i > 0 || !self.globals.is_empty() {
+                writeln!(f)?;
+            }
+            writeln!(f, "[{}]", name)?;
+            write_keys(f, keys)?;
+        }
+        Ok(())
+    }
+}
+
+impl Diffable for IniFile {
+    type P = String;
+    fn to_node(&self) -> Node<Self::P> {
+        let globals = self.globals.iter().map(|(k, v)| (k.clone(), v.to_node()));
+        let sections = self.sections.iter().map(|(k, v)| (k.clone(), v.to_node()));
+        Node::Map(globals.chain(sections).collect())
+    }
+
+    fn from_node(node: Node<Self::P>) -> Result<Self, ApplyError> {
+        let Node::Map(map) = node else {
+            return Err(ApplyError::TypeMismatch(vec![]));
+        };
+        let mut file = IniFile::default();
+        for (key, value) in map {
+            match value {
+                Node::Leaf(value) => {
+                    file.globals.insert(key, value);
+                }
+                Node::Map(_) => {
+                    let keys = BTreeMap::from_node(value)
+                        .map_err(|e| e.within(PathSegment::Key(key.clone())))?;
+                    file.sections.insert(key, keys);
+                }
+                _ => return Err(ApplyError::TypeMismatch(vec![PathSegment::Key(key)])),
+            }
+        }
+        Ok(file)
+    }
+}
+
+/// Error returned when a line of an [`IniFile`] is neither a section header,
+/// a key, a comment nor blank, or names a section after a key of the same name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseIniError {
+    /// The number of the line, starting at 1
+    pub line: usize,
+}
+
+impl fmt::Display for ParseIniError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid ini line {}", self.line)
+    }
+}
+
+impl std::error::Error for ParseIniError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recursive::{apply, diff, ChangeKind};
+
+    #[test]
+    fn test_parse_and_write() {
+        let file: IniFile = "; settings\nname = app\n\n[server]\nport=80\n# x\n[log]\nlevel = info = 1\n[server]\nport = 8080\n"
+            .parse()
+            .unwrap();
+        assert_eq!(file.globals["name"], "app");
+        assert_eq!(file.sections["server"]["port"], "8080");
+        assert_eq!(file.sections["log"]["level"], "info = 1");
+        assert_eq!(
+            file.to_string(),
+            "name = app\n\n[log]\nlevel = info = 1\n\n[server]\nport = 8080\n"
+        );
+        assert_eq!(file.to_string().parse(), Ok(file));
+
+        for (text, line) in [
+            ("a = 1\nb\n", 2),
+            ("[s\n", 1),
+            ("= 1\n", 1),
+            ("s = 1\n[s]\n", 2),
+        ] {
+            assert_eq!(text.parse::<IniFile>(), Err(ParseIniError { line }));
+        }
+    }
+
+    #[test]
+    fn test_diff_and_apply() {
+        let old: IniFile = "[a]\nx = 1\n[b]\ny = 2\n".parse().unwrap();
+        let new: IniFile = "top = 0\n[a]\nx = 1\nz = 3\n".parse().unwrap();
+        let changes = diff(&old, &new);
+        assert_eq!(changes.len(), 3);
+        assert!(changes
+            .iter()
+            .any(|c| c.kind == ChangeKind::Added("0".to_string())));
+        assert_eq!(apply(&old, &changes), Ok(new));
+    }
+}
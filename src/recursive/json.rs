@@ -0,0 +1,894 @@
+use crate::recursive::changeset::ChangeSet;
+use crate::recursive::diffable::Diffable;
+use crate::recursive::types::{ApplyError, Change, ChangeKind, Node, PathSegment, Primitive};
+use crate::recursive::{addition, apply_at, apply_node_in_place, removal};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// Converts a list of changes into a JSON Patch (RFC 6902) document,
+/// an array of `add`, `remove` and `replace` operations applied in order,
+/// and `move` operations for renamed keys.
+///
+/// If `strict`, each `remove` and `replace` is preceded by a `test` operation
+/// checking the old value, like [`apply_strict`](crate::recursive::apply_strict) does.
+/// The removal of the whole document has no operation of its own:
+/// the `add` following it replaces the document.
+/// ```
+/// use std::collections::HashMap;
+/// use diffkit::recursive::{diff, to_json_patch};
+/// use serde_json::json;
+///
+/// let old = HashMap::from([("a".to_string(), vec![1, 2])]);
+/// let new = HashMap::from([("a".to_string(), vec![1, 3])]);
+/// let patch = to_json_patch(&diff(&old, &new), true).unwrap();
+/// assert_eq!(
+///     patch,
+///     json!([
+///         { "op": "test", "path": "/a/1", "value": 2 },
+///         { "op": "replace", "path": "/a/1", "value": 3 },
+///     ])
+/// );
+/// ```
+///
+/// # Errors
+///
+/// Returns the error of `serde_json` if a leaf can't be serialized,
+/// if a change adds or removes a set member, or if it marks a truncated value.
+pub fn to_json_patch<P: Primitive + Serialize>(
+    changes: &[Change<P>],
+    strict: bool,
+) -> Result<Value, serde_json::Error> {
+    let mut operations = vec![];
+    for change in changes {
+        if change.path.contains(&PathSegment::Member) {
+            return Err(serde::ser::Error::custom(
+                "set members can't be addressed by a JSON Pointer",
+            ));
+        }
+        let path = pointer(&change.path);
+        let child = |segment: PathSegment| format!("{}{}", path, pointer(&[segment]));
+        let (old, new) = match &change.kind {
+            ChangeKind::Added(new) => (None, Some(serde_json::to_value(new)?)),
+            ChangeKind::NodeAdded(new) => (None, Some(node_to_value(new)?)),
+            ChangeKind::Removed(old) => (Some(serde_json::to_value(old)?), None),
+            ChangeKind::NodeRemoved(old) => (Some(node_to_value(old)?), None),
+            ChangeKind::Modified(old, new) => (
+                Some(serde_json::to_value(old)?),
+                Some(serde_json::to_value(new)?),
+            ),
+            ChangeKind::Renamed { from, to } => {
+                let from = child(PathSegment::Key(from.clone()));
+                let to = child(PathSegment::Key(to.clone()));
+                operations.push(json!({ "op": "move", "from": from, "path": to }));
+                continue;
+            }
+            ChangeKind::Moved {
+                from_index,
+                to_index,
+            } => {
+                let from = child(PathSegment::Index(*from_index));
+                let to = child(PathSegment::Index(*to_index));
+                operations.push(json!({ "op": "move", "from": from, "path": to }));
+                continue;
+            }
+            ChangeKind::TruncatedSubtreeChanged { .. }
+            | ChangeKind::TruncatedLeafChanged { .. } => {
+                return Err(serde::ser::Error::custom(
+                    "truncated changes can't be exported",
+                ));
+            }
+            ChangeKind::BlobPatched(_) => {
+                return Err(serde::ser::Error::custom("blob deltas can't be exported"));
+            }
+        };
+        if let (true, Some(old)) = (strict, &old) {
+            operations.push(json!({ "op": "test", "path": path, "value": old }));
+        }
+        match (old, new) {
+            (Some(_), Some(new)) => {
+                operations.push(json!({ "op": "replace", "path": path, "value": new }))
+            }
+            (None, Some(new)) => {
+                operations.push(json!({ "op": "add", "path": path, "value": new }))
+            }
+            (Some(_), None) if change.path.is_empty() => {}
+            (Some(_), None) => operations.push(json!({ "op": "remove", "path": path })),
+            (None, None) => unreachable!(),
+        }
+    }
+    Ok(Value::Array(operations))
+}
+
+/// Converts a JSON Patch (RFC 6902) document into a list of changes to `base`.
+///
+/// The operations are replayed on `base`, since they don't record the values
+/// they remove or replace, and a pointer like `/0` may lead to a map key
+/// as well as to an index. `move` and `copy` become a removal and an addition,
+/// and `test` operations are checked without producing changes.
+/// ```
+/// use std::collections::HashMap;
+/// use diffkit::recursive::{apply, from_json_patch};
+/// use serde_json::json;
+///
+/// let base = HashMap::from([("a".to_string(), vec![1, 2])]);
+/// let patch = json!([
+///     { "op": "test", "path": "/a/0", "value": 1 },
+///     { "op": "add", "path": "/a/-", "value": 3 },
+///     { "op": "copy", "from": "/a", "path": "/b" },
+/// ]);
+/// let changes = from_json_patch(&base, &patch).unwrap();
+/// let patched = apply(&base, &changes).unwrap();
+/// assert_eq!(patched["a"], vec![1, 2, 3]);
+/// assert_eq!(patched["b"], vec![1, 2, 3]);
+/// ```
+///
+/// # Errors
+///
+/// Returns a [`JsonPatchError`] for the first operation that is malformed,
+/// doesn't fit the document or fails its test.
+pub fn from_json_patch<T>(base: &T, patch: &Value) -> Result<Vec<Change<T::P>>, JsonPatchError>
+where
+    T: Diffable,
+    T::P: DeserializeOwned,
+{
+    let operations = patch.as_array().ok_or(JsonPatchError::NotAnArray)?;
+    let mut node = base.to_node();
+    let mut changes = vec![];
+    for (index, operation) in operations.iter().enumerate() {
+        for change in replay(&node, operation, index)? {
+            apply_at(&mut node, &change, 0, false)
+                .map_err(|_| JsonPatchError::InvalidOperation(index))?;
+            changes.push(change);
+        }
+    }
+    Ok(changes)
+}
+
+/// The changes made by the operation at `index`, applied to `node`.
+fn replay<P: Primitive + DeserializeOwned>(
+    node: &Node<P>,
+    operation: &Value,
+    index: usize,
+) -> Result<Vec<Change<P>>, JsonPatchError> {
+    let invalid = || JsonPatchError::InvalidOperation(index);
+    let field = |name| operation.get(name).ok_or_else(invalid);
+    let string = |name| field(name)?.as_str().ok_or_else(invalid);
+    let value = || value_to_node(field("value")?).map_err(|_| JsonPatchError::BadValue(index));
+    let target = |node, pointer: &str, adding| {
+        resolve(node, pointer, adding)
+            .ok_or_else(|| JsonPatchError::PathNotFound(index, pointer.to_string()))
+    };
+
+    let op = string("op")?;
+    let path = string("path")?;
+    match op {
+        "add" => {
+            let (path, existing) = target(node, path, true)?;
+            Ok(add(path, existing, value()?))
+        }
+        "remove" => {
+            let (path, existing) = target(node, path, false)?;
+            Ok(vec![removal(path, existing.cloned().ok_or_else(invalid)?)])
+        }
+        "replace" => {
+            let (path, existing) = target(node, path, false)?;
+            Ok(replace(
+                path,
+                existing.cloned().ok_or_else(invalid)?,
+                value()?,
+            ))
+        }
+        "copy" => {
+            let (_, source) = target(node, string("from")?, false)?;
+            let copied = source.cloned().ok_or_else(invalid)?;
+            let (path, existing) = target(node, path, true)?;
+            Ok(add(path, existing, copied))
+        }
+        "move" => {
+            let from = string("from")?;
+            if path
+                .strip_prefix(from)
+                .is_some_and(|rest| rest.starts_with('/'))
+            {
+                // a node can't be moved inside itself
+                return Err(invalid());
+            }
+            let (from_path, source) = target(node, from, false)?;
+            let moved = source.cloned().ok_or_else(invalid)?;
+            let removed = removal(from_path, moved.clone());
+            let mut after = node.clone();
+            apply_at(&mut after, &removed, 0, false).map_err(|_| invalid())?;
+            let (path, existing) = target(&after, path, true)?;
+            let mut changes = vec![removed];
+            changes.extend(add(path, existing, moved));
+            Ok(changes)
+        }
+        "test" => {
+            let (_, existing) = target(node, path, false)?;
+            if existing != Some(&value()?) {
+                return Err(JsonPatchError::TestFailed(index, path.to_string()));
+            }
+            Ok(vec![])
+        }
+        _ => Err(invalid()),
+    }
+}
+
+/// The changes adding `new` at `path`: an insertion into a sequence,
+/// or the replacement of the existing value.
+fn add<P: Primitive>(
+    path: Vec<PathSegment>,
+    existing: Option<&Node<P>>,
+    new: Node<P>,
+) -> Vec<Change<P>> {
+    match (path.last(), existing) {
+        (Some(PathSegment::Index(_)), _) | (_, None) => vec![addition(path, new)],
+        (_, Some(old)) => replace(path, old.clone(), new),
+    }
+}
+
+fn replace<P: Primitive>(path: Vec<PathSegment>, old: Node<P>, new: Node<P>) -> Vec<Change<P>> {
+    match (old, new) {
+        (old, new) if old == new => vec![],
+        (Node::Leaf(old), Node::Leaf(new)) => vec![Change {
+            path,
+            kind: ChangeKind::Modified(old, new),
+        }],
+        (old, new) => vec![removal(path.clone(), old), addition(path, new)],
+    }
+}
+
+/// Follows a JSON Pointer in `node`, returning its path and the value found there.
+/// If `adding`, the last segment may be a missing key or the end of a sequence.
+fn resolve<'a, P: Primitive>(
+    node: &'a Node<P>,
+    pointer: &str,
+    adding: bool,
+) -> Option<(Vec<PathSegment>, Option<&'a Node<P>>)> {
+    if pointer.is_empty() {
+        return Some((vec![], Some(node)));
+    }
+    let tokens: Vec<String> = pointer
+        .strip_prefix('/')?
+        .split('/')
+        .map(|token| token.replace("~1", "/").replace("~0", "~"))
+        .collect();
+    let last = tokens.len() - 1;
+    let mut path = vec![];
+    let mut current = Some(node);
+    for (i, token) in tokens.into_iter().enumerate() {
+        let (segment, child) = match current? {
+            Node::Map(map) => {
+                let child = map.get(&token);
+                (PathSegment::Key(token), child)
+            }
+            Node::Sequence(sequence) | Node::Tuple(sequence) => {
+                let index = match token.as_str() {
+                    "-" => sequence.len(),
+                    "0" => 0,
+                    t if !t.starts_with('0') && t.bytes().all(|b| b.is_ascii_digit()) => {
+                        t.parse().ok()?
+                    }
+                    _ => return None,
+                };
+                if index > sequence.len() {
+                    return None;
+                }
+                (PathSegment::Index(index), sequence.get(index))
+            }
+            Node::Variant(tag, content) => {
+                let child = (*tag == token).then_some(&**content);
+                (PathSegment::Key(token), child)
+            }
+            Node::Set(_) | Node::Leaf(_) | Node::Null => return None,
+        };
+        if child.is_none() && !(adding && i == last) {
+            return None;
+        }
+        path.push(segment);
+        current = child;
+    }
+    Some((path, current))
+}
+
+/// The JSON Pointer of `path`, e.g. `/spec/containers/0`.
+fn pointer(path: &[PathSegment]) -> String {
+    path.iter()
+        .map(|segment| {
+            let token = segment.to_string().replace('~', "~0").replace('/', "~1");
+            format!("/{}", token)
+        })
+        .collect()
+}
+
+/// Builds a JSON Merge Patch (RFC 7386) document turning `base`
+/// into the result of applying `changes` to it.
+///
+/// Removed keys are set to `null`, so `Node::Null` and leaves serializing to `null`
+/// can't be set. A merge patch can't change part of an array,
+/// so a change inside a sequence sets the whole sequence.
+/// ```
+/// use std::collections::HashMap;
+/// use diffkit::recursive::{diff, to_merge_patch};
+/// use serde_json::json;
+///
+/// let old = HashMap::from([
+///     ("a".to_string(), vec![1, 2]),
+///     ("b".to_string(), vec![3]),
+/// ]);
+/// let new = HashMap::from([("a".to_string(), vec![1, 5])]);
+/// let patch = to_merge_patch(&old, &diff(&old, &new)).unwrap();
+/// assert_eq!(patch, json!({ "a": [1, 5], "b": null }));
+/// ```
+///
+/// # Errors
+///
+/// Returns [`MergePatchError::Apply`] if the changes don't fit `base`,
+/// and [`MergePatchError::Json`] if a leaf can't be serialized.
+pub fn to_merge_patch<T>(base: &T, changes: &[Change<T::P>]) -> Result<Value, MergePatchError>
+where
+    T: Diffable,
+    T::P: Serialize,
+{
+    let old = base.to_node();
+    let mut new = old.clone();
+    apply_node_in_place(&mut new, changes).map_err(MergePatchError::Apply)?;
+    let changes: Vec<&Change<T::P>> = changes.iter().collect();
+    merge_patch(&old, &new, &changes, 0).map_err(|e| MergePatchError::Json(e.to_string()))
+}
+
+/// The merge patch turning `old` into `new`, found at the first `depth` segments
+/// of the paths of `changes`. Only the keys touched by a change are compared.
+fn merge_patch<P: Primitive + Serialize>(
+    old: &Node<P>,
+    new: &Node<P>,
+    changes: &[&Change<P>],
+    depth: usize,
+) -> Result<Value, serde_json::Error> {
+    let (Node::Map(old), Node::Map(new)) = (old, new) else {
+        return node_to_value(new);
+    };
+    let replaced = changes.iter().any(|c| c.path.len() == depth);
+    let mut keys: Vec<&String> = if replaced {
+        old.keys().chain(new.keys()).collect()
+    } else {
+        changes
+            .iter()
+            .filter_map(|c| match &c.path[depth] {
+                PathSegment::Key(key) => Some(key),
+                PathSegment::Index(_) | PathSegment::Member => None,
+            })
+            .collect()
+    };
+    keys.sort();
+    keys.dedup();
+
+    let mut patch = serde_json::Map::new();
+    for key in keys {
+        let value = match (old.get(key), new.get(key)) {
+            (_, None) => Value::Null,
+            (Some(a), Some(b)) if a == b => continue,
+            (Some(a), Some(b)) => {
+                let inner: Vec<&Change<P>> = changes
+                    .iter()
+                    .filter(|c| {
+                        c.path.len() == depth || c.path[depth] == PathSegment::Key(key.clone())
+                    })
+                    .copied()
+                    .collect();
+                merge_patch(a, b, &inner, depth + 1)?
+            }
+            (None, Some(b)) => node_to_value(b)?,
+        };
+        patch.insert(key.clone(), value);
+    }
+    Ok(Value::Object(patch))
+}
+
+/// Applies a JSON Merge Patch (RFC 7386) to `node`.
+///
+/// `null` removes a key, an object is merged into the map at the same key,
+/// and any other value replaces the node.
+/// ```
+/// use diffkit::recursive::{apply_merge_patch, Diffable};
+/// use std::collections::HashMap;
+/// use serde_json::json;
+///
+/// let old = HashMap::from([("a".to_string(), 1), ("b".to_string(), 2)]);
+/// let patch = json!({ "a": 3, "b": null, "c": 4 });
+/// let node = apply_merge_patch(old.to_node(), &patch).unwrap();
+/// let new = HashMap::from_node(node).unwrap();
+/// assert_eq!(new, HashMap::from([("a".to_string(), 3), ("c".to_string(), 4)]));
+/// ```
+///
+/// # Errors
+///
+/// Returns [`MergePatchError::Json`] if a value doesn't convert to the leaf type.
+pub fn apply_merge_patch<P: Primitive + DeserializeOwned>(
+    node: Node<P>,
+    patch: &Value,
+) -> Result<Node<P>, MergePatchError> {
+    merge_into(Some(node), patch).map_err(|e| MergePatchError::Json(e.to_string()))
+}
+
+fn merge_into<P: Primitive + DeserializeOwned>(
+    node: Option<Node<P>>,
+    patch: &Value,
+) -> Result<Node<P>, serde_json::Error> {
+    let Value::Object(patch) = patch else {
+        return value_to_node(patch);
+    };
+    let mut map = match node {
+        Some(Node::Map(map)) => map,
+        _ => HashMap::new(),
+    };
+    for (key, value) in patch {
+        if value.is_null() {
+            map.remove(key);
+        } else {
+            let merged = merge_into(map.remove(key), value)?;
+            map.insert(key.clone(), merged);
+        }
+    }
+    Ok(Node::Map(map))
+}
+
+impl<P: Primitive + Serialize> Node<P> {
+    /// Converts the tree to JSON: maps become objects, sequences, tuples and sets
+    /// arrays, a variant an object with its name as only key, and leaves
+    /// are serialized with `serde`.
+    /// ```
+    /// use diffkit::node;
+    /// use diffkit::recursive::Node;
+    /// use serde_json::json;
+    ///
+    /// let tree: Node<i32> = node!({ "a": [1, 2] });
+    /// assert_eq!(tree.to_json().unwrap(), json!({ "a": [1, 2] }));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of `serde_json` if a leaf can't be serialized.
+    pub fn to_json(&self) -> Result<Value, serde_json::Error> {
+        node_to_value(self)
+    }
+}
+
+impl<P: Primitive + DeserializeOwned> Node<P> {
+    /// Converts JSON to a tree, e.g. to load a fixture: objects become maps,
+    /// arrays sequences, `null` [`Node::Null`], and the other values leaves,
+    /// deserialized with `serde`. Use `Node<serde_json::Value>` to keep any JSON leaf.
+    /// ```
+    /// use diffkit::node;
+    /// use diffkit::recursive::Node;
+    /// use serde_json::json;
+    ///
+    /// let tree = Node::<String>::from_json(&json!({ "a": ["b", null] })).unwrap();
+    /// let expected: Node<String> = node!({ "a": ["b", null] });
+    /// assert_eq!(tree, expected);
+    /// assert!(Node::<String>::from_json(&json!([1])).is_err());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of `serde_json` if a leaf doesn't deserialize into `P`.
+    pub fn from_json(value: &Value) -> Result<Self, serde_json::Error> {
+        value_to_node(value)
+    }
+}
+
+fn node_to_value<P: Primitive + Serialize>(node: &Node<P>) -> Result<Value, serde_json::Error> {
+    match node {
+        Node::Map(map) => map
+            .iter()
+            .map(|(k, v)| Ok((k.clone(), node_to_value(v)?)))
+            .collect::<Result<_, _>>()
+            .map(Value::Object),
+        Node::Sequence(sequence) | Node::Tuple(sequence) | Node::Set(sequence) => sequence
+            .iter()
+            .map(node_to_value)
+            .collect::<Result<_, _>>()
+            .map(Value::Array),
+        Node::Variant(tag, content) => Ok(Value::Object(
+            [(tag.clone(), node_to_value(content)?)]
+                .into_iter()
+                .collect(),
+        )),
+        Node::Leaf(v) => serde_json::to_value(v),
+        Node::Null => Ok(Value::Null),
+    }
+}
+
+fn value_to_node<P: Primitive + DeserializeOwned>(
+    value: &Value,
+) -> Result<Node<P>, serde_json::Error> {
+    match value {
+        Value::Object(map) => map
+            .iter()
+            .map(|(k, v)| Ok((k.clone(), value_to_node(v)?)))
+            .collect::<Result<_, _>>()
+            .map(Node::Map),
+        Value::Array(array) => array
+            .iter()
+            .map(value_to_node)
+            .collect::<Result<_, _>>()
+            .map(Node::Sequence),
+        Value::Null => Ok(Node::Null),
+        v => P::deserialize(v).map(Node::Leaf),
+    }
+}
+
+impl<P: Primitive + Serialize> ChangeSet<P> {
+    /// Converts the change set into a JSON object holding its `id`, its `meta`data
+    /// and its changes as a JSON Patch `patch`, see [`to_json_patch`].
+    /// ```
+    /// use std::collections::HashMap;
+    /// use diffkit::recursive::{diff, ChangeSet};
+    /// use serde_json::json;
+    ///
+    /// let old = HashMap::from([("a".to_string(), 1)]);
+    /// let new = HashMap::from([("a".to_string(), 2)]);
+    /// let set = ChangeSet::new("1", diff(&old, &new)).meta("author", "ann");
+    /// assert_eq!(
+    ///     set.to_json(false).unwrap(),
+    ///     json!({
+    ///         "id": "1",
+    ///         "meta": { "author": "ann" },
+    ///         "patch": [{ "op": "replace", "path": "/a", "value": 2 }],
+    ///     })
+    /// );
+    /// assert_eq!(ChangeSet::from_json(&old, &set.to_json(true).unwrap()), Ok(set));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of [`to_json_patch`].
+    pub fn to_json(&self, strict: bool) -> Result<Value, serde_json::Error> {
+        Ok(json!({
+            "id": self.id,
+            "meta": self.meta,
+            "patch": to_json_patch(&self.changes, strict)?,
+        }))
+    }
+}
+
+impl<P: Primitive + DeserializeOwned> ChangeSet<P> {
+    /// Converts a JSON object written by [`ChangeSet::to_json`]
+    /// into a change set to `base`, see [`from_json_patch`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`JsonPatchError::NotAChangeSet`] if `value` lacks the `id`, the `meta`
+    /// or the `patch`, or the error of [`from_json_patch`].
+    pub fn from_json<T>(base: &T, value: &Value) -> Result<Self, JsonPatchError>
+    where
+        T: Diffable<P = P>,
+    {
+        let id = value["id"].as_str().ok_or(JsonPatchError::NotAChangeSet)?;
+        let meta: BTreeMap<String, String> = serde_json::from_value(value["meta"].clone())
+            .map_err(|_| JsonPatchError::NotAChangeSet)?;
+        let changes = match value.get("patch") {
+            Some(patch) => from_json_patch(base, patch)?,
+            None => return Err(JsonPatchError::NotAChangeSet),
+        };
+        Ok(ChangeSet {
+            id: id.to_string(),
+            meta,
+            changes,
+        })
+    }
+}
+
+/// Error returned when a JSON Patch can't be converted into changes.
+/// Each variant but `NotAnArray` and `NotAChangeSet` carries the index
+/// of the offending operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JsonPatchError {
+    /// The document isn't an array of operations.
+    NotAnArray,
+    /// The document isn't a change set object, see [`ChangeSet::from_json`].
+    NotAChangeSet,
+    /// The operation is malformed, e.g. it lacks a `path` or has an unknown `op`.
+    InvalidOperation(usize),
+    /// The pointer doesn't lead to a value of the document.
+    PathNotFound(usize, String),
+    /// The `test` operation found another value at the pointer.
+    TestFailed(usize, String),
+    /// The value of the operation doesn't convert to the leaf type.
+    BadValue(usize),
+}
+
+impl fmt::Display for JsonPatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonPatchError::NotAnArray => write!(f, "JSON Patch is not an array of operations"),
+            JsonPatchError::NotAChangeSet => write!(f, "not a change set object"),
+            JsonPatchError::InvalidOperation(i) => write!(f, "malformed operation #{}", i),
+            JsonPatchError::PathNotFound(i, pointer) => {
+                write!(f, "path not found at {} in operation #{}", pointer, i)
+            }
+            JsonPatchError::TestFailed(i, pointer) => {
+                write!(f, "test failed at {} in operation #{}", pointer, i)
+            }
+            JsonPatchError::BadValue(i) => write!(f, "unconvertible value in operation #{}", i),
+        }
+    }
+}
+
+impl std::error::Error for JsonPatchError {}
+
+/// Error returned when a JSON Merge Patch can't be built or applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MergePatchError {
+    /// The changes don't fit the value they are applied to.
+    Apply(ApplyError),
+    /// A leaf can't be converted to or from JSON; carries the message of `serde_json`.
+    Json(String),
+}
+
+impl fmt::Display for MergePatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MergePatchError::Apply(e) => write!(f, "{}", e),
+            MergePatchError::Json(message) => write!(f, "unconvertible value: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for MergePatchError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recursive::{apply, diff};
+
+    fn key(k: &str) -> PathSegment {
+        PathSegment::Key(k.to_string())
+    }
+
+    #[test]
+    fn test_export() {
+        let changes = vec![
+            Change {
+                path: vec![key("a/b"), PathSegment::Index(0)],
+                kind: ChangeKind::Added(1),
+            },
+            Change {
+                path: vec![key("c~")],
+                kind: ChangeKind::Removed(2),
+            },
+        ];
+        assert_eq!(
+            to_json_patch(&changes, false).unwrap(),
+            json!([
+                { "op": "add", "path": "/a~1b/0", "value": 1 },
+                { "op": "remove", "path": "/c~0" },
+            ])
+        );
+        assert_eq!(
+            to_json_patch(&changes, true).unwrap()[1],
+            json!({ "op": "test", "path": "/c~0", "value": 2 })
+        );
+    }
+
+    #[test]
+    fn test_export_moves() {
+        let changes: Vec<Change<i32>> = vec![
+            Change {
+                path: vec![key("a")],
+                kind: ChangeKind::Renamed {
+                    from: "x".to_string(),
+                    to: "y/z".to_string(),
+                },
+            },
+            Change {
+                path: vec![key("b")],
+                kind: ChangeKind::Moved {
+                    from_index: 2,
+                    to_index: 0,
+                },
+            },
+        ];
+        assert_eq!(
+            to_json_patch(&changes, true).unwrap(),
+            json!([
+                { "op": "move", "from": "/a/x", "path": "/a/y~1z" },
+                { "op": "move", "from": "/b/2", "path": "/b/0" },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_root_replacement() {
+        let changes = vec![
+            Change {
+                path: vec![],
+                kind: ChangeKind::NodeRemoved(Node::Sequence(vec![Node::Leaf(1)])),
+            },
+            Change {
+                path: vec![],
+                kind: ChangeKind::NodeAdded(Node::Sequence(vec![])),
+            },
+        ];
+        assert_eq!(
+            to_json_patch(&changes, false).unwrap(),
+            json!([{ "op": "add", "path": "", "value": [] }])
+        );
+    }
+
+    #[test]
+    fn test_import_round_trip() {
+        let old = HashMap::from([("a".to_string(), vec![1, 2, 3]), ("b".to_string(), vec![4])]);
+        let new = HashMap::from([
+            ("a".to_string(), vec![0, 2, 3, 5]),
+            ("c".to_string(), vec![]),
+        ]);
+        let patch = to_json_patch(&diff(&old, &new), true).unwrap();
+        let changes = from_json_patch(&old, &patch).unwrap();
+        assert_eq!(apply(&old, &changes), Ok(new));
+    }
+
+    #[test]
+    fn test_import_move_and_numeric_keys() {
+        let base = HashMap::from([("0".to_string(), vec![1]), ("1".to_string(), vec![])]);
+        let patch = json!([{ "op": "move", "from": "/0/0", "path": "/1/0" }]);
+        let changes = from_json_patch(&base, &patch).unwrap();
+        assert_eq!(
+            changes,
+            vec![
+                Change {
+                    path: vec![key("0"), PathSegment::Index(0)],
+                    kind: ChangeKind::Removed(1),
+                },
+                Change {
+                    path: vec![key("1"), PathSegment::Index(0)],
+                    kind: ChangeKind::Added(1),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_import_errors() {
+        let base = vec![1, 2];
+        let errors = [
+            (json!({}), JsonPatchError::NotAnArray),
+            (
+                json!([{ "op": "jump", "path": "/0" }]),
+                JsonPatchError::InvalidOperation(0),
+            ),
+            (
+                json!([{ "op": "remove", "path": "/2" }]),
+                JsonPatchError::PathNotFound(0, "/2".to_string()),
+            ),
+            (
+                json!([{ "op": "add", "path": "/01", "value": 1 }]),
+                JsonPatchError::PathNotFound(0, "/01".to_string()),
+            ),
+            (
+                json!([{ "op": "remove", "path": "/0" }, { "op": "test", "path": "/0", "value": 1 }]),
+                JsonPatchError::TestFailed(1, "/0".to_string()),
+            ),
+            (
+                json!([{ "op": "replace", "path": "/0", "value": "x" }]),
+                JsonPatchError::BadValue(0),
+            ),
+        ];
+        for (patch, error) in errors {
+            assert_eq!(from_json_patch(&base, &patch), Err(error));
+        }
+    }
+
+    #[test]
+    fn test_merge_patch_of_replaced_map() {
+        let old = HashMap::from([("a".to_string(), HashMap::from([("x".to_string(), 1)]))]);
+        let changes = vec![
+            Change {
+                path: vec![key("a")],
+                kind: ChangeKind::NodeRemoved(old["a"].to_node()),
+            },
+            Change {
+                path: vec![key("a")],
+                kind: ChangeKind::NodeAdded(Node::Map(HashMap::from([(
+                    "y".to_string(),
+                    Node::Leaf(2),
+                )]))),
+            },
+        ];
+        assert_eq!(
+            to_merge_patch(&old, &changes).unwrap(),
+            json!({ "a": { "x": null, "y": 2 } })
+        );
+        assert_eq!(
+            to_merge_patch(&old, &changes[..1]),
+            Ok(json!({ "a": null }))
+        );
+    }
+
+    #[test]
+    fn test_merge_patch_of_undone_change() {
+        let old = HashMap::from([("a".to_string(), 1)]);
+        let new = HashMap::from([("a".to_string(), 2)]);
+        let changes = [diff(&old, &new), diff(&new, &old)].concat();
+        assert_eq!(to_merge_patch(&old, &changes), Ok(json!({})));
+    }
+
+    #[test]
+    fn test_apply_merge_patch() {
+        // from the examples of RFC 7386
+        let node: Node<String> =
+            Node::from_json(&json!({ "a": { "b": "c" }, "d": ["e"] })).unwrap();
+        let patch = json!({ "a": { "b": "d", "c": { "e": "f" } }, "d": null });
+        let expected = Node::from_json(&json!({ "a": { "b": "d", "c": { "e": "f" } } })).unwrap();
+        assert_eq!(apply_merge_patch(node, &patch), Ok(expected));
+
+        let node: Node<String> = Node::from_json(&json!(["a"])).unwrap();
+        let expected = Node::from_json(&json!({ "a": "b" })).unwrap();
+        assert_eq!(apply_merge_patch(node, &json!({ "a": "b" })), Ok(expected));
+        assert!(apply_merge_patch(Node::Leaf(1), &json!("a")).is_err());
+    }
+
+    #[test]
+    fn test_diff_json_values() {
+        let old = json!({ "name": "a", "tags": ["x", 1], "owner": null });
+        let new = json!({ "name": "b", "tags": ["x", 1, true], "size": 2.5 });
+        let changes = diff(&old, &new);
+        assert!(changes.contains(&Change {
+            path: vec![key("name")],
+            kind: ChangeKind::Modified(json!("a"), json!("b")),
+        }));
+        assert!(changes.contains(&Change {
+            path: vec![key("owner")],
+            kind: ChangeKind::NodeRemoved(Node::Null),
+        }));
+        assert_eq!(apply(&old, &changes), Ok(new.clone()));
+
+        let patch = to_json_patch(&changes, true).unwrap();
+        assert_eq!(
+            apply(&old, &from_json_patch(&old, &patch).unwrap()),
+            Ok(new)
+        );
+    }
+
+    #[test]
+    fn test_null_is_not_a_missing_key() {
+        let changes = diff(&json!({}), &json!({ "owner": null }));
+        assert_eq!(
+            changes,
+            vec![Change {
+                path: vec![key("owner")],
+                kind: ChangeKind::NodeAdded(Node::Null),
+            }]
+        );
+        let changes = diff(&json!({ "owner": null }), &json!({ "owner": "a" }));
+        assert_eq!(
+            apply(&json!({ "owner": null }), &changes),
+            Ok(json!({ "owner": "a" }))
+        );
+        assert!(changes
+            .iter()
+            .all(|change| change.path == vec![key("owner")]));
+    }
+
+    #[test]
+    fn test_change_set_from_json() {
+        let base = json!({ "a": 1 });
+        let value = json!({ "id": "7", "meta": {}, "patch": [{ "op": "remove", "path": "/a" }] });
+        let set = ChangeSet::from_json(&base, &value).unwrap();
+        assert_eq!((set.id.as_str(), set.changes.len()), ("7", 1));
+        assert_eq!(
+            ChangeSet::from_json(
+                &base,
+                &json!({ "id": "7", "meta": { "a": 1 }, "patch": [] })
+            ),
+            Err(JsonPatchError::NotAChangeSet)
+        );
+        assert_eq!(
+            ChangeSet::from_json(&base, &json!({ "id": "7", "meta": {} })),
+            Err(JsonPatchError::NotAChangeSet)
+        );
+    }
+}
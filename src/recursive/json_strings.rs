@@ -0,0 +1,73 @@
+use crate::recursive::{apply, diff, from_json_patch, to_json_patch, HtmlReport, Report};
+use serde_json::Value;
+
+/// Diffs two JSON documents given as text, and returns the JSON Patch (RFC 6902)
+/// between them as text, with `test` operations checking the old values.
+///
+/// This and the other `*_json_strings` functions only take and return strings,
+/// so they can be exported as they are by `wasm-bindgen`, e.g. to diff documents
+/// in a browser. Their errors are messages, which convert to JavaScript errors.
+/// ```
+/// use diffkit::recursive::diff_json_strings;
+///
+/// let patch = diff_json_strings(r#"{"replicas": 3}"#, r#"{"replicas": 5}"#).unwrap();
+/// assert_eq!(
+///     patch,
+///     r#"[{"op":"test","path":"/replicas","value":3},{"op":"replace","path":"/replicas","value":5}]"#
+/// );
+/// ```
+///
+/// # Errors
+///
+/// Returns a message if a document isn't valid JSON.
+pub fn diff_json_strings(old: &str, new: &str) -> Result<String, String> {
+    let changes = diff(&parse(old, "old")?, &parse(new, "new")?);
+    let patch = to_json_patch(&changes, true).map_err(|e| e.to_string())?;
+    Ok(patch.to_string())
+}
+
+/// Applies a JSON Patch to a JSON document, both given as text,
+/// and returns the patched document as text.
+/// ```
+/// use diffkit::recursive::{apply_json_patch_strings, diff_json_strings};
+///
+/// let (old, new) = (r#"{"a": [1, 2]}"#, r#"{"a": [1, 3], "b": null}"#);
+/// let patch = diff_json_strings(old, new).unwrap();
+/// assert_eq!(apply_json_patch_strings(old, &patch).unwrap(), r#"{"a":[1,3],"b":null}"#);
+/// assert!(apply_json_patch_strings(new, &patch).is_err());
+/// ```
+///
+/// # Errors
+///
+/// Returns a message if the document or the patch isn't valid JSON,
+/// or if the patch doesn't apply to the document.
+pub fn apply_json_patch_strings(document: &str, patch: &str) -> Result<String, String> {
+    let document = parse(document, "document")?;
+    let changes = from_json_patch(&document, &parse(patch, "patch")?).map_err(|e| e.to_string())?;
+    let patched = apply(&document, &changes).map_err(|e| e.to_string())?;
+    Ok(patched.to_string())
+}
+
+/// Diffs two JSON documents given as text, and returns a [`Report`] of the changes
+/// between them, or an [`HtmlReport`] fragment if `html`, e.g. for a review page.
+/// ```
+/// use diffkit::recursive::report_json_strings;
+///
+/// let report = report_json_strings(r#"{"replicas": 3}"#, r#"{"replicas": 5}"#, false);
+/// assert_eq!(report.unwrap(), "~ replicas: Number(3) → Number(5)\n");
+/// ```
+///
+/// # Errors
+///
+/// Returns a message if a document isn't valid JSON.
+pub fn report_json_strings(old: &str, new: &str, html: bool) -> Result<String, String> {
+    let changes = diff(&parse(old, "old")?, &parse(new, "new")?);
+    if html {
+        return Ok(HtmlReport::new(&changes).to_string());
+    }
+    Ok(Report::new(&changes).to_string())
+}
+
+fn parse(json: &str, name: &str) -> Result<Value, String> {
+    serde_json::from_str(json).map_err(|e| format!("invalid {} document: {}", name, e))
+}
@@ -0,0 +1,301 @@
+use crate::recursive::types::{Change, ChangeKind, Node, PathSegment, Primitive};
+use crate::recursive::{compose, invert_changes};
+
+/// Result of [`merge`].
+///
+/// `changes` turns the common base into the merged structure.
+/// It holds all the changes of ours, and the changes of theirs
+/// that don't conflict with them, rebased after ours.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct MergeOutcome<P: Primitive> {
+    pub changes: Vec<Change<P>>,
+    pub conflicts: Vec<Conflict<P>>,
+}
+
+impl<P: Primitive> MergeOutcome<P> {
+    /// Whether both change sets merged without conflicts.
+    pub fn is_clean(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+}
+
+/// Changes of both sides touching the same value in different ways,
+/// as found in the change sets passed to [`merge`].
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Conflict<P: Primitive> {
+    pub ours: Change<P>,
+    pub theirs: Change<P>,
+}
+
+/// Merges two change sets computed from the same base.
+///
+/// Changes to different values are combined, and changes made by both sides
+/// are kept once. Two changes to the same value, or to a value and a node
+/// containing it, are reported as a [`Conflict`]; the merged changes keep ours,
+/// and leave out the changes of theirs made inside a node whose addition conflicts.
+/// Elements inserted by both sides at the same place in a sequence are all kept,
+/// ours first.
+/// ```
+/// use std::collections::HashMap;
+/// use diffkit::recursive::{apply, diff, merge};
+///
+/// let base = HashMap::from([("a".to_string(), 1), ("b".to_string(), 1)]);
+/// let ours = HashMap::from([("a".to_string(), 2), ("b".to_string(), 1)]);
+/// let theirs = HashMap::from([("a".to_string(), 3), ("b".to_string(), 3)]);
+///
+/// let outcome = merge(&diff(&base, &ours), &diff(&base, &theirs));
+/// assert_eq!(outcome.conflicts.len(), 1);
+/// let merged = apply(&base, &outcome.changes).unwrap();
+/// assert_eq!(merged["a"], 2);
+/// assert_eq!(merged["b"], 3);
+/// ```
+pub fn merge<P: Primitive>(
+    base_to_ours: &[Change<P>],
+    base_to_theirs: &[Change<P>],
+) -> MergeOutcome<P> {
+    let mut changes = base_to_ours.to_vec();
+    let mut conflicts = vec![];
+    // ours, applying from the base with the accepted changes of theirs
+    let mut ours: Rebased<P> = base_to_ours.iter().cloned().map(Some).collect();
+    // inverses of the rejected changes of theirs, leading from the state
+    // the next change of theirs applies to, back to the accepted ones
+    let mut rejected: Rebased<P> = vec![];
+
+    for original in base_to_theirs {
+        let (theirs, rebased_rejected, _) = ladder(Some(original.clone()), &rejected);
+        let (theirs, rebased_ours, conflict) = ladder(theirs, &ours);
+        match conflict {
+            Some(index) => {
+                conflicts.push(Conflict {
+                    ours: base_to_ours[index].clone(),
+                    theirs: original.clone(),
+                });
+                rejected.insert(0, Some(original.invert()));
+            }
+            None => {
+                rejected = rebased_rejected;
+                ours = rebased_ours;
+                changes.extend(theirs);
+            }
+        }
+    }
+
+    MergeOutcome { changes, conflicts }
+}
+
+/// Transforms two change sets computed concurrently from the same base
+/// into `(a', b')`, where `a'` applies after `b` and `b'` after `a`,
+/// both leading to the same result, as in operational transformation.
+///
+/// Sequence indices are shifted past the elements the other side inserted or removed,
+/// and changes made void by the other side are dropped. Where both sides
+/// change the same value in different ways, `a` wins, as ours does in [`merge`]:
+/// `b'` leaves the change of `b` out, and `a'` overrides it.
+/// ```
+/// use diffkit::recursive::{apply, diff, transform};
+///
+/// let base = vec!['a', 'b'];
+/// let a = diff(&base, &vec!['x', 'a', 'b']);
+/// let b = diff(&base, &vec!['a']);
+/// let (a2, b2) = transform(&a, &b);
+/// let ab = apply(&apply(&base, &a).unwrap(), &b2).unwrap();
+/// let ba = apply(&apply(&base, &b).unwrap(), &a2).unwrap();
+/// assert_eq!(ab, vec!['x', 'a']);
+/// assert_eq!(ab, ba);
+/// ```
+pub fn transform<P: Primitive>(
+    a: &[Change<P>],
+    b: &[Change<P>],
+) -> (Vec<Change<P>>, Vec<Change<P>>) {
+    let merged = merge(a, b).changes;
+    let b_after_a = merged[a.len()..].to_vec();
+    // from the result of b, back to the base and on to the merged result
+    let a_after_b = compose(&invert_changes(b), &merged);
+    (a_after_b, b_after_a)
+}
+
+/// Sequential changes rebased after others, with `None` for the ones made void.
+type Rebased<P> = Vec<Option<Change<P>>>;
+
+/// Rebases `change` after the sequential `changes`, both applying to the same state,
+/// and rebases `changes` after `change`. Also returns the index of the first change conflicting with `change`.
+fn ladder<P: Primitive>(
+    change: Option<Change<P>>,
+    changes: &[Option<Change<P>>],
+) -> (Option<Change<P>>, Rebased<P>, Option<usize>) {
+    let mut change = change;
+    let mut rebased = vec![];
+    let mut conflict = None;
+    for (index, other) in changes.iter().enumerate() {
+        match (&change, other) {
+            (Some(c), Some(o)) => {
+                if conflict.is_none() && conflicts_with(c, o) {
+                    conflict = Some(index);
+                }
+                rebased.push(rebase(o, c, true));
+                change = rebase(c, o, false);
+            }
+            _ => rebased.push(other.clone()),
+        }
+    }
+    (change, rebased, conflict)
+}
+
+/// Adjusts `a` to apply after `b`, both applying to the same state.
+/// `a_first` decides which goes first when both insert at the same index.
+/// Returns `None` if `b` already made `a`, or removed its target.
+fn rebase<P: Primitive>(a: &Change<P>, b: &Change<P>, a_first: bool) -> Option<Change<P>> {
+    if a == b {
+        return None;
+    }
+    if a.path.starts_with(&b.path) && a.path.len() > b.path.len() && !is_insertion(b) {
+        // b replaced or removed a node containing a's target
+        return None;
+    }
+
+    let mut a = a.clone();
+    let Some((PathSegment::Index(j), parent)) = b.path.split_last() else {
+        return Some(a);
+    };
+    if matches!(
+        b.kind,
+        ChangeKind::Modified(..)
+            | ChangeKind::Renamed { .. }
+            | ChangeKind::Moved { .. }
+            | ChangeKind::TruncatedSubtreeChanged { .. }
+            | ChangeKind::BlobPatched(_)
+            | ChangeKind::TruncatedLeafChanged {
+                old: Some(_),
+                new: Some(_)
+            }
+    ) || !a.path.starts_with(parent)
+        || a.path.len() == parent.len()
+    {
+        return Some(a);
+    }
+    let a_inserts_here = is_insertion(&a) && a.path.len() == b.path.len();
+    let PathSegment::Index(i) = &mut a.path[parent.len()] else {
+        return Some(a);
+    };
+    if is_insertion(b) {
+        if *i > *j || (*i == *j && !(a_inserts_here && a_first)) {
+            *i += 1;
+        }
+    } else if *i > *j {
+        *i -= 1;
+    } else if *i == *j && !a_inserts_here {
+        // the element targeted by a is gone
+        return None;
+    }
+    Some(a)
+}
+
+/// Whether both changes touch the same value in different ways.
+fn conflicts_with<P: Primitive>(a: &Change<P>, b: &Change<P>) -> bool {
+    let related = a.path.starts_with(&b.path) || b.path.starts_with(&a.path);
+    related
+        && a != b
+        && !(is_insertion(a) && a.path.len() <= b.path.len())
+        && !(is_insertion(b) && b.path.len() <= a.path.len())
+        && !(a.path == b.path && member(a) != member(b))
+}
+
+/// The set member added or removed by the change, if its path leads to one.
+fn member<P: Primitive>(change: &Change<P>) -> Option<Node<P>> {
+    if change.path.last() != Some(&PathSegment::Member) {
+        return None;
+    }
+    match &change.kind {
+        ChangeKind::Added(v) | ChangeKind::Removed(v) => Some(Node::Leaf(v.clone())),
+        ChangeKind::NodeAdded(v) | ChangeKind::NodeRemoved(v) => Some(v.clone()),
+        ChangeKind::Modified(..)
+        | ChangeKind::Renamed { .. }
+        | ChangeKind::Moved { .. }
+        | ChangeKind::TruncatedSubtreeChanged { .. }
+        | ChangeKind::BlobPatched(_)
+        | ChangeKind::TruncatedLeafChanged { .. } => None,
+    }
+}
+
+/// Whether the change inserts an element into a sequence,
+/// leaving the existing elements untouched.
+fn is_insertion<P: Primitive>(change: &Change<P>) -> bool {
+    matches!(change.path.last(), Some(PathSegment::Index(_)))
+        && matches!(
+            change.kind,
+            ChangeKind::Added(_)
+                | ChangeKind::NodeAdded(_)
+                | ChangeKind::TruncatedLeafChanged { old: None, .. }
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recursive::{apply, diff};
+    use std::collections::{BTreeMap, BTreeSet};
+
+    #[test]
+    fn test_sequence_merge() {
+        let base = vec![1, 2, 3, 4];
+        let ours = vec![0, 1, 2, 4];
+        let theirs = vec![1, 20, 3, 4, 5];
+        let outcome = merge(&diff(&base, &ours), &diff(&base, &theirs));
+        assert!(outcome.is_clean());
+        assert_eq!(apply(&base, &outcome.changes), Ok(vec![0, 1, 20, 4, 5]));
+    }
+
+    #[test]
+    fn test_same_change_is_kept_once() {
+        let base = vec![1, 2];
+        let both = vec![1, 3];
+        let outcome = merge(&diff(&base, &both), &diff(&base, &both));
+        assert!(outcome.is_clean());
+        assert_eq!(outcome.changes, diff(&base, &both));
+    }
+
+    #[test]
+    fn test_concurrent_insertions_keep_ours_first() {
+        let base = vec![1];
+        let outcome = merge(&diff(&base, &vec![1, 2]), &diff(&base, &vec![1, 3]));
+        assert!(outcome.is_clean());
+        assert_eq!(apply(&base, &outcome.changes), Ok(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn test_set_members() {
+        let base = BTreeSet::from([1, 2]);
+        let ours = BTreeSet::from([1, 3]);
+        let theirs = BTreeSet::from([2, 4]);
+        let outcome = merge(&diff(&base, &ours), &diff(&base, &theirs));
+        assert!(outcome.is_clean());
+        assert_eq!(apply(&base, &outcome.changes), Ok(BTreeSet::from([3, 4])));
+    }
+
+    #[test]
+    fn test_rejected_change_shifts_later_ones() {
+        let base = vec![1, 2, 3];
+        // ours modifies 1, theirs removes it and modifies 3
+        let ours = vec![10, 2, 3];
+        let theirs = vec![2, 30];
+        let outcome = merge(&diff(&base, &ours), &diff(&base, &theirs));
+        assert_eq!(outcome.conflicts.len(), 1);
+        assert_eq!(apply(&base, &outcome.changes), Ok(vec![10, 2, 30]));
+    }
+
+    #[test]
+    fn test_transform_conflict() {
+        let base = BTreeMap::from([("a".to_string(), 1)]);
+        let a = diff(&base, &BTreeMap::from([("a".to_string(), 2)]));
+        let b = diff(&base, &BTreeMap::from([("a".to_string(), 3)]));
+        let (a_after_b, b_after_a) = transform(&a, &b);
+        assert!(b_after_a.is_empty());
+        assert_eq!(
+            a_after_b,
+            vec![Change {
+                path: vec![PathSegment::Key("a".to_string())],
+                kind: ChangeKind::Modified(3, 2),
+            }]
+        );
+    }
+}
@@ -1,14 +1,101 @@
+mod binary;
+mod blob;
+mod build;
+mod cache;
+#[cfg(feature = "cbor")]
+mod cbor;
+mod changeset;
+#[cfg(feature = "chrono")]
+mod chrono;
+mod compose;
+#[cfg(feature = "csv")]
+mod csv;
 mod diffable;
+mod digest;
+mod dot;
+mod edit_distance;
+mod env;
+#[cfg(feature = "float")]
+mod float;
+mod hash;
+mod history;
+mod html;
+mod ini;
+#[cfg(feature = "json")]
+mod json;
+#[cfg(feature = "json")]
+mod json_strings;
+mod merge;
+#[cfg(feature = "msgpack")]
+mod msgpack;
+mod normalize;
+mod path;
+mod policy;
+mod queue;
+mod redact;
+mod report;
+mod shared;
+mod stats;
+mod subscribe;
+mod text;
+#[cfg(feature = "toml")]
+mod toml;
 mod types;
+#[cfg(feature = "uuid")]
+mod uuid;
+mod walk;
 
+#[cfg(feature = "csv")]
+pub use self::csv::*;
+#[cfg(feature = "toml")]
+pub use self::toml::*;
+pub use binary::*;
+pub use blob::*;
+pub use cache::*;
+#[cfg(feature = "cbor")]
+pub use cbor::*;
+pub use changeset::*;
+pub use compose::*;
 pub use diffable::*;
+pub use digest::*;
+pub use dot::*;
+pub use edit_distance::*;
+pub use env::*;
+pub use history::*;
+pub use html::*;
+pub use ini::*;
+#[cfg(feature = "json")]
+pub use json::*;
+#[cfg(feature = "json")]
+pub use json_strings::*;
+pub use merge::*;
+#[cfg(feature = "msgpack")]
+pub use msgpack::*;
+pub use normalize::*;
+pub use path::*;
+pub use policy::*;
+pub use queue::*;
+pub use redact::*;
+pub use report::*;
+pub use shared::*;
+pub use stats::*;
+pub use subscribe::*;
 pub use types::*;
+pub use walk::*;
+
+#[cfg(feature = "derive")]
+pub use diffkit_derive::Diffable;
 
 use crate::myers;
 use crate::myers::Edit;
 use std::collections::{HashMap, HashSet};
 
 /// Builds a list of changes for two nodes.
+///
+/// The changes come in a canonical order, the same from run to run:
+/// map keys are visited in sorted order and sequence elements by index.
+/// Set members follow the iteration order of the set,
+/// so only sets like `BTreeSet` give reproducible changes.
 /// ```
 /// use std::collections::HashMap;
 /// use diffkit::recursive::{diff, Change, PathSegment, ChangeKind};
@@ -26,149 +113,1349 @@ use std::collections::{HashMap, HashSet};
 ///     }]
 /// );
 /// ```
-pub fn diff<T: Diffable>(old: &T, new: &T) -> Vec<Change<T::P>> {
-    diff_nodes(old.to_node(), new.to_node(), vec![])
+pub fn diff<T: Diffable + ?Sized>(old: &T, new: &T) -> Vec<Change<T::P>> {
+    diff_with(old, new, &DiffOptions::default())
+}
+
+/// Builds a list of changes for two nodes like [`diff`], following `options`.
+/// ```
+/// use std::collections::HashMap;
+/// use diffkit::recursive::{diff_with, DiffOptions};
+///
+/// let old = HashMap::from([("etag".to_string(), 1), ("size".to_string(), 1)]);
+/// let new = HashMap::from([("etag".to_string(), 2), ("size".to_string(), 1)]);
+/// let options = DiffOptions::new().ignore("**/etag".parse().unwrap());
+/// assert!(diff_with(&old, &new, &options).is_empty());
+/// ```
+pub fn diff_with<T: Diffable + ?Sized>(
+    old: &T,
+    new: &T,
+    options: &DiffOptions<T::P>,
+) -> Vec<Change<T::P>> {
+    diff_trees(old.to_node(), new.to_node(), options)
 }
 
-fn diff_nodes<P: Primitive>(old: Node<P>, new: Node<P>, path: Vec<PathSegment>) -> Vec<Change<P>> {
+/// Diffs two whole trees like [`diff_with`].
+fn diff_trees<P: Primitive>(
+    mut old: Node<P>,
+    mut new: Node<P>,
+    options: &DiffOptions<P>,
+) -> Vec<Change<P>> {
+    if let Some(normalizer) = &options.normalizer {
+        normalizer.apply(&mut old);
+        normalizer.apply(&mut new);
+    }
+    let mut changes = diff_nodes(&old, &new, vec![], options);
+    if let Some(max_len) = options.truncate_leaves {
+        digest::truncate_leaves(&mut changes, max_len);
+    }
+    if let Some(redaction) = &options.redaction {
+        redaction.apply(&mut changes);
+    }
+    changes
+}
+
+/// Diffs two nodes found at `path`.
+///
+/// Nested nodes are diffed from an explicit stack of [`Step`]s rather than
+/// by recursion, so deep trees don't exhaust the stack.
+fn diff_nodes<P: Primitive>(
+    old: &Node<P>,
+    new: &Node<P>,
+    path: Vec<PathSegment>,
+    options: &DiffOptions<P>,
+) -> Vec<Change<P>> {
+    let mut changes = vec![];
+    let hashes = options
+        .hash_subtrees
+        .then(|| (old.subtree_hashes(), new.subtree_hashes()));
+    let mut stack = vec![Step::Diff(old, new, path)];
+    while let Some(step) = stack.pop() {
+        match step {
+            Step::Emit(change) => changes.push(change),
+            Step::Diff(old, new, path) => {
+                if let Some((old_hashes, new_hashes)) = &hashes {
+                    let old_hash = old_hashes.get(&(old as *const Node<P>));
+                    // equal hashes only tell that the subtrees may be equal
+                    if old_hash.is_some()
+                        && old_hash == new_hashes.get(&(new as *const Node<P>))
+                        && old == new
+                    {
+                        continue;
+                    }
+                }
+                let steps = diff_step(old, new, path, options, changes.len());
+                stack.extend(steps.into_iter().rev());
+            }
+            Step::Variant {
+                old,
+                new,
+                path,
+                start,
+            } => {
+                // a variant can't lose its content, so a replaced content replaces the variant
+                if changes[start..].iter().any(|c| {
+                    c.path.len() == path.len() + 1
+                        && !matches!(
+                            c.kind,
+                            ChangeKind::Modified(..) | ChangeKind::BlobPatched(_)
+                        )
+                }) {
+                    changes.truncate(start);
+                    changes.push(removal(path.clone(), old.clone()));
+                    changes.push(addition(path, new.clone()));
+                }
+            }
+        }
+    }
+    changes
+}
+
+/// What is left to do to diff two trees, in order.
+enum Step<'a, P: Primitive> {
+    Emit(Change<P>),
+    Diff(&'a Node<P>, &'a Node<P>, Vec<PathSegment>),
+    /// Checks the changes to the content of two variants, emitted from `start` on.
+    Variant {
+        old: &'a Node<P>,
+        new: &'a Node<P>,
+        path: Vec<PathSegment>,
+        start: usize,
+    },
+}
+
+/// Diffs two nodes found at `path` down to their children,
+/// given the number of changes `emitted` before.
+fn diff_step<'a, P: Primitive>(
+    old: &'a Node<P>,
+    new: &'a Node<P>,
+    path: Vec<PathSegment>,
+    options: &DiffOptions<P>,
+    emitted: usize,
+) -> Vec<Step<'a, P>> {
+    if options.ignores(&path) {
+        return vec![];
+    }
+    if options.truncate.is_some_and(|depth| path.len() >= depth) {
+        return match (old, new) {
+            (Node::Leaf(a), Node::Leaf(b)) if options.leaves_equal(a, b) => vec![],
+            (Node::Leaf(a), Node::Leaf(b)) => vec![Step::Emit(modification(path, a, b, options))],
+            (old, new) => {
+                let (old_hash, new_hash) = (old.structural_hash(), new.structural_hash());
+                if old_hash == new_hash {
+                    return vec![];
+                }
+                vec![Step::Emit(Change {
+                    path,
+                    kind: ChangeKind::TruncatedSubtreeChanged { old_hash, new_hash },
+                })]
+            }
+        };
+    }
+    if options.compares_whole(&path) {
+        return match (old, new) {
+            (Node::Leaf(a), Node::Leaf(b)) if options.leaves_equal(a, b) => vec![],
+            (old, new) if old == new => vec![],
+            (Node::Leaf(a), Node::Leaf(b)) => vec![Step::Emit(modification(path, a, b, options))],
+            (old, new) => replacement(path, old, new),
+        };
+    }
     match (old, new) {
         (Node::Leaf(a), Node::Leaf(b)) => {
-            if a != b {
-                vec![Change {
-                    path,
-                    kind: ChangeKind::Modified(a, b),
-                }]
+            if !options.leaves_equal(a, b) {
+                vec![Step::Emit(modification(path, a, b, options))]
             } else {
                 vec![]
             }
         }
-        (Node::Sequence(a), Node::Sequence(b)) => {
-            let result = myers::diff(&a, &b);
-            if result.iter().all(|e| matches!(e, Edit::Equal(_))) {
-                vec![]
-            } else {
-                vec![Change {
+        (Node::Null, Node::Null) => vec![],
+        (Node::Sequence(a), Node::Sequence(b)) => diff_sequences(a, b, path, options),
+        (Node::Tuple(a), Node::Tuple(b)) if a.len() == b.len() => a
+            .iter()
+            .zip(b)
+            .enumerate()
+            .map(|(i, (a, b))| {
+                let mut new_path = path.clone();
+                new_path.push(PathSegment::Index(i));
+                Step::Diff(a, b, new_path)
+            })
+            .collect(),
+        (Node::Variant(tag_a, a), Node::Variant(tag_b, b)) if tag_a == tag_b => {
+            let mut content_path = path.clone();
+            content_path.push(PathSegment::Key(tag_a.clone()));
+            vec![
+                Step::Diff(a, b, content_path),
+                Step::Variant {
+                    old,
+                    new,
                     path,
-                    kind: ChangeKind::SequenceChange(result),
-                }]
-            }
+                    start: emitted,
+                },
+            ]
         }
-        (Node::Map(a), Node::Map(b)) => {
-            let keys_a = a.keys().collect::<HashSet<_>>();
-            let keys_b = b.keys().collect::<HashSet<_>>();
-
-            keys_a
-                .union(&keys_b)
-                .flat_map(|key| {
-                    let mut new_path = path.clone();
-                    new_path.push(PathSegment::Key(key.to_string()));
-                    match (a.get(*key), b.get(*key)) {
-                        (Some(va), Some(vb)) => diff_nodes(va.clone(), vb.clone(), new_path),
-                        (Some(va), None) => match va {
-                            Node::Leaf(ve) => vec![Change {
-                                path: new_path,
-                                kind: ChangeKind::Removed(ve.clone()),
-                            }],
-                            ve => vec![Change {
-                                path: new_path,
-                                kind: ChangeKind::NodeRemoved(ve.clone()),
-                            }],
-                        },
-                        (None, Some(vb)) => match vb {
-                            Node::Leaf(ve) => vec![Change {
-                                path: new_path,
-                                kind: ChangeKind::Added(ve.clone()),
-                            }],
-                            ve => vec![Change {
-                                path: new_path,
-                                kind: ChangeKind::NodeAdded(ve.clone()),
-                            }],
-                        },
-                        (None, None) => unreachable!(),
-                    }
-                })
+        (Node::Set(a), Node::Set(b)) => {
+            let mut member_path = path;
+            member_path.push(PathSegment::Member);
+            if options.ignores(&member_path) {
+                return vec![];
+            }
+            let removed = a.iter().filter(|v| !b.contains(v));
+            let added = b.iter().filter(|v| !a.contains(v));
+            removed
+                .map(|v| removal(member_path.clone(), v.clone()))
+                .chain(added.map(|v| addition(member_path.clone(), v.clone())))
+                .map(Step::Emit)
                 .collect()
         }
-        (old, new) => vec![
-            Change {
-                path: path.clone(),
-                kind: ChangeKind::NodeRemoved(old),
-            },
-            Change {
-                path,
-                kind: ChangeKind::NodeAdded(new),
-            },
-        ],
+        (Node::Map(a), Node::Map(b)) => diff_maps(a, b, path, options),
+        (old, new) => replacement(path, old, new),
+    }
+}
+
+/// The change modifying the leaf `old` into `new` at `path`,
+/// with a delta if `options` ask for one and both are blobs.
+fn modification<P: Primitive>(
+    path: Vec<PathSegment>,
+    old: &P,
+    new: &P,
+    options: &DiffOptions<P>,
+) -> Change<P> {
+    let kind = match options.blob_deltas.then(|| blob::leaf_delta(old, new)) {
+        Some(Some(delta)) => ChangeKind::BlobPatched(delta),
+        _ => ChangeKind::Modified(old.clone(), new.clone()),
+    };
+    Change { path, kind }
+}
+
+/// The steps replacing `old` with `new` at `path`.
+fn replacement<'a, P: Primitive>(
+    path: Vec<PathSegment>,
+    old: &Node<P>,
+    new: &Node<P>,
+) -> Vec<Step<'a, P>> {
+    vec![
+        Step::Emit(removal(path.clone(), old.clone())),
+        Step::Emit(addition(path, new.clone())),
+    ]
+}
+
+fn diff_maps<'a, P: Primitive>(
+    a: &'a HashMap<String, Node<P>>,
+    b: &'a HashMap<String, Node<P>>,
+    path: Vec<PathSegment>,
+    options: &DiffOptions<P>,
+) -> Vec<Step<'a, P>> {
+    let at = |key: &String| {
+        let mut new_path = path.clone();
+        new_path.push(PathSegment::Key(key.to_string()));
+        new_path
+    };
+    let mut changes = vec![];
+    let mut renamed = HashSet::new();
+    if options.detect_renames {
+        let mut removed: Vec<&String> = a
+            .keys()
+            .filter(|k| !b.contains_key(*k) && !options.ignores(&at(k)))
+            .collect();
+        let mut added: Vec<&String> = b
+            .keys()
+            .filter(|k| !a.contains_key(*k) && !options.ignores(&at(k)))
+            .collect();
+        removed.sort();
+        added.sort();
+        for from in removed {
+            if let Some(i) = added.iter().position(|to| a[from] == b[*to]) {
+                let to = added.remove(i);
+                renamed.extend([from, to]);
+                changes.push(Step::Emit(Change {
+                    path: path.clone(),
+                    kind: ChangeKind::Renamed {
+                        from: from.clone(),
+                        to: to.clone(),
+                    },
+                }));
+            }
+        }
+    }
+
+    let mut keys: Vec<&String> = a
+        .keys()
+        .chain(b.keys().filter(|k| !a.contains_key(*k)))
+        .filter(|k| !renamed.contains(k))
+        .collect();
+    keys.sort();
+    changes.extend(keys.into_iter().flat_map(|key| {
+        let new_path = at(key);
+        if options.ignores(&new_path) {
+            return vec![];
+        }
+        match (a.get(key), b.get(key)) {
+            (Some(va), Some(vb)) => vec![Step::Diff(va, vb, new_path)],
+            (Some(va), None) => vec![Step::Emit(removal(new_path, va.clone()))],
+            (None, Some(vb)) => vec![Step::Emit(addition(new_path, vb.clone()))],
+            (None, None) => unreachable!(),
+        }
+    }));
+    changes
+}
+
+/// Diffs two sequences with Myers, emitting one change per element.
+/// Indices account for the changes emitted before, see [`ChangeKind`].
+fn diff_sequences<'a, P: Primitive>(
+    old: &'a [Node<P>],
+    new: &'a [Node<P>],
+    path: Vec<PathSegment>,
+    options: &DiffOptions<P>,
+) -> Vec<Step<'a, P>> {
+    let strategy = options.sequence_strategy_at(&path);
+    match strategy {
+        SequenceStrategy::Positional => return diff_positions(old, new, path),
+        SequenceStrategy::Unordered => return diff_multisets(old, new, path),
+        SequenceStrategy::Myers | SequenceStrategy::Keyed(_) | SequenceStrategy::KeyedBy(_) => {}
+    }
+    let mut changes = vec![];
+    // Myers clones the elements it returns, so it works on references
+    let old: Vec<&Node<P>> = if options.detect_moves {
+        move_elements(old, new, &path, &mut changes)
+    } else {
+        old.iter().collect()
+    };
+    let new: Vec<&Node<P>> = new.iter().collect();
+    let identities = |elements: &[&'a Node<P>]| -> Vec<Identity<'a, P>> {
+        elements
+            .iter()
+            .map(|element| identity(element, strategy))
+            .collect()
+    };
+    let mut index = 0;
+    let mut removed = vec![];
+    let mut added = vec![];
+    let (mut i, mut j) = (0, 0);
+    for edit in myers::diff(&identities(&old), &identities(&new)) {
+        match edit {
+            Edit::Delete(_) => {
+                removed.push(old[i]);
+                i += 1;
+            }
+            Edit::Insert(_) => {
+                added.push(new[j]);
+                j += 1;
+            }
+            Edit::Equal(_) => {
+                replace_run(&mut removed, &mut added, &mut index, &path, &mut changes);
+                // elements with the same key may still differ
+                if old[i] != new[j] {
+                    let mut element_path = path.clone();
+                    element_path.push(PathSegment::Index(index));
+                    changes.push(Step::Diff(old[i], new[j], element_path));
+                }
+                index += 1;
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    replace_run(&mut removed, &mut added, &mut index, &path, &mut changes);
+    changes
+}
+
+/// Pairs the elements of two sequences by index, then removes
+/// or adds the elements past the end of the shorter one.
+fn diff_positions<'a, P: Primitive>(
+    old: &'a [Node<P>],
+    new: &'a [Node<P>],
+    path: Vec<PathSegment>,
+) -> Vec<Step<'a, P>> {
+    let at = |index: usize| {
+        let mut path = path.clone();
+        path.push(PathSegment::Index(index));
+        path
+    };
+    let mut changes: Vec<Step<P>> = old
+        .iter()
+        .zip(new)
+        .enumerate()
+        .map(|(i, (a, b))| Step::Diff(a, b, at(i)))
+        .collect();
+    // each removal shifts the next element to the same index
+    for element in old.iter().skip(new.len()) {
+        changes.push(Step::Emit(removal(at(new.len()), element.clone())));
+    }
+    for (i, element) in new.iter().enumerate().skip(old.len()) {
+        changes.push(Step::Emit(addition(at(i), element.clone())));
+    }
+    changes
+}
+
+/// Removes the elements of `old` missing from `new`, counting duplicates,
+/// then appends the elements of `new` missing from `old`.
+fn diff_multisets<'a, P: Primitive>(
+    old: &'a [Node<P>],
+    new: &'a [Node<P>],
+    path: Vec<PathSegment>,
+) -> Vec<Step<'a, P>> {
+    let at = |index: usize| {
+        let mut path = path.clone();
+        path.push(PathSegment::Index(index));
+        path
+    };
+    // the indices of the elements of new not matched yet, by hash
+    let mut unmatched: HashMap<u64, Vec<usize>> = HashMap::new();
+    for (j, element) in new.iter().enumerate() {
+        unmatched
+            .entry(element.structural_hash())
+            .or_default()
+            .push(j);
+    }
+    let mut changes = vec![];
+    let mut index = 0;
+    for element in old {
+        let matched = unmatched
+            .get_mut(&element.structural_hash())
+            .and_then(|indices| {
+                let position = indices.iter().position(|&j| new[j] == *element)?;
+                Some(indices.remove(position))
+            });
+        match matched {
+            Some(_) => index += 1,
+            None => changes.push(Step::Emit(removal(at(index), element.clone()))),
+        }
+    }
+    let mut added: Vec<usize> = unmatched.into_values().flatten().collect();
+    added.sort_unstable();
+    for (offset, j) in added.into_iter().enumerate() {
+        changes.push(Step::Emit(addition(at(index + offset), new[j].clone())));
+    }
+    changes
+}
+
+/// What sequence elements are aligned on.
+#[derive(Clone, PartialEq, Eq)]
+enum Identity<'a, P: Primitive> {
+    Whole(&'a Node<P>),
+    Key(Vec<&'a Node<P>>),
+}
+
+fn identity<'a, P: Primitive>(
+    element: &'a Node<P>,
+    strategy: &SequenceStrategy,
+) -> Identity<'a, P> {
+    match (strategy, element) {
+        (SequenceStrategy::Keyed(key), Node::Map(map)) => map
+            .get(key)
+            .map_or(Identity::Whole(element), |v| Identity::Key(vec![v])),
+        (SequenceStrategy::KeyedBy(keys), Node::Map(map)) => keys
+            .iter()
+            .map(|key| map.get(key))
+            .collect::<Option<_>>()
+            .map_or(Identity::Whole(element), Identity::Key),
+        _ => Identity::Whole(element),
+    }
+}
+
+/// Emits a `Moved` change for each element removed from `old` while an equal one
+/// is added to `new`, and returns `old` with these elements moved.
+/// Each element is moved right after the element preceding it in `new`
+/// among the ones left in place or already moved.
+fn move_elements<'a, P: Primitive>(
+    old: &'a [Node<P>],
+    new: &[Node<P>],
+    path: &[PathSegment],
+    changes: &mut Vec<Step<'a, P>>,
+) -> Vec<&'a Node<P>> {
+    // the index in old of the element found at each index of new, if kept
+    let mut sources = vec![None; new.len()];
+    let mut removed = vec![];
+    let mut added = vec![];
+    let (mut i, mut j) = (0, 0);
+    let mut sequence: Vec<&Node<P>> = old.iter().collect();
+    let new_refs: Vec<&Node<P>> = new.iter().collect();
+    for edit in myers::diff(&sequence, &new_refs) {
+        match edit {
+            Edit::Delete(_) => {
+                removed.push(i);
+                i += 1;
+            }
+            Edit::Insert(_) => {
+                added.push(j);
+                j += 1;
+            }
+            Edit::Equal(_) => {
+                sources[j] = Some(i);
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+
+    // the index in old of each element of sequence
+    let mut origins: Vec<usize> = (0..old.len()).collect();
+    for j in added {
+        let Some(r) = removed.iter().position(|&i| old[i] == new[j]) else {
+            continue;
+        };
+        let i = removed.remove(r);
+        sources[j] = Some(i);
+        let from_index = origins.iter().position(|&o| o == i).unwrap();
+        let element = sequence.remove(from_index);
+        origins.remove(from_index);
+        let to_index = sources[..j]
+            .iter()
+            .rev()
+            .find_map(|source| *source)
+            .map_or(0, |anchor| {
+                origins.iter().position(|&o| o == anchor).unwrap() + 1
+            });
+        sequence.insert(to_index, element);
+        origins.insert(to_index, i);
+        if from_index != to_index {
+            changes.push(Step::Emit(Change {
+                path: path.to_vec(),
+                kind: ChangeKind::Moved {
+                    from_index,
+                    to_index,
+                },
+            }));
+        }
+    }
+    sequence
+}
+
+/// Emits the changes turning a run of removed elements into a run of added ones,
+/// starting at `index`. Removed and added elements are paired in order
+/// and diffed in turn, so a change inside an element keeps its path.
+fn replace_run<'a, P: Primitive>(
+    removed: &mut Vec<&'a Node<P>>,
+    added: &mut Vec<&'a Node<P>>,
+    index: &mut usize,
+    path: &[PathSegment],
+    changes: &mut Vec<Step<'a, P>>,
+) {
+    let at = |index: usize| {
+        let mut path = path.to_vec();
+        path.push(PathSegment::Index(index));
+        path
+    };
+    let paired = removed.len().min(added.len());
+    let mut removed = removed.drain(..);
+    let mut added = added.drain(..);
+    for (old, new) in removed.by_ref().zip(added.by_ref()).take(paired) {
+        changes.push(Step::Diff(old, new, at(*index)));
+        *index += 1;
+    }
+    for old in removed {
+        changes.push(Step::Emit(removal(at(*index), old.clone())));
+    }
+    for new in added {
+        changes.push(Step::Emit(addition(at(*index), new.clone())));
+        *index += 1;
+    }
+}
+
+fn removal<P: Primitive>(path: Vec<PathSegment>, node: Node<P>) -> Change<P> {
+    let kind = match node {
+        Node::Leaf(v) => ChangeKind::Removed(v),
+        node => ChangeKind::NodeRemoved(node),
+    };
+    Change { path, kind }
+}
+
+fn addition<P: Primitive>(path: Vec<PathSegment>, node: Node<P>) -> Change<P> {
+    let kind = match node {
+        Node::Leaf(v) => ChangeKind::Added(v),
+        node => ChangeKind::NodeAdded(node),
+    };
+    Change { path, kind }
+}
+
+/// Applies a list of changes to an input. Reverse of `diff`
+///
+/// # Errors
+///
+/// Returns an [`ApplyError`] if a change doesn't fit the input,
+/// e.g. its path doesn't exist or leads to a node of another type.
+pub fn apply<T: Diffable>(old: &T, changes: &[Change<T::P>]) -> Result<T, ApplyError> {
+    apply_with(old, changes, &ApplyOptions::default())
+}
+
+/// Applies a list of changes like [`apply`], first checking that the old values
+/// recorded in `Modified`, `Removed` and `NodeRemoved` match the input,
+/// and that added map keys don't exist yet.
+///
+/// Use it when the input may have changed since the changes were computed:
+/// a concurrent edit is reported instead of being overwritten.
+/// ```
+/// use std::collections::HashMap;
+/// use diffkit::recursive::{apply_strict, diff, ApplyError, PathSegment};
+///
+/// let old = HashMap::from([("a".to_string(), 1)]);
+/// let new = HashMap::from([("a".to_string(), 2)]);
+/// let changes = diff(&old, &new);
+///
+/// let edited = HashMap::from([("a".to_string(), 3)]);
+/// assert_eq!(
+///     apply_strict(&edited, &changes),
+///     Err(ApplyError::Conflict(vec![PathSegment::Key("a".to_string())]))
+/// );
+/// assert_eq!(apply_strict(&old, &changes), Ok(new));
+/// ```
+///
+/// # Errors
+///
+/// Returns [`ApplyError::Conflict`] on a mismatch, and the errors of [`apply`].
+pub fn apply_strict<T: Diffable>(old: &T, changes: &[Change<T::P>]) -> Result<T, ApplyError> {
+    apply_with(old, changes, &ApplyOptions::new().strict())
+}
+
+/// The changes skipped by [`apply_lenient`], each with the reason it doesn't fit.
+pub type Skipped<P> = Vec<(Change<P>, ApplyError)>;
+
+/// Applies a list of changes like [`apply`], skipping those that don't fit the input
+/// instead of failing, e.g. for a sync client applying remote changes on a best-effort basis.
+/// Returns the result along with the skipped changes and why they don't fit.
+///
+/// A skipped change doesn't modify anything, but the changes after it
+/// are applied as they are: their indices aren't adjusted.
+/// ```
+/// use std::collections::HashMap;
+/// use diffkit::recursive::{apply_lenient, diff, ApplyError, PathSegment};
+///
+/// let old = HashMap::from([("a".to_string(), 1), ("b".to_string(), 2)]);
+/// let new = HashMap::from([("a".to_string(), 3)]);
+/// let changes = diff(&old, &new);
+///
+/// let edited = HashMap::from([("a".to_string(), 1)]);
+/// let (patched, skipped) = apply_lenient(&edited, &changes).unwrap();
+/// assert_eq!(patched, new);
+/// assert_eq!(skipped[0].1, ApplyError::PathNotFound(vec![PathSegment::Key("b".to_string())]));
+/// ```
+///
+/// # Errors
+///
+/// Returns an [`ApplyError`] if the result doesn't convert back to `T`.
+pub fn apply_lenient<T: Diffable>(
+    old: &T,
+    changes: &[Change<T::P>],
+) -> Result<(T, Skipped<T::P>), ApplyError> {
+    let mut node = old.to_node();
+    let mut skipped = vec![];
+    for change in changes {
+        if let Err(e) = apply_at(&mut node, change, 0, false) {
+            skipped.push((change.clone(), e));
+        }
+    }
+    T::from_node(node).map(|value| (value, skipped))
+}
+
+/// Applies a list of changes like [`apply`], with the given [`ApplyOptions`].
+/// ```
+/// use std::collections::HashMap;
+/// use diffkit::recursive::{apply_with, ApplyOptions, Change, ChangeKind, PathSegment};
+///
+/// let change = Change {
+///     path: ["metadata", "labels", "team"]
+///         .map(|k| PathSegment::Key(k.to_string()))
+///         .to_vec(),
+///     kind: ChangeKind::Added(1),
+/// };
+/// type Config = HashMap<String, HashMap<String, HashMap<String, i32>>>;
+/// let options = ApplyOptions::new().create_missing();
+/// let patched = apply_with(&Config::new(), &[change], &options).unwrap();
+/// assert_eq!(patched["metadata"]["labels"]["team"], 1);
+/// ```
+///
+/// # Errors
+///
+/// Returns the errors of [`apply`], and of [`apply_strict`] if `options.strict`.
+pub fn apply_with<T: Diffable>(
+    old: &T,
+    changes: &[Change<T::P>],
+    options: &ApplyOptions,
+) -> Result<T, ApplyError> {
+    let mut node = old.to_node();
+    apply_node_with(&mut node, changes, options)?;
+    T::from_node(node)
+}
+
+/// Applies a list of changes to `target` like [`apply`], replacing it with the result.
+/// `target` is left untouched if a change doesn't fit.
+///
+/// This is only a convenience wrapper: `target` still goes through
+/// [`Diffable::to_node`] and [`Diffable::from_node`] once. To patch a tree
+/// without converting it, keep it as a [`Node`] and use [`apply_node_in_place`].
+/// ```
+/// use diffkit::recursive::{apply_in_place, diff};
+///
+/// let mut config = vec![1, 2, 3];
+/// let changes = diff(&config, &vec![1, 3]);
+/// apply_in_place(&mut config, &changes).unwrap();
+/// assert_eq!(config, vec![1, 3]);
+/// ```
+///
+/// # Errors
+///
+/// Returns the errors of [`apply`].
+pub fn apply_in_place<T: Diffable>(
+    target: &mut T,
+    changes: &[Change<T::P>],
+) -> Result<(), ApplyError> {
+    *target = apply(target, changes)?;
+    Ok(())
+}
+
+/// Applies several lists of changes in turn like [`apply`], all or nothing:
+/// if a change of one of them doesn't fit, none is applied,
+/// and the error comes with the index of that list.
+/// ```
+/// use std::collections::HashMap;
+/// use diffkit::recursive::{apply_transaction, diff, ApplyError, PathSegment};
+///
+/// let v1 = HashMap::from([("a".to_string(), 1)]);
+/// let v2 = HashMap::from([("a".to_string(), 2)]);
+/// let v3 = HashMap::from([("b".to_string(), 3)]);
+/// let (first, second) = (diff(&v1, &v2), diff(&v2, &v3));
+/// assert_eq!(apply_transaction(&v1, &[&first, &second]), Ok(v3));
+///
+/// // the second list expects the state left by the first one
+/// assert_eq!(
+///     apply_transaction(&v1, &[&second, &first]),
+///     Err((1, ApplyError::PathNotFound(vec![PathSegment::Key("a".to_string())])))
+/// );
+/// ```
+///
+/// # Errors
+///
+/// Returns the index of the failing list with the errors of [`apply`];
+/// if the result doesn't convert back to `T`, the index of the last list.
+pub fn apply_transaction<T: Diffable>(
+    old: &T,
+    change_sets: &[&[Change<T::P>]],
+) -> Result<T, (usize, ApplyError)> {
+    let mut node = old.to_node();
+    for (i, changes) in change_sets.iter().enumerate() {
+        apply_node_in_place(&mut node, changes).map_err(|e| (i, e))?;
+    }
+    T::from_node(node).map_err(|e| (change_sets.len().saturating_sub(1), e))
+}
+
+/// Applies a list of changes to a [`Node`] tree like [`apply`], modifying it in place
+/// instead of building a new tree: only the nodes on the path of each change are visited.
+///
+/// A change that doesn't fit is reported before modifying anything,
+/// but the changes before it stay applied.
+///
+/// # Errors
+///
+/// Returns the errors of [`apply`].
+pub fn apply_node_in_place<P: Primitive>(
+    node: &mut Node<P>,
+    changes: &[Change<P>],
+) -> Result<(), ApplyError> {
+    apply_node_with(node, changes, &ApplyOptions::default())
+}
+
+fn apply_node_with<P: Primitive>(
+    node: &mut Node<P>,
+    changes: &[Change<P>],
+    options: &ApplyOptions,
+) -> Result<(), ApplyError> {
+    changes.iter().try_for_each(|change| {
+        if options.create_missing {
+            create_parents(node, change);
+        }
+        apply_at(node, change, 0, options.strict)
+    })
+}
+
+/// Inserts the maps missing on the path of an addition,
+/// as long as the rest of its path is made of map keys.
+fn create_parents<P: Primitive>(node: &mut Node<P>, change: &Change<P>) {
+    let Some((_, parents)) = change.path.split_last() else {
+        return;
+    };
+    if added_node(&change.kind).is_none() {
+        return;
+    }
+    let mut node = node;
+    for (depth, segment) in parents.iter().enumerate() {
+        let next = match (node, segment) {
+            (Node::Map(m), PathSegment::Key(k)) => {
+                let keys = change.path[depth..]
+                    .iter()
+                    .all(|s| matches!(s, PathSegment::Key(_)));
+                if keys {
+                    Some(
+                        m.entry(k.clone())
+                            .or_insert_with(|| Node::Map(HashMap::new())),
+                    )
+                } else {
+                    m.get_mut(k)
+                }
+            }
+            (Node::Sequence(s) | Node::Tuple(s), PathSegment::Index(i)) => s.get_mut(*i),
+            (Node::Variant(tag, content), PathSegment::Key(k)) if k == tag => Some(&mut **content),
+            _ => None,
+        };
+        let Some(next) = next else {
+            return;
+        };
+        node = next;
+    }
+}
+
+/// Builds the list of changes undoing `changes`, e.g. for an undo stack.
+///
+/// Changes are inverted with [`Change::invert`] and applied in reverse order,
+/// so sequence indices stay valid.
+/// ```
+/// use diffkit::recursive::{apply, diff, invert_changes};
+///
+/// let old = vec![1, 2, 3];
+/// let new = vec![0, 1, 3, 4];
+/// let changes = diff(&old, &new);
+/// assert_eq!(apply(&new, &invert_changes(&changes)), Ok(old));
+/// ```
+pub fn invert_changes<P: Primitive>(changes: &[Change<P>]) -> Vec<Change<P>> {
+    changes.iter().rev().map(Change::invert).collect()
+}
+
+/// Applies `change` to `node`, found at the first `depth` segments of its path.
+/// Nothing is modified if the change doesn't fit.
+///
+/// The nodes on the path are walked in a loop, so deep trees don't exhaust the stack.
+fn apply_at<P: Primitive>(
+    node: &mut Node<P>,
+    change: &Change<P>,
+    depth: usize,
+    strict: bool,
+) -> Result<(), ApplyError> {
+    let mut node = node;
+    let mut depth = depth;
+    while descends(change, depth) {
+        let Some(segment) = change.path.get(depth) else {
+            break;
+        };
+        node = match (node, segment) {
+            (Node::Map(m), PathSegment::Key(k)) => m.get_mut(k),
+            (Node::Sequence(s) | Node::Tuple(s), PathSegment::Index(i)) => s.get_mut(*i),
+            (Node::Variant(tag, content), PathSegment::Key(k)) if k == tag => Some(&mut **content),
+            (Node::Variant(..), PathSegment::Key(_)) => None,
+            _ => return Err(ApplyError::TypeMismatch(change.path[..depth].to_vec())),
+        }
+        .ok_or_else(|| not_found(change, depth))?;
+        depth += 1;
+    }
+
+    match (node, change.path.get(depth)) {
+        (node, None) => {
+            if added_node(&change.kind).is_none() && !rearranges(change) {
+                check_target(Some(node), change, depth, strict)?;
+            }
+            match &change.kind {
+                ChangeKind::Added(new) | ChangeKind::Modified(_, new) => {
+                    *node = Node::Leaf(new.clone());
+                }
+                ChangeKind::NodeAdded(new) => *node = new.clone(),
+                ChangeKind::BlobPatched(delta) => patch(node, delta),
+                // the root can't be removed, it's replaced by the following addition
+                ChangeKind::Removed(_) | ChangeKind::NodeRemoved(_) => {}
+                ChangeKind::Renamed { from, to } => rename(node, from, to, change, strict)?,
+                ChangeKind::Moved {
+                    from_index,
+                    to_index,
+                } => move_element(node, *from_index, *to_index, change)?,
+                ChangeKind::TruncatedSubtreeChanged { .. }
+                | ChangeKind::TruncatedLeafChanged { .. } => {
+                    return Err(ApplyError::InvalidChangeKind(change.path.clone()))
+                }
+            }
+            Ok(())
+        }
+        (Node::Map(m), Some(PathSegment::Key(k))) => apply_to_map(m, k, change, depth, strict),
+        (Node::Sequence(s) | Node::Tuple(s), Some(PathSegment::Index(i))) => {
+            apply_to_sequence(s, *i, change, depth, strict)
+        }
+        (Node::Variant(tag, content), Some(PathSegment::Key(k))) if k == tag => {
+            apply_to_variant(content, change, depth, strict)
+        }
+        (Node::Variant(..), Some(PathSegment::Key(_))) => Err(not_found(change, depth)),
+        (Node::Set(s), Some(PathSegment::Member)) => apply_to_set(s, change, strict),
+        _ => Err(ApplyError::TypeMismatch(change.path[..depth].to_vec())),
+    }
+}
+
+/// Adds, removes or modifies the value at `key` in `map`.
+fn apply_to_map<P: Primitive>(
+    map: &mut HashMap<String, Node<P>>,
+    key: &String,
+    change: &Change<P>,
+    depth: usize,
+    strict: bool,
+) -> Result<(), ApplyError> {
+    if let Some(new) = added_node(&change.kind) {
+        if strict && map.contains_key(key) {
+            return Err(ApplyError::Conflict(change.path.clone()));
+        }
+        map.insert(key.clone(), new);
+    } else {
+        check_target(map.get(key), change, depth, strict)?;
+        match &change.kind {
+            ChangeKind::Modified(_, new) => {
+                map.insert(key.clone(), Node::Leaf(new.clone()));
+            }
+            ChangeKind::BlobPatched(delta) => patch(map.get_mut(key).unwrap(), delta),
+            _ => {
+                map.remove(key);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Inserts, removes or modifies the element at `index` of `sequence`.
+fn apply_to_sequence<P: Primitive>(
+    sequence: &mut Vec<Node<P>>,
+    index: usize,
+    change: &Change<P>,
+    depth: usize,
+    strict: bool,
+) -> Result<(), ApplyError> {
+    if let Some(new) = added_node(&change.kind) {
+        if index > sequence.len() {
+            return Err(not_found(change, depth));
+        }
+        sequence.insert(index, new);
+    } else {
+        check_target(sequence.get(index), change, depth, strict)?;
+        match &change.kind {
+            ChangeKind::Modified(_, new) => sequence[index] = Node::Leaf(new.clone()),
+            ChangeKind::BlobPatched(delta) => patch(&mut sequence[index], delta),
+            _ => {
+                sequence.remove(index);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Modifies the content of a variant, the only change it can take.
+fn apply_to_variant<P: Primitive>(
+    content: &mut Node<P>,
+    change: &Change<P>,
+    depth: usize,
+    strict: bool,
+) -> Result<(), ApplyError> {
+    check_target(Some(content), change, depth, strict)?;
+    match &change.kind {
+        ChangeKind::Modified(_, new) => {
+            *content = Node::Leaf(new.clone());
+            Ok(())
+        }
+        ChangeKind::BlobPatched(delta) => {
+            patch(content, delta);
+            Ok(())
+        }
+        _ => Err(ApplyError::InvalidChangeKind(change.path.clone())),
+    }
+}
+
+/// Adds or removes the member held by `change`. Adding a member twice
+/// is a conflict only if `strict`, while removing a missing member always fails.
+fn apply_to_set<P: Primitive>(
+    set: &mut Vec<Node<P>>,
+    change: &Change<P>,
+    strict: bool,
+) -> Result<(), ApplyError> {
+    if let Some(new) = added_node(&change.kind) {
+        if !set.contains(&new) {
+            set.push(new);
+        } else if strict {
+            return Err(ApplyError::Conflict(change.path.clone()));
+        }
+    } else {
+        let old = match &change.kind {
+            ChangeKind::Removed(old) => Node::Leaf(old.clone()),
+            ChangeKind::NodeRemoved(old) => old.clone(),
+            _ => return Err(ApplyError::InvalidChangeKind(change.path.clone())),
+        };
+        let position = set
+            .iter()
+            .position(|v| *v == old)
+            .ok_or_else(|| ApplyError::PathNotFound(change.path.clone()))?;
+        set.swap_remove(position);
+    }
+    Ok(())
+}
+
+/// Whether `change` applies below the child found at `depth`,
+/// or to the child itself for the changes acting on a whole node.
+fn descends<P: Primitive>(change: &Change<P>, depth: usize) -> bool {
+    depth + 1 < change.path.len() || rearranges(change)
+}
+
+/// Whether `change` rearranges the children of the node at its path.
+fn rearranges<P: Primitive>(change: &Change<P>) -> bool {
+    matches!(
+        change.kind,
+        ChangeKind::Renamed { .. } | ChangeKind::Moved { .. }
+    )
+}
+
+/// Moves the value of `from` to `to` in the map `node`.
+/// Overwriting a key is a conflict only if `strict`.
+fn rename<P: Primitive>(
+    node: &mut Node<P>,
+    from: &String,
+    to: &String,
+    change: &Change<P>,
+    strict: bool,
+) -> Result<(), ApplyError> {
+    let Node::Map(map) = node else {
+        return Err(ApplyError::TypeMismatch(change.path.clone()));
+    };
+    let key_path = |key: &String| {
+        let mut path = change.path.clone();
+        path.push(PathSegment::Key(key.clone()));
+        path
+    };
+    if !map.contains_key(from) {
+        return Err(ApplyError::PathNotFound(key_path(from)));
+    }
+    if strict && from != to && map.contains_key(to) {
+        return Err(ApplyError::Conflict(key_path(to)));
+    }
+    let value = map.remove(from).unwrap();
+    map.insert(to.clone(), value);
+    Ok(())
+}
+
+/// Moves the element at `from_index` of the sequence `node` so that it ends up at `to_index`.
+fn move_element<P: Primitive>(
+    node: &mut Node<P>,
+    from_index: usize,
+    to_index: usize,
+    change: &Change<P>,
+) -> Result<(), ApplyError> {
+    let Node::Sequence(sequence) = node else {
+        return Err(ApplyError::TypeMismatch(change.path.clone()));
+    };
+    move_within(sequence, from_index, to_index, change)
+}
+
+/// Moves an element of `sequence` for [`move_element`], whatever its elements.
+fn move_within<T, P: Primitive>(
+    sequence: &mut Vec<T>,
+    from_index: usize,
+    to_index: usize,
+    change: &Change<P>,
+) -> Result<(), ApplyError> {
+    for index in [from_index, to_index] {
+        if index >= sequence.len() {
+            let mut path = change.path.clone();
+            path.push(PathSegment::Index(index));
+            return Err(ApplyError::PathNotFound(path));
+        }
     }
+    let element = sequence.remove(from_index);
+    sequence.insert(to_index, element);
+    Ok(())
 }
 
-/// Applies a list of changes to an input. Reverse of `diff`
-pub fn apply<T: Diffable>(old: &T, changes: &[Change<T::P>]) -> T {
-    let new_node = changes.iter().fold(old.to_node(), apply_change);
-    T::from_node(new_node)
-}
+/// Applies `delta` to the blob `node`, already checked by [`check_target`].
+fn patch<P: Primitive>(node: &mut Node<P>, delta: &BlobDelta) {
+    if let Node::Leaf(leaf) = node {
+        blob::patch_leaf(leaf, delta);
+    }
+}
+
+/// The node inserted by an addition.
+fn added_node<P: Primitive>(kind: &ChangeKind<P>) -> Option<Node<P>> {
+    match kind {
+        ChangeKind::Added(new) => Some(Node::Leaf(new.clone())),
+        ChangeKind::NodeAdded(new) => Some(new.clone()),
+        _ => None,
+    }
+}
+
+/// Checks that a removal or modification finds a value of the right type,
+/// and if `strict`, the value recorded in the change.
+fn check_target<P: Primitive>(
+    current: Option<&Node<P>>,
+    change: &Change<P>,
+    depth: usize,
+    strict: bool,
+) -> Result<(), ApplyError> {
+    let current = current.ok_or_else(|| not_found(change, depth))?;
+    let (fits, expected) = match (&change.kind, current) {
+        (ChangeKind::Removed(old) | ChangeKind::Modified(old, _), Node::Leaf(v)) => {
+            (true, old == v)
+        }
+        (ChangeKind::Removed(_) | ChangeKind::Modified(..), _) => (false, false),
+        (ChangeKind::NodeRemoved(old), node) => (!matches!(node, Node::Leaf(_)), old == node),
+        (ChangeKind::BlobPatched(delta), Node::Leaf(v)) => match blob::leaf_bytes(v) {
+            Some(bytes) => (delta.fits(bytes), delta.matches(bytes)),
+            None => (false, false),
+        },
+        (ChangeKind::BlobPatched(_), _) => (false, false),
+        (ChangeKind::Added(_) | ChangeKind::NodeAdded(_), _) => (true, true),
+        (
+            ChangeKind::Renamed { .. }
+            | ChangeKind::Moved { .. }
+            | ChangeKind::TruncatedSubtreeChanged { .. }
+            | ChangeKind::TruncatedLeafChanged { .. },
+            _,
+        ) => (false, false),
+    };
+    if !fits {
+        Err(ApplyError::InvalidChangeKind(change.path.clone()))
+    } else if strict && !expected {
+        Err(ApplyError::Conflict(change.path.clone()))
+    } else {
+        Ok(())
+    }
+}
+
+fn not_found<P: Primitive>(change: &Change<P>, depth: usize) -> ApplyError {
+    ApplyError::PathNotFound(change.path[..=depth].to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::borrow::Cow;
+    use std::collections::{BTreeMap, BTreeSet, VecDeque};
+    use std::net::{IpAddr, SocketAddr};
+    use std::path::PathBuf;
+    use std::rc::Rc;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_optional_values() {
+        let old = HashMap::from([("a".to_string(), Some(1)), ("b".to_string(), None)]);
+        let new = HashMap::from([("a".to_string(), None), ("b".to_string(), None)]);
+        let a = vec![PathSegment::Key("a".to_string())];
+        assert_eq!(
+            diff(&old, &new),
+            vec![
+                Change {
+                    path: a.clone(),
+                    kind: ChangeKind::Removed(1),
+                },
+                Change {
+                    path: a,
+                    kind: ChangeKind::NodeAdded(Node::Null),
+                },
+            ]
+        );
+        assert_eq!(apply(&old, &diff(&old, &new)), Ok(new.clone()));
+        assert_eq!(apply(&new, &diff(&new, &old)), Ok(old));
+    }
+
+    #[test]
+    fn test_tuples_and_arrays() {
+        let old = vec![(1, Some(2)), (3, None)];
+        let new = vec![(1, None), (4, None)];
+        assert_eq!(
+            diff(&old, &new),
+            vec![
+                Change {
+                    path: vec![PathSegment::Index(0), PathSegment::Index(1)],
+                    kind: ChangeKind::Removed(2),
+                },
+                Change {
+                    path: vec![PathSegment::Index(0), PathSegment::Index(1)],
+                    kind: ChangeKind::NodeAdded(Node::Null),
+                },
+                Change {
+                    path: vec![PathSegment::Index(1), PathSegment::Index(0)],
+                    kind: ChangeKind::Modified(3, 4),
+                },
+            ]
+        );
+        assert_eq!(apply(&old, &diff(&old, &new)), Ok(new));
+
+        let old = [[1, 2, 3], [4, 5, 6]];
+        let new = [[3, 1, 2], [4, 5, 6]];
+        assert_eq!(diff(&old, &new).len(), 3);
+        assert_eq!(apply(&old, &diff(&old, &new)), Ok(new));
+        assert_eq!(
+            <[i32; 2]>::from_node(Node::Tuple(vec![Node::Leaf(1)])),
+            Err(ApplyError::TypeMismatch(vec![]))
+        );
+        assert_eq!(
+            <(i32, i32)>::from_node(Node::Tuple(vec![Node::Leaf(1), Node::Null])),
+            Err(ApplyError::TypeMismatch(vec![PathSegment::Index(1)]))
+        );
+    }
+
+    #[test]
+    fn test_variants() {
+        let variant = |tag: &str, content| Node::Variant(tag.to_string(), Box::new(content));
+        let circle = vec![PathSegment::Key("Circle".to_string())];
+        let changes = diff_nodes(
+            &variant("Circle", Node::Leaf(1)),
+            &variant("Circle", Node::Leaf(2)),
+            vec![],
+            &DiffOptions::default(),
+        );
+        assert_eq!(
+            changes,
+            vec![Change {
+                path: circle.clone(),
+                kind: ChangeKind::Modified(1, 2),
+            }]
+        );
+
+        let old = variant("Circle", Node::Leaf(1));
+        for new in [
+            variant("Circle", Node::Sequence(vec![Node::Leaf(1)])),
+            variant("Point", Node::Map(HashMap::new())),
+        ] {
+            let changes = diff_nodes(&old, &new, vec![], &DiffOptions::default());
+            assert_eq!(changes[0].kind, ChangeKind::NodeRemoved(old.clone()));
+            assert_eq!(changes[1].kind, ChangeKind::NodeAdded(new.clone()));
+            let mut applied = old.clone();
+            assert_eq!(
+                apply_node_with(&mut applied, &changes, &ApplyOptions::new().strict()),
+                Ok(())
+            );
+            assert_eq!(applied, new);
+        }
 
-fn apply_change<P: Primitive>(node: Node<P>, change: &Change<P>) -> Node<P> {
-    match (node, change.path.first()) {
-        (Node::Map(m), Some(PathSegment::Key(k))) => apply_to_map(m, k, change),
-        (Node::Sequence(_), _) => match &change.kind {
-            ChangeKind::SequenceChange(edits) => apply_to_sequence(edits.to_vec()),
-            _ => unreachable!(),
-        },
+        let removed = Change {
+            path: circle,
+            kind: ChangeKind::Removed(1),
+        };
+        assert_eq!(
+            apply_at(&mut old.clone(), &removed, 0, false),
+            Err(ApplyError::InvalidChangeKind(removed.path.clone()))
+        );
+        let elsewhere = Change {
+            path: vec![PathSegment::Key("Point".to_string())],
+            kind: ChangeKind::Removed(1),
+        };
+        assert_eq!(
+            apply_at(&mut old.clone(), &elsewhere, 0, false),
+            Err(ApplyError::PathNotFound(elsewhere.path.clone()))
+        );
+    }
 
-        (Node::Leaf(_), _) => match &change.kind {
-            ChangeKind::Modified(_, new) => Node::Leaf(new.clone()),
-            _ => unreachable!(),
-        },
-        (Node::Map(_), _) => unreachable!(),
+    #[test]
+    fn test_btree_maps() {
+        let old = BTreeMap::from([("a".to_string(), vec![1]), ("b".to_string(), vec![2])]);
+        let new = BTreeMap::from([("a".to_string(), vec![1, 3]), ("c".to_string(), vec![])]);
+        let changes = diff(&old, &new);
+        assert_eq!(changes.len(), 3);
+        assert!(changes.contains(&Change {
+            path: vec![PathSegment::Key("a".to_string()), PathSegment::Index(1)],
+            kind: ChangeKind::Added(3),
+        }));
+        assert_eq!(apply(&old, &changes), Ok(new));
     }
-}
 
-fn apply_to_map<P: Primitive>(
-    map: HashMap<String, Node<P>>,
-    key: &String,
-    change: &Change<P>,
-) -> Node<P> {
-    let mut new_map = map;
-    let node = if change.path.len() > 1 {
-        let new_change = Change {
-            kind: change.kind.clone(),
-            path: change.path[1..].to_vec(),
-        };
-        new_map.insert(
-            key.to_string(),
-            apply_change(new_map.get(key).unwrap().clone(), &new_change),
+    #[test]
+    fn test_sets() {
+        let old = HashMap::from([("tags".to_string(), HashSet::from([1, 2, 3]))]);
+        let new = HashMap::from([("tags".to_string(), HashSet::from([3, 1, 4]))]);
+        let path = vec![PathSegment::Key("tags".to_string()), PathSegment::Member];
+        assert_eq!(
+            diff(&old, &new),
+            vec![
+                Change {
+                    path: path.clone(),
+                    kind: ChangeKind::Removed(2),
+                },
+                Change {
+                    path: path.clone(),
+                    kind: ChangeKind::Added(4),
+                },
+            ]
         );
-        new_map
-    } else {
-        match &change.kind {
-            ChangeKind::NodeAdded(new) => new_map.insert(key.clone(), new.clone()),
-            ChangeKind::Added(new) => new_map.insert(key.clone(), Node::Leaf(new.clone())),
-            ChangeKind::NodeRemoved(_) | ChangeKind::Removed(_) => new_map.remove(key),
-            ChangeKind::Modified(_, new) => new_map.insert(key.clone(), Node::Leaf(new.clone())),
-            _ => unreachable!(),
+        assert_eq!(apply_strict(&old, &diff(&old, &new)), Ok(new.clone()));
+        assert_eq!(apply(&new, &diff(&new, &old)), Ok(old.clone()));
+
+        let added = vec![Change {
+            path: path.clone(),
+            kind: ChangeKind::Added(1),
+        }];
+        assert_eq!(apply(&old, &added), Ok(old.clone()));
+        assert_eq!(
+            apply_strict(&old, &added),
+            Err(ApplyError::Conflict(path.clone()))
+        );
+        let removed = Change {
+            path: path.clone(),
+            kind: ChangeKind::Removed(4),
         };
-        new_map
-    };
+        assert_eq!(apply(&old, &[removed]), Err(ApplyError::PathNotFound(path)));
 
-    Node::Map(node)
-}
+        let old = BTreeSet::from([vec![1], vec![2]]);
+        let new = BTreeSet::from([vec![2], vec![1, 2]]);
+        assert_eq!(diff(&old, &new).len(), 2);
+        assert_eq!(apply(&old, &diff(&old, &new)), Ok(new));
+    }
 
-fn apply_to_sequence<P: Primitive>(edits: Vec<Edit<Node<P>>>) -> Node<P> {
-    let mut result = vec![];
-    for edit in edits {
-        match edit {
-            Edit::Equal(v) => result.push(v.clone()),
-            Edit::Insert(v) => result.push(v.clone()),
-            Edit::Delete(_) => {}
-        }
+    #[test]
+    fn test_other_sequences() {
+        let old = VecDeque::from([1, 2, 3]);
+        let new = VecDeque::from([0, 1, 3]);
+        assert_eq!(apply(&old, &diff(&old, &new)), Ok(new));
+
+        let old: Box<[i32]> = Box::new([1, 2]);
+        let new: Box<[i32]> = Box::new([2]);
+        assert_eq!(apply(&old, &diff(&old, &new)), Ok(new));
+
+        let values = [1, 2, 3, 4];
+        let changes = diff(&values[..2], &values[1..3]);
+        assert_eq!(apply(&values[..2].to_vec(), &changes), Ok(vec![2, 3]));
     }
-    Node::Sequence(result)
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_pointers() {
+        let old = (Box::new(1), Rc::new(2), Arc::new(3));
+        let new = (Box::new(1), Rc::new(4), Arc::new(3));
+        let changes = diff(&old, &new);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(apply(&old, &changes), Ok(new));
+
+        let old: Cow<[i32]> = Cow::Borrowed(&[1, 2]);
+        let new: Cow<[i32]> = Cow::Owned(vec![2]);
+        assert_eq!(apply(&old, &diff(&old, &new)), Ok(new));
+        let old: Cow<str> = Cow::Borrowed("a");
+        let new: Cow<str> = Cow::Owned("b".to_string());
+        assert_eq!(apply(&old, &diff(&old, &new)), Ok(new));
+    }
+
+    #[test]
+    fn test_durations() {
+        let old = HashMap::from([("timeout".to_string(), Duration::from_secs(30))]);
+        let new = HashMap::from([("timeout".to_string(), Duration::from_millis(45_500))]);
+        assert_eq!(apply(&old, &diff(&old, &new)), Ok(new));
+        let long = Duration::new(u64::MAX, 999_999_999);
+        assert_eq!(Duration::try_from(Scalar::from(long)), Ok(long));
+        assert!(Duration::try_from(Scalar::UInt(u128::MAX)).is_err());
+    }
+
+    #[test]
+    fn test_addresses_and_paths() {
+        let old = HashMap::from([
+            (
+                "listen".to_string(),
+                "127.0.0.1:80".parse::<SocketAddr>().unwrap(),
+            ),
+            ("peer".to_string(), "[::1]:80".parse().unwrap()),
+        ]);
+        let mut new = old.clone();
+        new.insert("listen".to_string(), "0.0.0.0:80".parse().unwrap());
+        assert_eq!(diff(&old, &new).len(), 1);
+        assert_eq!(apply(&old, &diff(&old, &new)), Ok(new));
+
+        let ip: IpAddr = "::1".parse().unwrap();
+        assert_eq!(IpAddr::try_from(Scalar::from(ip)), Ok(ip));
+        let path = PathBuf::from("/etc/hosts");
+        assert_eq!(PathBuf::try_from(Scalar::from(path.clone())), Ok(path));
+    }
 
     #[test]
     fn test_key_added() {
@@ -231,19 +1518,85 @@ mod tests {
     fn test_sequence_of_primitives() {
         let a = vec![1, 2, 3];
         let b = vec![1, 3, 4];
+        let result = diff(&a, &b);
+        assert_eq!(
+            result,
+            vec![
+                Change {
+                    path: vec![PathSegment::Index(1)],
+                    kind: ChangeKind::Removed(2)
+                },
+                Change {
+                    path: vec![PathSegment::Index(2)],
+                    kind: ChangeKind::Added(4)
+                }
+            ]
+        );
+        assert_eq!(apply(&a, &result), Ok(b));
+    }
+
+    #[test]
+    fn test_sequence_element_modified() {
+        let a = vec![1, 2, 3, 4];
+        let b = vec![1, 5, 6, 7, 4];
+        let result = diff(&a, &b);
+        assert_eq!(
+            result,
+            vec![
+                Change {
+                    path: vec![PathSegment::Index(1)],
+                    kind: ChangeKind::Modified(2, 5)
+                },
+                Change {
+                    path: vec![PathSegment::Index(2)],
+                    kind: ChangeKind::Modified(3, 6)
+                },
+                Change {
+                    path: vec![PathSegment::Index(3)],
+                    kind: ChangeKind::Added(7)
+                }
+            ]
+        );
+        assert_eq!(apply(&a, &result), Ok(b));
+    }
+
+    #[test]
+    fn test_diff_inside_sequence_elements() {
+        let mut first = HashMap::new();
+        first.insert("id".to_string(), 1);
+        first.insert("name".to_string(), 10);
+        let mut second = first.clone();
+        second.insert("id".to_string(), 2);
+        let a = vec![first.clone(), second.clone()];
+        second.insert("name".to_string(), 20);
+        let b = vec![first, second];
+
         let result = diff(&a, &b);
         assert_eq!(
             result,
             vec![Change {
-                path: vec![],
-                kind: ChangeKind::SequenceChange(vec![
-                    Edit::Equal(Node::Leaf(1)),
-                    Edit::Delete(Node::Leaf(2)),
-                    Edit::Equal(Node::Leaf(3)),
-                    Edit::Insert(Node::Leaf(4))
-                ])
+                path: vec![PathSegment::Index(1), PathSegment::Key("name".to_string())],
+                kind: ChangeKind::Modified(10, 20)
+            }]
+        );
+        assert_eq!(apply(&a, &result), Ok(b));
+    }
+
+    #[test]
+    fn test_nested_sequence_paths() {
+        let mut a = HashMap::new();
+        a.insert("list".to_string(), vec![vec![1], vec![2]]);
+        let mut b = HashMap::new();
+        b.insert("list".to_string(), vec![vec![2]]);
+        let result = diff(&a, &b);
+        assert_eq!(
+            result,
+            vec![Change {
+                path: vec![PathSegment::Key("list".to_string()), PathSegment::Index(0)],
+                kind: ChangeKind::NodeRemoved(Node::Sequence(vec![Node::Leaf(1)]))
             }]
         );
+        assert_eq!(apply(&a, &result), Ok(b));
     }
 
     #[test]
@@ -253,4 +1606,628 @@ mod tests {
 
         assert_eq!(result, vec![]);
     }
+
+    #[test]
+    fn test_apply_errors() {
+        let mut a = HashMap::new();
+        a.insert("a".to_string(), vec![1]);
+        let change = |path, kind| Change { path, kind };
+        let key = |k: &str| PathSegment::Key(k.to_string());
+
+        assert_eq!(
+            apply(
+                &a,
+                &[change(
+                    vec![key("b"), PathSegment::Index(0)],
+                    ChangeKind::Removed(1)
+                )]
+            ),
+            Err(ApplyError::PathNotFound(vec![key("b")]))
+        );
+        assert_eq!(
+            apply(
+                &a,
+                &[change(
+                    vec![key("a"), PathSegment::Index(2)],
+                    ChangeKind::Added(1)
+                )]
+            ),
+            Err(ApplyError::PathNotFound(vec![
+                key("a"),
+                PathSegment::Index(2)
+            ]))
+        );
+        assert_eq!(
+            apply(
+                &a,
+                &[change(vec![key("a"), key("x")], ChangeKind::Added(1))]
+            ),
+            Err(ApplyError::TypeMismatch(vec![key("a")]))
+        );
+        assert_eq!(
+            apply(&a, &[change(vec![key("a")], ChangeKind::Modified(1, 2))]),
+            Err(ApplyError::InvalidChangeKind(vec![key("a")]))
+        );
+        // the change itself fits, but the result doesn't have the input's shape
+        assert_eq!(
+            apply(&a, &[change(vec![key("a")], ChangeKind::Added(1))]),
+            Err(ApplyError::TypeMismatch(vec![key("a")]))
+        );
+        assert_eq!(
+            ApplyError::PathNotFound(vec![key("a"), PathSegment::Index(2)]).to_string(),
+            "path not found at /a/2"
+        );
+    }
+
+    #[test]
+    fn test_apply_strict_conflicts() {
+        let a = vec![1, 2, 3];
+        let b = vec![1, 3];
+        let changes = diff(&a, &b);
+        assert_eq!(apply_strict(&a, &changes), Ok(b.clone()));
+        assert_eq!(
+            apply_strict(&vec![1, 5, 3], &changes),
+            Err(ApplyError::Conflict(vec![PathSegment::Index(1)]))
+        );
+        // non strict apply removes whatever is there
+        assert_eq!(apply(&vec![1, 5, 3], &changes), Ok(b));
+
+        let mut old = HashMap::new();
+        old.insert("a".to_string(), 1);
+        let mut new = old.clone();
+        new.insert("b".to_string(), 2);
+        let changes = diff(&old, &new);
+        assert_eq!(
+            apply_strict(&new, &changes),
+            Err(ApplyError::Conflict(vec![PathSegment::Key(
+                "b".to_string()
+            )]))
+        );
+    }
+
+    #[test]
+    fn test_hash_subtrees() {
+        let old: Node<i32> = crate::node!({
+            "a": { "b": [1, 2, 3], "c": { "d": 4 } },
+            "e": [{ "f": 5 }, { "g": 6 }],
+            "h": 7
+        });
+        let mut new = old.clone();
+        *new.get_mut(&[PathSegment::Key("a".into()), PathSegment::Key("b".into())])
+            .unwrap() = crate::node!([1, 3]);
+        new.remove(&[PathSegment::Key("h".into())]);
+        let options = DiffOptions::new().hash_subtrees();
+        for (old, new) in [(&old, &new), (&new, &old), (&old, &old)] {
+            assert_eq!(
+                diff_nodes(old, new, vec![], &options),
+                diff_nodes(old, new, vec![], &DiffOptions::default())
+            );
+        }
+    }
+
+    #[test]
+    fn test_keyed_sequences() {
+        let element = |id: i32, value: i32| crate::node!({ "id": id, "value": value });
+        let old = Node::Sequence(vec![element(1, 1), element(2, 2), element(3, 3)]);
+        let new = Node::Sequence(vec![element(2, 5), element(4, 4), element(3, 3)]);
+        let options = DiffOptions::new().sequence_strategy(SequenceStrategy::Keyed("id".into()));
+        let changes = diff_nodes(&old, &new, vec![], &options);
+        let key = |k: &str| PathSegment::Key(k.to_string());
+        assert_eq!(
+            changes,
+            vec![
+                removal(vec![PathSegment::Index(0)], element(1, 1)),
+                Change {
+                    path: vec![PathSegment::Index(0), key("value")],
+                    kind: ChangeKind::Modified(2, 5),
+                },
+                addition(vec![PathSegment::Index(1)], element(4, 4)),
+            ]
+        );
+        let mut result = old.clone();
+        apply_node_in_place(&mut result, &changes).unwrap();
+        assert_eq!(result, new);
+
+        // without the key, elements are compared whole
+        let old = Node::Sequence(vec![Node::Leaf(1), element(1, 1)]);
+        let new = Node::Sequence(vec![Node::Leaf(2), element(1, 2)]);
+        let changes = diff_nodes(&old, &new, vec![], &options);
+        assert_eq!(changes[0].kind, ChangeKind::Modified(1, 2));
+        assert_eq!(changes[1].path, vec![PathSegment::Index(1), key("value")]);
+    }
+
+    #[test]
+    fn test_positional_sequences() {
+        let options = DiffOptions::new().sequence_strategy(SequenceStrategy::Positional);
+        let (old, new) = (vec![1, 2, 3, 4], vec![0, 1, 2]);
+        let changes = diff_with(&old, &new, &options);
+        let at = |i| vec![PathSegment::Index(i)];
+        assert_eq!(
+            changes,
+            vec![
+                Change {
+                    path: at(0),
+                    kind: ChangeKind::Modified(1, 0),
+                },
+                Change {
+                    path: at(1),
+                    kind: ChangeKind::Modified(2, 1),
+                },
+                Change {
+                    path: at(2),
+                    kind: ChangeKind::Modified(3, 2),
+                },
+                Change {
+                    path: at(3),
+                    kind: ChangeKind::Removed(4),
+                },
+            ]
+        );
+        assert_eq!(apply(&old, &changes), Ok(new.clone()));
+        assert_eq!(apply(&new, &diff_with(&new, &old, &options)), Ok(old));
+
+        let old = vec![vec![1, 2]];
+        let new = vec![vec![1, 3], vec![4], vec![5]];
+        let changes = diff_with(&old, &new, &options);
+        assert_eq!(
+            changes[0].path,
+            vec![PathSegment::Index(0), PathSegment::Index(1)]
+        );
+        assert_eq!(changes[2].path, at(2));
+        assert_eq!(apply(&old, &changes), Ok(new));
+    }
+
+    #[test]
+    fn test_unordered_sequences() {
+        let options = DiffOptions::new().sequence_strategy(SequenceStrategy::Unordered);
+        let tags = |tags: &[&str]| tags.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+        let old = tags(&["a", "b", "a", "c"]);
+        assert!(diff_with(&old, &tags(&["c", "a", "b", "a"]), &options).is_empty());
+
+        let new = tags(&["d", "a", "c", "d"]);
+        let changes = diff_with(&old, &new, &options);
+        let at = |i| vec![PathSegment::Index(i)];
+        let kinds = |v: &[&str], f: fn(String) -> ChangeKind<String>| {
+            v.iter().map(|t| f(t.to_string())).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            changes.iter().map(|c| c.path.clone()).collect::<Vec<_>>(),
+            vec![at(1), at(1), at(2), at(3)]
+        );
+        assert_eq!(
+            changes.into_iter().map(|c| c.kind).collect::<Vec<_>>(),
+            [
+                kinds(&["b", "a"], ChangeKind::Removed),
+                kinds(&["d", "d"], ChangeKind::Added)
+            ]
+            .concat()
+        );
+        let changes = diff_with(&old, &new, &options);
+        assert_eq!(apply(&old, &changes), Ok(tags(&["a", "c", "d", "d"])));
+    }
+
+    #[test]
+    fn test_apply_lenient() {
+        let old = vec![1, 2, 3];
+        let changes = diff(&old, &vec![1, 3, 4]);
+        let (patched, skipped) = apply_lenient(&vec![1], &changes).unwrap();
+        assert_eq!(patched, vec![1]);
+        assert_eq!(
+            skipped,
+            vec![
+                (
+                    changes[0].clone(),
+                    ApplyError::PathNotFound(vec![PathSegment::Index(1)])
+                ),
+                (
+                    changes[1].clone(),
+                    ApplyError::PathNotFound(vec![PathSegment::Index(2)])
+                ),
+            ]
+        );
+
+        let mut map = HashMap::from([("a".to_string(), vec![1])]);
+        let changes = diff(&map, &HashMap::from([("a".to_string(), vec![2])]));
+        map.insert("a".to_string(), vec![]);
+        map.insert("b".to_string(), vec![5]);
+        let changes = [diff(&map, &HashMap::new()), changes].concat();
+        let (patched, skipped) = apply_lenient(&map, &changes).unwrap();
+        assert_eq!(patched, HashMap::new());
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(apply(&map, &changes), Err(skipped[0].1.clone()));
+    }
+
+    #[test]
+    fn test_create_missing_paths() {
+        let key = |k: &str| PathSegment::Key(k.to_string());
+        let tree: Node<i32> = crate::node!({ "a": { "b": [1] } });
+        let options = ApplyOptions::new().create_missing();
+        let added = |path: Vec<PathSegment>| Change {
+            path,
+            kind: ChangeKind::Added(2),
+        };
+
+        let mut result = tree.clone();
+        let changes = [added(vec![key("a"), key("c"), key("d")])];
+        assert_eq!(apply_node_with(&mut result, &changes, &options), Ok(()));
+        assert_eq!(result, crate::node!({ "a": { "b": [1], "c": { "d": 2 } } }));
+        assert_eq!(
+            apply_node_in_place(&mut tree.clone(), &changes),
+            Err(ApplyError::PathNotFound(vec![key("a"), key("c")]))
+        );
+
+        // only maps are created, and only for additions
+        let mut result = tree.clone();
+        let changes = [added(vec![key("x"), PathSegment::Index(0)])];
+        assert_eq!(
+            apply_node_with(&mut result, &changes, &options),
+            Err(ApplyError::PathNotFound(vec![key("x")]))
+        );
+        let changes = [Change {
+            path: vec![key("x"), key("y")],
+            kind: ChangeKind::Removed(2),
+        }];
+        assert_eq!(
+            apply_node_with(&mut result, &changes, &options),
+            Err(ApplyError::PathNotFound(vec![key("x")]))
+        );
+        assert_eq!(result, tree);
+    }
+
+    #[test]
+    fn test_type_change_round_trip() {
+        let old = Node::Map(HashMap::from([("a".to_string(), Node::Leaf(1))]));
+        let new = Node::Map(HashMap::from([(
+            "a".to_string(),
+            Node::Sequence(vec![Node::Leaf(1)]),
+        )]));
+        let changes = diff_nodes(&old, &new, vec![], &DiffOptions::default());
+        assert_eq!(changes[0].kind, ChangeKind::Removed(1));
+        let mut result = old;
+        assert_eq!(
+            apply_node_with(&mut result, &changes, &ApplyOptions::new().strict()),
+            Ok(())
+        );
+        assert_eq!(result, new);
+    }
+
+    #[test]
+    fn test_ignored_paths() {
+        let mut first = HashMap::new();
+        first.insert("updated_at".to_string(), 1);
+        first.insert("name".to_string(), 1);
+        let mut second = first.clone();
+        second.insert("updated_at".to_string(), 2);
+        second.insert("name".to_string(), 2);
+        second.insert("etag".to_string(), 3);
+        let a = HashMap::from([("metadata".to_string(), first)]);
+        let b = HashMap::from([("metadata".to_string(), second)]);
+
+        let options_by_path = DiffOptions::new()
+            .ignore("metadata.updated_at".parse().unwrap())
+            .ignore("**/etag".parse().unwrap());
+        assert_eq!(
+            diff_with(&a, &b, &options_by_path),
+            vec![Change {
+                path: vec![
+                    PathSegment::Key("metadata".to_string()),
+                    PathSegment::Key("name".to_string())
+                ],
+                kind: ChangeKind::Modified(1, 2)
+            }]
+        );
+        // keys are ignored at any depth, and aren't patterns
+        let options = DiffOptions::new().ignore_keys(["updated_at", "etag", "metadata.name"]);
+        assert_eq!(
+            diff_with(&a, &b, &options),
+            diff_with(&a, &b, &options_by_path)
+        );
+        let c = HashMap::from([("etag".to_string(), HashMap::new())]);
+        assert_eq!(diff_with(&a, &c, &options).len(), 1);
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let old = HashMap::from([("a".to_string(), vec![1, 2]), ("b".to_string(), vec![3])]);
+        let new = HashMap::from([("a".to_string(), vec![1, 4]), ("b".to_string(), vec![3])]);
+        let a = vec![PathSegment::Key("a".to_string())];
+        assert_eq!(
+            diff_with(&old, &new, &DiffOptions::new().max_depth(1)),
+            vec![
+                Change {
+                    path: a.clone(),
+                    kind: ChangeKind::NodeRemoved(old["a"].to_node()),
+                },
+                Change {
+                    path: a,
+                    kind: ChangeKind::NodeAdded(new["a"].to_node()),
+                },
+            ]
+        );
+        let changes = diff_with(&old, &new, &DiffOptions::new().max_depth(0));
+        assert_eq!(changes.len(), 2);
+        assert_eq!(apply(&old, &changes), Ok(new.clone()));
+        assert_eq!(
+            diff_with(&old, &new, &DiffOptions::new().max_depth(2)),
+            diff(&old, &new)
+        );
+    }
+
+    #[test]
+    fn test_renamed_keys() {
+        let section = HashMap::from([("port".to_string(), 80)]);
+        let old = vec![HashMap::from([
+            ("server".to_string(), section.clone()),
+            ("client".to_string(), section.clone()),
+        ])];
+        let new = vec![HashMap::from([
+            ("http".to_string(), section.clone()),
+            ("client".to_string(), HashMap::new()),
+        ])];
+        let options = DiffOptions::new().detect_renames();
+        let changes = diff_with(&old, &new, &options);
+        assert_eq!(
+            changes[0],
+            Change {
+                path: vec![PathSegment::Index(0)],
+                kind: ChangeKind::Renamed {
+                    from: "server".to_string(),
+                    to: "http".to_string(),
+                },
+            }
+        );
+        assert_eq!(changes.len(), 2);
+        assert_eq!(apply_strict(&old, &changes), Ok(new.clone()));
+        assert_eq!(apply(&new, &invert_changes(&changes)), Ok(old.clone()));
+        assert_eq!(diff(&old, &new).len(), 3);
+
+        let clash = vec![HashMap::from([
+            ("server".to_string(), section.clone()),
+            ("http".to_string(), section),
+        ])];
+        assert_eq!(
+            apply_strict(&clash, &changes[..1]),
+            Err(ApplyError::Conflict(vec![
+                PathSegment::Index(0),
+                PathSegment::Key("http".to_string())
+            ]))
+        );
+        assert_eq!(
+            apply(&new, &changes[..1]),
+            Err(ApplyError::PathNotFound(vec![
+                PathSegment::Index(0),
+                PathSegment::Key("server".to_string())
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_moved_elements() {
+        let old = vec!['a', 'b', 'c', 'd'];
+        let new = vec!['d', 'a', 'b', 'c'];
+        let options = DiffOptions::new().detect_moves();
+        let changes = diff_with(&old, &new, &options);
+        assert_eq!(
+            changes,
+            vec![Change {
+                path: vec![],
+                kind: ChangeKind::Moved {
+                    from_index: 3,
+                    to_index: 0,
+                },
+            }]
+        );
+        assert_eq!(diff(&old, &new).len(), 2);
+        assert_eq!(apply(&old, &changes), Ok(new.clone()));
+        assert_eq!(apply(&new, &invert_changes(&changes)), Ok(old.clone()));
+
+        let new = vec!['b', 'x', 'a', 'd'];
+        let changes = diff_with(&old, &new, &options);
+        assert!(changes
+            .iter()
+            .any(|c| matches!(c.kind, ChangeKind::Moved { .. })));
+        assert_eq!(apply_strict(&old, &changes), Ok(new));
+
+        assert_eq!(
+            apply(&vec!['a'], &changes[..1]),
+            Err(ApplyError::PathNotFound(vec![PathSegment::Index(1)]))
+        );
+    }
+
+    #[test]
+    fn test_changes_in_key_order() {
+        let keys = ["d", "a", "c", "b", "e"];
+        let old: HashMap<String, Vec<i32>> =
+            keys.iter().map(|k| (k.to_string(), vec![1])).collect();
+        let new: HashMap<String, Vec<i32>> =
+            keys.iter().map(|k| (k.to_string(), vec![2])).collect();
+        let paths: Vec<String> = diff(&old, &new)
+            .iter()
+            .map(|c| c.path[0].to_string())
+            .collect();
+        assert_eq!(paths, ["a", "b", "c", "d", "e"]);
+    }
+
+    #[test]
+    fn test_apply_in_place() {
+        let old = HashMap::from([("a".to_string(), vec![1, 2]), ("b".to_string(), vec![3])]);
+        let new = HashMap::from([("a".to_string(), vec![2]), ("c".to_string(), vec![])]);
+        let changes = diff(&old, &new);
+        let mut target = old.clone();
+        apply_in_place(&mut target, &changes).unwrap();
+        assert_eq!(target, new);
+
+        let mut node = old.to_node();
+        apply_node_in_place(&mut node, &changes).unwrap();
+        assert_eq!(node, new.to_node());
+
+        // a failing change leaves the tree as the changes before it left it
+        let missing = Change {
+            path: vec![PathSegment::Key("d".to_string()), PathSegment::Index(0)],
+            kind: ChangeKind::Removed(1),
+        };
+        let mut node = old.to_node();
+        assert_eq!(
+            apply_node_in_place(&mut node, &[changes[0].clone(), missing]),
+            Err(ApplyError::PathNotFound(vec![PathSegment::Key(
+                "d".to_string()
+            )]))
+        );
+        assert_eq!(Ok(node), apply(&old, &changes[..1]).map(|m| m.to_node()));
+        let mut target = old.clone();
+        assert!(apply_in_place(&mut target, &changes[1..]).is_ok());
+    }
+
+    #[test]
+    fn test_apply_transaction() {
+        let v1 = vec![1, 2, 3];
+        let v2 = vec![1, 3];
+        let v3 = vec![0, 1, 3, 4];
+        let (first, second) = (diff(&v1, &v2), diff(&v2, &v3));
+        assert_eq!(apply_transaction(&v1, &[]), Ok(v1.clone()));
+        assert_eq!(apply_transaction(&v1, &[&first, &second]), Ok(v3.clone()));
+
+        // the first lists are undone along with the failing one
+        let invalid = [Change {
+            path: vec![PathSegment::Index(9)],
+            kind: ChangeKind::Removed(1),
+        }];
+        assert_eq!(
+            apply_transaction(&v1, &[&first, &second, &invalid]),
+            Err((2, ApplyError::PathNotFound(vec![PathSegment::Index(9)])))
+        );
+        assert_eq!(
+            apply_transaction(&v1, &[&first, &invalid, &second]).map_err(|(i, _)| i),
+            Err(1)
+        );
+
+        let map = HashMap::from([("a".to_string(), 1)]);
+        let nested = [Change {
+            path: vec![PathSegment::Key("b".to_string())],
+            kind: ChangeKind::NodeAdded(Node::Sequence(vec![])),
+        }];
+        assert_eq!(
+            apply_transaction(&map, &[&[], &nested]),
+            Err((
+                1,
+                ApplyError::TypeMismatch(vec![PathSegment::Key("b".to_string())])
+            ))
+        );
+    }
+
+    #[test]
+    fn test_deep_trees() {
+        // a stack far too small to recurse once per level
+        let handle = std::thread::Builder::new().stack_size(256 * 1024);
+        let run = move || {
+            let nest = |leaf| {
+                (0..3_000).fold(Node::Leaf(leaf), |node, _| {
+                    Node::Map(HashMap::from([("a".to_string(), node)]))
+                })
+            };
+            let (mut old, new) = (nest(1), nest(2));
+            let changes = diff_nodes(&old, &new, vec![], &DiffOptions::default());
+            assert_eq!(changes.len(), 1);
+            assert_eq!(changes[0].kind, ChangeKind::Modified(1, 2));
+            apply_node_in_place(&mut old, &changes).unwrap();
+            assert_eq!(old.get(&changes[0].path), Some(&Node::Leaf(2)));
+            // dropping a tree recurses too, so take them apart from the top
+            for mut node in [old, new] {
+                while let Node::Map(mut map) = node {
+                    node = map.remove("a").unwrap_or(Node::Null);
+                }
+            }
+        };
+        handle.spawn(run).unwrap().join().unwrap();
+    }
+
+    #[test]
+    fn test_truncated_diff() {
+        let old = HashMap::from([
+            ("a".to_string(), HashMap::from([("x".to_string(), vec![1])])),
+            ("b".to_string(), HashMap::from([("y".to_string(), vec![2])])),
+            ("c".to_string(), HashMap::from([("z".to_string(), vec![3])])),
+        ]);
+        let mut new = old.clone();
+        new.get_mut("a")
+            .unwrap()
+            .insert("x".to_string(), vec![1, 1]);
+        new.get_mut("b").unwrap().remove("y");
+        let changes = diff_with(&old, &new, &DiffOptions::new().truncate(1));
+        let hash = |map: &HashMap<String, HashMap<String, Vec<i32>>>, key: &str| {
+            map[key].to_node().structural_hash()
+        };
+        assert_eq!(
+            changes,
+            vec![
+                Change {
+                    path: vec![PathSegment::Key("a".to_string())],
+                    kind: ChangeKind::TruncatedSubtreeChanged {
+                        old_hash: hash(&old, "a"),
+                        new_hash: hash(&new, "a"),
+                    },
+                },
+                Change {
+                    path: vec![PathSegment::Key("b".to_string())],
+                    kind: ChangeKind::TruncatedSubtreeChanged {
+                        old_hash: hash(&old, "b"),
+                        new_hash: hash(&new, "b"),
+                    },
+                },
+            ]
+        );
+        assert_eq!(
+            apply(&old, &changes),
+            Err(ApplyError::InvalidChangeKind(changes[0].path.clone()))
+        );
+
+        let changes = diff_with(&vec![1, 2], &vec![1, 3], &DiffOptions::new().truncate(1));
+        assert_eq!(changes[0].kind, ChangeKind::Modified(2, 3));
+    }
+
+    #[test]
+    fn test_compare_leaves() {
+        let options = DiffOptions::new().compare_leaves(|a: &i32, b: &i32| (a - b).abs() <= 1);
+        let old = HashMap::from([("a".to_string(), 10), ("b".to_string(), 20)]);
+        let new = HashMap::from([("a".to_string(), 11), ("b".to_string(), 25)]);
+        assert_eq!(
+            diff_with(&old, &new, &options),
+            vec![Change {
+                path: vec![PathSegment::Key("b".to_string())],
+                kind: ChangeKind::Modified(20, 25),
+            }]
+        );
+        assert!(diff_with(&vec![1, 5], &vec![2, 5], &options).is_empty());
+        assert_eq!(options, options.clone());
+        assert_ne!(
+            options,
+            options.clone().compare_leaves(|a: &i32, b: &i32| a == b)
+        );
+    }
+
+    #[test]
+    fn test_normalize() {
+        let lowercase_keys = |_: &[PathSegment], node: &mut Node<i32>| {
+            if let Node::Map(map) = node {
+                *map = std::mem::take(map)
+                    .into_iter()
+                    .map(|(k, v)| (k.to_lowercase(), v))
+                    .collect();
+            }
+        };
+        let options = DiffOptions::new().normalize(lowercase_keys);
+        let old = HashMap::from([("A".to_string(), HashMap::from([("B".to_string(), 1)]))]);
+        let new = HashMap::from([("a".to_string(), HashMap::from([("b".to_string(), 2)]))]);
+        assert_eq!(
+            diff_with(&old, &new, &options),
+            vec![Change {
+                path: vec![
+                    PathSegment::Key("a".to_string()),
+                    PathSegment::Key("b".to_string())
+                ],
+                kind: ChangeKind::Modified(1, 2),
+            }]
+        );
+        assert_eq!(old["A"]["B"], 1);
+    }
 }
@@ -0,0 +1,200 @@
+use crate::recursive::diffable::Diffable;
+use crate::recursive::types::{ApplyError, Node, PathSegment, Primitive};
+use rmpv::Value;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+/// A leaf of a MessagePack value: an `rmpv::Value` other than an array
+/// or a map with string keys.
+///
+/// Maps with other keys, or with the same key twice, are kept whole as leaves.
+/// Floats are compared by their bits, so `NaN` equals itself.
+#[derive(Clone, Debug)]
+pub struct MsgpackLeaf(pub Value);
+
+impl PartialEq for MsgpackLeaf {
+    fn eq(&self, other: &Self) -> bool {
+        same(&self.0, &other.0)
+    }
+}
+
+impl Eq for MsgpackLeaf {}
+
+impl Hash for MsgpackLeaf {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        hash_value(&self.0, state);
+    }
+}
+
+impl Primitive for MsgpackLeaf {}
+
+fn same(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::F32(a), Value::F32(b)) => a.to_bits() == b.to_bits(),
+        (Value::F64(a), Value::F64(b)) => a.to_bits() == b.to_bits(),
+        (Value::Array(a), Value::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same(a, b))
+        }
+        (Value::Map(a), Value::Map(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .zip(b)
+                    .all(|((ka, va), (kb, vb))| same(ka, kb) && same(va, vb))
+        }
+        (a, b) => a == b,
+    }
+}
+
+/// Hashes a value consistently with [`same`].
+fn hash_value<H: Hasher>(value: &Value, state: &mut H) {
+    std::mem::discriminant(value).hash(state);
+    match value {
+        Value::Nil => {}
+        Value::Boolean(v) => v.hash(state),
+        Value::Integer(v) => (v.as_i64(), v.as_u64()).hash(state),
+        Value::F32(v) => v.to_bits().hash(state),
+        Value::F64(v) => v.to_bits().hash(state),
+        Value::String(v) => v.as_bytes().hash(state),
+        Value::Binary(v) => v.hash(state),
+        Value::Array(values) => {
+            values.len().hash(state);
+            values.iter().for_each(|v| hash_value(v, state));
+        }
+        Value::Map(entries) => {
+            entries.len().hash(state);
+            for (k, v) in entries {
+                hash_value(k, state);
+                hash_value(v, state);
+            }
+        }
+        Value::Ext(tag, data) => (tag, data).hash(state),
+    }
+}
+
+/// The entries of a map keyed by distinct strings.
+fn string_keyed(entries: &[(Value, Value)]) -> Option<HashMap<String, Node<MsgpackLeaf>>> {
+    let mut map = HashMap::new();
+    for (key, value) in entries {
+        if map
+            .insert(key.as_str()?.to_string(), value.to_node())
+            .is_some()
+        {
+            return None;
+        }
+    }
+    Some(map)
+}
+
+/// MessagePack values: maps with string keys become maps, arrays sequences,
+/// `nil` becomes [`Node::Null`], and the other values [`MsgpackLeaf`]s.
+/// Map entries converted back from a [`Node`] are sorted by key.
+/// ```
+/// use diffkit::recursive::{apply, diff};
+/// use rmpv::Value;
+///
+/// let old = Value::Map(vec![("id".into(), 1.into()), ("tags".into(), Value::Array(vec![]))]);
+/// let new = Value::Map(vec![("id".into(), 1.into()), ("tags".into(), Value::Array(vec!["a".into()]))]);
+/// let changes = diff(&old, &new);
+/// assert_eq!(changes.len(), 1);
+/// assert_eq!(apply(&old, &changes), Ok(new));
+/// ```
+impl Diffable for Value {
+    type P = MsgpackLeaf;
+    fn to_node(&self) -> Node<Self::P> {
+        match self {
+            Value::Array(array) => Node::Sequence(array.iter().map(Diffable::to_node).collect()),
+            Value::Map(entries) => match string_keyed(entries) {
+                Some(map) => Node::Map(map),
+                None => Node::Leaf(MsgpackLeaf(self.clone())),
+            },
+            Value::Nil => Node::Null,
+            leaf => Node::Leaf(MsgpackLeaf(leaf.clone())),
+        }
+    }
+
+    fn from_node(node: Node<Self::P>) -> Result<Self, ApplyError> {
+        match node {
+            Node::Map(map) => {
+                let mut entries = map
+                    .into_iter()
+                    .map(|(k, v)| match Self::from_node(v) {
+                        Ok(v) => Ok((k, v)),
+                        Err(e) => Err(e.within(PathSegment::Key(k))),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+                Ok(Value::Map(
+                    entries.into_iter().map(|(k, v)| (k.into(), v)).collect(),
+                ))
+            }
+            Node::Sequence(sequence) | Node::Tuple(sequence) | Node::Set(sequence) => sequence
+                .into_iter()
+                .enumerate()
+                .map(|(i, v)| Self::from_node(v).map_err(|e| e.within(PathSegment::Index(i))))
+                .collect::<Result<_, _>>()
+                .map(Value::Array),
+            Node::Leaf(leaf) => Ok(leaf.0),
+            Node::Null => Ok(Value::Nil),
+            Node::Variant(tag, content) => {
+                Self::from_node(Node::Map(HashMap::from([(tag, *content)])))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recursive::{apply, diff, ChangeKind};
+
+    fn map(entries: Vec<(&str, Value)>) -> Value {
+        Value::Map(entries.into_iter().map(|(k, v)| (k.into(), v)).collect())
+    }
+
+    #[test]
+    fn test_encoded_values() {
+        let old = map(vec![
+            ("name", "a".into()),
+            ("ratio", Value::F64(f64::NAN)),
+            ("blob", Value::Binary(vec![1, 2])),
+        ]);
+        let new = map(vec![
+            ("name", "a".into()),
+            ("ratio", Value::F64(f64::NAN)),
+            ("blob", Value::Binary(vec![1, 3])),
+        ]);
+        let mut bytes = vec![];
+        rmpv::encode::write_value(&mut bytes, &old).unwrap();
+        let decoded = rmpv::decode::read_value(&mut bytes.as_slice()).unwrap();
+
+        let changes = diff(&decoded, &new);
+        assert_eq!(
+            changes[0].kind,
+            ChangeKind::Modified(
+                MsgpackLeaf(Value::Binary(vec![1, 2])),
+                MsgpackLeaf(Value::Binary(vec![1, 3]))
+            )
+        );
+        let patched = apply(&decoded, &changes).unwrap();
+        assert!(same(
+            &patched,
+            &map(vec![
+                ("blob", Value::Binary(vec![1, 3])),
+                ("name", "a".into()),
+                ("ratio", Value::F64(f64::NAN)),
+            ])
+        ));
+    }
+
+    #[test]
+    fn test_maps_without_string_keys_are_leaves() {
+        let old = Value::Map(vec![(1.into(), "a".into())]);
+        let new = Value::Map(vec![(1.into(), "b".into())]);
+        assert_eq!(
+            diff(&old, &new)[0].kind,
+            ChangeKind::Modified(MsgpackLeaf(old.clone()), MsgpackLeaf(new.clone()))
+        );
+        let duplicated = map(vec![("a", 1.into()), ("a", 2.into())]);
+        assert!(matches!(duplicated.to_node(), Node::Leaf(_)));
+    }
+}
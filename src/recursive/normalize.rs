@@ -0,0 +1,233 @@
+use crate::recursive::types::{Change, ChangeKind, PathSegment, Primitive};
+use std::collections::{BTreeMap, HashMap};
+
+/// Cleans up a list of changes assembled from several sources, e.g. before storing it.
+///
+/// - changes without effect are dropped: modifications, renames and moves
+///   to the same value, key or index
+/// - changes to the same map key, or to the root, are deduplicated: the last one wins.
+///   A removal followed by an addition replaces the value, so the addition
+///   keeps the removal. Renames, moves, and the changes to sequence elements
+///   and set members are kept, since each of them counts
+/// - changes to different map keys are ordered by key, like the changes of
+///   [`diff`](crate::recursive::diff). The changes to the same key or sequence keep
+///   their order, since the later ones may depend on the earlier ones
+///
+/// Deduplicating expects the changes to a value to describe it from the same version,
+/// like two sources reporting the same modification; [`compose`](crate::recursive::compose)
+/// squashes changes made one after the other instead.
+/// ```
+/// use diffkit::recursive::{normalize_changes, Change, ChangeKind, PathSegment};
+///
+/// let change = |key: &str, kind| Change { path: vec![PathSegment::Key(key.to_string())], kind };
+/// let changes = vec![
+///     change("replicas", ChangeKind::Modified(3, 4)),
+///     change("image", ChangeKind::Modified(1, 1)),
+///     change("replicas", ChangeKind::Modified(3, 5)),
+///     change("port", ChangeKind::Added(80)),
+/// ];
+/// assert_eq!(
+///     normalize_changes(&changes),
+///     vec![change("port", ChangeKind::Added(80)), change("replicas", ChangeKind::Modified(3, 5))]
+/// );
+/// ```
+pub fn normalize_changes<P: Primitive>(changes: &[Change<P>]) -> Vec<Change<P>> {
+    // the index of the last change replacing each value, and of the last one
+    // that isn't an addition, which alone supersedes a removal
+    let mut last: HashMap<&[PathSegment], (usize, Option<usize>)> = HashMap::new();
+    for (i, change) in changes.iter().enumerate() {
+        if replaces_value(change) {
+            let (last, last_non_addition) = last.entry(&change.path).or_default();
+            *last = i;
+            if !is_addition(change) {
+                *last_non_addition = Some(i);
+            }
+        }
+    }
+    let superseded = |i: usize, change: &Change<P>| {
+        if !replaces_value(change) {
+            return false;
+        }
+        let (last, last_non_addition) = last[&change.path[..]];
+        if is_removal(change) {
+            last_non_addition > Some(i)
+        } else {
+            last > i
+        }
+    };
+    let changes: Vec<&Change<P>> = changes
+        .iter()
+        .enumerate()
+        .filter(|&(i, change)| !is_noop(change) && !superseded(i, change))
+        .map(|(_, change)| change)
+        .collect();
+    let mut result = Vec::with_capacity(changes.len());
+    order_by_key(changes, 0, &mut result);
+    result
+}
+
+/// Whether a change leaves the value it's made to as it is.
+fn is_noop<P: Primitive>(change: &Change<P>) -> bool {
+    match &change.kind {
+        ChangeKind::Modified(old, new) => old == new,
+        ChangeKind::Renamed { from, to } => from == to,
+        ChangeKind::Moved {
+            from_index,
+            to_index,
+        } => from_index == to_index,
+        ChangeKind::TruncatedSubtreeChanged { old_hash, new_hash } => old_hash == new_hash,
+        ChangeKind::TruncatedLeafChanged { old, new } => old == new,
+        _ => false,
+    }
+}
+
+/// Whether a change gives a new version of the value at a map key or at the root,
+/// superseding the earlier changes to it.
+fn replaces_value<P: Primitive>(change: &Change<P>) -> bool {
+    let at_key = matches!(change.path.last(), None | Some(PathSegment::Key(_)));
+    at_key
+        && !matches!(
+            change.kind,
+            ChangeKind::Renamed { .. } | ChangeKind::Moved { .. }
+        )
+}
+
+fn is_addition<P: Primitive>(change: &Change<P>) -> bool {
+    matches!(change.kind, ChangeKind::Added(_) | ChangeKind::NodeAdded(_))
+}
+
+fn is_removal<P: Primitive>(change: &Change<P>) -> bool {
+    matches!(
+        change.kind,
+        ChangeKind::Removed(_) | ChangeKind::NodeRemoved(_)
+    )
+}
+
+/// Appends `changes` to `result`, ordered by the map keys found at `depth`
+/// when they are all made below one.
+fn order_by_key<P: Primitive>(changes: Vec<&Change<P>>, depth: usize, result: &mut Vec<Change<P>>) {
+    let mut groups: BTreeMap<&str, Vec<&Change<P>>> = BTreeMap::new();
+    for change in &changes {
+        match change.path.get(depth) {
+            Some(PathSegment::Key(key)) => groups.entry(key).or_default().push(change),
+            _ => {
+                result.extend(changes.into_iter().cloned());
+                return;
+            }
+        }
+    }
+    for group in groups.into_values() {
+        order_by_key(group, depth + 1, result);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recursive::{
+        apply, apply_node_with, diff, diff_nodes, invert_changes, ApplyOptions, DiffOptions, Node,
+    };
+    use std::collections::BTreeMap;
+
+    fn key(k: &str) -> PathSegment {
+        PathSegment::Key(k.to_string())
+    }
+
+    fn change(path: Vec<PathSegment>, kind: ChangeKind<i32>) -> Change<i32> {
+        Change { path, kind }
+    }
+
+    #[test]
+    fn test_normalize_changes() {
+        let changes = vec![
+            change(vec![key("b"), key("y")], ChangeKind::Modified(1, 2)),
+            change(vec![key("b"), PathSegment::Index(1)], ChangeKind::Added(7)),
+            change(
+                vec![key("a")],
+                ChangeKind::Renamed {
+                    from: "x".to_string(),
+                    to: "x".to_string(),
+                },
+            ),
+            change(vec![key("b"), key("y")], ChangeKind::Modified(1, 3)),
+            change(vec![key("b"), PathSegment::Index(0)], ChangeKind::Added(7)),
+            change(vec![key("a"), key("z")], ChangeKind::Modified(1, 1)),
+            change(
+                vec![key("a"), key("z")],
+                ChangeKind::NodeRemoved(Node::Null),
+            ),
+            change(
+                vec![key("c")],
+                ChangeKind::Moved {
+                    from_index: 0,
+                    to_index: 1,
+                },
+            ),
+            change(
+                vec![key("c"), PathSegment::Index(0)],
+                ChangeKind::Removed(1),
+            ),
+        ];
+        assert_eq!(
+            normalize_changes(&changes),
+            vec![
+                changes[6].clone(),
+                // a mix of keys and indices keeps its order
+                changes[1].clone(),
+                changes[3].clone(),
+                changes[4].clone(),
+                changes[7].clone(),
+                changes[8].clone(),
+            ]
+        );
+        assert_eq!(normalize_changes(&changes[4..5]), &changes[4..5]);
+        assert_eq!(normalize_changes::<i32>(&[]), vec![]);
+    }
+
+    #[test]
+    fn test_normalize_diff() {
+        let old = BTreeMap::from([("a", 1), ("b", 2), ("c", 3)].map(|(k, v)| (k.to_string(), v)));
+        let new = BTreeMap::from([("a", 0), ("c", 4), ("d", 5)].map(|(k, v)| (k.to_string(), v)));
+        let changes = diff(&old, &new);
+        let mut shuffled = changes.clone();
+        shuffled.reverse();
+        shuffled.push(shuffled[0].clone());
+        assert_eq!(normalize_changes(&shuffled), changes);
+        assert_eq!(apply(&old, &normalize_changes(&shuffled)), Ok(new));
+    }
+
+    #[test]
+    fn test_normalize_keeps_replacements() {
+        let old: Node<i32> = crate::node!({ "a": 1, "b": [2], "c": 3 });
+        let new: Node<i32> = crate::node!({ "a": [1], "b": 2, "c": 4 });
+        let changes = diff_nodes(&old, &new, vec![], &DiffOptions::default());
+        let normalized = normalize_changes(&changes);
+        assert_eq!(normalized, changes);
+        let strict = ApplyOptions::new().strict();
+        let apply_strict = |node: &Node<i32>, changes: &[Change<i32>]| {
+            let mut node = node.clone();
+            apply_node_with(&mut node, changes, &strict).map(|_| node)
+        };
+        assert_eq!(
+            apply_strict(&old, &normalized),
+            apply_strict(&old, &changes)
+        );
+        assert_eq!(apply_strict(&old, &normalized), Ok(new.clone()));
+        assert_eq!(apply_strict(&new, &invert_changes(&normalized)), Ok(old));
+
+        // a later change still supersedes the pair
+        let mut twice = changes.clone();
+        twice.extend(changes.clone());
+        assert_eq!(normalize_changes(&twice), changes);
+        let modified = change(vec![key("a")], ChangeKind::Modified(1, 5));
+        twice.push(modified.clone());
+        assert!(normalize_changes(&twice).contains(&modified));
+        assert_eq!(
+            normalize_changes(&twice)
+                .iter()
+                .filter(|c| c.path == [key("a")])
+                .count(),
+            1
+        );
+    }
+}
@@ -0,0 +1,302 @@
+use crate::recursive::types::{Change, PathSegment, Primitive};
+use std::fmt;
+use std::str::FromStr;
+
+/// A glob-like pattern over [`PathSegment`]s, such as `metadata.updated_at`,
+/// `**/etag` or `spec.containers[*].image`.
+///
+/// Segments are separated by `.` or `/`, and a leading `/` is ignored.
+/// - a name matches the map key with that name; a number also matches that index
+/// - `[n]` matches index `n`, and `[*]` any index
+/// - `*` matches any single key or index
+/// - `**` matches any number of segments, including none
+///
+/// ```
+/// use diffkit::recursive::{PathPattern, PathSegment};
+///
+/// let pattern: PathPattern = "spec.containers[*].image".parse().unwrap();
+/// let path = vec![
+///     PathSegment::Key("spec".to_string()),
+///     PathSegment::Key("containers".to_string()),
+///     PathSegment::Index(2),
+///     PathSegment::Key("image".to_string()),
+/// ];
+/// assert!(pattern.matches(&path));
+/// assert!("**/image".parse::<PathPattern>().unwrap().matches(&path));
+/// ```
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct PathPattern {
+    segments: Vec<PatternSegment>,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+enum PatternSegment {
+    Key(String),
+    Index(usize),
+    AnyIndex,
+    Any,
+    AnyDepth,
+}
+
+impl PathPattern {
+    /// Whether `path` matches the whole pattern.
+    pub fn matches(&self, path: &[PathSegment]) -> bool {
+        matches_from(&self.segments, path)
+    }
+
+    /// Whether `path` leads to a value matching the pattern,
+    /// or to a value inside one.
+    pub fn matches_prefix_of(&self, path: &[PathSegment]) -> bool {
+        (0..=path.len()).any(|len| self.matches(&path[..len]))
+    }
+
+    /// Whether a value matching the pattern may be found at `path`
+    /// or inside the value there, whatever it holds.
+    pub fn matches_within(&self, path: &[PathSegment]) -> bool {
+        matches_start(&self.segments, path)
+    }
+}
+
+fn matches_from(pattern: &[PatternSegment], path: &[PathSegment]) -> bool {
+    let Some((first, rest)) = pattern.split_first() else {
+        return path.is_empty();
+    };
+    if *first == PatternSegment::AnyDepth {
+        return (0..=path.len()).any(|skip| matches_from(rest, &path[skip..]));
+    }
+    let Some((segment, path_rest)) = path.split_first() else {
+        return false;
+    };
+    matches_segment(first, segment) && matches_from(rest, path_rest)
+}
+
+fn matches_segment(pattern: &PatternSegment, segment: &PathSegment) -> bool {
+    match (pattern, segment) {
+        (PatternSegment::Key(k), PathSegment::Key(key)) => k == key,
+        (PatternSegment::Key(k), PathSegment::Index(index)) => *k == index.to_string(),
+        (PatternSegment::Index(i), PathSegment::Index(index)) => i == index,
+        (PatternSegment::AnyIndex, PathSegment::Index(_)) => true,
+        (PatternSegment::Any, _) => true,
+        _ => false,
+    }
+}
+
+/// Whether the segments of `pattern` matching `path` can be followed by the others.
+fn matches_start(pattern: &[PatternSegment], path: &[PathSegment]) -> bool {
+    let Some((segment, path_rest)) = path.split_first() else {
+        return true;
+    };
+    match pattern.split_first() {
+        None => false,
+        Some((PatternSegment::AnyDepth, _)) => true,
+        Some((first, rest)) => matches_segment(first, segment) && matches_start(rest, path_rest),
+    }
+}
+
+/// The changes made exactly at `path`.
+///
+/// Paths are compared as recorded in the changes: inside a sequence,
+/// an index refers to the sequence as left by the changes before it.
+pub fn changes_at<'a, P: Primitive>(
+    changes: &'a [Change<P>],
+    path: &[PathSegment],
+) -> Vec<&'a Change<P>> {
+    changes.iter().filter(|c| c.path == path).collect()
+}
+
+/// The changes made at `prefix` or anywhere below it,
+/// answering "what changed under this subtree?".
+/// ```
+/// use std::collections::HashMap;
+/// use diffkit::recursive::{changes_under, diff, PathSegment};
+///
+/// let old = HashMap::from([
+///     ("spec".to_string(), HashMap::from([("replicas".to_string(), 1)])),
+///     ("status".to_string(), HashMap::from([("ready".to_string(), 0)])),
+/// ]);
+/// let new = HashMap::from([
+///     ("spec".to_string(), HashMap::from([("replicas".to_string(), 2)])),
+///     ("status".to_string(), HashMap::from([("ready".to_string(), 2)])),
+/// ]);
+/// let changes = diff(&old, &new);
+/// let spec = changes_under(&changes, &[PathSegment::Key("spec".to_string())]);
+/// assert_eq!(spec.len(), 1);
+/// ```
+pub fn changes_under<'a, P: Primitive>(
+    changes: &'a [Change<P>],
+    prefix: &[PathSegment],
+) -> Vec<&'a Change<P>> {
+    changes
+        .iter()
+        .filter(|c| c.path.starts_with(prefix))
+        .collect()
+}
+
+/// The changes made at a path matching `pattern`, or below one.
+/// ```
+/// use diffkit::recursive::{diff, select, PathPattern};
+///
+/// let old = vec![vec![1, 2], vec![3]];
+/// let new = vec![vec![1, 5], vec![3, 4]];
+/// let changes = diff(&old, &new);
+/// let pattern: PathPattern = "[*][1]".parse().unwrap();
+/// assert_eq!(select(&changes, &pattern).len(), 2);
+/// ```
+pub fn select<'a, P: Primitive>(
+    changes: &'a [Change<P>],
+    pattern: &PathPattern,
+) -> Vec<&'a Change<P>> {
+    changes
+        .iter()
+        .filter(|c| pattern.matches_prefix_of(&c.path))
+        .collect()
+}
+
+/// Error returned when parsing an invalid [`PathPattern`].
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct PatternError {
+    /// The pattern being parsed
+    pub pattern: String,
+    /// What is wrong with it
+    pub message: String,
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid path pattern '{}': {}",
+            self.pattern, self.message
+        )
+    }
+}
+
+impl std::error::Error for PatternError {}
+
+impl FromStr for PathPattern {
+    type Err = PatternError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = |message: &str| PatternError {
+            pattern: s.to_string(),
+            message: message.to_string(),
+        };
+        let mut segments = vec![];
+        let trimmed = s.strip_prefix('/').unwrap_or(s);
+        if trimmed.is_empty() {
+            return Ok(PathPattern { segments });
+        }
+        for part in trimmed.split(['.', '/']) {
+            // a part is a name followed by any number of [..] suffixes
+            let (name, mut brackets) = part.split_at(part.find('[').unwrap_or(part.len()));
+            match name {
+                "" if brackets.is_empty() => return Err(error("empty segment")),
+                "" => {}
+                "*" => segments.push(PatternSegment::Any),
+                "**" => segments.push(PatternSegment::AnyDepth),
+                name => segments.push(PatternSegment::Key(name.to_string())),
+            }
+            while !brackets.is_empty() {
+                let (inner, rest) = brackets[1..]
+                    .split_once(']')
+                    .ok_or_else(|| error("unclosed '['"))?;
+                if !rest.is_empty() && !rest.starts_with('[') {
+                    return Err(error("unexpected text after ']'"));
+                }
+                segments.push(match inner {
+                    "*" => PatternSegment::AnyIndex,
+                    index => {
+                        PatternSegment::Index(index.parse().map_err(|_| error("invalid index"))?)
+                    }
+                });
+                brackets = rest;
+            }
+        }
+        Ok(PathPattern { segments })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(segments: &[&str]) -> Vec<PathSegment> {
+        segments
+            .iter()
+            .map(|s| match s.parse() {
+                Ok(index) => PathSegment::Index(index),
+                Err(_) => PathSegment::Key(s.to_string()),
+            })
+            .collect()
+    }
+
+    fn pattern(s: &str) -> PathPattern {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_matches() {
+        assert!(pattern("metadata.updated_at").matches(&path(&["metadata", "updated_at"])));
+        assert!(pattern("/metadata/updated_at").matches(&path(&["metadata", "updated_at"])));
+        assert!(!pattern("metadata").matches(&path(&["metadata", "updated_at"])));
+        assert!(pattern("**/etag").matches(&path(&["etag"])));
+        assert!(pattern("**/etag").matches(&path(&["a", "0", "etag"])));
+        assert!(!pattern("**/etag").matches(&path(&["etag", "a"])));
+        assert!(pattern("a.*.c").matches(&path(&["a", "b", "c"])));
+        assert!(pattern("list[1][*]").matches(&path(&["list", "1", "5"])));
+        assert!(pattern("list.1").matches(&path(&["list", "1"])));
+        assert!(!pattern("list[*]").matches(&path(&["list", "key"])));
+        assert!(pattern("").matches(&[]));
+    }
+
+    #[test]
+    fn test_matches_prefix_of() {
+        assert!(pattern("status").matches_prefix_of(&path(&["status", "phase"])));
+        assert!(!pattern("status.phase").matches_prefix_of(&path(&["status"])));
+        assert!(pattern("status.phase").matches_within(&path(&["status"])));
+        assert!(pattern("status.phase").matches_within(&path(&["status", "phase"])));
+        assert!(!pattern("status.phase").matches_within(&path(&["status", "phase", "x"])));
+        assert!(!pattern("status.phase").matches_within(&path(&["spec"])));
+        assert!(pattern("a.**.c").matches_within(&path(&["a", "b", "x"])));
+    }
+
+    #[test]
+    fn test_invalid_patterns() {
+        assert_eq!(
+            "a..b".parse::<PathPattern>().unwrap_err().to_string(),
+            "invalid path pattern 'a..b': empty segment"
+        );
+        assert!("a[1".parse::<PathPattern>().is_err());
+        assert!("a[x]".parse::<PathPattern>().is_err());
+        assert!("a[1]b".parse::<PathPattern>().is_err());
+    }
+
+    #[test]
+    fn test_change_queries() {
+        use crate::recursive::ChangeKind;
+
+        let change = |segments: &[&str]| Change {
+            path: path(segments),
+            kind: ChangeKind::Added(1),
+        };
+        let changes = vec![
+            change(&["spec", "containers", "0", "image"]),
+            change(&["spec", "containers", "1", "image"]),
+            change(&["spec", "containers", "1", "name"]),
+            change(&["spec"]),
+            change(&["status"]),
+        ];
+
+        assert_eq!(changes_at(&changes, &path(&["spec"])), vec![&changes[3]]);
+        assert_eq!(changes_under(&changes, &path(&["spec"])).len(), 4);
+        assert_eq!(
+            changes_under(&changes, &path(&["spec", "containers", "1"])),
+            vec![&changes[1], &changes[2]]
+        );
+        assert_eq!(
+            select(&changes, &pattern("spec.containers[*].image")),
+            vec![&changes[0], &changes[1]]
+        );
+        assert_eq!(select(&changes, &pattern("status")), vec![&changes[4]]);
+    }
+}
@@ -0,0 +1,244 @@
+use crate::recursive::path::PathPattern;
+use crate::recursive::types::{PathSegment, SequenceStrategy};
+use std::fmt;
+use std::str::FromStr;
+
+/// How the differ handles the values at some paths, set by [`DiffOptions::policy`].
+///
+/// [`DiffOptions::policy`]: crate::recursive::DiffOptions::policy
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum Policy {
+    /// Skips the values, like [`DiffOptions::ignore`](crate::recursive::DiffOptions::ignore).
+    Ignore,
+    /// Compares the values whole, and replaces them if they differ,
+    /// like the values at [`DiffOptions::max_depth`](crate::recursive::DiffOptions::max_depth).
+    Whole,
+    /// Pairs the elements of the sequences following a strategy
+    /// instead of [`DiffOptions::sequence_strategy`](crate::recursive::DiffOptions::sequence_strategy).
+    Sequences(SequenceStrategy),
+}
+
+/// Policies for the subtrees of a document, e.g. Kubernetes-style manifests where
+/// `spec.tags` is a set, `spec.containers` are keyed by `name` and `status` is ignored.
+///
+/// The last policy whose pattern matches a path applies there;
+/// the other paths follow the rest of the [`DiffOptions`](crate::recursive::DiffOptions).
+/// ```
+/// use std::collections::HashMap;
+/// use diffkit::recursive::{diff_with, DiffOptions, Policies};
+///
+/// let policies: Policies = "
+///     status.* = ignore
+///     spec.tags = unordered
+///     spec.containers = keyed name
+/// "
+/// .parse()
+/// .unwrap();
+///
+/// let document = |tags: [i32; 2], ready: i32| {
+///     HashMap::from([
+///         ("spec".to_string(), HashMap::from([("tags".to_string(), tags.to_vec())])),
+///         ("status".to_string(), HashMap::from([("ready".to_string(), vec![ready])])),
+///     ])
+/// };
+/// let options = DiffOptions::new().policies(policies);
+/// assert!(diff_with(&document([1, 2], 0), &document([2, 1], 1), &options).is_empty());
+/// assert_eq!(diff_with(&document([1, 2], 0), &document([2, 3], 1), &options).len(), 2);
+/// ```
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct Policies(pub Vec<(PathPattern, Policy)>);
+
+impl Policies {
+    /// The policy applying at `path`, if any.
+    pub fn get(&self, path: &[PathSegment]) -> Option<&Policy> {
+        self.0
+            .iter()
+            .rev()
+            .find(|(pattern, _)| pattern.matches(path))
+            .map(|(_, policy)| policy)
+    }
+}
+
+/// Parses `pattern = policy` lines, skipping blank lines and `#` comments.
+/// Patterns are [`PathPattern`]s, and policies one of:
+/// - `ignore` and `whole`
+/// - `myers`, `positional` and `unordered`, the [`SequenceStrategy`] of that name
+/// - `keyed` followed by a key, or by several keys separated by commas
+impl FromStr for Policies {
+    type Err = ParsePolicyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut policies = vec![];
+        for (i, line) in s.lines().enumerate() {
+            let error = |message: &str| ParsePolicyError {
+                line: i + 1,
+                message: message.to_string(),
+            };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (pattern, policy) = line.split_once('=').ok_or_else(|| error("missing '='"))?;
+            let pattern = pattern
+                .trim()
+                .parse()
+                .map_err(|e: crate::recursive::PatternError| error(&e.message))?;
+            let (name, keys) = policy.trim().split_once(' ').unwrap_or((policy.trim(), ""));
+            let keys: Vec<String> = keys
+                .split(',')
+                .map(|key| key.trim().to_string())
+                .filter(|key| !key.is_empty())
+                .collect();
+            let policy = match (name, &keys[..]) {
+                ("ignore", []) => Policy::Ignore,
+                ("whole", []) => Policy::Whole,
+                ("myers", []) => Policy::Sequences(SequenceStrategy::Myers),
+                ("positional", []) => Policy::Sequences(SequenceStrategy::Positional),
+                ("unordered", []) => Policy::Sequences(SequenceStrategy::Unordered),
+                ("keyed", []) => return Err(error("missing key")),
+                ("keyed", [key]) => Policy::Sequences(SequenceStrategy::Keyed(key.clone())),
+                ("keyed", _) => Policy::Sequences(SequenceStrategy::KeyedBy(keys)),
+                _ => return Err(error("unknown policy")),
+            };
+            policies.push((pattern, policy));
+        }
+        Ok(Policies(policies))
+    }
+}
+
+/// Error returned when parsing invalid [`Policies`].
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ParsePolicyError {
+    /// The number of the line, starting at 1
+    pub line: usize,
+    /// What is wrong with it
+    pub message: String,
+}
+
+impl fmt::Display for ParsePolicyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid policy line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParsePolicyError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node;
+    use crate::recursive::{diff_nodes, ChangeKind, DiffOptions, Node};
+
+    fn key(k: &str) -> PathSegment {
+        PathSegment::Key(k.to_string())
+    }
+
+    #[test]
+    fn test_parse() {
+        let policies: Policies = "status = ignore\n\n# comment\n  spec.ports = keyed port, protocol\nspec.ports[0] = whole\n"
+            .parse()
+            .unwrap();
+        assert_eq!(policies.0.len(), 3);
+        assert_eq!(policies.get(&[key("status")]), Some(&Policy::Ignore));
+        assert_eq!(
+            policies.get(&[key("spec"), key("ports")]),
+            Some(&Policy::Sequences(SequenceStrategy::KeyedBy(vec![
+                "port".to_string(),
+                "protocol".to_string()
+            ])))
+        );
+        assert_eq!(
+            policies.get(&[key("spec"), key("ports"), PathSegment::Index(0)]),
+            Some(&Policy::Whole)
+        );
+        assert_eq!(policies.get(&[key("spec")]), None);
+
+        let error = |line, message: &str| ParsePolicyError {
+            line,
+            message: message.to_string(),
+        };
+        assert_eq!("a".parse::<Policies>(), Err(error(1, "missing '='")));
+        assert_eq!(
+            "\na..b = ignore".parse::<Policies>(),
+            Err(error(2, "empty segment"))
+        );
+        assert_eq!(
+            "a = keyed".parse::<Policies>(),
+            Err(error(1, "missing key"))
+        );
+        assert_eq!(
+            "a = ignore x".parse::<Policies>(),
+            Err(error(1, "unknown policy"))
+        );
+        assert_eq!(
+            error(3, "unknown policy").to_string(),
+            "invalid policy line 3: unknown policy"
+        );
+    }
+
+    #[test]
+    fn test_diff_with_policies() {
+        let old: Node<i32> = node!({
+            "spec": {
+                "containers": [{ "name": 1, "image": 1 }, { "name": 2, "image": 2 }],
+                "tags": [1, 2],
+                "labels": { "x": 1, "y": 1 }
+            },
+            "status": { "ready": 0 }
+        });
+        let new: Node<i32> = node!({
+            "spec": {
+                "containers": [
+                    { "name": 3, "image": 0 },
+                    { "name": 1, "image": 5 },
+                    { "name": 2, "image": 2 }
+                ],
+                "tags": [2, 1],
+                "labels": { "x": 2, "y": 1 }
+            },
+            "status": { "ready": 1 }
+        });
+        let policies: Policies = "
+            status.* = ignore
+            spec.tags = unordered
+            spec.containers = keyed name
+            spec.labels = whole
+        "
+        .parse()
+        .unwrap();
+        let changes = diff_nodes(&old, &new, vec![], &DiffOptions::new().policies(policies));
+        let containers = [key("spec"), key("containers")];
+        let at = |path: &[PathSegment], segment| [path, &[segment]].concat();
+        assert_eq!(
+            changes.iter().map(|c| c.path.clone()).collect::<Vec<_>>(),
+            [
+                at(&containers, PathSegment::Index(0)),
+                at(&at(&containers, PathSegment::Index(1)), key("image")),
+                vec![key("spec"), key("labels")],
+                vec![key("spec"), key("labels")],
+            ]
+        );
+        assert_eq!(changes[1].kind, ChangeKind::Modified(1, 5));
+
+        // the last matching policy applies
+        let options = DiffOptions::new()
+            .sequence_strategy(SequenceStrategy::Unordered)
+            .policy("spec".parse().unwrap(), Policy::Whole)
+            .policy("spec".parse().unwrap(), Policy::Ignore)
+            .policy("**/ready".parse().unwrap(), Policy::Whole)
+            .policy("status.*".parse().unwrap(), Policy::Ignore);
+        assert_eq!(diff_nodes(&old, &new, vec![], &options), vec![]);
+        let options = options
+            .policy(
+                "spec".parse().unwrap(),
+                Policy::Sequences(SequenceStrategy::Myers),
+            )
+            .policy("spec.containers".parse().unwrap(), Policy::Ignore)
+            .policy("spec.labels".parse().unwrap(), Policy::Ignore);
+        assert_eq!(diff_nodes(&old, &new, vec![], &options), vec![]);
+        let options = options.policy("spec.tags".parse().unwrap(), Policy::Whole);
+        let changes = diff_nodes(&old, &new, vec![], &options);
+        assert_eq!(changes.len(), 2);
+        assert!(changes.iter().all(|c| c.path == [key("spec"), key("tags")]));
+    }
+}
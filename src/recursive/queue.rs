@@ -0,0 +1,237 @@
+use crate::recursive::apply_node_in_place;
+use crate::recursive::changeset::ChangeSet;
+use crate::recursive::diffable::Diffable;
+use crate::recursive::history::Snapshot;
+use crate::recursive::types::{ApplyError, Primitive};
+use std::fmt;
+
+/// An ordered history of change sets, each one applying on top of the one before,
+/// its parent.
+///
+/// Change sets are identified by their [`ChangeSet::id`], which must be unique.
+/// The whole queue can be applied to the base it started from,
+/// or only the change sets up to one of them.
+/// ```
+/// use std::collections::HashMap;
+/// use diffkit::recursive::{diff, ChangeQueue, ChangeSet};
+///
+/// let v1 = HashMap::from([("a".to_string(), 1)]);
+/// let v2 = HashMap::from([("a".to_string(), 2)]);
+/// let v3 = HashMap::from([("a".to_string(), 3)]);
+/// let mut queue = ChangeQueue::new();
+/// queue.append(ChangeSet::new("first", diff(&v1, &v2))).unwrap();
+/// queue.append(ChangeSet::new("second", diff(&v2, &v3))).unwrap();
+/// assert_eq!(queue.parent("second").map(|set| set.id.as_str()), Some("first"));
+/// assert_eq!(queue.apply_all(&v1), Ok(v3));
+/// assert_eq!(queue.replay_until(&v1, "first"), Ok(v2));
+/// ```
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ChangeQueue<P: Primitive> {
+    sets: Vec<ChangeSet<P>>,
+}
+
+impl<P: Primitive> Default for ChangeQueue<P> {
+    fn default() -> Self {
+        ChangeQueue { sets: vec![] }
+    }
+}
+
+impl<P: Primitive> ChangeQueue<P> {
+    /// An empty queue.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends `set` on top of the last change set.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`QueueError::DuplicateId`] if a change set with the same id is queued.
+    pub fn append(&mut self, set: ChangeSet<P>) -> Result<(), QueueError> {
+        if self.position(&set.id).is_some() {
+            return Err(QueueError::DuplicateId(set.id));
+        }
+        self.sets.push(set);
+        Ok(())
+    }
+
+    /// Appends `set` if its parent is the last change set, `None` for an empty queue,
+    /// e.g. to reject a change set computed against an outdated version.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`QueueError::NotLast`] if `parent` isn't the last change set,
+    /// or the error of [`append`](ChangeQueue::append).
+    pub fn append_after(
+        &mut self,
+        parent: Option<&str>,
+        set: ChangeSet<P>,
+    ) -> Result<(), QueueError> {
+        if self.last().map(|last| last.id.as_str()) != parent {
+            return Err(QueueError::NotLast(set.id));
+        }
+        self.append(set)
+    }
+
+    /// The last change set, if any.
+    pub fn last(&self) -> Option<&ChangeSet<P>> {
+        self.sets.last()
+    }
+
+    /// The change set with the given id, if any.
+    pub fn get(&self, id: &str) -> Option<&ChangeSet<P>> {
+        self.position(id).map(|i| &self.sets[i])
+    }
+
+    /// The change set before the one with the given id, if any.
+    pub fn parent(&self, id: &str) -> Option<&ChangeSet<P>> {
+        self.position(id)
+            .and_then(|i| i.checked_sub(1))
+            .map(|i| &self.sets[i])
+    }
+
+    /// The change sets queued after the one with the given id,
+    /// e.g. to replay them on a snapshot taken there.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`QueueError::UnknownId`] if no change set has the id.
+    pub fn since(&self, id: &str) -> Result<&[ChangeSet<P>], QueueError> {
+        let i = self.position_of(id)?;
+        Ok(&self.sets[i + 1..])
+    }
+
+    /// The change sets, from the first one.
+    pub fn iter(&self) -> impl Iterator<Item = &ChangeSet<P>> {
+        self.sets.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.sets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sets.is_empty()
+    }
+
+    /// Applies every change set to `base`, in order.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`QueueError::Apply`] with the id of the first change set
+    /// that doesn't fit.
+    pub fn apply_all<T: Diffable<P = P>>(&self, base: &T) -> Result<T, QueueError> {
+        replay(base, &self.sets)
+    }
+
+    /// Applies the change sets to `base` up to the one with the given id, included.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`QueueError::UnknownId`] if no change set has the id,
+    /// or the error of [`apply_all`](ChangeQueue::apply_all).
+    pub fn replay_until<T: Diffable<P = P>>(&self, base: &T, id: &str) -> Result<T, QueueError> {
+        let i = self.position_of(id)?;
+        replay(base, &self.sets[..=i])
+    }
+
+    /// Applies the change sets queued after the snapshot to its value, in order,
+    /// e.g. to rebuild the last version from a saved snapshot.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`QueueError::UnknownId`] if no change set has the id of the snapshot,
+    /// or the error of [`apply_all`](ChangeQueue::apply_all).
+    pub fn replay_from<T: Diffable<P = P>>(&self, snapshot: &Snapshot<T>) -> Result<T, QueueError> {
+        replay(&snapshot.value, self.since(&snapshot.id)?)
+    }
+
+    fn position(&self, id: &str) -> Option<usize> {
+        self.sets.iter().position(|set| set.id == id)
+    }
+
+    fn position_of(&self, id: &str) -> Result<usize, QueueError> {
+        self.position(id)
+            .ok_or_else(|| QueueError::UnknownId(id.to_string()))
+    }
+}
+
+fn replay<T: Diffable>(base: &T, sets: &[ChangeSet<T::P>]) -> Result<T, QueueError> {
+    let mut node = base.to_node();
+    for set in sets {
+        apply_node_in_place(&mut node, &set.changes)
+            .map_err(|e| QueueError::Apply(set.id.clone(), e))?;
+    }
+    // the last change set left a node that doesn't convert back
+    let last = sets.last().map_or(String::new(), |set| set.id.clone());
+    T::from_node(node).map_err(|e| QueueError::Apply(last, e))
+}
+
+/// Error returned when a [`ChangeQueue`] can't be extended or applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueueError {
+    /// A change set with the id is already queued.
+    DuplicateId(String),
+    /// No change set has the id.
+    UnknownId(String),
+    /// The change set with the id wasn't computed on top of the last one.
+    NotLast(String),
+    /// The change set with the id doesn't fit the value it is applied to.
+    Apply(String, ApplyError),
+}
+
+impl fmt::Display for QueueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueueError::DuplicateId(id) => write!(f, "change set {} is already queued", id),
+            QueueError::UnknownId(id) => write!(f, "no change set {}", id),
+            QueueError::NotLast(id) => {
+                write!(f, "change set {} isn't based on the last one", id)
+            }
+            QueueError::Apply(id, e) => write!(f, "change set {}: {}", id, e),
+        }
+    }
+}
+
+impl std::error::Error for QueueError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recursive::{diff, PathSegment};
+
+    #[test]
+    fn test_queue() {
+        let (v1, v2, v3) = (vec![1], vec![1, 2], vec![2]);
+        let mut queue = ChangeQueue::new();
+        queue
+            .append_after(None, ChangeSet::new("a", diff(&v1, &v2)))
+            .unwrap();
+        assert_eq!(
+            queue.append_after(None, ChangeSet::new("b", diff(&v2, &v3))),
+            Err(QueueError::NotLast("b".to_string()))
+        );
+        queue
+            .append_after(Some("a"), ChangeSet::new("b", diff(&v2, &v3)))
+            .unwrap();
+        assert_eq!(
+            queue.append(ChangeSet::new("a", vec![])),
+            Err(QueueError::DuplicateId("a".to_string()))
+        );
+        assert_eq!(queue.len(), 2);
+        assert!(queue.parent("a").is_none());
+        assert_eq!(queue.since("a").map(|sets| sets.len()), Ok(1));
+        assert_eq!(
+            queue.replay_until(&v1, "c"),
+            Err(QueueError::UnknownId("c".to_string()))
+        );
+        assert_eq!(queue.replay_until(&v1, "b"), Ok(v3));
+        assert_eq!(
+            queue.apply_all(&Vec::<i32>::new()),
+            Err(QueueError::Apply(
+                "a".to_string(),
+                ApplyError::PathNotFound(vec![PathSegment::Index(1)])
+            ))
+        );
+    }
+}
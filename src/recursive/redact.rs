@@ -0,0 +1,152 @@
+use crate::recursive::path::PathPattern;
+use crate::recursive::types::{Change, ChangeKind, Node, PathSegment, Primitive};
+
+/// The placeholder for redacted strings.
+pub const REDACTED: &str = "<redacted>";
+
+/// Hides the values found under the paths matching `patterns`, e.g. passwords and
+/// tokens, before changes are logged or sent: they are replaced by `placeholder`,
+/// and nodes holding such values by a `placeholder` leaf.
+///
+/// Changes still record that the values changed, as `Modified` from and to the placeholder
+/// for modifications, blob deltas and truncated subtrees or leaves, whose hashes could give the
+/// values away; but they can't be applied anymore.
+/// ```
+/// use diffkit::recursive::{redact_changes, Change, ChangeKind, PathSegment, REDACTED};
+///
+/// let mut changes = vec![Change {
+///     path: vec![PathSegment::Key("user".into()), PathSegment::Key("password".into())],
+///     kind: ChangeKind::Modified("hunter2".to_string(), "letmein".to_string()),
+/// }];
+/// redact_changes(&mut changes, &["**/password".parse().unwrap()], &REDACTED.to_string());
+/// assert_eq!(
+///     changes[0].kind,
+///     ChangeKind::Modified(REDACTED.to_string(), REDACTED.to_string())
+/// );
+/// ```
+pub fn redact_changes<P: Primitive>(
+    changes: &mut [Change<P>],
+    patterns: &[PathPattern],
+    placeholder: &P,
+) {
+    for change in changes {
+        if redacts(patterns, &change.path) {
+            redact_kind(&mut change.kind, placeholder);
+            continue;
+        }
+        if let ChangeKind::NodeAdded(node) | ChangeKind::NodeRemoved(node) = &mut change.kind {
+            node.walk_mut(&mut |path: &[PathSegment], node: &mut Node<P>| {
+                let mut path = path.to_vec();
+                path.splice(0..0, change.path.iter().cloned());
+                if redacts(patterns, &path) {
+                    *node = Node::Leaf(placeholder.clone());
+                }
+            });
+        }
+    }
+}
+
+/// Whether `path` or one of its ancestors matches a pattern.
+fn redacts(patterns: &[PathPattern], path: &[PathSegment]) -> bool {
+    (0..=path.len()).any(|depth| patterns.iter().any(|p| p.matches(&path[..depth])))
+}
+
+fn redact_kind<P: Primitive>(kind: &mut ChangeKind<P>, placeholder: &P) {
+    let redacted = || placeholder.clone();
+    *kind = match kind {
+        ChangeKind::Added(_) => ChangeKind::Added(redacted()),
+        ChangeKind::Removed(_) => ChangeKind::Removed(redacted()),
+        ChangeKind::NodeAdded(_) => ChangeKind::NodeAdded(Node::Leaf(redacted())),
+        ChangeKind::NodeRemoved(_) => ChangeKind::NodeRemoved(Node::Leaf(redacted())),
+        ChangeKind::TruncatedLeafChanged { old: None, .. } => ChangeKind::Added(redacted()),
+        ChangeKind::TruncatedLeafChanged { new: None, .. } => ChangeKind::Removed(redacted()),
+        ChangeKind::Modified(..)
+        | ChangeKind::BlobPatched(_)
+        | ChangeKind::TruncatedSubtreeChanged { .. }
+        | ChangeKind::TruncatedLeafChanged { .. } => ChangeKind::Modified(redacted(), redacted()),
+        // only keys and indices change
+        ChangeKind::Renamed { .. } | ChangeKind::Moved { .. } => return,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node;
+    use crate::patch::PatchHeader;
+    use crate::recursive::{diff_with, DiffOptions};
+    use crate::serialization::ToPatch;
+    use std::collections::HashMap;
+
+    fn key(k: &str) -> PathSegment {
+        PathSegment::Key(k.to_string())
+    }
+
+    #[test]
+    fn test_redact_changes() {
+        let redacted = REDACTED.to_string();
+        let mut changes: Vec<Change<String>> = vec![
+            Change {
+                path: vec![key("api")],
+                kind: ChangeKind::NodeAdded(node!({ "tokens": ["t1", "t2"], "url": "u" })),
+            },
+            Change {
+                path: vec![key("db"), key("password")],
+                kind: ChangeKind::TruncatedSubtreeChanged {
+                    old_hash: 1,
+                    new_hash: 2,
+                },
+            },
+            Change {
+                path: vec![key("db"), key("password"), PathSegment::Index(0)],
+                kind: ChangeKind::Removed("p".to_string()),
+            },
+            Change {
+                path: vec![key("db")],
+                kind: ChangeKind::Renamed {
+                    from: "password".to_string(),
+                    to: "secret".to_string(),
+                },
+            },
+        ];
+        let patterns = [
+            "api.tokens".parse().unwrap(),
+            "**/password".parse().unwrap(),
+        ];
+        redact_changes(&mut changes, &patterns, &redacted);
+        let kinds: Vec<_> = changes.into_iter().map(|c| c.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ChangeKind::NodeAdded(node!({ "tokens": "<redacted>", "url": "u" })),
+                ChangeKind::Modified(redacted.clone(), redacted.clone()),
+                ChangeKind::Removed(redacted),
+                ChangeKind::Renamed {
+                    from: "password".to_string(),
+                    to: "secret".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_redacted_diff() {
+        let db = |host: &str, password: &str| {
+            let db = [("host", host), ("password", password)];
+            HashMap::from(db.map(|(k, v)| (k.to_string(), v.to_string())))
+        };
+        let old = HashMap::from([("db".to_string(), db("a", "p1"))]);
+        let new = HashMap::from([("db".to_string(), db("b", "p2"))]);
+        let options =
+            DiffOptions::new().redact("**/password".parse().unwrap(), REDACTED.to_string());
+        let changes = diff_with(&old, &new, &options);
+        assert_eq!(changes.len(), 2);
+        assert!(changes.contains(&Change {
+            path: vec![key("db"), key("host")],
+            kind: ChangeKind::Modified("a".to_string(), "b".to_string()),
+        }));
+        let text = changes.to_patch(&PatchHeader::default());
+        assert!(!text.contains("p1") && !text.contains("p2"));
+        assert!(text.contains("<redacted>"));
+    }
+}
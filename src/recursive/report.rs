@@ -0,0 +1,458 @@
+use crate::recursive::types::{Change, ChangeKind, PathSegment, Primitive};
+use std::fmt;
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const CYAN: &str = "\x1b[36m";
+const RESET: &str = "\x1b[0m";
+
+/// Renders a list of changes for humans, one line per change:
+/// ```text
+/// ~ spec.replicas: 3 → 5
+/// + metadata.labels.team: "core"
+/// - containers[2]
+/// > metadata.name → metadata.title
+/// ```
+/// `+`, `-` and `~` mark additions, removals and modifications, with the values
+/// of leaves formatted with `Debug`; `>` marks renamed keys and moved elements,
+/// and `?` values changed below a truncated diff.
+/// Paths use the syntax of [`PathPattern`](crate::recursive::PathPattern),
+/// with `[*]` for a set member and `.` for the root.
+/// ```
+/// use std::collections::HashMap;
+/// use diffkit::recursive::{diff, Report};
+///
+/// let old = HashMap::from([("replicas".to_string(), 3)]);
+/// let new = HashMap::from([("replicas".to_string(), 5)]);
+/// let changes = diff(&old, &new);
+/// assert_eq!(Report::new(&changes).to_string(), "~ replicas: 3 → 5\n");
+/// ```
+#[derive(Clone, Debug)]
+pub struct Report<'a, P: Primitive> {
+    changes: &'a [Change<P>],
+    color: bool,
+    tree: bool,
+}
+
+impl<'a, P: Primitive> Report<'a, P> {
+    /// A report of `changes`, without colors.
+    pub fn new(changes: &'a [Change<P>]) -> Self {
+        Report {
+            changes,
+            color: false,
+            tree: false,
+        }
+    }
+
+    /// Whether to color each line with ANSI escape codes, e.g. for a terminal.
+    pub fn color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Whether to show changes under the keys and indices of their containers,
+    /// indented like the document, as in `kubectl diff`:
+    /// ```text
+    ///   spec:
+    /// ~   replicas: 3 → 5
+    ///     containers:
+    /// -     [2]
+    /// ```
+    /// The changes to a container are listed before its children, in the order they
+    /// first appear; renames and moves are shown within the container they happen in.
+    pub fn tree(mut self, tree: bool) -> Self {
+        self.tree = tree;
+        self
+    }
+}
+
+impl<P: Primitive + fmt::Debug> fmt::Display for Report<'_, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.tree {
+            for change in self.changes {
+                self.write_line(f, Mark::of(&change.kind), 0, &Line(change))?;
+            }
+            return Ok(());
+        }
+        for visit in Group::new(self.changes).walk() {
+            match visit {
+                Visit::Enter(segment, depth) => {
+                    writeln!(f, "  {:2$}{}:", "", DisplaySegment(segment), 2 * depth)?;
+                }
+                Visit::Change(change, depth) => {
+                    self.write_line(f, Mark::of(&change.kind), depth, &Label(change))?;
+                }
+                Visit::Leave(_) => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<P: Primitive> Report<'_, P> {
+    /// Writes a line after its marker, indented for its depth in the tree.
+    fn write_line(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        mark: Mark,
+        depth: usize,
+        line: &dyn fmt::Display,
+    ) -> fmt::Result {
+        let indent = 2 * depth;
+        if self.color {
+            let color = mark.color();
+            writeln!(
+                f,
+                "{}{} {:indent$}{}{}",
+                color,
+                mark.symbol(),
+                "",
+                line,
+                RESET
+            )
+        } else {
+            writeln!(f, "{} {:indent$}{}", mark.symbol(), "", line)
+        }
+    }
+}
+
+/// What a change does, as shown by its marker.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub(crate) enum Mark {
+    Added,
+    Removed,
+    Modified,
+    Moved,
+    Truncated,
+}
+
+impl Mark {
+    pub(crate) fn of<P: Primitive>(kind: &ChangeKind<P>) -> Self {
+        match kind {
+            ChangeKind::Added(_) | ChangeKind::NodeAdded(_) => Mark::Added,
+            ChangeKind::Removed(_) | ChangeKind::NodeRemoved(_) => Mark::Removed,
+            ChangeKind::Modified(..) | ChangeKind::BlobPatched(_) => Mark::Modified,
+            ChangeKind::Renamed { .. } | ChangeKind::Moved { .. } => Mark::Moved,
+            ChangeKind::TruncatedSubtreeChanged { .. } => Mark::Truncated,
+            ChangeKind::TruncatedLeafChanged { old, new } => match (old, new) {
+                (None, _) => Mark::Added,
+                (_, None) => Mark::Removed,
+                _ => Mark::Modified,
+            },
+        }
+    }
+
+    pub(crate) fn symbol(self) -> char {
+        match self {
+            Mark::Added => '+',
+            Mark::Removed => '-',
+            Mark::Modified => '~',
+            Mark::Moved => '>',
+            Mark::Truncated => '?',
+        }
+    }
+
+    fn color(self) -> &'static str {
+        match self {
+            Mark::Added => GREEN,
+            Mark::Removed => RED,
+            Mark::Modified | Mark::Truncated => YELLOW,
+            Mark::Moved => CYAN,
+        }
+    }
+}
+
+/// Changes grouped by the containers they are found in, for the renderers
+/// showing them as a tree.
+///
+/// Each group holds the changes to the children of its container, renames and moves
+/// within it, then its child groups, both in the order they first appear.
+#[derive(Debug)]
+pub(crate) struct Group<'a, P: Primitive> {
+    pub(crate) changes: Vec<&'a Change<P>>,
+    pub(crate) children: Vec<(&'a PathSegment, Group<'a, P>)>,
+}
+
+/// A step of the walk through the groups of [`Group::walk`], at a depth.
+pub(crate) enum Visit<'a, P: Primitive> {
+    Enter(&'a PathSegment, usize),
+    Change(&'a Change<P>, usize),
+    Leave(usize),
+}
+
+impl<'a, P: Primitive> Group<'a, P> {
+    pub(crate) fn new(changes: &'a [Change<P>]) -> Self {
+        let mut root = Group {
+            changes: vec![],
+            children: vec![],
+        };
+        for change in changes {
+            let container = match change.kind {
+                ChangeKind::Renamed { .. } | ChangeKind::Moved { .. } => &change.path[..],
+                _ => change
+                    .path
+                    .split_last()
+                    .map_or(&[][..], |(_, parents)| parents),
+            };
+            let mut group = &mut root;
+            for segment in container {
+                let index = match group.children.iter().position(|(s, _)| *s == segment) {
+                    Some(index) => index,
+                    None => {
+                        let child = Group {
+                            changes: vec![],
+                            children: vec![],
+                        };
+                        group.children.push((segment, child));
+                        group.children.len() - 1
+                    }
+                };
+                group = &mut group.children[index].1;
+            }
+            group.changes.push(change);
+        }
+        root
+    }
+
+    /// The groups and changes depth first, from an explicit stack
+    /// so that deep paths don't exhaust it.
+    pub(crate) fn walk(&self) -> Vec<Visit<'a, P>> {
+        let mut visits = vec![];
+        let mut stack = vec![(Some(self), None, 0)];
+        while let Some((group, segment, depth)) = stack.pop() {
+            let Some(group) = group else {
+                visits.push(Visit::Leave(depth));
+                continue;
+            };
+            let mut depth = depth;
+            if let Some(segment) = segment {
+                visits.push(Visit::Enter(segment, depth));
+                stack.push((None, None, depth));
+                depth += 1;
+            }
+            visits.extend(group.changes.iter().map(|c| Visit::Change(c, depth)));
+            for (segment, child) in group.children.iter().rev() {
+                stack.push((Some(child), Some(*segment), depth));
+            }
+        }
+        visits
+    }
+}
+
+/// The part of a change shown under its group: its last path segment or the keys
+/// and indices it moves, then its values.
+pub(crate) struct Label<'a, P: Primitive>(pub(crate) &'a Change<P>);
+
+impl<P: Primitive + fmt::Debug> fmt::Display for Label<'_, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let change = self.0;
+        match &change.kind {
+            ChangeKind::Renamed { from, to } => write!(f, "{} → {}", from, to),
+            ChangeKind::Moved {
+                from_index,
+                to_index,
+            } => write!(f, "[{}] → [{}]", from_index, to_index),
+            _ => {
+                match change.path.last() {
+                    Some(segment) => write!(f, "{}", DisplaySegment(segment))?,
+                    None => write!(f, ".")?,
+                }
+                write!(f, "{}", Values(change))
+            }
+        }
+    }
+}
+
+/// A change, rendered after its marker.
+struct Line<'a, P: Primitive>(&'a Change<P>);
+
+impl<P: Primitive + fmt::Debug> fmt::Display for Line<'_, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let change = self.0;
+        let child = |segment: PathSegment| {
+            let mut path = change.path.clone();
+            path.push(segment);
+            path
+        };
+        match &change.kind {
+            ChangeKind::Renamed { from, to } => {
+                let from = child(PathSegment::Key(from.clone()));
+                let to = child(PathSegment::Key(to.clone()));
+                write!(f, "{} → {}", DisplayPath(&from), DisplayPath(&to))
+            }
+            ChangeKind::Moved {
+                from_index,
+                to_index,
+            } => {
+                let from = child(PathSegment::Index(*from_index));
+                let to = child(PathSegment::Index(*to_index));
+                write!(f, "{} → {}", DisplayPath(&from), DisplayPath(&to))
+            }
+            _ => write!(f, "{}{}", DisplayPath(&change.path), Values(change)),
+        }
+    }
+}
+
+/// The values of a change after its path, like `: 3 → 5`; nothing for nodes.
+pub(crate) struct Values<'a, P: Primitive>(pub(crate) &'a Change<P>);
+
+impl<P: Primitive + fmt::Debug> fmt::Display for Values<'_, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0.kind {
+            ChangeKind::Added(v) | ChangeKind::Removed(v) => write!(f, ": {:?}", v),
+            ChangeKind::Modified(old, new) => write!(f, ": {:?} → {:?}", old, new),
+            ChangeKind::BlobPatched(delta) => write!(
+                f,
+                ": {} bytes at {} → {} bytes",
+                delta.removed.len(),
+                delta.offset,
+                delta.inserted.len()
+            ),
+            ChangeKind::TruncatedLeafChanged { old, new } => match (old, new) {
+                (Some(old), Some(new)) => write!(f, ": {} bytes → {} bytes", old.len, new.len),
+                (Some(digest), None) | (None, Some(digest)) => write!(f, ": {} bytes", digest.len),
+                (None, None) => Ok(()),
+            },
+            ChangeKind::NodeAdded(_)
+            | ChangeKind::NodeRemoved(_)
+            | ChangeKind::TruncatedSubtreeChanged { .. }
+            | ChangeKind::Renamed { .. }
+            | ChangeKind::Moved { .. } => Ok(()),
+        }
+    }
+}
+
+/// A path like `spec.containers[2].image`.
+pub(crate) struct DisplayPath<'a>(pub(crate) &'a [PathSegment]);
+
+impl fmt::Display for DisplayPath<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return write!(f, ".");
+        }
+        for (i, segment) in self.0.iter().enumerate() {
+            match segment {
+                PathSegment::Key(key) if i > 0 => write!(f, ".{}", key)?,
+                segment => write!(f, "{}", DisplaySegment(segment))?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A segment of a path on its own, like `image` or `[2]`.
+pub(crate) struct DisplaySegment<'a>(pub(crate) &'a PathSegment);
+
+impl fmt::Display for DisplaySegment<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            PathSegment::Key(key) => write!(f, "{}", key),
+            PathSegment::Index(index) => write!(f, "[{}]", index),
+            PathSegment::Member => write!(f, "[*]"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recursive::Node;
+
+    fn key(k: &str) -> PathSegment {
+        PathSegment::Key(k.to_string())
+    }
+
+    #[test]
+    fn test_report() {
+        let changes = vec![
+            Change {
+                path: vec![key("spec"), key("replicas")],
+                kind: ChangeKind::Modified(3, 5),
+            },
+            Change {
+                path: vec![key("containers"), PathSegment::Index(2)],
+                kind: ChangeKind::NodeRemoved(Node::Null),
+            },
+            Change {
+                path: vec![key("tags"), PathSegment::Member],
+                kind: ChangeKind::Added(1),
+            },
+            Change {
+                path: vec![key("metadata")],
+                kind: ChangeKind::Renamed {
+                    from: "name".to_string(),
+                    to: "title".to_string(),
+                },
+            },
+            Change {
+                path: vec![],
+                kind: ChangeKind::Moved {
+                    from_index: 0,
+                    to_index: 1,
+                },
+            },
+        ];
+        assert_eq!(
+            Report::new(&changes).to_string(),
+            "~ spec.replicas: 3 → 5\n\
+             - containers[2]\n\
+             + tags[*]: 1\n\
+             > metadata.name → metadata.title\n\
+             > [0] → [1]\n"
+        );
+        assert_eq!(
+            Report::new(&changes[..1]).color(true).to_string(),
+            "\x1b[33m~ spec.replicas: 3 → 5\x1b[0m\n"
+        );
+    }
+
+    #[test]
+    fn test_tree_report() {
+        let changes = vec![
+            Change {
+                path: vec![key("spec"), key("replicas")],
+                kind: ChangeKind::Modified(3, 5),
+            },
+            Change {
+                path: vec![key("spec"), key("containers"), PathSegment::Index(2)],
+                kind: ChangeKind::NodeRemoved(Node::Null),
+            },
+            Change {
+                path: vec![key("debug")],
+                kind: ChangeKind::Added(1),
+            },
+            Change {
+                path: vec![key("spec")],
+                kind: ChangeKind::Renamed {
+                    from: "name".to_string(),
+                    to: "title".to_string(),
+                },
+            },
+            Change {
+                path: vec![key("spec"), key("containers")],
+                kind: ChangeKind::Moved {
+                    from_index: 0,
+                    to_index: 1,
+                },
+            },
+        ];
+        assert_eq!(
+            Report::new(&changes).tree(true).to_string(),
+            "+ debug: 1\n  \
+               spec:\n\
+             ~   replicas: 3 → 5\n\
+             >   name → title\n    \
+                 containers:\n\
+             -     [2]\n\
+             >     [0] → [1]\n"
+        );
+        assert_eq!(
+            Report::new(&changes[..1])
+                .tree(true)
+                .color(true)
+                .to_string(),
+            "  spec:\n\x1b[33m~   replicas: 3 → 5\x1b[0m\n"
+        );
+    }
+}
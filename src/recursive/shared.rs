@@ -0,0 +1,285 @@
+use crate::recursive::types::{ApplyError, Change, ChangeKind, Node, PathSegment, Primitive};
+use crate::recursive::{
+    added_node, apply_at, check_target, move_within, not_found, patch, rearranges,
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// A [`Node`] tree whose containers are shared between versions.
+///
+/// Cloning it is cheap, and applying changes to it only copies the containers
+/// on the path of each change: the unchanged subtrees are shared with the
+/// previous version instead of being cloned, e.g. to keep the history
+/// of a large document edited in small steps.
+/// ```
+/// use std::sync::Arc;
+/// use diffkit::node;
+/// use diffkit::recursive::{diff, Change, ChangeKind, Node, PathSegment, SharedNode};
+///
+/// let tree: Node<i32> = node!({ "spec": { "replicas": 3 }, "status": { "ready": 3 } });
+/// let v1 = SharedNode::from(tree);
+/// let change = Change {
+///     path: vec![PathSegment::Key("spec".into()), PathSegment::Key("replicas".into())],
+///     kind: ChangeKind::Modified(3, 5),
+/// };
+/// let v2 = v1.apply(&[change]).unwrap();
+///
+/// let status = |v: &SharedNode<i32>| match v {
+///     SharedNode::Map(map) => match &map["status"] {
+///         SharedNode::Map(status) => status.clone(),
+///         _ => unreachable!(),
+///     },
+///     _ => unreachable!(),
+/// };
+/// assert!(Arc::ptr_eq(&status(&v1), &status(&v2)));
+/// assert_eq!(Node::from(v2), node!({ "spec": { "replicas": 5 }, "status": { "ready": 3 } }));
+/// ```
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum SharedNode<P: Primitive> {
+    Map(Arc<HashMap<String, SharedNode<P>>>),
+    Sequence(Arc<Vec<SharedNode<P>>>),
+    Tuple(Arc<Vec<SharedNode<P>>>),
+    Variant(String, Arc<SharedNode<P>>),
+    Set(Arc<Vec<SharedNode<P>>>),
+    Leaf(P),
+    Null,
+}
+
+impl<P: Primitive> SharedNode<P> {
+    /// The version of the tree after `changes`, sharing what they don't touch.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`apply`](crate::recursive::apply).
+    pub fn apply(&self, changes: &[Change<P>]) -> Result<Self, ApplyError> {
+        self.apply_with(changes, false)
+    }
+
+    /// Like [`apply`](SharedNode::apply), checking the changes
+    /// like [`apply_strict`](crate::recursive::apply_strict).
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`apply_strict`](crate::recursive::apply_strict).
+    pub fn apply_strict(&self, changes: &[Change<P>]) -> Result<Self, ApplyError> {
+        self.apply_with(changes, true)
+    }
+
+    fn apply_with(&self, changes: &[Change<P>], strict: bool) -> Result<Self, ApplyError> {
+        let mut tree = self.clone();
+        for change in changes {
+            apply_shared(&mut tree, change, strict)?;
+        }
+        Ok(tree)
+    }
+}
+
+/// Applies `change` by copying the containers down to the node it acts on,
+/// then changing the element of a sequence it targets, or running [`apply_at`]
+/// on a copy of that node holding only the children involved.
+fn apply_shared<P: Primitive>(
+    tree: &mut SharedNode<P>,
+    change: &Change<P>,
+    strict: bool,
+) -> Result<(), ApplyError> {
+    // the node holding the target of the change, or the target itself
+    // for the changes rearranging its children
+    let (parents, last) = match change.path.split_last() {
+        Some((last, parents)) if !rearranges(change) => (parents, Some(last)),
+        _ => (&change.path[..], None),
+    };
+    let mut node = tree;
+    for (depth, segment) in parents.iter().enumerate() {
+        node = match (node, segment) {
+            (SharedNode::Map(m), PathSegment::Key(k)) => Arc::make_mut(m).get_mut(k),
+            (SharedNode::Sequence(s) | SharedNode::Tuple(s), PathSegment::Index(i)) => {
+                Arc::make_mut(s).get_mut(*i)
+            }
+            (SharedNode::Variant(tag, content), PathSegment::Key(k)) if k == tag => {
+                Some(Arc::make_mut(content))
+            }
+            (SharedNode::Variant(..), PathSegment::Key(_)) => None,
+            _ => return Err(ApplyError::TypeMismatch(change.path[..depth].to_vec())),
+        }
+        .ok_or_else(|| ApplyError::PathNotFound(change.path[..=depth].to_vec()))?;
+    }
+
+    let depth = parents.len();
+    match (node, last, &change.kind) {
+        (SharedNode::Map(map), Some(PathSegment::Key(key)), _) => {
+            let mut partial = HashMap::new();
+            if let Some(child) = map.get(key) {
+                partial.insert(key.clone(), Node::from(child.clone()));
+            }
+            let mut partial = Node::Map(partial);
+            apply_at(&mut partial, change, depth, strict)?;
+            let Node::Map(mut partial) = partial else {
+                unreachable!()
+            };
+            let map = Arc::make_mut(map);
+            match partial.remove(key) {
+                Some(child) => map.insert(key.clone(), child.into()),
+                None => map.remove(key),
+            };
+        }
+        (
+            SharedNode::Sequence(sequence) | SharedNode::Tuple(sequence),
+            Some(PathSegment::Index(index)),
+            kind,
+        ) => {
+            let index = *index;
+            if let Some(new) = added_node(kind) {
+                if index > sequence.len() {
+                    return Err(not_found(change, depth));
+                }
+                Arc::make_mut(sequence).insert(index, new.into());
+                return Ok(());
+            }
+            // only the element at the index is needed, the others keep their places
+            let element = sequence.get(index).map(|e| Node::from(e.clone()));
+            check_target(element.as_ref(), change, depth, strict)?;
+            let sequence = Arc::make_mut(sequence);
+            match kind {
+                ChangeKind::Modified(_, new) => sequence[index] = SharedNode::Leaf(new.clone()),
+                ChangeKind::BlobPatched(delta) => {
+                    let mut element = element.unwrap();
+                    patch(&mut element, delta);
+                    sequence[index] = element.into();
+                }
+                _ => {
+                    sequence.remove(index);
+                }
+            }
+        }
+        (SharedNode::Map(map), None, ChangeKind::Renamed { from, to }) => {
+            // renaming only checks which keys exist
+            let partial = [from, to]
+                .into_iter()
+                .filter(|key| map.contains_key(*key))
+                .map(|key| (key.clone(), Node::Null));
+            let mut partial = Node::Map(partial.collect());
+            apply_at(&mut partial, change, depth, strict)?;
+            let map = Arc::make_mut(map);
+            let value = map.remove(from).unwrap();
+            map.insert(to.clone(), value);
+        }
+        (
+            SharedNode::Sequence(sequence),
+            None,
+            ChangeKind::Moved {
+                from_index,
+                to_index,
+            },
+        ) => move_within(Arc::make_mut(sequence), *from_index, *to_index, change)?,
+        (node, _, _) => {
+            let mut whole = Node::from(node.clone());
+            apply_at(&mut whole, change, depth, strict)?;
+            *node = whole.into();
+        }
+    }
+    Ok(())
+}
+
+impl<P: Primitive> From<Node<P>> for SharedNode<P> {
+    fn from(node: Node<P>) -> Self {
+        let shared = |nodes: Vec<Node<P>>| Arc::new(nodes.into_iter().map(Self::from).collect());
+        match node {
+            Node::Map(map) => SharedNode::Map(Arc::new(
+                map.into_iter().map(|(k, v)| (k, v.into())).collect(),
+            )),
+            Node::Sequence(sequence) => SharedNode::Sequence(shared(sequence)),
+            Node::Tuple(tuple) => SharedNode::Tuple(shared(tuple)),
+            Node::Variant(tag, content) => SharedNode::Variant(tag, Arc::new((*content).into())),
+            Node::Set(members) => SharedNode::Set(shared(members)),
+            Node::Leaf(v) => SharedNode::Leaf(v),
+            Node::Null => SharedNode::Null,
+        }
+    }
+}
+
+/// Copies the shared containers, unless they aren't shared anymore.
+impl<P: Primitive> From<SharedNode<P>> for Node<P> {
+    fn from(node: SharedNode<P>) -> Self {
+        let owned = |nodes: Arc<Vec<SharedNode<P>>>| {
+            let nodes = Arc::unwrap_or_clone(nodes);
+            nodes.into_iter().map(Self::from).collect()
+        };
+        match node {
+            SharedNode::Map(map) => Node::Map(
+                Arc::unwrap_or_clone(map)
+                    .into_iter()
+                    .map(|(k, v)| (k, v.into()))
+                    .collect(),
+            ),
+            SharedNode::Sequence(sequence) => Node::Sequence(owned(sequence)),
+            SharedNode::Tuple(tuple) => Node::Tuple(owned(tuple)),
+            SharedNode::Variant(tag, content) => {
+                Node::Variant(tag, Box::new(Arc::unwrap_or_clone(content).into()))
+            }
+            SharedNode::Set(members) => Node::Set(owned(members)),
+            SharedNode::Leaf(v) => Node::Leaf(v),
+            SharedNode::Null => Node::Null,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node;
+    use crate::recursive::{apply_node_in_place, diff_nodes, DiffOptions};
+
+    #[test]
+    fn test_apply_shares_unchanged_subtrees() {
+        let old: Node<i32> = node!({ "a": [1, 2, 3], "b": { "c": 4 }, "d": [[5], [6]] });
+        let new: Node<i32> = node!({ "a": [2, 3, 7], "b": { "c": 4 }, "e": 8, "d": [[5], [9]] });
+        let options = DiffOptions::new().detect_moves().detect_renames();
+        let changes = diff_nodes(&old, &new, vec![], &options);
+        let shared = SharedNode::from(old.clone());
+        let patched = shared.apply_strict(&changes).unwrap();
+        assert_eq!(Node::from(patched.clone()), new);
+
+        let (SharedNode::Map(before), SharedNode::Map(after)) = (&shared, &patched) else {
+            unreachable!()
+        };
+        let (SharedNode::Map(b_before), SharedNode::Map(b_after)) = (&before["b"], &after["b"])
+        else {
+            unreachable!()
+        };
+        assert!(Arc::ptr_eq(b_before, b_after));
+        let (SharedNode::Sequence(d_before), SharedNode::Sequence(d_after)) =
+            (&before["d"], &after["d"])
+        else {
+            unreachable!()
+        };
+        assert!(!Arc::ptr_eq(d_before, d_after));
+        assert_eq!(d_before[0], d_after[0]);
+        // the previous version is left as it was
+        assert_eq!(Node::from(shared), old);
+
+        // changes that don't fit fail like they do on nodes
+        let key = |k: &str| PathSegment::Key(k.to_string());
+        let moved = ChangeKind::Moved {
+            from_index: 0,
+            to_index: 3,
+        };
+        for (path, kind) in [
+            (vec![key("b"), key("x"), key("y")], ChangeKind::Added(1)),
+            (vec![key("a"), key("x")], ChangeKind::Added(1)),
+            (vec![key("a"), PathSegment::Index(4)], ChangeKind::Added(1)),
+            (
+                vec![key("a"), PathSegment::Index(3)],
+                ChangeKind::Removed(7),
+            ),
+            (
+                vec![key("a"), PathSegment::Index(0)],
+                ChangeKind::NodeRemoved(node!([2])),
+            ),
+            (vec![key("a")], moved),
+        ] {
+            let changes = [Change { path, kind }];
+            let error = apply_node_in_place(&mut new.clone(), &changes).unwrap_err();
+            assert_eq!(SharedNode::from(new.clone()).apply(&changes), Err(error));
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use crate::recursive::types::{Change, ChangeKind, PathSegment, Primitive};
+use std::collections::BTreeSet;
+
+/// A summary of a list of changes.
+///
+/// Additions, removals and modifications are counted whether they act on leaves
+/// or on whole nodes; `moved` counts renamed keys and moved elements,
+/// and `truncated` the markers of a truncated diff and the changes to truncated leaves.
+/// `depth` is the length of the longest path touched, so 1 for a change
+/// to a top-level key, and 0 for the root itself.
+/// ```
+/// use std::collections::HashMap;
+/// use diffkit::recursive::{diff, ChangeStats};
+///
+/// let old = HashMap::from([("a".to_string(), vec![1]), ("b".to_string(), vec![2])]);
+/// let new = HashMap::from([("a".to_string(), vec![1, 2]), ("b".to_string(), vec![3])]);
+/// let stats = ChangeStats::from(&diff(&old, &new)[..]);
+/// assert_eq!((stats.added, stats.modified), (1, 1));
+/// assert_eq!(stats.keys.len(), 2);
+/// assert_eq!(stats.depth, 2);
+/// ```
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+#[non_exhaustive]
+pub struct ChangeStats {
+    pub added: usize,
+    pub removed: usize,
+    pub modified: usize,
+    pub moved: usize,
+    pub truncated: usize,
+    /// The top-level map keys containing or holding a change.
+    pub keys: BTreeSet<String>,
+    pub depth: usize,
+}
+
+impl<P: Primitive> From<&[Change<P>]> for ChangeStats {
+    fn from(changes: &[Change<P>]) -> Self {
+        let mut stats = ChangeStats::default();
+        for change in changes {
+            let mut depth = change.path.len();
+            match &change.kind {
+                ChangeKind::Added(_) | ChangeKind::NodeAdded(_) => stats.added += 1,
+                ChangeKind::Removed(_) | ChangeKind::NodeRemoved(_) => stats.removed += 1,
+                ChangeKind::Modified(..) | ChangeKind::BlobPatched(_) => stats.modified += 1,
+                ChangeKind::Renamed { from, to } => {
+                    stats.moved += 1;
+                    depth += 1;
+                    if change.path.is_empty() {
+                        stats.keys.extend([from.clone(), to.clone()]);
+                    }
+                }
+                ChangeKind::Moved { .. } => {
+                    stats.moved += 1;
+                    depth += 1;
+                }
+                ChangeKind::TruncatedSubtreeChanged { .. }
+                | ChangeKind::TruncatedLeafChanged { .. } => stats.truncated += 1,
+            }
+            if let Some(PathSegment::Key(key)) = change.path.first() {
+                stats.keys.insert(key.clone());
+            }
+            stats.depth = stats.depth.max(depth);
+        }
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recursive::Node;
+
+    #[test]
+    fn test_stats() {
+        let changes: Vec<Change<i32>> = vec![
+            Change {
+                path: vec![],
+                kind: ChangeKind::Renamed {
+                    from: "a".to_string(),
+                    to: "b".to_string(),
+                },
+            },
+            Change {
+                path: vec![PathSegment::Key("c".to_string()), PathSegment::Index(0)],
+                kind: ChangeKind::NodeRemoved(Node::Null),
+            },
+            Change {
+                path: vec![PathSegment::Key("c".to_string())],
+                kind: ChangeKind::Moved {
+                    from_index: 0,
+                    to_index: 2,
+                },
+            },
+        ];
+        let stats = ChangeStats::from(&changes[..]);
+        assert_eq!(
+            stats,
+            ChangeStats {
+                added: 0,
+                removed: 1,
+                modified: 0,
+                moved: 2,
+                truncated: 0,
+                keys: BTreeSet::from(["a".to_string(), "b".to_string(), "c".to_string()]),
+                depth: 2,
+            }
+        );
+        assert_eq!(ChangeStats::from(&changes[..0]), ChangeStats::default());
+    }
+}
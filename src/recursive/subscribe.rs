@@ -0,0 +1,203 @@
+use crate::recursive::diffable::Diffable;
+use crate::recursive::path::PathPattern;
+use crate::recursive::types::{ApplyError, Change, ChangeKind, PathSegment, Primitive};
+use std::fmt;
+
+type Callback<'a, P> = Box<dyn FnMut(&Change<P>) + 'a>;
+
+/// Callbacks registered on parts of a tree, called for the changes touching them,
+/// see [`apply_subscribed`]: an application can react to the changes of its
+/// settings without diffing them again.
+///
+/// A change touches the values at the paths matching a pattern when it's made
+/// at one of them or below, or replaces a value holding one;
+/// renames and moves touch the keys and indices on both sides.
+/// ```
+/// use std::collections::HashMap;
+/// use diffkit::recursive::{apply_subscribed, diff, Subscriptions};
+///
+/// let old = HashMap::from([
+///     ("network".to_string(), HashMap::from([("port".to_string(), 80)])),
+///     ("log".to_string(), HashMap::from([("level".to_string(), 1)])),
+/// ]);
+/// let mut new = old.clone();
+/// new.get_mut("network").unwrap().insert("port".to_string(), 8080);
+/// new.get_mut("log").unwrap().insert("level".to_string(), 2);
+///
+/// let mut restarts = 0;
+/// let mut subscriptions = Subscriptions::new().on("network".parse().unwrap(), |_| restarts += 1);
+/// let patched = apply_subscribed(&old, &diff(&old, &new), &mut subscriptions).unwrap();
+/// drop(subscriptions);
+/// assert_eq!(patched, new);
+/// assert_eq!(restarts, 1);
+/// ```
+pub struct Subscriptions<'a, P: Primitive> {
+    callbacks: Vec<(PathPattern, Callback<'a, P>)>,
+}
+
+impl<'a, P: Primitive> Subscriptions<'a, P> {
+    /// No subscriptions.
+    pub fn new() -> Self {
+        Subscriptions { callbacks: vec![] }
+    }
+
+    /// Calls `callback` for each change touching the values matching `pattern`.
+    pub fn on(mut self, pattern: PathPattern, callback: impl FnMut(&Change<P>) + 'a) -> Self {
+        self.callbacks.push((pattern, Box::new(callback)));
+        self
+    }
+
+    /// Calls the callbacks for the changes touching their values, in the order
+    /// of the changes, then in the order the callbacks were registered.
+    /// Changes are passed as they are, whether or not they were applied.
+    pub fn notify(&mut self, changes: &[Change<P>]) {
+        for change in changes {
+            let paths = touched(change);
+            for (pattern, callback) in &mut self.callbacks {
+                let touches = paths
+                    .iter()
+                    .any(|path| pattern.matches_prefix_of(path) || pattern.matches_within(path));
+                if touches {
+                    callback(change);
+                }
+            }
+        }
+    }
+}
+
+impl<P: Primitive> Default for Subscriptions<'_, P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P: Primitive> fmt::Debug for Subscriptions<'_, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let patterns: Vec<_> = self.callbacks.iter().map(|(pattern, _)| pattern).collect();
+        f.debug_struct("Subscriptions")
+            .field("patterns", &patterns)
+            .finish()
+    }
+}
+
+/// The paths of the values a change modifies.
+fn touched<P: Primitive>(change: &Change<P>) -> Vec<Vec<PathSegment>> {
+    let child = |segment: PathSegment| {
+        let mut path = change.path.clone();
+        path.push(segment);
+        path
+    };
+    match &change.kind {
+        ChangeKind::Renamed { from, to } => vec![
+            child(PathSegment::Key(from.clone())),
+            child(PathSegment::Key(to.clone())),
+        ],
+        ChangeKind::Moved {
+            from_index,
+            to_index,
+        } => vec![
+            child(PathSegment::Index(*from_index)),
+            child(PathSegment::Index(*to_index)),
+        ],
+        _ => vec![change.path.clone()],
+    }
+}
+
+/// Applies a list of changes like [`apply`](crate::recursive::apply), then calls
+/// the callbacks of `subscriptions` for the changes touching their values.
+///
+/// The callbacks are only called once every change is applied:
+/// if one doesn't fit, none of them is.
+///
+/// # Errors
+///
+/// Returns the errors of [`apply`](crate::recursive::apply).
+pub fn apply_subscribed<T: Diffable>(
+    old: &T,
+    changes: &[Change<T::P>],
+    subscriptions: &mut Subscriptions<'_, T::P>,
+) -> Result<T, ApplyError> {
+    let new = crate::recursive::apply(old, changes)?;
+    subscriptions.notify(changes);
+    Ok(new)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recursive::Node;
+    use std::collections::HashMap;
+
+    fn key(k: &str) -> PathSegment {
+        PathSegment::Key(k.to_string())
+    }
+
+    #[test]
+    fn test_notify() {
+        let changes: Vec<Change<i32>> = vec![
+            Change {
+                path: vec![key("config"), key("network"), key("port")],
+                kind: ChangeKind::Modified(80, 8080),
+            },
+            Change {
+                path: vec![key("config")],
+                kind: ChangeKind::NodeRemoved(Node::Null),
+            },
+            Change {
+                path: vec![key("config")],
+                kind: ChangeKind::Renamed {
+                    from: "net".to_string(),
+                    to: "network".to_string(),
+                },
+            },
+            Change {
+                path: vec![key("config"), key("log")],
+                kind: ChangeKind::Added(1),
+            },
+            Change {
+                path: vec![key("other")],
+                kind: ChangeKind::Added(2),
+            },
+        ];
+        let mut network = vec![];
+        let mut all = 0;
+        let mut subscriptions = Subscriptions::new()
+            .on("config.network".parse().unwrap(), |c: &Change<i32>| {
+                network.push(c.clone())
+            })
+            .on("**".parse().unwrap(), |_| all += 1);
+        assert_eq!(
+            format!("{:?}", subscriptions),
+            "Subscriptions { patterns: [PathPattern { segments: [Key(\"config\"), Key(\"network\")] }, PathPattern { segments: [AnyDepth] }] }"
+        );
+        subscriptions.notify(&changes);
+        drop(subscriptions);
+        assert_eq!(network, changes[..3]);
+        assert_eq!(all, 5);
+    }
+
+    #[test]
+    fn test_apply_subscribed() {
+        let old = HashMap::from([("a".to_string(), 1)]);
+        let mut calls = 0;
+        let mut subscriptions = Subscriptions::new().on("a".parse().unwrap(), |_| calls += 1);
+        let change = |kind| Change {
+            path: vec![key("a")],
+            kind,
+        };
+        let changes = [change(ChangeKind::Modified(1, 2))];
+        let new = apply_subscribed(&old, &changes, &mut subscriptions);
+        assert_eq!(new, Ok(HashMap::from([("a".to_string(), 2)])));
+
+        // nothing is called when a change doesn't fit
+        let missing = Change {
+            path: vec![key("b")],
+            kind: ChangeKind::Modified(1, 2),
+        };
+        let changes = [change(ChangeKind::Modified(1, 2)), missing];
+        let result = apply_subscribed(&old, &changes, &mut subscriptions);
+        drop(subscriptions);
+        assert_eq!(result, Err(ApplyError::PathNotFound(vec![key("b")])));
+        assert_eq!(calls, 1);
+    }
+}
@@ -0,0 +1,557 @@
+use crate::patch::PatchHeader;
+use crate::recursive::blob::{Blob, BlobDelta};
+use crate::recursive::digest::LeafDigest;
+use crate::recursive::types::{Change, ChangeKind, Node, PathSegment, Primitive};
+use crate::serialization::{FromPatch, Location, PatchError, ToPatch};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Structural patches as text, one change per line: an operation,
+/// the path of the change and its values.
+/// ```text
+/// modify spec.replicas 3 -> 5
+/// add-node spec.containers[2] {image: nginx, ports: [80, 443]}
+/// remove metadata.labels.team core
+/// rename metadata name -> title
+/// move spec.containers 0 -> 2
+/// ```
+/// The operations are `add`, `add-node`, `remove`, `remove-node`, `modify`,
+/// `rename`, `move`, `truncated`, `patch-blob` and `truncated-leaf`, after the [`ChangeKind`]s;
+/// the bytes of a blob delta are written in hexadecimal, like a [`Blob`],
+/// and leaf digests as their hexadecimal hash and length, e.g. `00c0ffee00c0ffee:1048576`,
+/// or `-` for a leaf added or removed.
+/// Paths are written like in a [`Report`](crate::recursive::Report), and nodes as
+/// `{key: value}` maps, `[a, b]` sequences, `(a, b)` tuples, `#[a, b]` sets,
+/// `@Name content` variants and `null`. Leaves are written with `Display`
+/// and read back with `FromStr`; keys and leaves holding anything
+/// but letters, digits and `_-.:+` are quoted, e.g. `"a b"` or `"null"`.
+///
+/// The header is ignored, and parsing a written patch gives back the same changes.
+/// ```
+/// use std::collections::HashMap;
+/// use diffkit::patch::PatchHeader;
+/// use diffkit::recursive::{diff, Change};
+/// use diffkit::serialization::{FromPatch, ToPatch};
+///
+/// let old = HashMap::from([("name".to_string(), "a b".to_string())]);
+/// let new = HashMap::from([("name".to_string(), "c".to_string())]);
+/// let changes = diff(&old, &new);
+/// let text = changes.to_patch(&PatchHeader::default());
+/// assert_eq!(text, "modify name \"a b\" -> c");
+/// assert_eq!(Vec::<Change<String>>::from_patch(&text), Ok(changes));
+/// ```
+impl<P: Primitive + ToString> ToPatch for Vec<Change<P>> {
+    fn to_patch(&self, _: &PatchHeader) -> String {
+        self.iter()
+            .map(change_to_line)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+impl<P: Primitive + FromStr> FromPatch for Vec<Change<P>> {
+    fn from_patch(s: &str) -> Result<Self, PatchError> {
+        let mut changes = vec![];
+        let mut offset = 0;
+        for (i, line) in s.split('\n').enumerate() {
+            let content = line.strip_suffix('\r').unwrap_or(line);
+            let start = offset;
+            let location = || Location {
+                line: i + 1,
+                offset: start,
+                content: content.to_string(),
+            };
+            offset += line.len() + 1;
+            if content.trim().is_empty() {
+                continue;
+            }
+            let mut cursor = Cursor { s: content, pos: 0 };
+            let op = cursor.bare().unwrap_or_default();
+            if !OPERATIONS.contains(&op) {
+                return Err(PatchError::BadLinePrefix(location()));
+            }
+            let change = cursor
+                .change(op)
+                .ok_or_else(|| PatchError::BadValue(location()))?;
+            cursor.skip_spaces();
+            if cursor.pos < content.len() {
+                return Err(PatchError::BadValue(location()));
+            }
+            changes.push(change);
+        }
+        Ok(changes)
+    }
+}
+
+const OPERATIONS: [&str; 10] = [
+    "add",
+    "add-node",
+    "remove",
+    "remove-node",
+    "modify",
+    "rename",
+    "move",
+    "truncated",
+    "patch-blob",
+    "truncated-leaf",
+];
+
+fn change_to_line<P: Primitive + ToString>(change: &Change<P>) -> String {
+    let path = path_to_string(&change.path);
+    match &change.kind {
+        ChangeKind::Added(v) => format!("add {} {}", path, leaf(v)),
+        ChangeKind::NodeAdded(node) => format!("add-node {} {}", path, node_to_string(node)),
+        ChangeKind::Removed(v) => format!("remove {} {}", path, leaf(v)),
+        ChangeKind::NodeRemoved(node) => {
+            format!("remove-node {} {}", path, node_to_string(node))
+        }
+        ChangeKind::Modified(old, new) => format!("modify {} {} -> {}", path, leaf(old), leaf(new)),
+        ChangeKind::Renamed { from, to } => {
+            format!("rename {} {} -> {}", path, key(from), key(to))
+        }
+        ChangeKind::Moved {
+            from_index,
+            to_index,
+        } => format!("move {} {} -> {}", path, from_index, to_index),
+        ChangeKind::TruncatedSubtreeChanged { old_hash, new_hash } => {
+            format!("truncated {} {:016x} -> {:016x}", path, old_hash, new_hash)
+        }
+        ChangeKind::BlobPatched(delta) => format!(
+            "patch-blob {} {} {} -> {}",
+            path,
+            delta.offset,
+            leaf(&Blob(delta.removed.clone())),
+            leaf(&Blob(delta.inserted.clone()))
+        ),
+        ChangeKind::TruncatedLeafChanged { old, new } => {
+            format!("truncated-leaf {} {} -> {}", path, digest(old), digest(new))
+        }
+    }
+}
+
+fn digest(digest: &Option<LeafDigest>) -> String {
+    match digest {
+        Some(digest) => format!("{:016x}:{}", digest.hash, digest.len),
+        None => "-".to_string(),
+    }
+}
+
+fn path_to_string(path: &[PathSegment]) -> String {
+    if path.is_empty() {
+        return ".".to_string();
+    }
+    let mut s = String::new();
+    for (i, segment) in path.iter().enumerate() {
+        match segment {
+            PathSegment::Key(k) if is_bare_key(k) => {
+                if i > 0 {
+                    s.push('.');
+                }
+                s.push_str(k);
+            }
+            PathSegment::Key(k) => s.push_str(&format!("[{}]", quote(k))),
+            PathSegment::Index(index) => s.push_str(&format!("[{}]", index)),
+            PathSegment::Member => s.push_str("[*]"),
+        }
+    }
+    s
+}
+
+fn node_to_string<P: Primitive + ToString>(node: &Node<P>) -> String {
+    let list = |nodes: &[Node<P>]| {
+        nodes
+            .iter()
+            .map(node_to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    match node {
+        Node::Map(map) => {
+            let mut entries: Vec<(&String, &Node<P>)> = map.iter().collect();
+            entries.sort_by_key(|(k, _)| *k);
+            let entries: Vec<String> = entries
+                .into_iter()
+                .map(|(k, v)| format!("{}: {}", key(k), node_to_string(v)))
+                .collect();
+            format!("{{{}}}", entries.join(", "))
+        }
+        Node::Sequence(v) => format!("[{}]", list(v)),
+        Node::Tuple(v) => format!("({})", list(v)),
+        Node::Set(v) => format!("#[{}]", list(v)),
+        Node::Variant(tag, content) => format!("@{} {}", key(tag), node_to_string(content)),
+        Node::Leaf(v) => leaf(v),
+        Node::Null => "null".to_string(),
+    }
+}
+
+fn leaf<P: ToString>(v: &P) -> String {
+    let s = v.to_string();
+    if is_bare(&s) && s != "null" {
+        s
+    } else {
+        quote(&s)
+    }
+}
+
+fn key(k: &str) -> String {
+    if is_bare_key(k) {
+        k.to_string()
+    } else {
+        quote(k)
+    }
+}
+
+fn is_bare(s: &str) -> bool {
+    !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-.:+".contains(c))
+}
+
+fn is_bare_key(s: &str) -> bool {
+    !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-".contains(c))
+}
+
+fn quote(s: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Reads a line of a structural patch, returning `None` on malformed input.
+struct Cursor<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn peek(&self) -> Option<char> {
+        self.s[self.pos..].chars().next()
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.peek() == Some(' ') {
+            self.pos += 1;
+        }
+    }
+
+    /// Skips spaces, then consumes `token` if it comes next.
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_spaces();
+        if self.s[self.pos..].starts_with(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Option<()> {
+        self.eat(token).then_some(())
+    }
+
+    /// A bare word, after spaces.
+    fn bare(&mut self) -> Option<&'a str> {
+        self.skip_spaces();
+        let rest = &self.s[self.pos..];
+        let len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || "_-.:+".contains(c)))
+            .unwrap_or(rest.len());
+        self.pos += len;
+        (len > 0).then(|| &rest[..len])
+    }
+
+    fn quoted(&mut self) -> Option<String> {
+        self.expect("\"")?;
+        let mut s = String::new();
+        let mut chars = self.s[self.pos..].chars();
+        loop {
+            let c = chars.next()?;
+            self.pos += c.len_utf8();
+            match c {
+                '"' => return Some(s),
+                '\\' => {
+                    let escaped = chars.next()?;
+                    self.pos += escaped.len_utf8();
+                    s.push(match escaped {
+                        'n' => '\n',
+                        'r' => '\r',
+                        't' => '\t',
+                        c => c,
+                    });
+                }
+                c => s.push(c),
+            }
+        }
+    }
+
+    fn key(&mut self) -> Option<String> {
+        self.skip_spaces();
+        if self.peek() == Some('"') {
+            return self.quoted();
+        }
+        self.bare_key()
+    }
+
+    fn leaf<P: FromStr>(&mut self) -> Option<P> {
+        self.skip_spaces();
+        if self.peek() == Some('"') {
+            return self.quoted()?.parse().ok();
+        }
+        self.bare()?.parse().ok()
+    }
+
+    fn index(&mut self) -> Option<usize> {
+        self.bare()?.parse().ok()
+    }
+
+    fn path(&mut self) -> Option<Vec<PathSegment>> {
+        self.skip_spaces();
+        let mut path = vec![];
+        if self.s[self.pos..].starts_with(". ") || &self.s[self.pos..] == "." {
+            self.pos += 1;
+            return Some(path);
+        }
+        loop {
+            match self.peek() {
+                Some('[') => {
+                    self.pos += 1;
+                    if self.peek() == Some('"') {
+                        path.push(PathSegment::Key(self.quoted()?));
+                    } else if self.eat("*") {
+                        path.push(PathSegment::Member);
+                    } else {
+                        path.push(PathSegment::Index(self.index()?));
+                    }
+                    if !self.s[self.pos..].starts_with(']') {
+                        return None;
+                    }
+                    self.pos += 1;
+                }
+                Some('.') if !path.is_empty() => {
+                    self.pos += 1;
+                    path.push(PathSegment::Key(self.bare_key()?));
+                }
+                Some(_) if path.is_empty() => path.push(PathSegment::Key(self.bare_key()?)),
+                _ => return (!path.is_empty()).then_some(path),
+            }
+        }
+    }
+
+    /// A bare key, which ends at the next `.`, `[` or `:` in a path or a map.
+    fn bare_key(&mut self) -> Option<String> {
+        let rest = &self.s[self.pos..];
+        let len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || "_-".contains(c)))
+            .unwrap_or(rest.len());
+        self.pos += len;
+        (len > 0).then(|| rest[..len].to_string())
+    }
+
+    fn node<P: FromStr + Primitive>(&mut self) -> Option<Node<P>> {
+        if self.eat("{") {
+            let mut map = HashMap::new();
+            if !self.eat("}") {
+                loop {
+                    let k = self.key()?;
+                    self.expect(":")?;
+                    map.insert(k, self.node()?);
+                    if self.eat("}") {
+                        break;
+                    }
+                    self.expect(",")?;
+                }
+            }
+            Some(Node::Map(map))
+        } else if self.eat("#[") {
+            Some(Node::Set(self.list("]")?))
+        } else if self.eat("[") {
+            Some(Node::Sequence(self.list("]")?))
+        } else if self.eat("(") {
+            Some(Node::Tuple(self.list(")")?))
+        } else if self.eat("@") {
+            let tag = self.key()?;
+            Some(Node::Variant(tag, Box::new(self.node()?)))
+        } else {
+            let start = self.pos;
+            if self.bare() == Some("null") {
+                return Some(Node::Null);
+            }
+            self.pos = start;
+            self.leaf().map(Node::Leaf)
+        }
+    }
+
+    fn list<P: FromStr + Primitive>(&mut self, end: &str) -> Option<Vec<Node<P>>> {
+        let mut nodes = vec![];
+        if self.eat(end) {
+            return Some(nodes);
+        }
+        loop {
+            nodes.push(self.node()?);
+            if self.eat(end) {
+                return Some(nodes);
+            }
+            self.expect(",")?;
+        }
+    }
+
+    fn hash(&mut self) -> Option<u64> {
+        u64::from_str_radix(self.bare()?, 16).ok()
+    }
+
+    fn digest(&mut self) -> Option<Option<LeafDigest>> {
+        let word = self.bare()?;
+        if word == "-" {
+            return Some(None);
+        }
+        let (hash, len) = word.split_once(':')?;
+        Some(Some(LeafDigest {
+            hash: u64::from_str_radix(hash, 16).ok()?,
+            len: len.parse().ok()?,
+        }))
+    }
+
+    fn change<P: FromStr + Primitive>(&mut self, op: &str) -> Option<Change<P>> {
+        let path = self.path()?;
+        let kind = match op {
+            "add" => ChangeKind::Added(self.leaf()?),
+            "add-node" => ChangeKind::NodeAdded(self.node()?),
+            "remove" => ChangeKind::Removed(self.leaf()?),
+            "remove-node" => ChangeKind::NodeRemoved(self.node()?),
+            "modify" => {
+                let old = self.leaf()?;
+                self.expect("->")?;
+                ChangeKind::Modified(old, self.leaf()?)
+            }
+            "rename" => {
+                let from = self.key()?;
+                self.expect("->")?;
+                ChangeKind::Renamed {
+                    from,
+                    to: self.key()?,
+                }
+            }
+            "move" => {
+                let from_index = self.index()?;
+                self.expect("->")?;
+                ChangeKind::Moved {
+                    from_index,
+                    to_index: self.index()?,
+                }
+            }
+            "truncated" => {
+                let old_hash = self.hash()?;
+                self.expect("->")?;
+                ChangeKind::TruncatedSubtreeChanged {
+                    old_hash,
+                    new_hash: self.hash()?,
+                }
+            }
+            "patch-blob" => {
+                let offset = self.index()?;
+                let removed = self.leaf::<Blob>()?;
+                self.expect("->")?;
+                ChangeKind::BlobPatched(BlobDelta {
+                    offset,
+                    removed: removed.0,
+                    inserted: self.leaf::<Blob>()?.0,
+                })
+            }
+            "truncated-leaf" => {
+                let old = self.digest()?;
+                self.expect("->")?;
+                ChangeKind::TruncatedLeafChanged {
+                    old,
+                    new: self.digest()?,
+                }
+            }
+            _ => return None,
+        };
+        Some(Change { path, kind })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(k: &str) -> PathSegment {
+        PathSegment::Key(k.to_string())
+    }
+
+    #[test]
+    fn test_nodes_round_trip() {
+        let node: Node<String> = Node::Map(HashMap::from([
+            (
+                "a b".to_string(),
+                Node::Sequence(vec![Node::Leaf("null".to_string()), Node::Null]),
+            ),
+            (
+                "c".to_string(),
+                Node::Tuple(vec![
+                    Node::Set(vec![Node::Leaf("x, y".to_string())]),
+                    Node::Variant("Some".to_string(), Box::new(Node::Leaf(String::new()))),
+                ]),
+            ),
+        ]));
+        let changes = vec![
+            Change {
+                path: vec![key("spec"), PathSegment::Index(2), key("a.b")],
+                kind: ChangeKind::NodeAdded(node),
+            },
+            Change {
+                path: vec![key("tags"), PathSegment::Member],
+                kind: ChangeKind::Removed("line\n\"quoted\"".to_string()),
+            },
+            Change {
+                path: vec![],
+                kind: ChangeKind::TruncatedSubtreeChanged {
+                    old_hash: 1,
+                    new_hash: u64::MAX,
+                },
+            },
+        ];
+        let text = changes.to_patch(&PatchHeader::default());
+        assert_eq!(
+            text.lines().next(),
+            Some(
+                "add-node spec[2][\"a.b\"] {\"a b\": [\"null\", null], \
+                 c: (#[\"x, y\"], @Some \"\")}"
+            )
+        );
+        assert_eq!(Vec::<Change<String>>::from_patch(&text), Ok(changes));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = Vec::<Change<i32>>::from_patch("move a 0 -> 1\r\nreplace a 1");
+        assert_eq!(
+            error,
+            Err(PatchError::BadLinePrefix(Location {
+                line: 2,
+                offset: 15,
+                content: "replace a 1".to_string(),
+            }))
+        );
+        assert!(matches!(
+            Vec::<Change<i32>>::from_patch("modify a x -> 1"),
+            Err(PatchError::BadValue(_))
+        ));
+        assert!(matches!(
+            Vec::<Change<i32>>::from_patch("add a 1 2"),
+            Err(PatchError::BadValue(_))
+        ));
+    }
+}
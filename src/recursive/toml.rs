@@ -0,0 +1,190 @@
+use crate::recursive::diffable::Diffable;
+use crate::recursive::types::{ApplyError, Node, PathSegment, Primitive};
+use ::toml::value::Datetime;
+use ::toml::{Table, Value};
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+/// A leaf of a TOML document: a `toml::Value` other than an array or a table.
+///
+/// Floats are compared by their bits, so `nan` equals itself
+/// while `0.0` and `-0.0` differ.
+#[derive(Clone, Debug)]
+pub enum TomlLeaf {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Datetime(Datetime),
+}
+
+impl PartialEq for TomlLeaf {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (TomlLeaf::String(a), TomlLeaf::String(b)) => a == b,
+            (TomlLeaf::Integer(a), TomlLeaf::Integer(b)) => a == b,
+            (TomlLeaf::Float(a), TomlLeaf::Float(b)) => a.to_bits() == b.to_bits(),
+            (TomlLeaf::Boolean(a), TomlLeaf::Boolean(b)) => a == b,
+            (TomlLeaf::Datetime(a), TomlLeaf::Datetime(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for TomlLeaf {}
+
+impl Hash for TomlLeaf {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            TomlLeaf::String(v) => v.hash(state),
+            TomlLeaf::Integer(v) => v.hash(state),
+            TomlLeaf::Float(v) => v.to_bits().hash(state),
+            TomlLeaf::Boolean(v) => v.hash(state),
+            TomlLeaf::Datetime(v) => v.to_string().hash(state),
+        }
+    }
+}
+
+impl Primitive for TomlLeaf {}
+
+impl From<TomlLeaf> for Value {
+    fn from(leaf: TomlLeaf) -> Self {
+        match leaf {
+            TomlLeaf::String(v) => Value::String(v),
+            TomlLeaf::Integer(v) => Value::Integer(v),
+            TomlLeaf::Float(v) => Value::Float(v),
+            TomlLeaf::Boolean(v) => Value::Boolean(v),
+            TomlLeaf::Datetime(v) => Value::Datetime(v),
+        }
+    }
+}
+
+/// TOML values: tables become maps, arrays (including arrays of tables)
+/// sequences, and the other values [`TomlLeaf`]s.
+/// ```
+/// use diffkit::recursive::{diff, ChangeKind, PathSegment, TomlLeaf};
+///
+/// let old: toml::Table = "[[bin]]\nname = \"a\"\n".parse().unwrap();
+/// let new: toml::Table = "[[bin]]\nname = \"b\"\n".parse().unwrap();
+/// let changes = diff(&old, &new);
+/// assert_eq!(
+///     changes[0].path,
+///     vec![
+///         PathSegment::Key("bin".to_string()),
+///         PathSegment::Index(0),
+///         PathSegment::Key("name".to_string()),
+///     ]
+/// );
+/// assert_eq!(
+///     changes[0].kind,
+///     ChangeKind::Modified(TomlLeaf::String("a".into()), TomlLeaf::String("b".into()))
+/// );
+/// ```
+impl Diffable for Value {
+    type P = TomlLeaf;
+    fn to_node(&self) -> Node<Self::P> {
+        match self {
+            Value::Table(table) => table.to_node(),
+            Value::Array(array) => Node::Sequence(array.iter().map(Diffable::to_node).collect()),
+            Value::String(v) => Node::Leaf(TomlLeaf::String(v.clone())),
+            Value::Integer(v) => Node::Leaf(TomlLeaf::Integer(*v)),
+            Value::Float(v) => Node::Leaf(TomlLeaf::Float(*v)),
+            Value::Boolean(v) => Node::Leaf(TomlLeaf::Boolean(*v)),
+            Value::Datetime(v) => Node::Leaf(TomlLeaf::Datetime(*v)),
+        }
+    }
+
+    fn from_node(node: Node<Self::P>) -> Result<Self, ApplyError> {
+        match node {
+            Node::Map(_) => Table::from_node(node).map(Value::Table),
+            Node::Sequence(sequence) | Node::Tuple(sequence) | Node::Set(sequence) => sequence
+                .into_iter()
+                .enumerate()
+                .map(|(i, v)| Self::from_node(v).map_err(|e| e.within(PathSegment::Index(i))))
+                .collect::<Result<_, _>>()
+                .map(Value::Array),
+            Node::Leaf(leaf) => Ok(leaf.into()),
+            Node::Null => Err(ApplyError::TypeMismatch(vec![])),
+            Node::Variant(tag, content) => {
+                Self::from_node(Node::Map(HashMap::from([(tag, *content)])))
+            }
+        }
+    }
+}
+
+/// A TOML document, as parsed with `str::parse::<toml::Table>`.
+impl Diffable for Table {
+    type P = TomlLeaf;
+    fn to_node(&self) -> Node<Self::P> {
+        Node::Map(self.iter().map(|(k, v)| (k.clone(), v.to_node())).collect())
+    }
+
+    fn from_node(node: Node<Self::P>) -> Result<Self, ApplyError> {
+        match node {
+            Node::Map(map) => map
+                .into_iter()
+                .map(|(k, v)| match Value::from_node(v) {
+                    Ok(v) => Ok((k, v)),
+                    Err(e) => Err(e.within(PathSegment::Key(k))),
+                })
+                .collect(),
+            _ => Err(ApplyError::TypeMismatch(vec![])),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recursive::{apply, diff, ChangeKind};
+
+    fn parse(s: &str) -> Table {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_cargo_manifest_drift() {
+        let old = parse(
+            r#"
+            [package]
+            name = "app"
+            version = "0.1.0"
+
+            [dependencies]
+            serde = "1"
+            tokio = { version = "1", features = ["rt"] }
+            "#,
+        );
+        let new = parse(
+            r#"
+            [package]
+            name = "app"
+            version = "0.2.0"
+
+            [dependencies]
+            tokio = { version = "1", features = ["rt", "macros"] }
+            "#,
+        );
+        let changes = diff(&old, &new);
+        assert_eq!(changes.len(), 3);
+        assert!(changes.iter().any(|c| c.path
+            == vec![
+                PathSegment::Key("dependencies".to_string()),
+                PathSegment::Key("tokio".to_string()),
+                PathSegment::Key("features".to_string()),
+                PathSegment::Index(1),
+            ]
+            && c.kind == ChangeKind::Added(TomlLeaf::String("macros".to_string()))));
+        assert_eq!(apply(&old, &changes), Ok(new));
+    }
+
+    #[test]
+    fn test_leaves() {
+        let old = parse("at = 1979-05-27T07:32:00Z\nratio = nan\nzero = 0.0\n");
+        let new = parse("at = 1979-05-27T07:32:01Z\nratio = nan\nzero = -0.0\n");
+        let changes = diff(&old, &new);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(apply(&old, &changes).unwrap().to_string(), new.to_string());
+    }
+}
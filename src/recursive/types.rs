@@ -1,5 +1,12 @@
-use crate::myers::types::Edit;
+use crate::recursive::blob::BlobDelta;
+use crate::recursive::digest::LeafDigest;
+use crate::recursive::path::PathPattern;
+use crate::recursive::policy::{Policies, Policy};
+use crate::recursive::redact::redact_changes;
 use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::sync::Arc;
 
 /// Represents a single change in a possibly recursive structure
 #[derive(Clone, Eq, PartialEq, Debug)]
@@ -8,24 +15,605 @@ pub struct Change<P: Primitive> {
     pub kind: ChangeKind<P>,
 }
 
-/// Represents either a list index or a map key
+impl<P: Primitive> Change<P> {
+    /// The change undoing this one, at the same path:
+    /// additions become removals and modifications are swapped.
+    pub fn invert(&self) -> Change<P> {
+        let kind = match &self.kind {
+            ChangeKind::Added(v) => ChangeKind::Removed(v.clone()),
+            ChangeKind::NodeAdded(v) => ChangeKind::NodeRemoved(v.clone()),
+            ChangeKind::Removed(v) => ChangeKind::Added(v.clone()),
+            ChangeKind::NodeRemoved(v) => ChangeKind::NodeAdded(v.clone()),
+            ChangeKind::Modified(old, new) => ChangeKind::Modified(new.clone(), old.clone()),
+            ChangeKind::Renamed { from, to } => ChangeKind::Renamed {
+                from: to.clone(),
+                to: from.clone(),
+            },
+            ChangeKind::Moved {
+                from_index,
+                to_index,
+            } => ChangeKind::Moved {
+                from_index: *to_index,
+                to_index: *from_index,
+            },
+            ChangeKind::TruncatedSubtreeChanged { old_hash, new_hash } => {
+                ChangeKind::TruncatedSubtreeChanged {
+                    old_hash: *new_hash,
+                    new_hash: *old_hash,
+                }
+            }
+            ChangeKind::BlobPatched(delta) => ChangeKind::BlobPatched(delta.invert()),
+            ChangeKind::TruncatedLeafChanged { old, new } => ChangeKind::TruncatedLeafChanged {
+                old: *new,
+                new: *old,
+            },
+        };
+        Change {
+            path: self.path.clone(),
+            kind,
+        }
+    }
+}
+
+/// Options of the recursive differ for trees with `P` leaves,
+/// see [`diff_with`](crate::recursive::diff_with). Built from the defaults with its builder methods:
+/// `DiffOptions::new().ignore(pattern).max_depth(2)`.
+///
+/// `ignore` lists the paths skipped by the differ, along with everything below them.
+/// Sequence elements are always reported when added or removed,
+/// since the indices of the following changes depend on them.
+///
+/// `ignore_keys` lists map keys skipped at any depth, like `**/key` patterns,
+/// e.g. volatile fields such as `updated_at` or `etag`.
+///
+/// `max_depth` stops the differ at paths of that length: values there are compared whole,
+/// and replaced if they differ.
+///
+/// `detect_renames` reports a map key removed while another one is added
+/// with the same value as [`ChangeKind::Renamed`].
+///
+/// `detect_moves` reports a sequence element removed while an equal one is added
+/// elsewhere as [`ChangeKind::Moved`].
+///
+/// `truncate` also stops the differ at paths of that length, but only reports that
+/// values there differ, with a [`ChangeKind::TruncatedSubtreeChanged`] marker
+/// for anything but two leaves. Values are compared by [`Node::structural_hash`].
+///
+/// `leaf_eq` replaces `==` wherever two leaves are diffed against each other,
+/// so that e.g. strings differing only by case aren't `Modified`.
+/// Sequence alignment, set members, and values compared whole at `max_depth`
+/// still use `==`.
+///
+/// `normalizer` rewrites every node of both trees before they are compared,
+/// see [`DiffOptions::normalize`].
+///
+/// `sequence_strategy` chooses how the elements of two sequences are paired,
+/// see [`SequenceStrategy`].
+///
+/// `hash_subtrees` hashes every node of both trees up front, Merkle-style,
+/// and skips the subtrees with equal [`Node::structural_hash`]es, once compared
+/// with `==`, instead of diffing them.
+///
+/// `truncate_leaves` replaces the string and blob leaves longer than that many bytes
+/// with their [`LeafDigest`] in the changes, see [`DiffOptions::truncate_leaves`].
+///
+/// `redaction` hides the values of the changes under some paths,
+/// see [`DiffOptions::redact`].
+///
+/// `blob_deltas` reports modified [`Blob`](crate::recursive::Blob) leaves as
+/// [`ChangeKind::BlobPatched`], holding only the bytes that differ.
+///
+/// `policies` ignore, compare whole, or pair the sequence elements of the values
+/// at some paths differently from the rest, see [`Policies`].
 #[derive(Clone, Eq, PartialEq, Debug)]
+#[non_exhaustive]
+pub struct DiffOptions<P: Primitive> {
+    pub ignore: Vec<PathPattern>,
+    pub ignore_keys: Vec<String>,
+    pub max_depth: Option<usize>,
+    pub detect_renames: bool,
+    pub detect_moves: bool,
+    pub truncate: Option<usize>,
+    pub leaf_eq: Option<LeafComparator<P>>,
+    pub normalizer: Option<Normalizer<P>>,
+    pub redaction: Option<Redaction<P>>,
+    pub truncate_leaves: Option<usize>,
+    pub blob_deltas: bool,
+    pub sequence_strategy: SequenceStrategy,
+    pub hash_subtrees: bool,
+    pub policies: Policies,
+}
+
+impl<P: Primitive> Default for DiffOptions<P> {
+    fn default() -> Self {
+        DiffOptions {
+            ignore: Vec::new(),
+            ignore_keys: Vec::new(),
+            max_depth: None,
+            detect_renames: false,
+            detect_moves: false,
+            truncate: None,
+            leaf_eq: None,
+            normalizer: None,
+            redaction: None,
+            truncate_leaves: None,
+            blob_deltas: false,
+            sequence_strategy: SequenceStrategy::default(),
+            hash_subtrees: false,
+            policies: Policies::default(),
+        }
+    }
+}
+
+impl<P: Primitive> DiffOptions<P> {
+    /// The default options: nothing ignored, no maximum depth.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Skips the paths matching `pattern`.
+    pub fn ignore(mut self, pattern: PathPattern) -> Self {
+        self.ignore.push(pattern);
+        self
+    }
+
+    /// Skips the map keys named `keys` wherever they are found.
+    /// ```
+    /// use std::collections::HashMap;
+    /// use diffkit::recursive::{diff_with, DiffOptions};
+    ///
+    /// let item = |revision: i32, size: i32| {
+    ///     HashMap::from([("revision".to_string(), revision), ("size".to_string(), size)])
+    /// };
+    /// let old = HashMap::from([("a".to_string(), item(1, 10)), ("b".to_string(), item(1, 20))]);
+    /// let new = HashMap::from([("a".to_string(), item(2, 10)), ("b".to_string(), item(3, 21))]);
+    /// let options = DiffOptions::new().ignore_keys(["revision", "etag"]);
+    /// assert_eq!(diff_with(&old, &new, &options).len(), 1);
+    /// ```
+    pub fn ignore_keys(mut self, keys: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.ignore_keys.extend(keys.into_iter().map(Into::into));
+        self
+    }
+
+    /// Compares the values at paths of length `depth` whole.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Reports renamed map keys as [`ChangeKind::Renamed`].
+    pub fn detect_renames(mut self) -> Self {
+        self.detect_renames = true;
+        self
+    }
+
+    /// Reports elements moved within a sequence as [`ChangeKind::Moved`].
+    pub fn detect_moves(mut self) -> Self {
+        self.detect_moves = true;
+        self
+    }
+
+    /// Only reports which values differ at paths of length `depth`,
+    /// e.g. for a quick preview of the changes to a large document.
+    pub fn truncate(mut self, depth: usize) -> Self {
+        self.truncate = Some(depth);
+        self
+    }
+
+    /// Only reports which top-level values differ, like `truncate(1)`:
+    /// a quick answer to "which sections changed?" on a large document.
+    /// ```
+    /// use std::collections::HashMap;
+    /// use diffkit::recursive::{diff_with, ChangeKind, DiffOptions, PathSegment};
+    ///
+    /// let section = |k: &str, v| HashMap::from([(k.to_string(), v)]);
+    /// let old = HashMap::from([
+    ///     ("spec".to_string(), section("replicas", 3)),
+    ///     ("status".to_string(), section("ready", 1)),
+    /// ]);
+    /// let mut new = old.clone();
+    /// new.insert("spec".to_string(), section("replicas", 5));
+    /// let changes = diff_with(&old, &new, &DiffOptions::new().shallow());
+    /// assert_eq!(changes.len(), 1);
+    /// assert_eq!(changes[0].path, vec![PathSegment::Key("spec".to_string())]);
+    /// assert!(matches!(changes[0].kind, ChangeKind::TruncatedSubtreeChanged { .. }));
+    /// ```
+    pub fn shallow(self) -> Self {
+        self.truncate(1)
+    }
+
+    /// Reports modified blobs as [`ChangeKind::BlobPatched`], e.g. so that
+    /// a small edit to a large image doesn't hold two copies of it.
+    /// ```
+    /// use diffkit::recursive::{diff_with, Blob, ChangeKind, DiffOptions};
+    ///
+    /// let old = vec![Blob(vec![0; 1000])];
+    /// let mut new = old.clone();
+    /// new[0].0[500] = 1;
+    /// let changes = diff_with(&old, &new, &DiffOptions::new().blob_deltas());
+    /// let ChangeKind::BlobPatched(delta) = &changes[0].kind else { panic!() };
+    /// assert_eq!((delta.offset, &delta.inserted[..]), (500, &[1][..]));
+    /// ```
+    pub fn blob_deltas(mut self) -> Self {
+        self.blob_deltas = true;
+        self
+    }
+
+    /// Skips the equal subtrees, found by their hashes, e.g. for large documents
+    /// where most values are unchanged. Hashing both trees takes a pass over them,
+    /// and subtrees with equal hashes are still compared before being skipped,
+    /// so a collision can't hide a change.
+    pub fn hash_subtrees(mut self) -> Self {
+        self.hash_subtrees = true;
+        self
+    }
+
+    /// Pairs the elements of sequences following `strategy`.
+    pub fn sequence_strategy(mut self, strategy: SequenceStrategy) -> Self {
+        self.sequence_strategy = strategy;
+        self
+    }
+
+    /// Handles the values at the paths matching `pattern` following `policy`,
+    /// after the policies added before.
+    pub fn policy(mut self, pattern: PathPattern, policy: Policy) -> Self {
+        self.policies.0.push((pattern, policy));
+        self
+    }
+
+    /// Adds `policies` after the policies added before,
+    /// e.g. ones parsed from a configuration file.
+    pub fn policies(mut self, policies: Policies) -> Self {
+        self.policies.0.extend(policies.0);
+        self
+    }
+
+    /// Compares leaves with `eq` instead of `==`.
+    /// ```
+    /// use diffkit::recursive::{diff_with, DiffOptions};
+    ///
+    /// let options = DiffOptions::new().compare_leaves(|a: &String, b: &String| {
+    ///     a.eq_ignore_ascii_case(b)
+    /// });
+    /// let old = vec!["Ada".to_string(), "Bob".to_string()];
+    /// let new = vec!["ada".to_string(), "Bob".to_string()];
+    /// assert!(diff_with(&old, &new, &options).is_empty());
+    /// ```
+    pub fn compare_leaves(mut self, eq: impl Fn(&P, &P) -> bool + Send + Sync + 'static) -> Self {
+        self.leaf_eq = Some(LeafComparator(Arc::new(eq)));
+        self
+    }
+
+    /// Runs `f` over every node of both trees before diffing them, e.g. to trim
+    /// strings or round numbers, like [`Node::walk_mut`]: parents before their
+    /// children, which are found after `f` modified the parent.
+    /// The values being diffed aren't modified, but the changes hold normalized values.
+    /// ```
+    /// use std::collections::HashMap;
+    /// use diffkit::recursive::{diff_with, DiffOptions, Node, PathSegment};
+    ///
+    /// let options = DiffOptions::new().normalize(|_: &[PathSegment], node: &mut Node<String>| {
+    ///     if let Node::Leaf(s) = node {
+    ///         *s = s.trim().to_string();
+    ///     }
+    /// });
+    /// let old = HashMap::from([("name".to_string(), "ada".to_string())]);
+    /// let new = HashMap::from([("name".to_string(), " ada\n".to_string())]);
+    /// assert!(diff_with(&old, &new, &options).is_empty());
+    /// ```
+    pub fn normalize(
+        mut self,
+        f: impl Fn(&[PathSegment], &mut Node<P>) + Send + Sync + 'static,
+    ) -> Self {
+        self.normalizer = Some(Normalizer(Arc::new(f)));
+        self
+    }
+
+    /// Leaves the `String`, `Scalar::String` and [`Blob`](crate::recursive::Blob) leaves
+    /// longer than `max_len` bytes out of the changes, e.g. so that a change to
+    /// a large embedded document doesn't hold two copies of it: their changes become
+    /// [`ChangeKind::TruncatedLeafChanged`], with a digest of each version.
+    /// These changes can't be applied; diff without this option to get the full values.
+    /// ```
+    /// use diffkit::recursive::{diff_with, ChangeKind, DiffOptions, LeafDigest};
+    ///
+    /// let old = vec!["a".repeat(1000), "b".to_string()];
+    /// let new = vec!["c".repeat(1000), "d".to_string()];
+    /// let changes = diff_with(&old, &new, &DiffOptions::new().truncate_leaves(100));
+    /// let ChangeKind::TruncatedLeafChanged { new: Some(digest), .. } = changes[0].kind else {
+    ///     panic!()
+    /// };
+    /// assert_eq!(digest, LeafDigest::of(&new[0]).unwrap());
+    /// assert_eq!(digest.len, 1000);
+    /// assert_eq!(changes[1].kind, ChangeKind::Modified("b".to_string(), "d".to_string()));
+    /// ```
+    pub fn truncate_leaves(mut self, max_len: usize) -> Self {
+        self.truncate_leaves = Some(max_len);
+        self
+    }
+
+    /// Replaces the values of the changes under the paths matching `pattern` with
+    /// `placeholder`, e.g. [`REDACTED`](crate::recursive::REDACTED) for passwords
+    /// and tokens, like [`redact_changes`](crate::recursive::redact_changes).
+    /// Patterns add up, and the last placeholder is used.
+    /// ```
+    /// use std::collections::HashMap;
+    /// use diffkit::recursive::{diff_with, ChangeKind, DiffOptions, REDACTED};
+    ///
+    /// let old = HashMap::from([("token".to_string(), "abc".to_string())]);
+    /// let new = HashMap::from([("token".to_string(), "def".to_string())]);
+    /// let options = DiffOptions::new().redact("token".parse().unwrap(), REDACTED.to_string());
+    /// let changes = diff_with(&old, &new, &options);
+    /// assert_eq!(changes[0].kind, ChangeKind::Modified(REDACTED.into(), REDACTED.into()));
+    /// ```
+    pub fn redact(mut self, pattern: PathPattern, placeholder: P) -> Self {
+        let mut patterns = self
+            .redaction
+            .take()
+            .map(|redaction| redaction.patterns)
+            .unwrap_or_default();
+        patterns.push(pattern);
+        self.redaction = Some(Redaction {
+            patterns,
+            placeholder,
+        });
+        self
+    }
+
+    /// Whether the value at `path` is skipped.
+    pub fn ignores(&self, path: &[PathSegment]) -> bool {
+        let ignored_key = match path.last() {
+            Some(PathSegment::Key(key)) => self.ignore_keys.contains(key),
+            _ => false,
+        };
+        ignored_key
+            || self.ignore.iter().any(|pattern| pattern.matches(path))
+            || self.policies.get(path) == Some(&Policy::Ignore)
+    }
+
+    /// Whether the values at `path` are compared whole, following `max_depth`
+    /// and `policies`.
+    pub fn compares_whole(&self, path: &[PathSegment]) -> bool {
+        self.max_depth.is_some_and(|depth| path.len() >= depth)
+            || self.policies.get(path) == Some(&Policy::Whole)
+    }
+
+    /// How the elements of the sequences at `path` are paired,
+    /// following `sequence_strategy` and `policies`.
+    pub fn sequence_strategy_at(&self, path: &[PathSegment]) -> &SequenceStrategy {
+        match self.policies.get(path) {
+            Some(Policy::Sequences(strategy)) => strategy,
+            _ => &self.sequence_strategy,
+        }
+    }
+
+    /// Whether two leaves are equal, following `leaf_eq`.
+    pub fn leaves_equal(&self, a: &P, b: &P) -> bool {
+        match &self.leaf_eq {
+            Some(comparator) => comparator.eq(a, b),
+            None => a == b,
+        }
+    }
+}
+
+/// Options of [`apply_with`](crate::recursive::apply_with), built like [`DiffOptions`]:
+/// `ApplyOptions::new().strict()`.
+///
+/// `strict` checks the old values recorded in the changes,
+/// see [`apply_strict`](crate::recursive::apply_strict).
+///
+/// `create_missing` inserts the maps missing on the path of an addition,
+/// like `mkdir -p`, instead of failing with [`ApplyError::PathNotFound`].
+/// Only maps are created, for paths made of keys.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+#[non_exhaustive]
+pub struct ApplyOptions {
+    pub strict: bool,
+    pub create_missing: bool,
+}
+
+impl ApplyOptions {
+    /// The default options, those of [`apply`](crate::recursive::apply).
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Checks that the changes find the values they recorded.
+    pub fn strict(mut self) -> Self {
+        self.strict = true;
+        self
+    }
+
+    /// Creates the maps missing on the path of an addition.
+    pub fn create_missing(mut self) -> Self {
+        self.create_missing = true;
+        self
+    }
+}
+
+/// How the elements of two sequences are paired before being diffed,
+/// set by [`DiffOptions::sequence_strategy`].
+///
+/// Paired elements are diffed recursively, at the index of the new element.
+/// The other ones are removed or added.
+/// ```
+/// use std::collections::HashMap;
+/// use diffkit::recursive::{diff_with, ChangeKind, DiffOptions, PathSegment, SequenceStrategy};
+///
+/// let user = |id: i32, age: i32| HashMap::from([("id".to_string(), id), ("age".to_string(), age)]);
+/// let old = vec![user(1, 30), user(2, 40)];
+/// let new = vec![user(2, 41)];
+/// let options = DiffOptions::new().sequence_strategy(SequenceStrategy::Keyed("id".to_string()));
+/// let changes = diff_with(&old, &new, &options);
+/// assert_eq!(changes.len(), 2);
+/// assert_eq!(changes[0].path, vec![PathSegment::Index(0)]);
+/// assert_eq!(changes[1].path, vec![PathSegment::Index(0), PathSegment::Key("age".to_string())]);
+/// assert_eq!(changes[1].kind, ChangeKind::Modified(40, 41));
+/// ```
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub enum SequenceStrategy {
+    /// Aligns whole elements with Myers, pairing the elements
+    /// replaced in the same place.
+    #[default]
+    Myers,
+    /// Aligns elements with Myers on the value of a map key, e.g. an `id`,
+    /// so that elements with the same value are paired wherever their content changed.
+    /// Elements without the key are compared whole.
+    Keyed(String),
+    /// Like `Keyed`, on the values of several map keys, e.g. the columns
+    /// of a composite key. Elements missing any of the keys are compared whole.
+    KeyedBy(Vec<String>),
+    /// Pairs elements by index, e.g. for time series or fixed-length records,
+    /// then removes or adds the elements past the end of the shorter sequence.
+    /// `detect_moves` doesn't apply.
+    Positional,
+    /// Treats sequences as multisets, e.g. lists of tags or dependencies:
+    /// only the elements found more times in one sequence than in the other
+    /// are removed, or added at the end, so reordering isn't a change.
+    /// The changes give back the new elements, in the order of the old sequence.
+    /// The count of an element is carried by its changes: one per occurrence
+    /// found in excess, e.g. two `Added` changes for a tag added twice,
+    /// so that they apply, invert and export like any other changes.
+    /// `detect_moves` doesn't apply.
+    Unordered,
+}
+
+type LeafFn<P> = dyn Fn(&P, &P) -> bool + Send + Sync;
+
+/// A comparison of `P` leaves, set by [`DiffOptions::compare_leaves`].
+///
+/// Comparators are equal when they are clones of each other.
+pub struct LeafComparator<P>(Arc<LeafFn<P>>);
+
+impl<P> LeafComparator<P> {
+    /// Compares `a` and `b`.
+    fn eq(&self, a: &P, b: &P) -> bool {
+        (self.0)(a, b)
+    }
+}
+
+impl<P> Clone for LeafComparator<P> {
+    fn clone(&self) -> Self {
+        LeafComparator(Arc::clone(&self.0))
+    }
+}
+
+impl<P> PartialEq for LeafComparator<P> {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl<P> Eq for LeafComparator<P> {}
+
+impl<P> fmt::Debug for LeafComparator<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("LeafComparator(..)")
+    }
+}
+
+type NormalizeFn<P> = dyn Fn(&[PathSegment], &mut Node<P>) + Send + Sync;
+
+/// A rewrite of the nodes of trees with `P` leaves, set by [`DiffOptions::normalize`].
+///
+/// Normalizers are equal when they are clones of each other.
+pub struct Normalizer<P: Primitive>(Arc<NormalizeFn<P>>);
+
+impl<P: Primitive> Normalizer<P> {
+    /// Rewrites every node of `tree`.
+    pub fn apply(&self, tree: &mut Node<P>) {
+        tree.walk_mut(&mut |path: &[PathSegment], node: &mut Node<P>| (self.0)(path, node));
+    }
+}
+
+impl<P: Primitive> Clone for Normalizer<P> {
+    fn clone(&self) -> Self {
+        Normalizer(Arc::clone(&self.0))
+    }
+}
+
+impl<P: Primitive> PartialEq for Normalizer<P> {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl<P: Primitive> Eq for Normalizer<P> {}
+
+impl<P: Primitive> fmt::Debug for Normalizer<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Normalizer(..)")
+    }
+}
+
+/// The paths whose values are hidden from changes, set by [`DiffOptions::redact`],
+/// with the placeholder replacing them.
+#[derive(Clone, Eq, PartialEq)]
+pub struct Redaction<P: Primitive> {
+    patterns: Vec<PathPattern>,
+    placeholder: P,
+}
+
+impl<P: Primitive> Redaction<P> {
+    /// Redacts `changes`.
+    pub fn apply(&self, changes: &mut [Change<P>]) {
+        redact_changes(changes, &self.patterns, &self.placeholder);
+    }
+}
+
+impl<P: Primitive> fmt::Debug for Redaction<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Redaction")
+            .field("patterns", &self.patterns)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Represents either a list index, a map key or a set member
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
 pub enum PathSegment {
     Key(String),  // map key
     Index(usize), // sequence index
+    /// A member of a set, added or removed by the change holding it
+    Member,
+}
+
+impl fmt::Display for PathSegment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathSegment::Key(key) => write!(f, "{}", key),
+            PathSegment::Index(index) => write!(f, "{}", index),
+            PathSegment::Member => write!(f, "*"),
+        }
+    }
 }
 
 /// Represents a change in a possibly recursive structure.
 ///
 /// `Added`, `Removed`, `Modified` are actions on leaves.
 /// `NodeAdded`, `NodeRemoved` are actions on nodes.
-/// `SequenceChange` contains the raw Myers edit script for a sequence.
+/// `Renamed` moves the value of the key `from` to the key `to`
+/// in the map found at its path.
+/// `Moved` takes the element at `from_index` out of the sequence found at its path
+/// and inserts it back so that it ends up at `to_index`.
+/// `TruncatedSubtreeChanged` only marks a value that changed below the depth
+/// the differ was told to stop at, with the [`Node::structural_hash`] of both versions;
+/// it can't be applied.
+/// `BlobPatched` modifies a [`Blob`](crate::recursive::Blob) leaf with a [`BlobDelta`]
+/// instead of holding both versions of it.
+/// `TruncatedLeafChanged` stands for the addition, removal or modification of a leaf
+/// too large to be kept, with a [`LeafDigest`] of each version it has; it can't be applied.
+///
+/// Inside a sequence, the path ends with the [`PathSegment::Index`] of the element.
+/// Changes are applied in order, so an index points into the sequence
+/// as left by the changes before it: adding shifts the following elements
+/// to the right, removing shifts them to the left.
 ///
 /// # Note
 ///
-/// We don't diff recursively inside lists as Rust lacks facilities
-/// to dispatch between `Vec<Primitive>` and `Vec<Node<Primitive>>`.
-/// For this reason we always apply Myers.
+/// By default, sequence elements are aligned with Myers on whole elements; elements replaced
+/// in the same place are then diffed recursively, like values under the same map key.
+/// Elements of a [`Node::Tuple`] are paired by position instead.
 #[derive(Clone, Eq, PartialEq, Debug)]
 pub enum ChangeKind<P: Primitive> {
     Added(P),
@@ -33,7 +621,23 @@ pub enum ChangeKind<P: Primitive> {
     Removed(P),
     NodeRemoved(Node<P>),
     Modified(P, P), // old, new
-    SequenceChange(Vec<Edit<Node<P>>>),
+    Renamed {
+        from: String,
+        to: String,
+    },
+    Moved {
+        from_index: usize,
+        to_index: usize,
+    },
+    TruncatedSubtreeChanged {
+        old_hash: u64,
+        new_hash: u64,
+    },
+    BlobPatched(BlobDelta),
+    TruncatedLeafChanged {
+        old: Option<LeafDigest>,
+        new: Option<LeafDigest>,
+    },
 }
 
 /// Represents a single Node.
@@ -42,9 +646,266 @@ pub enum ChangeKind<P: Primitive> {
 pub enum Node<P: Primitive> {
     Map(HashMap<String, Node<P>>),
     Sequence(Vec<Node<P>>),
+    /// A fixed-length sequence, e.g. a tuple or an array, diffed element by element
+    /// instead of with Myers
+    Tuple(Vec<Node<P>>),
+    /// An enum variant: its name, and its content found at the [`PathSegment::Key`] of the name.
+    /// Changing the variant replaces the whole node.
+    Variant(String, Box<Node<P>>),
+    /// An unordered collection of distinct members, which are only added or removed
+    /// at [`PathSegment::Member`]
+    Set(Vec<Node<P>>),
     Leaf(P),
+    /// The absence of a value, e.g. `None`
+    Null,
+}
+
+impl<P: Primitive> Node<P> {
+    /// Converts every leaf of the tree with `f`.
+    pub fn map_leaves<Q: Primitive>(self, f: &mut impl FnMut(P) -> Q) -> Node<Q> {
+        match self {
+            Node::Map(map) => {
+                Node::Map(map.into_iter().map(|(k, v)| (k, v.map_leaves(f))).collect())
+            }
+            Node::Sequence(sequence) => {
+                Node::Sequence(sequence.into_iter().map(|v| v.map_leaves(f)).collect())
+            }
+            Node::Tuple(tuple) => Node::Tuple(tuple.into_iter().map(|v| v.map_leaves(f)).collect()),
+            Node::Variant(tag, content) => Node::Variant(tag, Box::new(content.map_leaves(f))),
+            Node::Set(members) => Node::Set(members.into_iter().map(|v| v.map_leaves(f)).collect()),
+            Node::Leaf(v) => Node::Leaf(f(v)),
+            Node::Null => Node::Null,
+        }
+    }
+
+    /// Converts every leaf of the tree with `f`,
+    /// failing with [`ApplyError::TypeMismatch`] at the first leaf it rejects.
+    pub fn try_map_leaves<Q: Primitive>(
+        self,
+        f: &mut impl FnMut(P) -> Option<Q>,
+    ) -> Result<Node<Q>, ApplyError> {
+        match self {
+            Node::Map(map) => map
+                .into_iter()
+                .map(|(k, v)| match v.try_map_leaves(f) {
+                    Ok(v) => Ok((k, v)),
+                    Err(e) => Err(e.within(PathSegment::Key(k))),
+                })
+                .collect::<Result<_, _>>()
+                .map(Node::Map),
+            Node::Sequence(sequence) => try_map_elements(sequence, f).map(Node::Sequence),
+            Node::Tuple(tuple) => try_map_elements(tuple, f).map(Node::Tuple),
+            Node::Variant(tag, content) => match content.try_map_leaves(f) {
+                Ok(content) => Ok(Node::Variant(tag, Box::new(content))),
+                Err(e) => Err(e.within(PathSegment::Key(tag))),
+            },
+            Node::Set(members) => members
+                .into_iter()
+                .map(|v| {
+                    v.try_map_leaves(f)
+                        .map_err(|e| e.within(PathSegment::Member))
+                })
+                .collect::<Result<_, _>>()
+                .map(Node::Set),
+            Node::Leaf(v) => f(v).map(Node::Leaf).ok_or(ApplyError::TypeMismatch(vec![])),
+            Node::Null => Ok(Node::Null),
+        }
+    }
+
+    /// The node at `path`, if any. Set members can't be reached by path.
+    /// ```
+    /// use diffkit::node;
+    /// use diffkit::recursive::{Node, PathSegment};
+    ///
+    /// let tree: Node<i32> = node!({ "ports": [80, 443] });
+    /// let path = [PathSegment::Key("ports".to_string()), PathSegment::Index(1)];
+    /// assert_eq!(tree.get(&path), Some(&Node::Leaf(443)));
+    /// ```
+    pub fn get(&self, path: &[PathSegment]) -> Option<&Node<P>> {
+        path.iter()
+            .try_fold(self, |node, segment| match (node, segment) {
+                (Node::Map(map), PathSegment::Key(key)) => map.get(key),
+                (Node::Sequence(v) | Node::Tuple(v), PathSegment::Index(i)) => v.get(*i),
+                (Node::Variant(tag, content), PathSegment::Key(key)) if tag == key => {
+                    Some(&**content)
+                }
+                _ => None,
+            })
+    }
+
+    /// The node at `path`, if any, to modify in place.
+    pub fn get_mut(&mut self, path: &[PathSegment]) -> Option<&mut Node<P>> {
+        path.iter()
+            .try_fold(self, |node, segment| match (node, segment) {
+                (Node::Map(map), PathSegment::Key(key)) => map.get_mut(key),
+                (Node::Sequence(v) | Node::Tuple(v), PathSegment::Index(i)) => v.get_mut(*i),
+                (Node::Variant(tag, content), PathSegment::Key(key)) if tag == key => {
+                    Some(&mut **content)
+                }
+                _ => None,
+            })
+    }
+
+    /// Puts `value` at `path`, returning the node it replaces.
+    /// The last segment may be a missing map key, but the other ones must exist.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ApplyError::PathNotFound`] if the path leads nowhere.
+    pub fn set(
+        &mut self,
+        path: &[PathSegment],
+        value: Node<P>,
+    ) -> Result<Option<Node<P>>, ApplyError> {
+        let Some((last, parent)) = path.split_last() else {
+            return Ok(Some(std::mem::replace(self, value)));
+        };
+        let not_found = || ApplyError::PathNotFound(path.to_vec());
+        match (self.get_mut(parent).ok_or_else(not_found)?, last) {
+            (Node::Map(map), PathSegment::Key(key)) => Ok(map.insert(key.clone(), value)),
+            (Node::Sequence(v) | Node::Tuple(v), PathSegment::Index(i)) => {
+                let element = v.get_mut(*i).ok_or_else(not_found)?;
+                Ok(Some(std::mem::replace(element, value)))
+            }
+            (Node::Variant(tag, content), PathSegment::Key(key)) if tag == key => {
+                Ok(Some(std::mem::replace(&mut **content, value)))
+            }
+            _ => Err(not_found()),
+        }
+    }
+
+    /// Removes the node at `path`, shifting the following elements of a sequence.
+    /// The content of a variant and the whole tree can't be removed.
+    pub fn remove(&mut self, path: &[PathSegment]) -> Option<Node<P>> {
+        let (last, parent) = path.split_last()?;
+        match (self.get_mut(parent)?, last) {
+            (Node::Map(map), PathSegment::Key(key)) => map.remove(key),
+            (Node::Sequence(v) | Node::Tuple(v), PathSegment::Index(i)) if *i < v.len() => {
+                Some(v.remove(*i))
+            }
+            _ => None,
+        }
+    }
+}
+
+fn try_map_elements<P: Primitive, Q: Primitive>(
+    elements: Vec<Node<P>>,
+    f: &mut impl FnMut(P) -> Option<Q>,
+) -> Result<Vec<Node<Q>>, ApplyError> {
+    elements
+        .into_iter()
+        .enumerate()
+        .map(|(i, v)| {
+            v.try_map_leaves(f)
+                .map_err(|e| e.within(PathSegment::Index(i)))
+        })
+        .collect()
 }
 
 /// Trait for leaf values in a Node tree.
-/// Implemented for all Rust primitives except floats, which lack `[Eq]`
-pub trait Primitive: Eq + Clone {}
+/// Implemented for all Rust primitives except floats, which lack `[Eq]`,
+/// and with the `float` feature for their `OrderedFloat` wrappers.
+/// Leaves are hashed for [`Node::structural_hash`], and `'static`
+/// so that blob and string leaves can be told apart from the others.
+pub trait Primitive: Eq + Clone + Hash + 'static {}
+
+/// Error returned when changes don't fit the structure they are applied to,
+/// e.g. because they were computed against a different version of it.
+/// Each variant carries the path of the offending node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApplyError {
+    /// No value at the path: a missing map key, or an index past the end of a sequence.
+    PathNotFound(Vec<PathSegment>),
+    /// The node at the path doesn't have the expected shape,
+    /// e.g. an index into a map, or a sequence where a leaf was expected.
+    TypeMismatch(Vec<PathSegment>),
+    /// The change doesn't fit the value at its path, e.g. `Removed` on a map
+    /// or `NodeRemoved` on a leaf.
+    InvalidChangeKind(Vec<PathSegment>),
+    /// The value at the path isn't the one recorded in the change,
+    /// or an added key already exists. Only returned by `apply_strict`.
+    Conflict(Vec<PathSegment>),
+}
+
+impl ApplyError {
+    /// Prefixes the path of the error with `segment`,
+    /// for errors coming from a child node.
+    pub fn within(self, segment: PathSegment) -> Self {
+        let prefix = |mut path: Vec<PathSegment>| {
+            path.insert(0, segment);
+            path
+        };
+        match self {
+            ApplyError::PathNotFound(path) => ApplyError::PathNotFound(prefix(path)),
+            ApplyError::TypeMismatch(path) => ApplyError::TypeMismatch(prefix(path)),
+            ApplyError::InvalidChangeKind(path) => ApplyError::InvalidChangeKind(prefix(path)),
+            ApplyError::Conflict(path) => ApplyError::Conflict(prefix(path)),
+        }
+    }
+}
+
+impl fmt::Display for ApplyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (message, path) = match self {
+            ApplyError::PathNotFound(path) => ("path not found", path),
+            ApplyError::TypeMismatch(path) => ("unexpected node type", path),
+            ApplyError::InvalidChangeKind(path) => ("change doesn't fit the value", path),
+            ApplyError::Conflict(path) => ("conflicting value", path),
+        };
+        let path: Vec<String> = path.iter().map(|s| s.to_string()).collect();
+        write!(f, "{} at /{}", message, path.join("/"))
+    }
+}
+
+impl std::error::Error for ApplyError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node;
+
+    fn key(k: &str) -> PathSegment {
+        PathSegment::Key(k.to_string())
+    }
+
+    #[test]
+    fn test_path_accessors() {
+        let mut tree: Node<i32> = node!({ "a": [1, 2], "b": { "c": 3 } });
+        let variant = Node::Variant("V".to_string(), Box::new(Node::Leaf(4)));
+        assert_eq!(tree.set(&[key("v")], variant), Ok(None));
+
+        assert_eq!(tree.get(&[key("b"), key("c")]), Some(&Node::Leaf(3)));
+        assert_eq!(tree.get(&[key("v"), key("V")]), Some(&Node::Leaf(4)));
+        assert_eq!(tree.get(&[key("v"), key("W")]), None);
+        assert_eq!(tree.get(&[key("a"), PathSegment::Index(2)]), None);
+        assert_eq!(tree.get(&[]), Some(&tree));
+
+        *tree.get_mut(&[key("a"), PathSegment::Index(0)]).unwrap() = Node::Leaf(10);
+        assert_eq!(
+            tree.set(&[key("v"), key("V")], Node::Leaf(5)),
+            Ok(Some(Node::Leaf(4)))
+        );
+        assert_eq!(
+            tree.set(&[key("x"), key("y")], Node::Null),
+            Err(ApplyError::PathNotFound(vec![key("x"), key("y")]))
+        );
+        assert_eq!(
+            tree.set(&[key("a"), PathSegment::Index(2)], Node::Null),
+            Err(ApplyError::PathNotFound(vec![
+                key("a"),
+                PathSegment::Index(2)
+            ]))
+        );
+
+        assert_eq!(
+            tree.remove(&[key("a"), PathSegment::Index(0)]),
+            Some(Node::Leaf(10))
+        );
+        assert_eq!(tree.remove(&[key("b")]), Some(node!({ "c": 3 })));
+        assert_eq!(tree.remove(&[key("v"), key("V")]), None);
+        assert_eq!(tree.remove(&[]), None);
+        let expected: Node<i32> = node!({ "a": [2] })
+            .insert("v", Node::Variant("V".to_string(), Box::new(Node::Leaf(5))));
+        assert_eq!(tree, expected);
+    }
+}
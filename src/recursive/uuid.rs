@@ -0,0 +1,52 @@
+use crate::recursive::diffable::{Diffable, Scalar};
+use crate::recursive::types::{ApplyError, Node, Primitive};
+use ::uuid::Uuid;
+
+impl Primitive for Uuid {}
+
+impl Diffable for Uuid {
+    type P = Self;
+    fn to_node(&self) -> Node<Self::P> {
+        Node::Leaf(*self)
+    }
+
+    fn from_node(node: Node<Self::P>) -> Result<Self, ApplyError> {
+        match node {
+            Node::Leaf(v) => Ok(v),
+            _ => Err(ApplyError::TypeMismatch(vec![])),
+        }
+    }
+}
+
+/// Kept as a string, in its hyphenated form.
+impl From<Uuid> for Scalar {
+    fn from(v: Uuid) -> Self {
+        Scalar::String(v.to_string())
+    }
+}
+
+impl TryFrom<Scalar> for Uuid {
+    type Error = Scalar;
+    fn try_from(scalar: Scalar) -> Result<Self, Scalar> {
+        match &scalar {
+            Scalar::String(v) => v.parse().map_err(|_| scalar),
+            _ => Err(scalar),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recursive::{apply, diff, ChangeKind};
+
+    #[test]
+    fn test_uuids() {
+        let a = Uuid::from_u128(1);
+        let b = Uuid::from_u128(2);
+        let changes = diff(&vec![a], &vec![b]);
+        assert_eq!(changes[0].kind, ChangeKind::Modified(a, b));
+        assert_eq!(apply(&vec![a], &changes), Ok(vec![b]));
+        assert_eq!(Uuid::try_from(Scalar::from(a)), Ok(a));
+    }
+}
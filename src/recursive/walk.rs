@@ -0,0 +1,174 @@
+use crate::recursive::types::{Node, PathSegment, Primitive};
+
+/// Receives every node of a tree with its path, see [`Node::walk`].
+///
+/// Implemented for closures taking the path and the node.
+pub trait NodeVisitor<P: Primitive> {
+    fn visit(&mut self, path: &[PathSegment], node: &Node<P>);
+}
+
+impl<P: Primitive, F: FnMut(&[PathSegment], &Node<P>)> NodeVisitor<P> for F {
+    fn visit(&mut self, path: &[PathSegment], node: &Node<P>) {
+        self(path, node)
+    }
+}
+
+/// Receives every node of a tree with its path, to modify it in place,
+/// see [`Node::walk_mut`].
+///
+/// Implemented for closures taking the path and the node.
+pub trait NodeVisitorMut<P: Primitive> {
+    fn visit(&mut self, path: &[PathSegment], node: &mut Node<P>);
+}
+
+impl<P: Primitive, F: FnMut(&[PathSegment], &mut Node<P>)> NodeVisitorMut<P> for F {
+    fn visit(&mut self, path: &[PathSegment], node: &mut Node<P>) {
+        self(path, node)
+    }
+}
+
+impl<P: Primitive> Node<P> {
+    /// Visits every node of the tree depth-first, each one before its children,
+    /// with map entries in key order and set members at [`PathSegment::Member`].
+    /// ```
+    /// use diffkit::node;
+    /// use diffkit::recursive::{Node, PathSegment};
+    ///
+    /// let tree: Node<i32> = node!({ "b": [1], "a": 2 });
+    /// let mut leaves = vec![];
+    /// tree.walk(&mut |path: &[PathSegment], node: &Node<i32>| {
+    ///     if let Node::Leaf(v) = node {
+    ///         leaves.push((path.len(), *v));
+    ///     }
+    /// });
+    /// assert_eq!(leaves, vec![(1, 2), (2, 1)]);
+    /// ```
+    pub fn walk(&self, visitor: &mut impl NodeVisitor<P>) {
+        let mut stack = vec![(vec![], self)];
+        while let Some((path, node)) = stack.pop() {
+            visitor.visit(&path, node);
+            let mut children: Vec<(PathSegment, &Node<P>)> = match node {
+                Node::Map(map) => {
+                    let mut entries: Vec<(&String, &Node<P>)> = map.iter().collect();
+                    entries.sort_by_key(|(key, _)| *key);
+                    entries
+                        .into_iter()
+                        .map(|(key, value)| (PathSegment::Key(key.clone()), value))
+                        .collect()
+                }
+                Node::Sequence(v) | Node::Tuple(v) => v
+                    .iter()
+                    .enumerate()
+                    .map(|(i, value)| (PathSegment::Index(i), value))
+                    .collect(),
+                Node::Set(v) => v.iter().map(|value| (PathSegment::Member, value)).collect(),
+                Node::Variant(tag, content) => vec![(PathSegment::Key(tag.clone()), content)],
+                Node::Leaf(_) | Node::Null => vec![],
+            };
+            children.reverse();
+            stack.extend(children.into_iter().map(|(segment, child)| {
+                let mut path = path.clone();
+                path.push(segment);
+                (path, child)
+            }));
+        }
+    }
+
+    /// Like [`walk`](Node::walk), but the visitor may modify each node,
+    /// and then visits the children of the modified one.
+    /// ```
+    /// use diffkit::node;
+    /// use diffkit::recursive::{Node, PathSegment};
+    ///
+    /// let mut tree: Node<String> = node!({ "user": "ann", "password": "hunter2" });
+    /// tree.walk_mut(&mut |path: &[PathSegment], node: &mut Node<String>| {
+    ///     if path.last() == Some(&PathSegment::Key("password".to_string())) {
+    ///         *node = Node::Leaf("***".to_string());
+    ///     }
+    /// });
+    /// assert_eq!(tree, node!({ "user": "ann", "password": "***" }));
+    /// ```
+    pub fn walk_mut(&mut self, visitor: &mut impl NodeVisitorMut<P>) {
+        let mut stack = vec![(vec![], self)];
+        while let Some((path, node)) = stack.pop() {
+            visitor.visit(&path, node);
+            let mut children: Vec<(PathSegment, &mut Node<P>)> = match node {
+                Node::Map(map) => {
+                    let mut entries: Vec<(&String, &mut Node<P>)> = map.iter_mut().collect();
+                    entries.sort_by_key(|(key, _)| *key);
+                    entries
+                        .into_iter()
+                        .map(|(key, value)| (PathSegment::Key(key.clone()), value))
+                        .collect()
+                }
+                Node::Sequence(v) | Node::Tuple(v) => v
+                    .iter_mut()
+                    .enumerate()
+                    .map(|(i, value)| (PathSegment::Index(i), value))
+                    .collect(),
+                Node::Set(v) => v
+                    .iter_mut()
+                    .map(|value| (PathSegment::Member, value))
+                    .collect(),
+                Node::Variant(tag, content) => vec![(PathSegment::Key(tag.clone()), content)],
+                Node::Leaf(_) | Node::Null => vec![],
+            };
+            children.reverse();
+            stack.extend(children.into_iter().map(|(segment, child)| {
+                let mut path = path.clone();
+                path.push(segment);
+                (path, child)
+            }));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(k: &str) -> PathSegment {
+        PathSegment::Key(k.to_string())
+    }
+
+    #[test]
+    fn test_walk() {
+        let tree: Node<i32> = Node::Map(
+            [
+                ("b".to_string(), Node::Set(vec![Node::Leaf(1)])),
+                (
+                    "a".to_string(),
+                    Node::Variant("some".to_string(), Box::new(Node::Leaf(2))),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        let mut paths = vec![];
+        tree.walk(&mut |path: &[PathSegment], _: &Node<i32>| paths.push(path.to_vec()));
+        assert_eq!(
+            paths,
+            vec![
+                vec![],
+                vec![key("a")],
+                vec![key("a"), key("some")],
+                vec![key("b")],
+                vec![key("b"), PathSegment::Member],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_walk_mut_visits_replaced_nodes() {
+        let mut tree: Node<i32> = Node::Sequence(vec![Node::Null, Node::Leaf(1)]);
+        tree.walk_mut(&mut |_: &[PathSegment], node: &mut Node<i32>| match node {
+            Node::Null => *node = Node::Tuple(vec![Node::Leaf(0)]),
+            Node::Leaf(v) => *v += 1,
+            _ => {}
+        });
+        assert_eq!(
+            tree,
+            Node::Sequence(vec![Node::Tuple(vec![Node::Leaf(1)]), Node::Leaf(2)])
+        );
+    }
+}
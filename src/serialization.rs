@@ -1,44 +1,251 @@
 use crate::myers::Edit;
-use crate::patch::Hunk;
+use crate::patch::{
+    Column, CombinedHunk, CombinedLine, ContentHash, EmailPatch, Hunk, Patch, PatchFile,
+    PatchHeader, Relation, DEV_NULL,
+};
+use std::fmt;
+use std::str::FromStr;
 
 /// Serializes changes into the [unified diff format](https://en.wikipedia.org/wiki/Diff#Unified_format).
 ///
-/// `old_name` and `new_name` are optional file names for the `---`/`+++` header.
-/// Defaults to `"old"` and `"new"` if not provided.
+/// `header` provides the file names, and any other metadata, for the
+/// `---`/`+++` header; [`PatchHeader::default`] names the files `"old"` and `"new"`.
 ///
-/// Implemented for `Edit<T>`, `Hunk<T>`, and `Vec<Hunk<T>>`.
+/// Implemented for `Edit<T>`, `Diff<T>`, `Hunk<T>`, `Vec<Hunk<T>>`, [`PatchFile<T>`] and [`Patch<T>`].
+/// The latter two carry their own headers and ignore `header`.
+///
+/// A header with file modes, a rename or copy, or hashes is preceded by a
+/// `diff --git` section, in git's extended header format. The hashes are an
+/// extension of this crate to that format: `pre-image hash <hex>` and
+/// `post-image hash <hex>` lines, last in the section, holding the
+/// [`ContentHash`]es of the file before and after the patch. git doesn't
+/// write or check them.
 pub trait ToPatch: Sized {
-    fn to_patch(&self, old_name: Option<&str>, new_name: Option<&str>) -> String;
+    /// Serializes with `\n` line terminators.
+    fn to_patch(&self, header: &PatchHeader) -> String;
+
+    /// Serializes with the given line terminator, by replacing those of [`ToPatch::to_patch`].
+    fn to_patch_with_line_ending(&self, header: &PatchHeader, line_ending: LineEnding) -> String {
+        match line_ending {
+            LineEnding::Lf => self.to_patch(header),
+            LineEnding::CrLf => self.to_patch(header).replace('\n', "\r\n"),
+        }
+    }
+}
+
+/// Line terminator used when serializing a patch.
+///
+/// Parsing accepts both; use [`LineEnding::detect`] to serialize
+/// a parsed patch back with the terminator it was read with.
+/// ```
+/// use diffkit::patch::{Hunk, PatchHeader};
+/// use diffkit::serialization::{FromPatch, LineEnding, ToPatch};
+///
+/// let patch = "--- old\r\n+++ new\r\n@@ -1 +1 @@\r\n-a\r\n+b";
+/// let hunks = Vec::<Hunk<String>>::from_patch(patch).unwrap();
+/// assert_eq!(hunks[0].changes.len(), 2);
+/// let ending = LineEnding::detect(patch);
+/// assert_eq!(
+///     hunks.to_patch_with_line_ending(&PatchHeader::default(), ending),
+///     "--- old\r\n+++ new\r\n@@ -1,1 +1,1 @@\r\n-a\r\n+b"
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    /// `\n`, as used on Unix
+    #[default]
+    Lf,
+    /// `\r\n`, as used on Windows
+    CrLf,
+}
+
+impl LineEnding {
+    /// Returns the terminator as a string.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+
+    /// Detects the terminator of a patch from its first line only, which is
+    /// enough for patches written with a single terminator, like those of
+    /// [`ToPatch::to_patch_with_line_ending`]: a patch mixing both is read as
+    /// written with the terminator of its first line.
+    pub fn detect(patch: &str) -> LineEnding {
+        match patch.split('\n').next() {
+            Some(first) if first.ends_with('\r') && first.len() < patch.len() => LineEnding::CrLf,
+            _ => LineEnding::Lf,
+        }
+    }
 }
 
 /// Deserializes a unified diff patch into a structure.
-/// Restricted to `String` values since patches are text-based.
+/// Values are parsed from each line with [`FromStr`], mirroring the
+/// [`ToString`] bound of [`ToPatch`].
 ///
 /// Returns [`PatchError`] if the input is malformed.
 ///
-/// Implemented for `Edit<String>` and `Vec<Hunk<String>>`.
+/// Implemented for `Edit<T>`, `Diff<T>`, `Vec<Hunk<T>>`, `PatchFile<T>` and `Patch<T>`.
+/// ```
+/// use diffkit::myers::Edit;
+/// use diffkit::serialization::FromPatch;
+///
+/// assert_eq!(Edit::<u32>::from_patch("+42"), Ok(Edit::Insert(42)));
+/// ```
 pub trait FromPatch: Sized {
     /// Parse a unified diff patch string into a structured representation.
     ///
     /// # Errors
     ///
-    /// Returns [`PatchError::InvalidFormat`] if the patch header is missing or malformed.
-    /// Returns [`PatchError::UnexpectedToken`] if a line starts with an unexpected character.
+    /// Returns [`PatchError::MissingHeader`] if the `---`/`+++` header is missing,
+    /// or a change line appears before any `@@` hunk header.
+    /// Returns [`PatchError::BadHunkHeader`] if a `@@` hunk header is malformed.
+    /// Returns [`PatchError::BadLinePrefix`] if a line starts with an unexpected character.
+    /// Returns [`PatchError::BadValue`] if a value can't be parsed into `T`.
     fn from_patch(s: &str) -> Result<Self, PatchError>;
 }
 
 /// Represents an error parsing or applying a diff.
+///
+/// Parse errors carry a [`Location`] pointing at the offending line.
+/// Apply errors refer to the 1-based line of the input the patch is applied to.
 #[derive(Debug, PartialEq)]
 pub enum PatchError {
-    /// The patch is structurally invalid, e.g. missing `---`/`+++` header,
-    /// or the patch cannot be applied to the given structure.
-    InvalidFormat(String),
-    /// A line in the patch starts with an unexpected character.
-    UnexpectedToken(String),
+    /// The `---`/`+++` file header is missing, or a change line
+    /// appears before the first `@@` hunk header.
+    MissingHeader(Location),
+    /// A `@@ -a,b +c,d @@` hunk header is malformed.
+    BadHunkHeader(Location),
+    /// A line inside a hunk doesn't start with ` `, `+` or `-`.
+    BadLinePrefix(Location),
+    /// The value of a line can't be parsed into the target type.
+    BadValue(Location),
+    /// A context or deleted line of a hunk doesn't match the input.
+    ContextMismatch {
+        line: usize,
+        expected: String,
+        found: String,
+    },
+    /// The hunk at index `hunk` overlaps the previous one,
+    /// or reaches past the end of the input.
+    HunkOutOfBounds { hunk: usize, old_start: usize },
+    /// A file to be created already exists, or a file to be deleted
+    /// still has content once its hunks are applied.
+    FileConflict(String),
+    /// A file targeted by the patch could not be read or written.
+    Io(String),
+    /// A file name of the patch, once stripped, would reach outside the directory
+    /// the patch is applied to: it's absolute, or has a `..` component.
+    UnsafePath(String),
+    /// A file, before or after the patch, doesn't have the hash of the patch header.
+    HashMismatch {
+        side: PatchSide,
+        expected: ContentHash,
+        found: ContentHash,
+    },
+    /// A hash of the patch header can't be checked, since its algorithm needs
+    /// a disabled feature.
+    UncheckedHash(PatchSide),
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatchError::MissingHeader(l) => write!(f, "missing header at {}", l),
+            PatchError::BadHunkHeader(l) => write!(f, "malformed hunk header at {}", l),
+            PatchError::BadLinePrefix(l) => write!(f, "unexpected line prefix at {}", l),
+            PatchError::BadValue(l) => write!(f, "unparsable value at {}", l),
+            PatchError::ContextMismatch {
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "context mismatch at line {}: expected '{}', found '{}'",
+                line, expected, found
+            ),
+            PatchError::HunkOutOfBounds { hunk, old_start } => write!(
+                f,
+                "hunk {} starting at line {} doesn't fit the input",
+                hunk,
+                old_start + 1
+            ),
+            PatchError::FileConflict(message) | PatchError::Io(message) => {
+                write!(f, "{}", message)
+            }
+            PatchError::UnsafePath(name) => {
+                write!(f, "'{}' points outside the patched directory", name)
+            }
+            PatchError::HashMismatch {
+                side,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{} hash mismatch: expected {}, found {}",
+                image(side),
+                expected,
+                found
+            ),
+            PatchError::UncheckedHash(side) => write!(
+                f,
+                "{} hash can't be checked without the sha256 feature",
+                image(side)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+/// The version of a file an error is about: before or after the patch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatchSide {
+    Old,
+    New,
+}
+
+fn image(side: &PatchSide) -> &'static str {
+    match side {
+        PatchSide::Old => "pre-image",
+        PatchSide::New => "post-image",
+    }
+}
+
+/// Position of an offending line inside a patch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    /// 1-based line number
+    pub line: usize,
+    /// Byte offset of the start of the line
+    pub offset: usize,
+    /// The offending line
+    pub content: String,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {} (byte {}): '{}'",
+            self.line, self.offset, self.content
+        )
+    }
+}
+
+impl Location {
+    fn new(line: usize, offset: usize, content: &str) -> Self {
+        Location {
+            line,
+            offset,
+            content: content.to_string(),
+        }
+    }
 }
 
 impl<T: ToString> ToPatch for Edit<T> {
-    fn to_patch(&self, _: Option<&str>, _: Option<&str>) -> String {
+    fn to_patch(&self, _: &PatchHeader) -> String {
         match self {
             Edit::Equal(el) => format!(" {}", el.to_string()),
             Edit::Insert(el) => format!("+{}", el.to_string()),
@@ -47,141 +254,905 @@ impl<T: ToString> ToPatch for Edit<T> {
     }
 }
 
-impl FromPatch for Edit<String> {
+impl<T: FromStr> FromPatch for Edit<T> {
     fn from_patch(s: &str) -> Result<Self, PatchError> {
-        match s.chars().next() {
-            Some(' ') => Ok(Edit::Equal(s[1..].to_string())),
-            Some('+') => Ok(Edit::Insert(s[1..].to_string())),
-            Some('-') => Ok(Edit::Delete(s[1..].to_string())),
-            _ => Err(PatchError::UnexpectedToken(s.to_string())),
-        }
+        parse_edit(s, 1, 0)
     }
 }
 
-impl<T: ToString> ToPatch for Hunk<T> {
-    fn to_patch(&self, _old_name: Option<&str>, _new_name: Option<&str>) -> String {
-        let old_edits = self
-            .changes
-            .iter()
-            .filter(|e| !matches!(e, Edit::Insert(_)))
-            .count();
-        let new_edits = self
-            .changes
-            .iter()
-            .filter(|e| !matches!(e, Edit::Delete(_)))
-            .count();
-        let header = format!(
-            "@@ -{},{} +{},{} @@",
-            self.old_start, old_edits, self.new_start, new_edits
-        );
-        let body = self
-            .changes
-            .iter()
-            .map(|e| e.to_patch(None, None))
-            .collect::<Vec<String>>();
+fn parse_edit<T: FromStr>(s: &str, line: usize, offset: usize) -> Result<Edit<T>, PatchError> {
+    let value = || {
+        s[1..]
+            .parse()
+            .map_err(|_| PatchError::BadValue(Location::new(line, offset, s)))
+    };
+    match s.chars().next() {
+        Some(' ') => Ok(Edit::Equal(value()?)),
+        Some('+') => Ok(Edit::Insert(value()?)),
+        Some('-') => Ok(Edit::Delete(value()?)),
+        _ => Err(PatchError::BadLinePrefix(Location::new(line, offset, s))),
+    }
+}
 
-        format!("{}\n{}", header, body.join("\n"))
+impl<T: ToString> ToPatch for Hunk<T> {
+    fn to_patch(&self, _header: &PatchHeader) -> String {
+        hunk_to_patch(
+            self.old_start,
+            self.new_start,
+            &self.changes,
+            self.section.as_deref(),
+            (false, false),
+            LineEnding::Lf,
+        )
     }
 }
 
-impl<T: ToString> ToPatch for Vec<Hunk<T>> {
-    fn to_patch(&self, old_name: Option<&str>, new_name: Option<&str>) -> String {
+/// Serializes the whole edit script as a single hunk, without trimming context.
+impl<T: ToString> ToPatch for Vec<Edit<T>> {
+    fn to_patch(&self, header: &PatchHeader) -> String {
         if self.is_empty() {
             return String::new();
         }
 
-        let header = format!(
-            "--- {}\n+++ {}\n",
-            old_name.unwrap_or("old"),
-            new_name.unwrap_or("new")
-        );
-        let hunks = self
+        let mut lines = header_to_patch(header);
+        lines.push(hunk_to_patch(
+            0,
+            0,
+            self,
+            None,
+            (false, false),
+            LineEnding::Lf,
+        ));
+        lines.join("\n")
+    }
+}
+
+impl<T: ToString> ToPatch for Vec<Hunk<T>> {
+    fn to_patch(&self, header: &PatchHeader) -> String {
+        to_patch_with_sections(self, header, LineEnding::Lf, |h| h.section.clone())
+    }
+}
+
+impl<T: ToString> ToPatch for PatchFile<T> {
+    fn to_patch(&self, _header: &PatchHeader) -> String {
+        file_to_patch(&self.header, &self.hunks, LineEnding::Lf, |h| {
+            h.section.clone()
+        })
+    }
+}
+
+impl<T: ToString> ToPatch for Patch<T> {
+    fn to_patch(&self, _header: &PatchHeader) -> String {
+        self.files
             .iter()
-            .map(|h| h.to_patch(None, None))
+            .map(|f| f.to_patch(&f.header))
             .collect::<Vec<String>>()
-            .join("\n");
-        format!("{}{}", header, hunks)
+            .join("\n")
+    }
+}
+
+/// Serializes in git's combined diff format, with one `@` and one range
+/// per merge parent in the header, and one prefix column per parent on each line.
+impl<T: ToString> ToPatch for CombinedHunk<T> {
+    fn to_patch(&self, _header: &PatchHeader) -> String {
+        let parents = self.parent_starts.len();
+        let markers = "@".repeat(parents + 1);
+        let mut header = markers.clone();
+        for (parent, start) in self.parent_starts.iter().enumerate() {
+            let count = self.lines.iter().filter(|l| l.in_parent(parent)).count();
+            header.push_str(&format!(" -{},{}", to_line_number(*start, count), count));
+        }
+        let count = self.lines.iter().filter(|l| l.in_result()).count();
+        header.push_str(&format!(
+            " +{},{} {}",
+            to_line_number(self.new_start, count),
+            count,
+            markers
+        ));
+
+        let mut lines = vec![header];
+        lines.extend(self.lines.iter().map(|l| {
+            let mut line: String = l
+                .columns
+                .iter()
+                .map(|c| match c {
+                    Column::Context => ' ',
+                    Column::Added => '+',
+                    Column::Removed => '-',
+                })
+                .collect();
+            line.push_str(&l.value.to_string());
+            line
+        }));
+        lines.join("\n")
+    }
+}
+
+impl<T: ToString> ToPatch for Vec<CombinedHunk<T>> {
+    fn to_patch(&self, header: &PatchHeader) -> String {
+        if self.is_empty() {
+            return String::new();
+        }
+
+        let mut lines = header_to_patch(header);
+        lines.extend(self.iter().map(|h| h.to_patch(header)));
+        lines.join("\n")
+    }
+}
+
+/// Serializes hunks like [`ToPatch::to_patch`], computing the section heading
+/// printed after the closing `@@` of each hunk header with `section`.
+///
+/// ```
+/// use diffkit::myers::diff;
+/// use diffkit::patch::{hunks, PatchHeader};
+/// use diffkit::serialization::{to_patch_with_sections, LineEnding};
+///
+/// let old = vec!["fn apply() {", "a", "b", "c", "d", "}"];
+/// let new = vec!["fn apply() {", "a", "b", "c", "X", "}"];
+/// let hunks = hunks(diff(&old, &new));
+/// let patch = to_patch_with_sections(&hunks, &PatchHeader::default(), LineEnding::Lf, |h| {
+///     old[..h.old_start]
+///         .iter()
+///         .rev()
+///         .find(|l| l.starts_with("fn "))
+///         .map(|l| l.to_string())
+/// });
+/// assert!(patch.contains("@@ -2,5 +2,5 @@ fn apply() {"));
+/// ```
+pub fn to_patch_with_sections<T, F>(
+    hunks: &[Hunk<T>],
+    header: &PatchHeader,
+    line_ending: LineEnding,
+    section: F,
+) -> String
+where
+    T: ToString,
+    F: Fn(&Hunk<T>) -> Option<String>,
+{
+    if hunks.is_empty() {
+        return String::new();
+    }
+
+    file_to_patch(header, hunks, line_ending, section)
+}
+
+fn file_to_patch<T, F>(
+    header: &PatchHeader,
+    hunks: &[Hunk<T>],
+    line_ending: LineEnding,
+    section: F,
+) -> String
+where
+    T: ToString,
+    F: Fn(&Hunk<T>) -> Option<String>,
+{
+    let mut lines = header_to_patch(header);
+    lines.extend(hunks.iter().enumerate().map(|(i, h)| {
+        // only the last hunk can reach the end of the files
+        let missing_newline = match i + 1 == hunks.len() {
+            true => (header.old_missing_newline, header.new_missing_newline),
+            false => (false, false),
+        };
+        hunk_to_patch(
+            h.old_start,
+            h.new_start,
+            &h.changes,
+            section(h).as_deref(),
+            missing_newline,
+            line_ending,
+        )
+    }));
+    lines.join(line_ending.as_str())
+}
+
+/// The `---`/`+++` lines of `header`, preceded by a `diff --git` section
+/// if it carries file modes, a relation or hashes, which only git's format can express.
+fn header_to_patch(header: &PatchHeader) -> Vec<String> {
+    let mut lines = vec![];
+    if header.relation.is_some()
+        || header.old_mode.is_some()
+        || header.mode.is_some()
+        || header.old_hash.is_some()
+        || header.new_hash.is_some()
+    {
+        // git names both sides, even when one is missing
+        let old_name = if header.is_creation() {
+            format!("a/{}", strip_prefix(&header.new_name, "b/"))
+        } else {
+            header.old_name.clone()
+        };
+        let new_name = if header.is_deletion() {
+            format!("b/{}", strip_prefix(&header.old_name, "a/"))
+        } else {
+            header.new_name.clone()
+        };
+        lines.push(format!("diff --git {} {}", old_name, new_name));
+
+        match header.mode {
+            Some(mode) if header.is_creation() => lines.push(format!("new file mode {:o}", mode)),
+            Some(mode) if header.is_deletion() => {
+                lines.push(format!("deleted file mode {:o}", mode))
+            }
+            mode => {
+                if let Some(old_mode) = header.old_mode {
+                    lines.push(format!("old mode {:o}", old_mode));
+                }
+                if let Some(mode) = mode {
+                    lines.push(format!("new mode {:o}", mode));
+                }
+            }
+        }
+
+        if let Some(relation) = header.relation {
+            let (kind, similarity) = match relation {
+                Relation::Rename { similarity } => ("rename", similarity),
+                Relation::Copy { similarity } => ("copy", similarity),
+            };
+            lines.push(format!("similarity index {}%", similarity));
+            lines.push(format!("{} from {}", kind, strip_prefix(&old_name, "a/")));
+            lines.push(format!("{} to {}", kind, strip_prefix(&new_name, "b/")));
+        }
+
+        // last, like git's `index` line
+        if let Some(hash) = header.old_hash {
+            lines.push(format!("pre-image hash {}", hash));
+        }
+        if let Some(hash) = header.new_hash {
+            lines.push(format!("post-image hash {}", hash));
+        }
+    }
+
+    // unlike git, keep the ---/+++ lines even without hunks, so that
+    // the next file can't be mistaken for the content of this one
+    let name_line = |marker: &str, name: &str, time: &Option<String>| match time {
+        Some(time) => format!("{} {}\t{}", marker, name, time),
+        None => format!("{} {}", marker, name),
+    };
+    lines.push(name_line("---", &header.old_name, &header.old_time));
+    lines.push(name_line("+++", &header.new_name, &header.new_time));
+    lines
+}
+
+fn strip_prefix<'a>(name: &'a str, prefix: &str) -> &'a str {
+    name.strip_prefix(prefix).unwrap_or(name)
+}
+
+/// The marker following the last line of a file that doesn't end with a newline.
+const NO_NEWLINE: &str = "\\ No newline at end of file";
+
+/// Serializes a hunk, following the last old and new lines with [`NO_NEWLINE`]
+/// as `missing_newline` says.
+fn hunk_to_patch<T: ToString>(
+    old_start: usize,
+    new_start: usize,
+    changes: &[Edit<T>],
+    section: Option<&str>,
+    missing_newline: (bool, bool),
+    line_ending: LineEnding,
+) -> String {
+    let old_edits = changes
+        .iter()
+        .filter(|e| !matches!(e, Edit::Insert(_)))
+        .count();
+    let new_edits = changes
+        .iter()
+        .filter(|e| !matches!(e, Edit::Delete(_)))
+        .count();
+    // unified diffs count lines from 1, except for empty ranges
+    // which point at the line right before them
+    let mut header = format!(
+        "@@ -{},{} +{},{} @@",
+        to_line_number(old_start, old_edits),
+        old_edits,
+        to_line_number(new_start, new_edits),
+        new_edits
+    );
+    if let Some(section) = section {
+        header.push(' ');
+        header.push_str(section);
+    }
+    let mut lines = vec![header];
+    // edits ignore the header
+    let unused = PatchHeader::default();
+    let last_old = changes.iter().rposition(|e| !matches!(e, Edit::Insert(_)));
+    let last_new = changes.iter().rposition(|e| !matches!(e, Edit::Delete(_)));
+    let (old_missing, new_missing) = missing_newline;
+    for (i, edit) in changes.iter().enumerate() {
+        lines.push(edit.to_patch(&unused));
+        if (old_missing && last_old == Some(i)) || (new_missing && last_new == Some(i)) {
+            lines.push(NO_NEWLINE.to_string());
+        }
+    }
+    lines.join(line_ending.as_str())
+}
+
+fn to_line_number(start: usize, count: usize) -> usize {
+    if count == 0 {
+        start
+    } else {
+        start + 1
+    }
+}
+
+impl<T: FromStr> FromPatch for Vec<Hunk<T>> {
+    fn from_patch(s: &str) -> Result<Self, PatchError> {
+        if s.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut parser = Parser::new(s);
+        let file = parser.parse_file()?;
+        parser.finish()?;
+        Ok(file.hunks)
+    }
+}
+
+/// Expects at most one hunk, covering both sequences from their first line.
+impl<T: FromStr> FromPatch for Vec<Edit<T>> {
+    fn from_patch(s: &str) -> Result<Self, PatchError> {
+        if s.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut parser = Parser::new(s);
+        parser.parse_name("---")?;
+        parser.parse_name("+++")?;
+        let mut edits = vec![];
+        if parser.peek(0).is_some_and(|l| l.starts_with("@@")) {
+            let location = parser.location(parser.pos);
+            let hunk = parser.parse_hunk(&mut PatchHeader::default())?;
+            if hunk.old_start != 0 || hunk.new_start != 0 {
+                return Err(PatchError::BadHunkHeader(location));
+            }
+            edits = hunk.changes;
+        }
+        if parser.peek(0).is_some_and(|l| l.starts_with("@@")) {
+            return Err(PatchError::BadHunkHeader(parser.location(parser.pos)));
+        }
+        parser.finish()?;
+        Ok(edits)
     }
 }
 
-impl FromPatch for Vec<Hunk<String>> {
+impl<T: FromStr> FromPatch for Vec<CombinedHunk<T>> {
     fn from_patch(s: &str) -> Result<Self, PatchError> {
         if s.is_empty() {
             return Ok(vec![]);
         }
 
-        // can't use `.lines()` because of Windows \r
+        let mut parser = Parser::new(s);
+        parser.parse_name("---")?;
+        parser.parse_name("+++")?;
+        let mut hunks = vec![];
+        while parser.peek(0).is_some_and(|l| l.starts_with("@@@")) {
+            hunks.push(parser.parse_combined_hunk()?);
+        }
+        parser.finish()?;
+        Ok(hunks)
+    }
+}
+
+impl<T: FromStr> FromPatch for PatchFile<T> {
+    fn from_patch(s: &str) -> Result<Self, PatchError> {
+        let mut parser = Parser::new(s);
+        let file = parser.parse_file()?;
+        parser.finish()?;
+        Ok(file)
+    }
+}
+
+/// Lines that are not part of a `---`/`+++` file section, such as
+/// `diff --git` or `index` lines, are skipped.
+///
+/// In `diff --git` sections, `new file mode` and `deleted file mode` lines
+/// turn the missing side into [`DEV_NULL`], and sections without hunks,
+/// e.g. for empty files, are kept with the names of the `diff --git` line.
+/// File modes, similarity, rename/copy and `pre-image hash`/`post-image hash`
+/// lines are kept in the [`PatchHeader`].
+impl<T: FromStr> FromPatch for Patch<T> {
+    fn from_patch(s: &str) -> Result<Self, PatchError> {
+        Parser::new(s).parse_patch(false)
+    }
+}
+
+/// Parses a single email as written by `git format-patch`.
+///
+/// The commit message ends at the `---` line introducing the diffstat,
+/// and the patch is read like [`Patch`], skipping the diffstat and the
+/// trailing signature.
+///
+/// ```
+/// use diffkit::patch::EmailPatch;
+/// use diffkit::serialization::FromPatch;
+///
+/// let email = "From 0123abcd Mon Sep 17 00:00:00 2001\n\
+///              From: Jane Doe <jane@example.com>\n\
+///              Date: Tue, 1 Oct 2024 10:00:00 +0200\n\
+///              Subject: [PATCH 1/2] Fix greeting\n\
+///              \n\
+///              Say hello to everyone.\n\
+///              ---\n \
+///              hello.txt | 2 +-\n\
+///              \n\
+///              diff --git a/hello.txt b/hello.txt\n\
+///              --- a/hello.txt\n\
+///              +++ b/hello.txt\n\
+///              @@ -1 +1 @@\n\
+///              -hello\n\
+///              +hello everyone\n\
+///              -- \n\
+///              2.43.0\n";
+/// let email = EmailPatch::<String>::from_patch(email).unwrap();
+/// assert_eq!(email.author, "Jane Doe <jane@example.com>");
+/// assert_eq!(email.subject, "Fix greeting");
+/// assert_eq!(email.message, "Say hello to everyone.");
+/// assert_eq!(email.patch.files[0].hunks[0].changes.len(), 2);
+/// ```
+impl<T: FromStr> FromPatch for EmailPatch<T> {
+    fn from_patch(s: &str) -> Result<Self, PatchError> {
+        let mut parser = Parser::new(s);
+        let email = parser.parse_email()?;
+        parser.finish()?;
+        Ok(email)
+    }
+}
+
+/// Parses a mailbox of emails as written by `git format-patch --stdout`.
+///
+/// Each email starts at a `From ` line directly followed by a `From:` header.
+impl<T: FromStr> FromPatch for Vec<EmailPatch<T>> {
+    fn from_patch(s: &str) -> Result<Self, PatchError> {
+        let mut parser = Parser::new(s);
+        let mut emails = vec![];
+        while parser.peek(0).is_some() {
+            emails.push(parser.parse_email()?);
+        }
+        Ok(emails)
+    }
+}
+
+/// Cursor over the lines of a patch.
+struct Parser<'a> {
+    source: &'a str,
+    // (byte offset, line) pairs
+    lines: Vec<(usize, &'a str)>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(source: &'a str) -> Self {
+        // can't use `.lines()` because a lone \r in a line
         // would break the roundtrip property
-        let mut lines = s.split('\n');
-        let first_line = lines.next().unwrap_or("");
-        let second_line = lines.next().unwrap_or("");
-        if !first_line.starts_with("---") || !second_line.starts_with("+++") {
-            return Err(PatchError::InvalidFormat(format!(
-                "{}\n{}",
-                first_line, second_line
-            )));
+        let terminator = LineEnding::detect(source).as_str();
+        let mut offset = 0;
+        let mut lines = vec![];
+        for line in source.split(terminator) {
+            lines.push((offset, line));
+            offset += line.len() + terminator.len();
         }
+        if source.ends_with(terminator) {
+            lines.pop();
+        }
+        Parser {
+            source,
+            lines,
+            pos: 0,
+        }
+    }
+
+    fn peek(&self, ahead: usize) -> Option<&'a str> {
+        self.lines.get(self.pos + ahead).map(|(_, l)| *l)
+    }
 
-        let mut current = None;
+    fn next(&mut self) -> Option<&'a str> {
+        let line = self.peek(0);
+        self.pos += 1;
+        line
+    }
+
+    /// Location of the line at `pos`, or of the end of input.
+    fn location(&self, pos: usize) -> Location {
+        match self.lines.get(pos) {
+            Some((offset, line)) => Location::new(pos + 1, *offset, line),
+            None => Location::new(pos + 1, self.source.len(), ""),
+        }
+    }
+
+    fn parse_file<T: FromStr>(&mut self) -> Result<PatchFile<T>, PatchError> {
+        let (old_name, old_time) = self.parse_name("---")?;
+        let (new_name, new_time) = self.parse_name("+++")?;
+        let mut header = PatchHeader {
+            old_time,
+            new_time,
+            ..PatchHeader::new(old_name, new_name)
+        };
         let mut hunks = vec![];
+        while self.peek(0).is_some_and(|l| l.starts_with("@@")) {
+            hunks.push(self.parse_hunk(&mut header)?);
+        }
+        Ok(PatchFile { header, hunks })
+    }
+
+    /// Parses files until the end of input, or until the next email if `in_mailbox`.
+    fn parse_patch<T: FromStr>(&mut self, in_mailbox: bool) -> Result<Patch<T>, PatchError> {
+        let mut files = vec![];
+        while let Some(line) = self.peek(0) {
+            if in_mailbox && self.at_email_start() {
+                break;
+            } else if let Some(names) = line.strip_prefix("diff --git ") {
+                self.next();
+                files.push(self.parse_git_file(names)?);
+            } else if self.at_file_header() {
+                files.push(self.parse_file()?);
+            } else {
+                self.next();
+            }
+        }
+        Ok(Patch { files })
+    }
+
+    fn at_email_start(&self) -> bool {
+        self.peek(0).is_some_and(|l| l.starts_with("From "))
+            && self.peek(1).is_some_and(|l| l.starts_with("From:"))
+    }
+
+    fn parse_email<T: FromStr>(&mut self) -> Result<EmailPatch<T>, PatchError> {
+        let location = self.location(self.pos);
+        if !self.at_email_start() {
+            return Err(PatchError::MissingHeader(location));
+        }
+        let commit = self.next().unwrap_or_default()["From ".len()..]
+            .split(' ')
+            .next()
+            .unwrap_or_default()
+            .to_string();
 
-        for e in lines {
-            if e.starts_with("@@") {
-                if let Some(c) = current {
-                    hunks.push(c);
+        // headers end at the first empty line, and continue on indented lines
+        let mut headers: Vec<(&str, String)> = vec![];
+        while let Some(line) = self.next() {
+            if line.is_empty() {
+                break;
+            }
+            if line.starts_with([' ', '\t']) {
+                if let Some((_, value)) = headers.last_mut() {
+                    value.push(' ');
+                    value.push_str(line.trim_start());
                 }
+            } else if let Some((name, value)) = line.split_once(':') {
+                headers.push((name, value.trim_start().to_string()));
+            }
+        }
+        let header = |name: &str| {
+            headers
+                .iter()
+                .find(|(n, _)| n.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.clone())
+                .ok_or_else(|| PatchError::MissingHeader(location.clone()))
+        };
+        let author = header("From")?;
+        let date = header("Date")?;
+        let mut subject = header("Subject")?;
+        // drop prefixes like "[PATCH v2 1/3]"
+        while let Some(rest) = subject.strip_prefix('[') {
+            match rest.split_once(']') {
+                Some((_, rest)) => subject = rest.trim_start().to_string(),
+                None => break,
+            }
+        }
 
-                let (old_start, new_start) = parse_hunk_header(e)?;
-                current = Some(Hunk {
-                    old_start,
-                    new_start,
-                    changes: vec![],
-                });
-            } else if let Some(ref mut c) = current {
-                c.changes.push(Edit::from_patch(e)?);
-            } else {
-                return Err(PatchError::InvalidFormat(e.to_string()));
+        let mut message = vec![];
+        while let Some(line) = self.peek(0) {
+            if line == "---" || line.starts_with("diff --git ") || self.at_email_start() {
+                break;
             }
+            message.push(line);
+            self.next();
+        }
+        while message.last().is_some_and(|l| l.trim().is_empty()) {
+            message.pop();
         }
 
-        if let Some(c) = current {
-            hunks.push(c);
+        Ok(EmailPatch {
+            commit,
+            author,
+            date,
+            subject,
+            message: message.join("\n"),
+            patch: self.parse_patch(true)?,
+        })
+    }
+
+    fn at_file_header(&self) -> bool {
+        self.peek(0).is_some_and(|l| l.starts_with("--- "))
+            && self.peek(1).is_some_and(|l| l.starts_with("+++ "))
+    }
+
+    /// Parses the section following a `diff --git <names>` line.
+    fn parse_git_file<T: FromStr>(&mut self, names: &str) -> Result<PatchFile<T>, PatchError> {
+        let mut created = false;
+        let mut deleted = false;
+        let mut old_mode = None;
+        let mut mode = None;
+        let mut similarity = 100;
+        let mut relation: Option<fn(u8) -> Relation> = None;
+        let mut old_hash = None;
+        let mut new_hash = None;
+        while let Some(line) = self.peek(0) {
+            if line.starts_with("diff --git ") || self.at_file_header() {
+                break;
+            }
+            let octal = |m: &str| u32::from_str_radix(m.trim(), 8).ok();
+            if let Some(m) = line.strip_prefix("new file mode ") {
+                created = true;
+                mode = octal(m);
+            } else if let Some(m) = line.strip_prefix("deleted file mode ") {
+                deleted = true;
+                mode = octal(m);
+            } else if let Some(m) = line.strip_prefix("old mode ") {
+                old_mode = octal(m);
+            } else if let Some(m) = line.strip_prefix("new mode ") {
+                mode = octal(m);
+            } else if let Some(index) = line.strip_prefix("similarity index ") {
+                similarity = index.trim_end_matches('%').parse().unwrap_or(similarity);
+            } else if line.starts_with("rename from ") {
+                relation = Some(|similarity| Relation::Rename { similarity });
+            } else if line.starts_with("copy from ") {
+                relation = Some(|similarity| Relation::Copy { similarity });
+            } else if let Some(hash) = line.strip_prefix("pre-image hash ") {
+                old_hash = ContentHash::from_hex(hash.trim());
+            } else if let Some(hash) = line.strip_prefix("post-image hash ") {
+                new_hash = ContentHash::from_hex(hash.trim());
+            }
+            self.next();
         }
 
-        Ok(hunks)
+        let mut file = if self.at_file_header() {
+            self.parse_file()?
+        } else {
+            let (old_name, new_name) = names.split_once(" b/").map_or_else(
+                || (names.to_string(), names.to_string()),
+                |(old, new)| (old.to_string(), format!("b/{}", new)),
+            );
+            PatchFile {
+                header: PatchHeader::new(old_name, new_name),
+                hunks: vec![],
+            }
+        };
+        file.header.old_mode = old_mode;
+        file.header.mode = mode;
+        file.header.relation = relation.map(|relation| relation(similarity));
+        file.header.old_hash = old_hash;
+        file.header.new_hash = new_hash;
+        if created {
+            file.header.old_name = DEV_NULL.to_string();
+        }
+        if deleted {
+            file.header.new_name = DEV_NULL.to_string();
+        }
+        Ok(file)
+    }
+
+    /// Parses a `---`/`+++` line into the file name and its optional timestamp.
+    fn parse_name(&mut self, prefix: &str) -> Result<(String, Option<String>), PatchError> {
+        let location = self.location(self.pos);
+        let name = self
+            .next()
+            .and_then(|l| l.strip_prefix(prefix))
+            .ok_or(PatchError::MissingHeader(location))?;
+        let (name, time) = match name.split_once('\t') {
+            Some((name, time)) => (name, Some(time.to_string())),
+            None => (name, None),
+        };
+        Ok((name.trim_start().to_string(), time))
+    }
+
+    /// Parses a hunk, recording its [`NO_NEWLINE`] markers in `file_header`.
+    fn parse_hunk<T: FromStr>(
+        &mut self,
+        file_header: &mut PatchHeader,
+    ) -> Result<Hunk<T>, PatchError> {
+        let header_location = self.location(self.pos);
+        let header = self
+            .next()
+            .and_then(parse_hunk_header)
+            .ok_or_else(|| PatchError::BadHunkHeader(header_location.clone()))?;
+
+        let mut old_left = header.old_count;
+        let mut new_left = header.new_count;
+        let mut changes = vec![];
+        while old_left > 0 || new_left > 0 {
+            let location = self.location(self.pos);
+            let line = self
+                .next()
+                .ok_or_else(|| PatchError::BadHunkHeader(header_location.clone()))?;
+            if line.starts_with('\\') {
+                missing_newline(file_header, changes.last());
+                continue;
+            }
+            let edit = parse_edit(line, location.line, location.offset)?;
+            let left = match edit {
+                Edit::Equal(_) => (old_left.checked_sub(1), new_left.checked_sub(1)),
+                Edit::Delete(_) => (old_left.checked_sub(1), Some(new_left)),
+                Edit::Insert(_) => (Some(old_left), new_left.checked_sub(1)),
+            };
+            match left {
+                (Some(o), Some(n)) => (old_left, new_left) = (o, n),
+                _ => return Err(PatchError::BadHunkHeader(header_location)),
+            }
+            changes.push(edit);
+        }
+        if self.peek(0).is_some_and(|l| l.starts_with('\\')) {
+            self.next();
+            missing_newline(file_header, changes.last());
+        }
+
+        Ok(Hunk {
+            old_start: header.old_start,
+            new_start: header.new_start,
+            changes,
+            section: header.section,
+        })
+    }
+
+    fn parse_combined_hunk<T: FromStr>(&mut self) -> Result<CombinedHunk<T>, PatchError> {
+        let header_location = self.location(self.pos);
+        let bad_header = || PatchError::BadHunkHeader(header_location.clone());
+        let (starts, mut counts) = self
+            .next()
+            .and_then(parse_combined_hunk_header)
+            .ok_or_else(bad_header)?;
+        let parents = starts.len() - 1;
+
+        let mut lines = vec![];
+        while counts.iter().any(|c| *c > 0) {
+            let location = self.location(self.pos);
+            let line = self.next().ok_or_else(bad_header)?;
+            let bad_prefix = || PatchError::BadLinePrefix(location.clone());
+            let prefix = line.get(..parents).ok_or_else(bad_prefix)?;
+            let columns = prefix
+                .chars()
+                .map(|c| match c {
+                    ' ' => Ok(Column::Context),
+                    '+' => Ok(Column::Added),
+                    '-' => Ok(Column::Removed),
+                    _ => Err(bad_prefix()),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            if columns.contains(&Column::Added) && columns.contains(&Column::Removed) {
+                return Err(bad_prefix());
+            }
+            let value = line[parents..]
+                .parse()
+                .map_err(|_| PatchError::BadValue(location.clone()))?;
+            let line = CombinedLine { columns, value };
+
+            for (i, count) in counts.iter_mut().enumerate() {
+                let present = if i < parents {
+                    line.in_parent(i)
+                } else {
+                    line.in_result()
+                };
+                if present {
+                    *count = count.checked_sub(1).ok_or_else(bad_header)?;
+                }
+            }
+            lines.push(line);
+        }
+
+        let new_start = starts[parents];
+        Ok(CombinedHunk {
+            parent_starts: starts[..parents].to_vec(),
+            new_start,
+            lines,
+        })
+    }
+
+    /// Fails if there are lines left that don't belong to any hunk.
+    fn finish(&self) -> Result<(), PatchError> {
+        if self.pos < self.lines.len() {
+            return Err(PatchError::MissingHeader(self.location(self.pos)));
+        }
+        Ok(())
+    }
+}
+
+/// Parses `@@@ -1,4 -1,4 +1,5 @@@` into the 0-based starts and the counts
+/// of every parent, followed by those of the result.
+/// Records a [`NO_NEWLINE`] marker in `header`, for the sides of the `line` it follows.
+fn missing_newline<T>(header: &mut PatchHeader, line: Option<&Edit<T>>) {
+    match line {
+        Some(Edit::Equal(_)) => {
+            header.old_missing_newline = true;
+            header.new_missing_newline = true;
+        }
+        Some(Edit::Delete(_)) => header.old_missing_newline = true,
+        Some(Edit::Insert(_)) => header.new_missing_newline = true,
+        None => {}
+    }
+}
+
+fn parse_combined_hunk_header(s: &str) -> Option<(Vec<usize>, Vec<usize>)> {
+    let markers = s.len() - s.trim_start_matches('@').len();
+    if markers < 3 {
+        return None;
+    }
+    let closing = format!(" {}", &s[..markers]);
+    let (ranges, _section) = s[markers..].strip_prefix(' ')?.split_once(&closing)?;
+    let ranges: Vec<&str> = ranges.split(' ').collect();
+    if ranges.len() != markers {
+        return None;
     }
+    let (parents, result) = ranges.split_at(markers - 1);
+    let mut parsed = parents
+        .iter()
+        .map(|r| parse_range(r.strip_prefix('-')?))
+        .collect::<Option<Vec<_>>>()?;
+    parsed.push(parse_range(result[0].strip_prefix('+')?)?);
+    Some(parsed.into_iter().unzip())
 }
 
-fn parse_hunk_header(s: &str) -> Result<(usize, usize), PatchError> {
-    // s = "@@ -1,4 +1,4 @@"
-    let s = s.trim_start_matches("@@ ").trim_end_matches(" @@");
-    let parts: Vec<&str> = s.split(' ').collect();
-    // parts = ["-1,4", "+1,4"]
-    let old_start = parts[0]
-        .trim_start_matches('-')
-        .split(',')
-        .next()
-        .ok_or(PatchError::InvalidFormat(s.to_string()))?
-        .parse::<usize>()
-        .map_err(|_| PatchError::InvalidFormat(s.to_string()))?;
-    let new_start = parts[1]
-        .trim_start_matches('+')
-        .split(',')
-        .next()
-        .ok_or(PatchError::InvalidFormat(s.to_string()))?
-        .parse::<usize>()
-        .map_err(|_| PatchError::InvalidFormat(s.to_string()))?;
-    Ok((old_start, new_start))
+struct HunkHeader {
+    old_start: usize,
+    old_count: usize,
+    new_start: usize,
+    new_count: usize,
+    section: Option<String>,
+}
+
+fn parse_hunk_header(s: &str) -> Option<HunkHeader> {
+    // s = "@@ -1,4 +1,4 @@ optional section"
+    let (ranges, section) = s.strip_prefix("@@ ")?.split_once(" @@")?;
+    let section = match section {
+        "" => None,
+        section => Some(section.strip_prefix(' ')?.to_string()),
+    };
+    let (old, new) = ranges.split_once(' ')?;
+    // old = "-1,4", new = "+1,4"
+    let (old_start, old_count) = parse_range(old.strip_prefix('-')?)?;
+    let (new_start, new_count) = parse_range(new.strip_prefix('+')?)?;
+    Some(HunkHeader {
+        old_start,
+        old_count,
+        new_start,
+        new_count,
+        section,
+    })
+}
+
+/// Parses a `start,count` range into a 0-based start index.
+/// The count defaults to 1 when omitted.
+fn parse_range(s: &str) -> Option<(usize, usize)> {
+    let (start, count) = match s.split_once(',') {
+        Some((start, count)) => (start.parse::<usize>().ok()?, count.parse().ok()?),
+        None => (s.parse().ok()?, 1),
+    };
+    if count == 0 {
+        Some((start, count))
+    } else {
+        Some((start.checked_sub(1)?, count))
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::myers::diff;
-    use crate::patch::{hunks, Hunk};
+    use crate::myers::{diff, diff_lines};
+    use crate::patch::{combined_hunks, hunks, Hunk};
     use proptest::prelude::*;
 
     proptest! {
+        #[test]
+        fn test_edit_script_roundtrip(
+            old in prop::collection::vec(".*", 0..20usize),
+            new in prop::collection::vec(".*", 0..20usize),
+        ) {
+            let edits = diff(&old, &new);
+            let patch = edits.to_patch(&PatchHeader::default());
+
+            prop_assert_eq!(Vec::<Edit<String>>::from_patch(&patch).unwrap(), edits);
+        }
+
         #[test]
         fn test_serialization_roundtrip(
                     old in prop::collection::vec(".*", 0..20usize),
@@ -189,7 +1160,7 @@ mod tests {
         ) {
             let edits = diff(&old, &new);
             let hunks = hunks(edits.clone());
-            let patch = hunks.to_patch(None, None);
+            let patch = hunks.to_patch(&PatchHeader::default());
 
             prop_assert_eq!(Vec::<Hunk<String>>::from_patch(&patch).unwrap(), hunks);
         }
@@ -202,7 +1173,7 @@ mod tests {
         let edits = diff(&old, &new);
         let h = hunks(edits);
         assert_eq!(h.len(), 2, "expected 2 hunks");
-        let patch = h.to_patch(Some("old.txt"), Some("new.txt"));
+        let patch = h.to_patch(&PatchHeader::new("old.txt", "new.txt"));
         // Each @@ header must start on its own line
         for line in patch.lines() {
             if line.starts_with("@@") || line.starts_with("---") || line.starts_with("+++") {
@@ -228,8 +1199,350 @@ mod tests {
             .collect();
         let edits = diff(&old, &new);
         let h = hunks(edits);
-        let patch = h.to_patch(Some("old.txt"), Some("new.txt"));
+        let patch = h.to_patch(&PatchHeader::new("old.txt", "new.txt"));
         let parsed = Vec::<Hunk<String>>::from_patch(&patch).unwrap();
         assert_eq!(parsed, h);
     }
+
+    #[test]
+    fn test_missing_header_location() {
+        let patch = "--- old\n@@ -0,1 +0,1 @@\n-a\n+b";
+        assert_eq!(
+            Vec::<Hunk<String>>::from_patch(patch),
+            Err(PatchError::MissingHeader(Location {
+                line: 2,
+                offset: 8,
+                content: "@@ -0,1 +0,1 @@".to_string()
+            }))
+        );
+    }
+
+    #[test]
+    fn test_bad_hunk_header_location() {
+        let patch = "--- old\n+++ new\n@@ -0,1 @@\n-a";
+        assert_eq!(
+            Vec::<Hunk<String>>::from_patch(patch),
+            Err(PatchError::BadHunkHeader(Location {
+                line: 3,
+                offset: 16,
+                content: "@@ -0,1 @@".to_string()
+            }))
+        );
+    }
+
+    #[test]
+    fn test_bad_line_prefix_location() {
+        let patch = "--- old\n+++ new\n@@ -1,1 +1,1 @@\n-a\n*b";
+        assert_eq!(
+            Vec::<Hunk<String>>::from_patch(patch),
+            Err(PatchError::BadLinePrefix(Location {
+                line: 5,
+                offset: 35,
+                content: "*b".to_string()
+            }))
+        );
+    }
+
+    #[test]
+    fn test_section_roundtrip() {
+        let patch = "--- old\n+++ new\n@@ -1,2 +1,2 @@ fn apply()\n a\n-b\n+c";
+        let parsed = Vec::<Hunk<String>>::from_patch(patch).unwrap();
+        assert_eq!(parsed[0].section, Some("fn apply()".to_string()));
+        assert_eq!(parsed.to_patch(&PatchHeader::default()), patch);
+    }
+
+    #[test]
+    fn test_to_patch_with_sections() {
+        let old = vec!["a", "b", "c"];
+        let new = vec!["a", "x", "c"];
+        let h = hunks(diff(&old, &new));
+        let patch = to_patch_with_sections(&h, &PatchHeader::default(), LineEnding::Lf, |h| {
+            Some(format!("line {}", h.old_start))
+        });
+        assert_eq!(
+            patch,
+            "--- old\n+++ new\n@@ -1,3 +1,3 @@ line 0\n a\n+x\n-b\n c"
+        );
+    }
+
+    #[test]
+    fn test_multi_file_roundtrip() {
+        // "-- x" is deleted, producing a line that looks like a file header
+        let first = hunks(diff_lines("-- x\na", "a"));
+        let second = hunks(diff_lines("b", "c"));
+        let patch = Patch {
+            files: vec![
+                PatchFile {
+                    header: PatchHeader::new("a/first", "b/first"),
+                    hunks: first,
+                },
+                PatchFile {
+                    header: PatchHeader {
+                        relation: Some(Relation::Copy { similarity: 50 }),
+                        ..PatchHeader::new("a/second", "b/second")
+                    },
+                    hunks: second,
+                },
+            ],
+        };
+        let serialized = patch.to_patch(&PatchHeader::default());
+        assert!(serialized.contains("\n--- x\n"));
+        assert_eq!(Patch::<String>::from_patch(&serialized).unwrap(), patch);
+    }
+
+    #[test]
+    fn test_line_numbers_are_one_based() {
+        let h = hunks(diff(&["a", "b"], &["a", "b", "c"]));
+        assert_eq!(
+            h.to_patch(&PatchHeader::default()),
+            "--- old\n+++ new\n@@ -1,2 +1,3 @@\n a\n b\n+c"
+        );
+        let h = hunks(diff(&[], &["a"]));
+        assert_eq!(
+            h.to_patch(&PatchHeader::default()),
+            "--- old\n+++ new\n@@ -0,0 +1,1 @@\n+a"
+        );
+    }
+
+    #[test]
+    fn test_crlf_roundtrip_keeps_lone_carriage_returns() {
+        let old: Vec<String> = vec!["a\r".to_string(), "b".to_string()];
+        let new: Vec<String> = vec!["a\r".to_string(), "c\r".to_string()];
+        let h = hunks(diff(&old, &new));
+        let patch = h.to_patch_with_line_ending(&PatchHeader::default(), LineEnding::CrLf);
+        assert_eq!(
+            patch,
+            "--- old\r\n+++ new\r\n@@ -1,2 +1,2 @@\r\n a\r\r\n+c\r\r\n-b"
+        );
+        assert_eq!(LineEnding::detect(&patch), LineEnding::CrLf);
+        assert_eq!(Vec::<Hunk<String>>::from_patch(&patch).unwrap(), h);
+    }
+
+    #[test]
+    fn test_line_ending_of_external_impl() {
+        struct Note;
+        impl ToPatch for Note {
+            fn to_patch(&self, header: &PatchHeader) -> String {
+                format!("# {}\n# {}", header.old_name, header.new_name)
+            }
+        }
+        let patch = Note.to_patch_with_line_ending(&PatchHeader::default(), LineEnding::CrLf);
+        assert_eq!(patch, "# old\r\n# new");
+        assert_eq!(LineEnding::detect(&patch), LineEnding::CrLf);
+    }
+
+    #[test]
+    fn test_edit_script_keeps_all_context() {
+        let old = vec!["a", "b", "c", "d", "e", "f"];
+        let new = vec!["a", "b", "c", "d", "e", "X"];
+        let patch = diff(&old, &new).to_patch(&PatchHeader::default());
+        assert_eq!(
+            patch,
+            "--- old\n+++ new\n@@ -1,6 +1,6 @@\n a\n b\n c\n d\n e\n+X\n-f"
+        );
+    }
+
+    #[test]
+    fn test_edit_script_rejects_partial_hunks() {
+        let patch = "--- old\n+++ new\n@@ -3,1 +3,1 @@\n-a\n+b";
+        assert_eq!(
+            Vec::<Edit<String>>::from_patch(patch),
+            Err(PatchError::BadHunkHeader(Location {
+                line: 3,
+                offset: 16,
+                content: "@@ -3,1 +3,1 @@".to_string()
+            }))
+        );
+    }
+
+    #[test]
+    fn test_error_display() {
+        let err = Vec::<Hunk<String>>::from_patch("--- old\n+++ new\n@@ -1 +1 @@\n*a").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unexpected line prefix at line 4 (byte 28): '*a'"
+        );
+        let err: Box<dyn std::error::Error> = Box::new(err);
+        assert!(err.source().is_none());
+    }
+
+    #[test]
+    fn test_typed_roundtrip() {
+        let old = vec![1, 2, 3, 4];
+        let new = vec![1, 5, 3, 4];
+        let h = hunks(diff(&old, &new));
+        let patch = h.to_patch(&PatchHeader::default());
+        assert_eq!(Vec::<Hunk<i32>>::from_patch(&patch).unwrap(), h);
+    }
+
+    #[test]
+    fn test_bad_value_location() {
+        let patch = "--- old\n+++ new\n@@ -1 +1 @@\n-1\n+x";
+        assert_eq!(
+            Vec::<Hunk<i32>>::from_patch(patch),
+            Err(PatchError::BadValue(Location {
+                line: 5,
+                offset: 31,
+                content: "+x".to_string()
+            }))
+        );
+    }
+
+    #[test]
+    fn test_combined_roundtrip() {
+        let ours = vec!["a", "b", "c", "d"];
+        let theirs = vec!["a", "x", "c", "d"];
+        let merged = vec!["a", "b", "x", "d"];
+        let h = combined_hunks(&[diff(&ours, &merged), diff(&theirs, &merged)]);
+        let patch = h.to_patch(&PatchHeader::default());
+        assert_eq!(
+            patch,
+            "--- old\n+++ new\n@@@ -1,4 -1,4 +1,4 @@@\n  a\n +b\n+ x\n--c\n  d"
+        );
+        let parsed = Vec::<CombinedHunk<String>>::from_patch(&patch).unwrap();
+        assert_eq!(parsed.to_patch(&PatchHeader::default()), patch);
+    }
+
+    #[test]
+    fn test_git_creation_and_deletion() {
+        let patch = "diff --git a/empty b/empty\n\
+                     new file mode 100644\n\
+                     index 0000000..e69de29\n\
+                     diff --git a/old.txt b/old.txt\n\
+                     deleted file mode 100644\n\
+                     index 7898192..0000000\n\
+                     --- a/old.txt\n\
+                     +++ /dev/null\n\
+                     @@ -1 +0,0 @@\n\
+                     -a\n";
+        let patch = Patch::<String>::from_patch(patch).unwrap();
+        assert_eq!(patch.files.len(), 2);
+        assert!(patch.files[0].header.is_creation());
+        assert_eq!(patch.files[0].header.new_name, "b/empty");
+        assert_eq!(patch.files[0].header.mode, Some(0o100644));
+        assert!(patch.files[1].header.is_deletion());
+        assert_eq!(
+            patch.files[1].hunks[0].changes,
+            vec![Edit::Delete("a".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_missing_newline_roundtrip() {
+        let cases = [
+            (" a\n-b\n\\ No newline at end of file\n+b", (true, false)),
+            (" a\n-b\n+c\n\\ No newline at end of file", (false, true)),
+            ("-a\n+x\n b\n\\ No newline at end of file", (true, true)),
+        ];
+        for (lines, missing_newline) in cases {
+            let patch = format!("--- a/f\n+++ b/f\n@@ -1,2 +1,2 @@\n{}", lines);
+            let file = PatchFile::<String>::from_patch(&patch).unwrap();
+            let header = &file.header;
+            assert_eq!(
+                (header.old_missing_newline, header.new_missing_newline),
+                missing_newline
+            );
+            assert_eq!(file.hunks[0].changes.len(), 3);
+            assert_eq!(file.to_patch(&PatchHeader::default()), patch);
+        }
+    }
+
+    #[test]
+    fn test_pure_rename_roundtrip() {
+        let patch = Patch::<String> {
+            files: vec![PatchFile {
+                header: PatchHeader {
+                    relation: Some(Relation::Rename { similarity: 100 }),
+                    ..PatchHeader::new("a/old.rs", "b/new.rs")
+                },
+                hunks: vec![],
+            }],
+        };
+        let serialized = patch.to_patch(&PatchHeader::default());
+        assert_eq!(
+            serialized,
+            "diff --git a/old.rs b/new.rs\n\
+             similarity index 100%\n\
+             rename from old.rs\n\
+             rename to new.rs\n\
+             --- a/old.rs\n\
+             +++ b/new.rs"
+        );
+        assert_eq!(Patch::<String>::from_patch(&serialized).unwrap(), patch);
+    }
+
+    #[test]
+    fn test_header_metadata_roundtrip() {
+        let patch = "diff --git a/run.sh b/run.sh\n\
+                     old mode 100644\n\
+                     new mode 100755\n\
+                     --- a/run.sh\t2024-01-01 10:00:00.000000000 +0100\n\
+                     +++ b/run.sh\t2024-01-02 10:00:00.000000000 +0100\n\
+                     @@ -1,1 +1,1 @@\n\
+                     -echo a\n\
+                     +echo b";
+        let parsed = Patch::<String>::from_patch(patch).unwrap();
+        let header = &parsed.files[0].header;
+        assert_eq!(header.old_mode, Some(0o100644));
+        assert_eq!(header.mode, Some(0o100755));
+        assert_eq!(
+            header.new_time.as_deref(),
+            Some("2024-01-02 10:00:00.000000000 +0100")
+        );
+        assert_eq!(parsed.to_patch(&PatchHeader::default()), patch);
+    }
+
+    #[test]
+    fn test_mailbox() {
+        let mailbox = "From 1111111 Mon Sep 17 00:00:00 2001\n\
+                       From: A <a@example.com>\n\
+                       Date: Tue, 1 Oct 2024 10:00:00 +0200\n\
+                       Subject: [PATCH 1/2] First change with a\n \
+                       folded subject\n\
+                       \n\
+                       Body line.\n\
+                       \n\
+                       ---\n\
+                       --- a/x\n\
+                       +++ b/x\n\
+                       @@ -1 +1 @@\n\
+                       -a\n\
+                       +b\n\
+                       -- \n\
+                       2.43.0\n\
+                       \n\
+                       From 2222222 Mon Sep 17 00:00:00 2001\n\
+                       From: B <b@example.com>\n\
+                       Date: Wed, 2 Oct 2024 10:00:00 +0200\n\
+                       Subject: [PATCH 2/2] Second change\n\
+                       \n\
+                       diff --git a/y b/y\n\
+                       new file mode 100644\n\
+                       --- /dev/null\n\
+                       +++ b/y\n\
+                       @@ -0,0 +1 @@\n\
+                       +c\n";
+        let emails = Vec::<EmailPatch<String>>::from_patch(mailbox).unwrap();
+        assert_eq!(emails.len(), 2);
+        assert_eq!(emails[0].commit, "1111111");
+        assert_eq!(emails[0].subject, "First change with a folded subject");
+        assert_eq!(emails[0].message, "Body line.");
+        assert_eq!(emails[0].patch.files.len(), 1);
+        assert_eq!(emails[1].author, "B <b@example.com>");
+        assert_eq!(emails[1].message, "");
+        assert!(emails[1].patch.files[0].header.is_creation());
+    }
+
+    #[test]
+    fn test_email_missing_subject() {
+        let email = "From 1111111 Mon Sep 17 00:00:00 2001\nFrom: A\nDate: now\n\nbody";
+        assert_eq!(
+            EmailPatch::<String>::from_patch(email),
+            Err(PatchError::MissingHeader(Location {
+                line: 1,
+                offset: 0,
+                content: "From 1111111 Mon Sep 17 00:00:00 2001".to_string(),
+            }))
+        );
+    }
 }
@@ -0,0 +1,377 @@
+//! Compressed storage of change sets and patches, e.g. to keep a long history on disk.
+//!
+//! Each change set or patch is written as a frame: a byte for its kind, a byte for its
+//! compression, its compressed then decompressed lengths as little-endian `u32`s,
+//! then its compressed content. Content decompressing to more than its recorded
+//! length is rejected without being decompressed any further.
+//! Frames are written one after the other, so a file can be appended to, and the
+//! frames of a file may be compressed differently.
+//!
+//! Change sets hold their id, metadata and changes, the latter encoded with
+//! [`to_bytes`]; patches are stored as unified diffs.
+//! Zstandard needs the `zstd` feature, through the pure Rust `ruzstd`,
+//! and deflate the `deflate` feature, through `flate2`.
+//!
+//! ```
+//! use std::collections::HashMap;
+//! use diffkit::recursive::{diff, ChangeSet};
+//! use diffkit::store::{read_change_sets, write_change_set, Compression};
+//!
+//! let old = HashMap::from([("replicas".to_string(), 3)]);
+//! let new = HashMap::from([("replicas".to_string(), 5)]);
+//! let set = ChangeSet::new("42", diff(&old, &new)).meta("author", "ann");
+//!
+//! let mut file = vec![];
+//! write_change_set(&mut file, &set, Compression::None).unwrap();
+//! assert_eq!(read_change_sets::<i32>(&file[..]), Ok(vec![set]));
+//! ```
+
+use crate::patch::{Patch, PatchHeader};
+use crate::recursive::{from_bytes, to_bytes, BinaryError, ChangeSet, Primitive};
+use crate::serialization::{FromPatch, PatchError, ToPatch};
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::str::FromStr;
+
+/// How the content of a frame is compressed.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Compression {
+    None,
+    /// Zstandard, at the level of `zstd -1`
+    #[cfg(feature = "zstd")]
+    Zstd,
+    /// Deflate, at the default level of zlib
+    #[cfg(feature = "deflate")]
+    Deflate,
+}
+
+/// The kinds of frames.
+const CHANGE_SET: u8 = 0;
+const PATCH: u8 = 1;
+
+/// The compressions of frames.
+const NONE: u8 = 0;
+const ZSTD: u8 = 1;
+const DEFLATE: u8 = 2;
+
+/// Appends a change set to `writer`, as a frame compressed with `compression`.
+///
+/// # Errors
+///
+/// Returns the errors of `writer`, and [`io::ErrorKind::InvalidInput`]
+/// if the change set, or one of its strings, takes 4 GiB or more,
+/// or if it has 2³² metadata or more.
+pub fn write_change_set<W: Write, P: Primitive + ToString>(
+    writer: &mut W,
+    set: &ChangeSet<P>,
+    compression: Compression,
+) -> io::Result<()> {
+    let mut content = vec![];
+    write_string(&mut content, &set.id)?;
+    content.extend(length(set.meta.len(), "metadata")?.to_le_bytes());
+    for (key, value) in &set.meta {
+        write_string(&mut content, key)?;
+        write_string(&mut content, value)?;
+    }
+    content.extend(to_bytes(&set.changes));
+    write_frame(writer, CHANGE_SET, &content, compression)
+}
+
+/// Reads the change sets written by [`write_change_set`], until the end of `reader`.
+///
+/// # Errors
+///
+/// Returns a [`StoreError`] if `reader` fails, or doesn't hold change sets.
+pub fn read_change_sets<P: Primitive + FromStr>(
+    reader: impl Read,
+) -> Result<Vec<ChangeSet<P>>, StoreError> {
+    read_frames(reader, CHANGE_SET)?
+        .iter()
+        .map(|content| {
+            let mut content = &content[..];
+            let id = read_string(&mut content)?;
+            let mut set = ChangeSet::new(id, vec![]);
+            for _ in 0..read_u32(&mut content)? {
+                let key = read_string(&mut content)?;
+                set.meta.insert(key, read_string(&mut content)?);
+            }
+            set.changes = from_bytes(content).map_err(StoreError::Binary)?;
+            Ok(set)
+        })
+        .collect()
+}
+
+/// Appends a patch to `writer`, as a frame compressed with `compression`.
+///
+/// # Errors
+///
+/// Returns the errors of [`write_change_set`].
+pub fn write_patch<W: Write, T: ToString>(
+    writer: &mut W,
+    patch: &Patch<T>,
+    compression: Compression,
+) -> io::Result<()> {
+    let text = patch.to_patch(&PatchHeader::default());
+    write_frame(writer, PATCH, text.as_bytes(), compression)
+}
+
+/// Reads the patches written by [`write_patch`], until the end of `reader`.
+///
+/// # Errors
+///
+/// Returns a [`StoreError`] if `reader` fails, or doesn't hold patches.
+pub fn read_patches<T: FromStr>(reader: impl Read) -> Result<Vec<Patch<T>>, StoreError> {
+    read_frames(reader, PATCH)?
+        .into_iter()
+        .map(|content| {
+            let text = String::from_utf8(content).map_err(|_| StoreError::Malformed)?;
+            Patch::from_patch(&text).map_err(StoreError::Patch)
+        })
+        .collect()
+}
+
+/// Error returned when frames can't be read back.
+#[derive(Debug, PartialEq)]
+pub enum StoreError {
+    /// The reader failed.
+    Io(String),
+    /// The bytes end in the middle of a frame, or a frame is of another kind
+    /// or compressed in an unknown way.
+    Malformed,
+    /// A frame is compressed with a method whose feature, named here, is disabled.
+    Unsupported(&'static str),
+    /// The changes of a change set don't decode.
+    Binary(BinaryError),
+    /// A patch doesn't parse.
+    Patch(PatchError),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::Io(message) => write!(f, "{}", message),
+            StoreError::Malformed => write!(f, "malformed frame"),
+            StoreError::Unsupported(feature) => {
+                write!(f, "compressed frame needs the {} feature", feature)
+            }
+            StoreError::Binary(e) => write!(f, "{}", e),
+            StoreError::Patch(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+fn write_frame<W: Write>(
+    writer: &mut W,
+    kind: u8,
+    content: &[u8],
+    compression: Compression,
+) -> io::Result<()> {
+    let decompressed_len = length(content.len(), "frame")?;
+    let (method, content) = match compression {
+        Compression::None => (NONE, content.to_vec()),
+        #[cfg(feature = "zstd")]
+        Compression::Zstd => {
+            let level = ruzstd::encoding::CompressionLevel::Fastest;
+            (ZSTD, ruzstd::encoding::compress_to_vec(content, level))
+        }
+        #[cfg(feature = "deflate")]
+        Compression::Deflate => {
+            let level = flate2::Compression::default();
+            let mut encoder = flate2::write::DeflateEncoder::new(vec![], level);
+            encoder.write_all(content)?;
+            (DEFLATE, encoder.finish()?)
+        }
+    };
+    let len = length(content.len(), "compressed frame")?;
+    writer.write_all(&[kind, method])?;
+    writer.write_all(&len.to_le_bytes())?;
+    writer.write_all(&decompressed_len.to_le_bytes())?;
+    writer.write_all(&content)
+}
+
+/// `len` as a `u32`, or [`io::ErrorKind::InvalidInput`] naming `what` is too large.
+fn length(len: usize, what: &str) -> io::Result<u32> {
+    u32::try_from(len)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, format!("{} too large", what)))
+}
+
+/// The decompressed contents of the frames of `reader`, all of the given kind.
+fn read_frames(mut reader: impl Read, kind: u8) -> Result<Vec<Vec<u8>>, StoreError> {
+    let mut bytes = vec![];
+    reader
+        .read_to_end(&mut bytes)
+        .map_err(|e| StoreError::Io(e.to_string()))?;
+    let mut bytes = &bytes[..];
+    let mut contents = vec![];
+    while let Some((header, rest)) = bytes.split_first_chunk::<10>() {
+        let [frame_kind, method, l0, l1, l2, l3, d0, d1, d2, d3] = *header;
+        let len = u32::from_le_bytes([l0, l1, l2, l3]) as usize;
+        let decompressed_len = u32::from_le_bytes([d0, d1, d2, d3]);
+        let Some((content, rest)) = rest.split_at_checked(len).filter(|_| frame_kind == kind)
+        else {
+            return Err(StoreError::Malformed);
+        };
+        contents.push(decompress(method, content, decompressed_len)?);
+        bytes = rest;
+    }
+    if !bytes.is_empty() {
+        return Err(StoreError::Malformed);
+    }
+    Ok(contents)
+}
+
+/// Decompresses `content` to its recorded `len`, stopping one byte past it
+/// so that a frame can't decompress to much more than its header says.
+fn decompress(method: u8, content: &[u8], len: u32) -> Result<Vec<u8>, StoreError> {
+    let decompressed = match method {
+        NONE => content.to_vec(),
+        #[cfg(feature = "zstd")]
+        ZSTD => {
+            let decoder = ruzstd::decoding::StreamingDecoder::new(content)
+                .map_err(|_| StoreError::Malformed)?;
+            let mut decompressed = vec![];
+            decoder
+                .take(u64::from(len) + 1)
+                .read_to_end(&mut decompressed)
+                .map_err(|_| StoreError::Malformed)?;
+            decompressed
+        }
+        #[cfg(not(feature = "zstd"))]
+        ZSTD => return Err(StoreError::Unsupported("zstd")),
+        #[cfg(feature = "deflate")]
+        DEFLATE => {
+            let mut decompressed = vec![];
+            flate2::read::DeflateDecoder::new(content)
+                .take(u64::from(len) + 1)
+                .read_to_end(&mut decompressed)
+                .map_err(|_| StoreError::Malformed)?;
+            decompressed
+        }
+        #[cfg(not(feature = "deflate"))]
+        DEFLATE => return Err(StoreError::Unsupported("deflate")),
+        _ => return Err(StoreError::Malformed),
+    };
+    if decompressed.len() != len as usize {
+        return Err(StoreError::Malformed);
+    }
+    Ok(decompressed)
+}
+
+fn write_string(bytes: &mut Vec<u8>, s: &str) -> io::Result<()> {
+    bytes.extend(length(s.len(), "string")?.to_le_bytes());
+    bytes.extend(s.as_bytes());
+    Ok(())
+}
+
+fn read_u32(bytes: &mut &[u8]) -> Result<u32, StoreError> {
+    let (n, rest) = bytes.split_first_chunk().ok_or(StoreError::Malformed)?;
+    *bytes = rest;
+    Ok(u32::from_le_bytes(*n))
+}
+
+fn read_string(bytes: &mut &[u8]) -> Result<String, StoreError> {
+    let len = read_u32(bytes)? as usize;
+    let (s, rest) = bytes.split_at_checked(len).ok_or(StoreError::Malformed)?;
+    *bytes = rest;
+    String::from_utf8(s.to_vec()).map_err(|_| StoreError::Malformed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patch::PatchFile;
+    use crate::recursive::{Change, ChangeKind, Node, PathSegment};
+
+    fn change_set(id: &str) -> ChangeSet<i32> {
+        let changes = (0..100)
+            .map(|i| Change {
+                path: vec![PathSegment::Key("list".to_string()), PathSegment::Index(i)],
+                kind: ChangeKind::NodeAdded(Node::Sequence(vec![Node::Leaf(0); 20])),
+            })
+            .collect();
+        ChangeSet::new(id, changes).meta("author", "ann")
+    }
+
+    fn compressions() -> Vec<Compression> {
+        vec![
+            Compression::None,
+            #[cfg(feature = "zstd")]
+            Compression::Zstd,
+            #[cfg(feature = "deflate")]
+            Compression::Deflate,
+        ]
+    }
+
+    #[test]
+    fn test_change_sets() {
+        let mut file = vec![];
+        let mut sets = vec![];
+        for (i, compression) in compressions().into_iter().enumerate() {
+            let set = change_set(&i.to_string());
+            let start = file.len();
+            write_change_set(&mut file, &set, compression).unwrap();
+            if compression != Compression::None {
+                assert!(file.len() - start < to_bytes(&set.changes).len() / 4);
+            }
+            sets.push(set);
+        }
+        assert_eq!(read_change_sets(&file[..]), Ok(sets));
+        assert_eq!(read_change_sets::<i32>(&[][..]), Ok(vec![]));
+
+        assert_eq!(
+            read_change_sets::<i32>(&file[..file.len() - 1]),
+            Err(StoreError::Malformed)
+        );
+        assert_eq!(
+            read_patches::<String>(&file[..]),
+            Err(StoreError::Malformed)
+        );
+        // content decompressing past the length of its header
+        for compression in compressions() {
+            let mut frame = vec![];
+            write_change_set(&mut frame, &change_set("0"), compression).unwrap();
+            frame[6..10].copy_from_slice(&10u32.to_le_bytes());
+            assert_eq!(
+                read_change_sets::<i32>(&frame[..]),
+                Err(StoreError::Malformed)
+            );
+        }
+        file[1] = 9;
+        assert_eq!(
+            read_change_sets::<i32>(&file[..]),
+            Err(StoreError::Malformed)
+        );
+        #[cfg(not(feature = "zstd"))]
+        assert_eq!(
+            read_change_sets::<i32>(&[CHANGE_SET, ZSTD, 0, 0, 0, 0, 0, 0, 0, 0][..]),
+            Err(StoreError::Unsupported("zstd"))
+        );
+        #[cfg(not(feature = "deflate"))]
+        assert_eq!(
+            read_change_sets::<i32>(&[CHANGE_SET, DEFLATE, 0, 0, 0, 0, 0, 0, 0, 0][..]),
+            Err(StoreError::Unsupported("deflate"))
+        );
+    }
+
+    #[test]
+    fn test_patches() {
+        let patch: Patch<String> = Patch {
+            files: vec![PatchFile {
+                header: PatchHeader::new("a.txt", "a.txt"),
+                hunks: crate::patch::hunks(crate::myers::diff(
+                    &["x".to_string(), "y".to_string()],
+                    &["x".to_string(), "z".to_string()],
+                )),
+            }],
+        };
+        let mut file = vec![];
+        for compression in compressions() {
+            write_patch(&mut file, &patch, compression).unwrap();
+        }
+        let patches = read_patches::<String>(&file[..]).unwrap();
+        assert_eq!(patches.len(), compressions().len());
+        assert!(patches.iter().all(|p| *p == patch));
+    }
+}
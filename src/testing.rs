@@ -0,0 +1,215 @@
+//! Assertions for tests that show how the compared values differ,
+//! with the `testing` feature.
+//!
+//! See [`assert_eq_diff!`](crate::assert_eq_diff).
+
+use crate::myers::diff;
+use crate::patch::{hunks, PatchHeader};
+use crate::recursive::{self, Diffable, Report};
+use crate::serialization::ToPatch;
+use std::fmt;
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const CYAN: &str = "\x1b[36m";
+const RESET: &str = "\x1b[0m";
+
+/// Asserts that two expressions are equal, like `assert_eq!`, and otherwise panics
+/// with how they differ instead of both values:
+/// - a unified diff of their lines if they are strings, i.e. `AsRef<str>`
+/// - a [`Report`] of their changes if they are [`Diffable`], with leaves
+///   formatted with `Debug`
+/// - otherwise a unified diff of their pretty-printed `Debug` representations
+///
+/// The diff is colored, unless the `NO_COLOR` environment variable is set.
+/// Like `assert_eq!`, a message can follow the expressions.
+/// ```should_panic
+/// use std::collections::HashMap;
+/// use diffkit::assert_eq_diff;
+///
+/// assert_eq_diff!("a\nb\nc\n", "a\nc\n");
+///
+/// let old = HashMap::from([("replicas".to_string(), 3)]);
+/// let new = HashMap::from([("replicas".to_string(), 5)]);
+/// // panics with "~ replicas: 3 → 5"
+/// assert_eq_diff!(old, new, "after scaling {}", "web");
+/// ```
+#[macro_export]
+macro_rules! assert_eq_diff {
+    ($left:expr, $right:expr $(,)?) => {
+        match (&$left, &$right) {
+            (left, right) => {
+                if !(*left == *right) {
+                    ::std::panic!(
+                        "assertion `left == right` failed\n{}",
+                        $crate::__diff_report!(left, right)
+                    );
+                }
+            }
+        }
+    };
+    ($left:expr, $right:expr, $($arg:tt)+) => {
+        match (&$left, &$right) {
+            (left, right) => {
+                if !(*left == *right) {
+                    ::std::panic!(
+                        "assertion `left == right` failed: {}\n{}",
+                        ::std::format_args!($($arg)+),
+                        $crate::__diff_report!(left, right)
+                    );
+                }
+            }
+        }
+    };
+}
+
+/// Picks the report of two values by the traits they implement: the methods of
+/// the reports are found on fewer and fewer references to the operands,
+/// so the first report that applies is used.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __diff_report {
+    ($left:expr, $right:expr) => {{
+        #[allow(unused_imports)]
+        use $crate::testing::{DebugReport as _, StructuralReport as _, TextReport as _};
+        (&&&$crate::testing::Operands($left, $right)).report()
+    }};
+}
+
+#[doc(hidden)]
+pub struct Operands<'a, L: ?Sized, R: ?Sized>(pub &'a L, pub &'a R);
+
+#[doc(hidden)]
+pub trait TextReport {
+    fn report(&self) -> String;
+}
+
+impl<L: AsRef<str> + ?Sized, R: AsRef<str> + ?Sized> TextReport for &&Operands<'_, L, R> {
+    fn report(&self) -> String {
+        lines_report(self.0.as_ref(), self.1.as_ref())
+    }
+}
+
+#[doc(hidden)]
+pub trait StructuralReport {
+    fn report(&self) -> String;
+}
+
+impl<T> StructuralReport for &Operands<'_, T, T>
+where
+    T: Diffable + fmt::Debug + ?Sized,
+    T::P: fmt::Debug,
+{
+    fn report(&self) -> String {
+        let changes = recursive::diff(self.0, self.1);
+        if changes.is_empty() {
+            // equal as trees, but not for `PartialEq`
+            return debug_report(self.0, self.1);
+        }
+        Report::new(&changes).color(color()).to_string()
+    }
+}
+
+#[doc(hidden)]
+pub trait DebugReport {
+    fn report(&self) -> String;
+}
+
+impl<L: fmt::Debug + ?Sized, R: fmt::Debug + ?Sized> DebugReport for Operands<'_, L, R> {
+    fn report(&self) -> String {
+        debug_report(self.0, self.1)
+    }
+}
+
+fn debug_report(left: &(impl fmt::Debug + ?Sized), right: &(impl fmt::Debug + ?Sized)) -> String {
+    lines_report(&format!("{:#?}", left), &format!("{:#?}", right))
+}
+
+/// A unified diff from the lines of `left` to the lines of `right`.
+fn lines_report(left: &str, right: &str) -> String {
+    let (left, right): (Vec<&str>, Vec<&str>) = (left.lines().collect(), right.lines().collect());
+    let hunks = hunks(diff(&left, &right));
+    if hunks.is_empty() {
+        // only the line endings differ
+        return format!("  left: {:?}\n right: {:?}\n", left, right);
+    }
+    let patch = hunks.to_patch(&PatchHeader::new("left", "right"));
+    let mut report = String::new();
+    for line in patch.lines() {
+        let color = match line.as_bytes().first() {
+            _ if !color() || line.starts_with("---") || line.starts_with("+++") => None,
+            Some(b'-') => Some(RED),
+            Some(b'+') => Some(GREEN),
+            Some(b'@') => Some(CYAN),
+            _ => None,
+        };
+        match color {
+            Some(color) => report.push_str(&format!("{}{}{}\n", color, line, RESET)),
+            None => report.push_str(&format!("{}\n", line)),
+        }
+    }
+    report
+}
+
+/// Whether to color the reports, following <https://no-color.org>.
+fn color() -> bool {
+    std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::panic;
+
+    /// The message of the panic of `f`, without colors.
+    fn panic_message(f: impl FnOnce() + panic::UnwindSafe) -> String {
+        let payload = panic::catch_unwind(f).unwrap_err();
+        let message = payload.downcast_ref::<String>().unwrap();
+        let mut plain = String::new();
+        let mut parts = message.split('\x1b');
+        plain.push_str(parts.next().unwrap());
+        for part in parts {
+            plain.push_str(&part[part.find('m').unwrap() + 1..]);
+        }
+        plain
+    }
+
+    #[test]
+    fn test_assert_eq_diff() {
+        assert_eq_diff!("a\nb", "a\nb".to_string());
+        assert_eq_diff!(vec![1, 2], vec![1, 2], "equal");
+
+        let message = panic_message(|| assert_eq_diff!("a\nb\nc", "a\nc".to_string()));
+        assert_eq!(
+            message,
+            "assertion `left == right` failed\n--- left\n+++ right\n@@ -1,3 +1,2 @@\n a\n-b\n c\n"
+        );
+
+        let old = HashMap::from([("replicas".to_string(), 3)]);
+        let new = HashMap::from([("replicas".to_string(), 5)]);
+        let message = panic_message(|| assert_eq_diff!(old, new, "scaled to {}", 5));
+        assert_eq!(
+            message,
+            "assertion `left == right` failed: scaled to 5\n~ replicas: 3 → 5\n"
+        );
+
+        #[derive(Debug, PartialEq)]
+        struct Config {
+            name: &'static str,
+            port: u16,
+        }
+        let left = Config {
+            name: "web",
+            port: 80,
+        };
+        let right = Config {
+            name: "web",
+            port: 8080,
+        };
+        let message = panic_message(|| assert_eq_diff!(left, right));
+        assert_eq!(
+            message,
+            "assertion `left == right` failed\n--- left\n+++ right\n@@ -1,4 +1,4 @@\n Config {\n     name: \"web\",\n+    port: 8080,\n-    port: 80,\n }\n"
+        );
+    }
+}
@@ -0,0 +1,96 @@
+#![cfg(feature = "cli")]
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Runs `patchwork` with `args`, returning its exit code, stdout and stderr.
+fn patchwork(args: &[&str]) -> (i32, String, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_patchwork"))
+        .args(args)
+        .output()
+        .unwrap();
+    (
+        output.status.code().unwrap(),
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+/// Writes `files` to a fresh directory, returning their paths.
+fn files(test: &str, files: &[(&str, &str)]) -> Vec<String> {
+    let dir = std::env::temp_dir().join(format!("diffkit-cli-{}-{}", test, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    files
+        .iter()
+        .map(|(name, content)| {
+            let path: PathBuf = dir.join(name);
+            fs::write(&path, content).unwrap();
+            path.to_str().unwrap().to_string()
+        })
+        .collect()
+}
+
+#[test]
+fn test_unified() {
+    let paths = files(
+        "unified",
+        &[("old.txt", "a\nb\nc\n"), ("new.txt", "a\nx\nc\nd\n")],
+    );
+    let (old, new) = (&paths[0], &paths[1]);
+    let (code, patch, _) = patchwork(&["diff", old, new]);
+    assert_eq!(code, 1);
+    assert!(patch.contains("@@ -1,3 +1,4 @@\n a\n+x\n-b\n c\n+d\n"));
+    assert_eq!(
+        patchwork(&["diff", old, old]),
+        (0, String::new(), String::new())
+    );
+
+    let patch_path = &files("unified-patch", &[("patch.diff", &patch)])[0];
+    assert_eq!(
+        patchwork(&["apply", old, patch_path]),
+        (0, "a\nx\nc\nd\n".to_string(), String::new())
+    );
+    assert_eq!(
+        patchwork(&["revert", new, patch_path]),
+        (0, "a\nb\nc\n".to_string(), String::new())
+    );
+    let (code, _, error) = patchwork(&["apply", new, patch_path]);
+    assert_eq!(code, 2);
+    assert!(error.starts_with("patchwork: "));
+}
+
+#[test]
+fn test_json() {
+    let old = r#"{"name": "web", "ports": [80, 443], "labels": {"tier": "front"}, "env": "dev"}"#;
+    let new =
+        r#"{"name": "web", "ports": [443, 8080], "labels": {"tier": "back"}, "owner": "ann"}"#;
+    let paths = files("json", &[("old.json", old), ("new.json", new)]);
+    let (code, patch, _) = patchwork(&["diff", "--json", &paths[0], &paths[1]]);
+    assert_eq!(code, 1);
+    let patch_path = &files("json-patch", &[("patch.json", &patch)])[0];
+
+    let parse = |s: &str| serde_json::from_str::<serde_json::Value>(s).unwrap();
+    let (code, patched, _) = patchwork(&["apply", "--json", &paths[0], patch_path]);
+    assert_eq!((code, parse(&patched)), (0, parse(new)));
+    let (code, reverted, _) = patchwork(&["--json", "revert", &paths[1], patch_path]);
+    assert_eq!((code, parse(&reverted)), (0, parse(old)));
+
+    // reverting checks the new values
+    let (code, _, error) = patchwork(&["revert", "--json", &paths[0], patch_path]);
+    assert_eq!(code, 2);
+    assert!(error.contains("test failed"), "{}", error);
+}
+
+#[test]
+fn test_usage() {
+    let (code, _, error) = patchwork(&["diff", "only-one"]);
+    assert_eq!(code, 2);
+    assert!(error.contains("usage: patchwork diff [--json] OLD NEW"));
+    let (code, _, error) = patchwork(&["diff", "missing-a", "missing-b"]);
+    assert_eq!(code, 2);
+    assert!(error.starts_with("patchwork: missing-a, missing-b: "));
+    let (code, _, error) = patchwork(&["apply", "missing-a", "missing-b"]);
+    assert_eq!(code, 2);
+    assert!(error.starts_with("patchwork: missing-a: "));
+}
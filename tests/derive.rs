@@ -0,0 +1,210 @@
+#![cfg(feature = "derive")]
+
+use diffkit::recursive::*;
+use std::collections::HashMap;
+
+#[derive(Diffable, Clone, PartialEq, Debug)]
+struct Service {
+    name: String,
+    replicas: u32,
+    ports: Vec<u16>,
+    #[diffable(rename = "env")]
+    environment: HashMap<String, String>,
+    #[diffable(skip)]
+    cache: Vec<u8>,
+}
+
+#[derive(Diffable, Clone, PartialEq, Debug)]
+enum Shape {
+    Point,
+    Circle(u32),
+    Segment(i32, i32),
+    Rect { width: u32, height: u32 },
+}
+
+#[derive(Diffable, Clone, PartialEq, Debug)]
+struct Meters(u64);
+
+#[derive(Diffable, Clone, PartialEq, Debug)]
+#[diffable(leaf = i32)]
+struct Wrapper<T> {
+    inner: T,
+    shapes: Vec<Shape2>,
+}
+
+#[derive(Diffable, Clone, PartialEq, Debug)]
+#[diffable(leaf = i32)]
+struct Shape2(i32, i32);
+
+#[derive(Diffable, Clone, PartialEq, Debug)]
+struct Tree {
+    value: i8,
+    children: Vec<Tree>,
+}
+
+#[derive(Diffable, Clone, PartialEq, Debug)]
+struct List {
+    value: i8,
+    next: Option<Box<List>>,
+}
+
+fn service() -> Service {
+    Service {
+        name: "api".to_string(),
+        replicas: 1,
+        ports: vec![80],
+        environment: HashMap::from([("LOG".to_string(), "info".to_string())]),
+        cache: vec![1, 2, 3],
+    }
+}
+
+#[test]
+fn test_struct_fields_are_keys() {
+    let old = service();
+    let new = Service {
+        replicas: 3,
+        ports: vec![80, 443],
+        ..service()
+    };
+    let changes = diff(&old, &new);
+    assert_eq!(changes.len(), 2);
+    assert!(changes.contains(&Change {
+        path: vec![PathSegment::Key("replicas".to_string())],
+        kind: ChangeKind::Modified(Scalar::UInt(1), Scalar::UInt(3)),
+    }));
+    assert!(changes.contains(&Change {
+        path: vec![PathSegment::Key("ports".to_string()), PathSegment::Index(1)],
+        kind: ChangeKind::Added(Scalar::UInt(443)),
+    }));
+
+    let patched = apply(&old, &changes).unwrap();
+    assert_eq!(patched.cache, Vec::<u8>::new());
+    assert_eq!(
+        Service {
+            cache: vec![1, 2, 3],
+            ..patched
+        },
+        new
+    );
+}
+
+#[test]
+fn test_renamed_and_skipped_fields() {
+    let Node::Map(map) = service().to_node() else {
+        panic!("expected a map");
+    };
+    let mut keys: Vec<&String> = map.keys().collect();
+    keys.sort();
+    assert_eq!(keys, ["env", "name", "ports", "replicas"]);
+}
+
+#[test]
+fn test_enum_variants() {
+    let old = vec![
+        Shape::Point,
+        Shape::Rect {
+            width: 1,
+            height: 2,
+        },
+    ];
+    let new = vec![
+        Shape::Circle(4),
+        Shape::Rect {
+            width: 1,
+            height: 5,
+        },
+    ];
+    let changes = diff(&old, &new);
+    assert!(changes.contains(&Change {
+        path: vec![
+            PathSegment::Index(1),
+            PathSegment::Key("Rect".to_string()),
+            PathSegment::Key("height".to_string()),
+        ],
+        kind: ChangeKind::Modified(Scalar::UInt(2), Scalar::UInt(5)),
+    }));
+    assert_eq!(
+        changes[..2],
+        [
+            Change {
+                path: vec![PathSegment::Index(0)],
+                kind: ChangeKind::NodeRemoved(Shape::Point.to_node()),
+            },
+            Change {
+                path: vec![PathSegment::Index(0)],
+                kind: ChangeKind::NodeAdded(Shape::Circle(4).to_node()),
+            },
+        ]
+    );
+    assert_eq!(apply(&old, &changes), Ok(new));
+
+    let segment = Shape::Segment(-1, 1);
+    assert_eq!(Shape::from_node(segment.to_node()), Ok(segment));
+    let circle = Node::Map(HashMap::from([(
+        "Circle".to_string(),
+        Node::Leaf(Scalar::UInt(4)),
+    )]));
+    assert_eq!(Shape::from_node(circle), Ok(Shape::Circle(4)));
+}
+
+#[test]
+fn test_newtypes_and_generics() {
+    assert_eq!(Meters(5).to_node(), Node::Leaf(Scalar::UInt(5)));
+    let old = Wrapper {
+        inner: vec![1, 2],
+        shapes: vec![Shape2(0, 0)],
+    };
+    let new = Wrapper {
+        inner: vec![1],
+        shapes: vec![Shape2(0, 1)],
+    };
+    assert_eq!(apply(&old, &diff(&old, &new)), Ok(new));
+}
+
+#[test]
+fn test_recursive_types() {
+    let leaf = |value| Tree {
+        value,
+        children: vec![],
+    };
+    let old = Tree {
+        value: 0,
+        children: vec![leaf(1)],
+    };
+    let new = Tree {
+        value: 0,
+        children: vec![leaf(1), leaf(2)],
+    };
+    assert_eq!(apply(&old, &diff(&old, &new)), Ok(new));
+
+    let old = List {
+        value: 0,
+        next: None,
+    };
+    let new = List {
+        value: 0,
+        next: Some(Box::new(List {
+            value: 1,
+            next: None,
+        })),
+    };
+    assert_eq!(apply(&old, &diff(&old, &new)), Ok(new));
+}
+
+#[test]
+fn test_shape_mismatch() {
+    let node = Node::Map(HashMap::from([
+        ("value".to_string(), Node::Leaf(Scalar::Int(300))),
+        ("children".to_string(), Node::Sequence(vec![])),
+    ]));
+    assert_eq!(
+        Tree::from_node(node),
+        Err(ApplyError::TypeMismatch(vec![PathSegment::Key(
+            "value".to_string()
+        )]))
+    );
+    assert_eq!(
+        Shape::from_node(Node::Map(HashMap::new())),
+        Err(ApplyError::TypeMismatch(vec![]))
+    );
+}
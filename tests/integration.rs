@@ -1,8 +1,9 @@
+use diffkit::patch::PatchHeader;
 use diffkit::recursive::*;
+use diffkit::serialization::{FromPatch, ToPatch};
 use proptest::prelude::*;
 use std::collections::HashMap;
 
-// only flat for now
 proptest! {
     #[test]
     fn test_round_trip_map(
@@ -10,7 +11,7 @@ proptest! {
         new in prop::collection::hash_map(".*", any::<i32>(), 0..10),
     ) {
         let changes = diff(&old, &new);
-        let result = apply(&old, &changes);
+        let result = apply(&old, &changes).unwrap();
         prop_assert_eq!(result, new);
     }
 
@@ -20,9 +21,163 @@ proptest! {
         new in prop::collection::vec(any::<i32>(), 0..10),
     ) {
         let changes = diff(&old, &new);
-        let result = apply(&old, &changes);
+        let result = apply(&old, &changes).unwrap();
         prop_assert_eq!(result, new);
     }
+
+    #[test]
+    fn test_invert_round_trip(
+        old in prop::collection::vec(prop::collection::hash_map("[a-c]", any::<i8>(), 0..3), 0..6),
+        new in prop::collection::vec(prop::collection::hash_map("[a-c]", any::<i8>(), 0..3), 0..6),
+    ) {
+        let changes = diff(&old, &new);
+        let result = apply_strict(&new, &invert_changes(&changes)).unwrap();
+        prop_assert_eq!(result, old);
+    }
+
+    #[test]
+    fn test_compose(
+        a in prop::collection::vec(prop::collection::hash_map("[a-c]", any::<i8>(), 0..3), 0..6),
+        b in prop::collection::vec(prop::collection::hash_map("[a-c]", any::<i8>(), 0..3), 0..6),
+        c in prop::collection::vec(prop::collection::hash_map("[a-c]", any::<i8>(), 0..3), 0..6),
+    ) {
+        let first = diff(&a, &b);
+        let second = diff(&b, &c);
+        let composed = compose(&first, &second);
+        prop_assert!(composed.len() <= first.len() + second.len());
+        let result = apply_strict(&a, &composed).unwrap();
+        prop_assert_eq!(result, c);
+    }
+
+    #[test]
+    fn test_merge(
+        base in prop::collection::vec(prop::collection::hash_map("[a-c]", 0..3i8, 0..3), 0..6),
+        ours in prop::collection::vec(prop::collection::hash_map("[a-c]", 0..3i8, 0..3), 0..6),
+        theirs in prop::collection::vec(prop::collection::hash_map("[a-c]", 0..3i8, 0..3), 0..6),
+    ) {
+        let outcome = merge(&diff(&base, &ours), &diff(&base, &theirs));
+        let merged = apply_strict(&base, &outcome.changes).unwrap();
+        let swapped = merge(&diff(&base, &theirs), &diff(&base, &ours));
+        prop_assert_eq!(swapped.is_clean(), outcome.is_clean());
+        if theirs == base {
+            prop_assert_eq!(merged, ours);
+        } else if ours == base {
+            prop_assert_eq!(merged, theirs);
+        }
+    }
+
+    #[test]
+    fn test_transform_converges(
+        base in prop::collection::vec(prop::collection::hash_map("[a-c]", 0..3i8, 0..3), 0..6),
+        a in prop::collection::vec(prop::collection::hash_map("[a-c]", 0..3i8, 0..3), 0..6),
+        b in prop::collection::vec(prop::collection::hash_map("[a-c]", 0..3i8, 0..3), 0..6),
+    ) {
+        let (a_changes, b_changes) = (diff(&base, &a), diff(&base, &b));
+        let (a_after_b, b_after_a) = transform(&a_changes, &b_changes);
+        let ab = apply_strict(&a, &b_after_a).unwrap();
+        let ba = apply_strict(&b, &a_after_b).unwrap();
+        prop_assert_eq!(ab, ba);
+    }
+
+    #[test]
+    fn test_round_trip_options(
+        old in prop::collection::vec(prop::option::of(prop::collection::vec(any::<i8>(), 0..3)), 0..6),
+        new in prop::collection::vec(prop::option::of(prop::collection::vec(any::<i8>(), 0..3)), 0..6),
+    ) {
+        let changes = diff(&old, &new);
+        let result = apply_strict(&old, &changes).unwrap();
+        prop_assert_eq!(result, new);
+    }
+
+    #[test]
+    fn test_round_trip_vec_of_maps(
+        old in prop::collection::vec(prop::collection::hash_map("[a-c]", any::<i8>(), 0..3), 0..6),
+        new in prop::collection::vec(prop::collection::hash_map("[a-c]", any::<i8>(), 0..3), 0..6),
+    ) {
+        let changes = diff(&old, &new);
+        let result = apply(&old, &changes).unwrap();
+        prop_assert_eq!(result, new);
+    }
+
+    #[test]
+    fn test_round_trip_moves_and_renames(
+        old in prop::collection::vec(prop::collection::hash_map("[a-c]", any::<bool>(), 0..3), 0..6),
+        new in prop::collection::vec(prop::collection::hash_map("[a-c]", any::<bool>(), 0..3), 0..6),
+    ) {
+        let options = DiffOptions::new().detect_moves().detect_renames();
+        let changes = diff_with(&old, &new, &options);
+        prop_assert_eq!(apply_strict(&old, &changes).unwrap(), new.clone());
+        prop_assert_eq!(apply(&new, &invert_changes(&changes)).unwrap(), old);
+    }
+
+    #[test]
+    fn test_apply_foreign_changes(
+        old in prop::collection::vec(prop::option::of(prop::collection::vec(any::<i8>(), 0..3)), 0..4),
+        new in prop::collection::vec(prop::option::of(prop::collection::vec(any::<i8>(), 0..3)), 0..4),
+        other in prop::collection::vec(prop::option::of(prop::collection::vec(any::<i8>(), 0..3)), 0..4),
+    ) {
+        // changes computed against another value fail with an error, never a panic
+        let changes = diff(&old, &new);
+        if let Ok(result) = apply(&other, &changes) {
+            prop_assert_eq!(result.len() + old.len(), other.len() + new.len());
+        }
+        let _ = apply_strict(&other, &changes);
+    }
+
+    #[test]
+    fn test_shared_apply(
+        old in prop::collection::vec(prop::collection::hash_map("[a-c]", 0..3i8, 0..3), 0..5),
+        new in prop::collection::vec(prop::collection::hash_map("[a-c]", 0..3i8, 0..3), 0..5),
+        other in prop::collection::vec(prop::collection::hash_map("[a-c]", 0..3i8, 0..3), 0..5),
+    ) {
+        // shared trees give the same results as nodes, errors included
+        let options = DiffOptions::new().detect_moves().detect_renames();
+        let changes = diff_with(&old, &new, &options);
+        for target in [&old, &other] {
+            let mut node = target.to_node();
+            let expected = apply_node_in_place(&mut node, &changes).map(|_| node);
+            let shared = SharedNode::from(target.to_node()).apply(&changes);
+            prop_assert_eq!(shared.map(Node::from), expected);
+        }
+    }
+}
+
+proptest! {
+    #[test]
+    fn test_patch_encodings_round_trip(
+        old in prop::collection::hash_map(".*", prop::collection::vec(".*", 0..3), 0..4),
+        new in prop::collection::hash_map(".*", prop::collection::vec(".*", 0..3), 0..4),
+    ) {
+        let changes = diff(&old, &new);
+        let text = changes.to_patch(&PatchHeader::default());
+        prop_assert_eq!(Vec::<Change<String>>::from_patch(&text), Ok(changes.clone()));
+        prop_assert_eq!(from_bytes(&to_bytes(&changes)), Ok(changes));
+    }
+}
+
+#[cfg(feature = "json")]
+proptest! {
+    #[test]
+    fn test_json_patch_round_trip(
+        old in prop::collection::vec(prop::collection::hash_map("[a~/0]", any::<i8>(), 0..3), 0..6),
+        new in prop::collection::vec(prop::collection::hash_map("[a~/0]", any::<i8>(), 0..3), 0..6),
+        strict in any::<bool>(),
+    ) {
+        let patch = to_json_patch(&diff(&old, &new), strict).unwrap();
+        let changes = from_json_patch(&old, &patch).unwrap();
+        let result = apply_strict(&old, &changes).unwrap();
+        prop_assert_eq!(result, new);
+    }
+
+    #[test]
+    fn test_merge_patch_round_trip(
+        old in prop::collection::hash_map("[a-c]", prop::collection::hash_map("[a-c]", any::<i8>(), 0..3), 0..3),
+        new in prop::collection::hash_map("[a-c]", prop::collection::hash_map("[a-c]", any::<i8>(), 0..3), 0..3),
+    ) {
+        let patch = to_merge_patch(&old, &diff(&old, &new)).unwrap();
+        let node = apply_merge_patch(old.to_node(), &patch).unwrap();
+        prop_assert_eq!(node, new.to_node());
+    }
 }
 
 // nested structures
@@ -45,7 +200,7 @@ fn test_apply_round_trip_seq_with_maps() {
     new.push(c);
 
     let changes = diff(&old, &new);
-    let result = apply(&old, &changes);
+    let result = apply(&old, &changes).unwrap();
     assert_eq!(result, new);
 }
 
@@ -60,6 +215,6 @@ fn test_apply_nested_map() {
     nested_b.insert("nested".to_string(), 2);
     new.insert("b".to_string(), nested_b);
     let changes = diff(&old, &new);
-    let result = apply(&old, &changes);
+    let result = apply(&old, &changes).unwrap();
     assert_eq!(result, new);
 }